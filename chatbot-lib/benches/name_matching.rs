@@ -0,0 +1,48 @@
+//! Compares the cost of looking up a chatter by name with each [`NameMatching`].
+//!
+//! ```text
+//! cargo bench --bench name_matching
+//! ```
+
+use chatbot_lib::user::NameMatching;
+
+const POLICIES: [NameMatching; 3] = [
+    NameMatching::Exact,
+    NameMatching::AsciiCaseInsensitive,
+    NameMatching::UnicodeCaseFold,
+];
+
+// most display names only differ from the username in their case
+const NAMES: [&str; 3] = ["liquidnya", "LiquidNya", "HelperBlock_42"];
+const LOCALIZED_NAMES: [&str; 2] = ["ΣΊΣΥΦΟΣ", "Straße"];
+
+fn main() {
+    divan::main();
+}
+
+#[divan::bench(args = POLICIES)]
+fn key(bencher: divan::Bencher, matching: NameMatching) {
+    bencher.bench(|| {
+        for name in NAMES {
+            divan::black_box(matching.key(divan::black_box(name)));
+        }
+    });
+}
+
+#[divan::bench(args = POLICIES)]
+fn key_localized(bencher: divan::Bencher, matching: NameMatching) {
+    bencher.bench(|| {
+        for name in LOCALIZED_NAMES {
+            divan::black_box(matching.key(divan::black_box(name)));
+        }
+    });
+}
+
+#[divan::bench(args = POLICIES)]
+fn matches(bencher: divan::Bencher, matching: NameMatching) {
+    bencher.bench(|| {
+        for name in NAMES {
+            divan::black_box(matching.matches(divan::black_box(name), "liquidnya"));
+        }
+    });
+}
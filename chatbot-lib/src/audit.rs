@@ -0,0 +1,252 @@
+//! An append-only log of the commands answered by the bot, which helps finding out why the bot
+//! said something.
+//!
+//! The log is enabled with [`ChatBot::with_audit_log`](crate::ChatBot::with_audit_log) and written
+//! as JSON lines to `audit.jsonl` next to the persisted state of each channel. The file is
+//! rotated to `audit.1.jsonl`, `audit.2.jsonl`, … once it is too large or on a new day.
+
+use crate::command::CommandArguments;
+use crate::request::CommandRequest;
+use crate::response::Response;
+use crate::state::DataDir;
+use chrono::{DateTime, Utc};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+const FILENAME: &str = "audit";
+const EXTENSION: &str = "jsonl";
+/// How many characters of a response are stored.
+const MAX_RESPONSE_CHARS: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct AuditEntry {
+    pub time: DateTime<Utc>,
+    pub channel: String,
+    pub sender: String,
+    /// The name of the command, e.g. `!quote`.
+    pub command: String,
+    pub arguments: String,
+    /// The start of the response, `None` if the command was answered without a message.
+    pub response: Option<String>,
+}
+
+impl AuditEntry {
+    pub(crate) fn new(request: &CommandRequest<'_>, response: &Response<'_>) -> Self {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        let command = arguments.next().unwrap_or_default().to_owned();
+        let response = response.response().map(|response| {
+            let mut summary: String = response.chars().take(MAX_RESPONSE_CHARS).collect();
+            if summary.len() < response.len() {
+                summary.push('…');
+            }
+            summary
+        });
+        Self {
+            time: Utc::now(),
+            channel: request.channel().username().to_owned(),
+            sender: request.sender().username().to_owned(),
+            command,
+            arguments: arguments.as_str().to_owned(),
+            response,
+        }
+    }
+}
+
+/// Writes an [`AuditEntry`] for every command which was answered by a command processor.
+///
+/// ```
+/// # use chatbot_lib::audit::AuditLog;
+/// let log = AuditLog::new().max_size(1024 * 1024).max_files(3);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    dir: Option<PathBuf>,
+    max_size: u64,
+    rotate_daily: bool,
+    max_files: usize,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuditLog {
+    /// Rotates the log daily or once it is larger than 10 MiB, and keeps 7 old files.
+    pub fn new() -> Self {
+        Self {
+            dir: None,
+            max_size: 10 * 1024 * 1024,
+            rotate_daily: true,
+            max_files: 7,
+        }
+    }
+
+    /// Writes the logs of all channels to `dir/<channel>` instead of the data directory.
+    pub fn in_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Rotates the log once it would grow beyond `bytes`.
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = bytes;
+        self
+    }
+
+    pub fn rotate_daily(mut self, rotate_daily: bool) -> Self {
+        self.rotate_daily = rotate_daily;
+        self
+    }
+
+    /// How many rotated files are kept, older files are deleted.
+    pub fn max_files(mut self, files: usize) -> Self {
+        self.max_files = files;
+        self
+    }
+
+    fn path(&self, channel: &str, index: usize) -> anyhow::Result<PathBuf> {
+        let mut path = match &self.dir {
+            Some(dir) => dir.join(channel),
+            None => DataDir::default().channel_dir(channel)?,
+        };
+        match index {
+            0 => path.push(format!("{}.{}", FILENAME, EXTENSION)),
+            index => path.push(format!("{}.{}.{}", FILENAME, index, EXTENSION)),
+        }
+        Ok(path)
+    }
+
+    pub async fn record(&self, entry: &AuditEntry) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let audit = self.clone();
+        let channel = entry.channel.clone();
+        let now = entry.time;
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            let path = audit.path(&channel, 0)?;
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            if audit.needs_rotation(&path, line.len() as u64, now)? {
+                audit.rotate(&channel)?;
+            }
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            file.write_all(&line)?;
+            Ok(())
+        })
+        .await??;
+        Ok(())
+    }
+
+    fn needs_rotation(
+        &self,
+        path: &Path,
+        additional: u64,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<bool> {
+        let metadata = match std::fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        if metadata.len() > 0 && metadata.len() + additional > self.max_size {
+            return Ok(true);
+        }
+        if self.rotate_daily {
+            let modified = DateTime::<Utc>::from(metadata.modified()?);
+            return Ok(modified.date_naive() != now.date_naive());
+        }
+        Ok(false)
+    }
+
+    // the newest rotated file has index 1
+    fn rotate(&self, channel: &str) -> anyhow::Result<()> {
+        if self.max_files == 0 {
+            std::fs::remove_file(self.path(channel, 0)?)?;
+            return Ok(());
+        }
+        for index in (0..self.max_files).rev() {
+            match std::fs::rename(self.path(channel, index)?, self.path(channel, index + 1)?) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The last `n` entries of the channel, the newest first.
+    pub async fn recent(&self, channel: &str, n: usize) -> anyhow::Result<Vec<AuditEntry>> {
+        let audit = self.clone();
+        let channel = channel.to_owned();
+        tokio::task::spawn_blocking(move || {
+            let mut entries = Vec::new();
+            for index in 0..=audit.max_files {
+                if entries.len() >= n {
+                    break;
+                }
+                let file = match std::fs::File::open(audit.path(&channel, index)?) {
+                    Ok(file) => file,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e.into()),
+                };
+                let mut lines = Vec::new();
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    // a line might be incomplete if the bot stopped while writing it
+                    match serde_json::from_str::<AuditEntry>(&line) {
+                        Ok(entry) => lines.push(entry),
+                        Err(e) => log::warn!("Skipping invalid audit log entry: {:?}", e),
+                    }
+                }
+                entries.extend(lines.into_iter().rev().take(n - entries.len()));
+            }
+            Ok(entries)
+        })
+        .await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn entry(arguments: &str) -> AuditEntry {
+        AuditEntry {
+            time: Utc::now(),
+            channel: "channel".to_owned(),
+            sender: "user".to_owned(),
+            command: "!quote".to_owned(),
+            arguments: arguments.to_owned(),
+            response: Some("nya".to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_record_and_rotate() {
+        let dir = std::env::temp_dir().join(format!("chatbot-test-audit-{}", std::process::id()));
+        let log = AuditLog::new().in_dir(&dir).max_size(300).max_files(1);
+        block_on(async {
+            for i in 0..5 {
+                log.record(&entry(&i.to_string())).await.unwrap();
+            }
+            let recent = log.recent("channel", 3).await.unwrap();
+            let arguments: Vec<_> = recent.iter().map(|e| e.arguments.as_str()).collect();
+            assert_eq!(arguments, ["4", "3", "2"]);
+            // the oldest entries were in the file deleted by the rotation
+            let all = log.recent("channel", 10).await.unwrap();
+            assert!(all.len() < 5);
+            assert_eq!(all[0], recent[0]);
+        });
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
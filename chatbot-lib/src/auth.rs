@@ -0,0 +1,299 @@
+//! Keeps the OAuth token of the bot valid by refreshing it before it expires, see
+//! [`RefreshingLoginCredentials`].
+
+use crate::config::{Config, ConfigError};
+use crate::helix::HelixClient;
+use crate::state::{read_from_root, store_in_root, DataDir, PersistedType};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{broadcast, RwLock};
+use twitchchat::UserConfig;
+
+const TOKEN_URL: &str = "https://id.twitch.tv/oauth2/token";
+const VALIDATE_URL: &str = "https://id.twitch.tv/oauth2/validate";
+/// Tokens are refreshed this long before they expire.
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+/// How long to wait before trying again after refreshing failed.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+/// Tokens without a known expiry are validated this often.
+const VALIDATE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The current tokens, which are written to disk whenever they are refreshed.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct StoredToken {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// `None` until the expiry of the token is known.
+    pub expires_at: Option<SystemTime>,
+}
+
+impl PersistedType for StoredToken {
+    const FILENAME: &'static str = "token";
+    const BACKUPS: usize = 0;
+
+    fn init(_channel: &str) -> Self {
+        Self {
+            access_token: String::new(),
+            refresh_token: String::new(),
+            expires_at: None,
+        }
+    }
+}
+
+impl StoredToken {
+    /// How long to wait until the token has to be refreshed.
+    fn refresh_in(&self, now: SystemTime) -> Option<Duration> {
+        let expires_at = self.expires_at?;
+        Some(
+            expires_at
+                .duration_since(now)
+                .unwrap_or_default()
+                .saturating_sub(REFRESH_MARGIN),
+        )
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: String,
+    expires_in: u64,
+}
+
+#[derive(serde::Deserialize)]
+struct ValidateResponse {
+    expires_in: u64,
+}
+
+impl TokenResponse {
+    fn into_token(self, now: SystemTime) -> StoredToken {
+        StoredToken {
+            access_token: self.access_token,
+            refresh_token: self.refresh_token,
+            expires_at: Some(now + Duration::from_secs(self.expires_in)),
+        }
+    }
+}
+
+/// Sent to the receivers of [`RefreshingLoginCredentials::subscribe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenEvent {
+    /// The token was refreshed and is valid until `expires_at`.
+    Refreshed { expires_at: SystemTime },
+    /// Refreshing failed, the token expires at `expires_at` if it is known.
+    RefreshFailed {
+        error: String,
+        expires_at: Option<SystemTime>,
+    },
+}
+
+/// The OAuth tokens of the bot, which are refreshed with the client credentials of the
+/// application before they expire and stored on disk, so the refreshed token is used after a
+/// restart.
+///
+/// Connecting again after the token expired needs a [`UserConfig`] with the current token, see
+/// [`RefreshingLoginCredentials::user_config`].
+#[derive(Clone)]
+pub struct RefreshingLoginCredentials {
+    client: reqwest::Client,
+    client_id: String,
+    client_secret: String,
+    token: Arc<RwLock<StoredToken>>,
+    events: broadcast::Sender<TokenEvent>,
+    data_dir: DataDir,
+}
+
+impl RefreshingLoginCredentials {
+    pub fn new(
+        client_id: String,
+        client_secret: String,
+        access_token: String,
+        refresh_token: String,
+    ) -> Self {
+        let (events, _) = broadcast::channel(16);
+        Self {
+            client: reqwest::Client::new(),
+            client_id,
+            client_secret,
+            token: Arc::new(RwLock::new(StoredToken {
+                access_token: access_token.trim_start_matches("oauth:").to_owned(),
+                refresh_token,
+                expires_at: None,
+            })),
+            events,
+            data_dir: DataDir::default(),
+        }
+    }
+
+    /// Stores the refreshed tokens in `data_dir` instead of `data`.
+    pub fn with_data_dir(mut self, data_dir: DataDir) -> Self {
+        self.data_dir = data_dir;
+        self
+    }
+
+    /// Uses the token, refresh token, client id and client secret of the config.
+    pub fn from_config(config: &Config) -> Result<Self, ConfigError> {
+        if config.token.is_empty() {
+            return Err(ConfigError::Missing("token"));
+        }
+        let credentials = Self::new(
+            config
+                .client_id
+                .clone()
+                .ok_or(ConfigError::Missing("client_id"))?,
+            config
+                .client_secret
+                .clone()
+                .ok_or(ConfigError::Missing("client_secret"))?,
+            config.token.clone(),
+            config
+                .refresh_token
+                .clone()
+                .ok_or(ConfigError::Missing("refresh_token"))?,
+        );
+        Ok(credentials.with_data_dir(config.data_dir()))
+    }
+
+    /// Replaces the tokens with the tokens stored by a previous refresh, which are newer than the
+    /// tokens passed to [`RefreshingLoginCredentials::new`].
+    pub async fn restore(&self) -> anyhow::Result<()> {
+        if let Some(token) = read_from_root::<StoredToken>(&self.data_dir).await? {
+            *self.token.write().await = token;
+        }
+        Ok(())
+    }
+
+    pub async fn access_token(&self) -> String {
+        self.token.read().await.access_token.clone()
+    }
+
+    pub async fn expires_at(&self) -> Option<SystemTime> {
+        self.token.read().await.expires_at
+    }
+
+    /// The login for Twitch chat with the current token.
+    pub async fn user_config(&self, login: &str) -> Result<UserConfig, ConfigError> {
+        UserConfig::builder()
+            .name(login)
+            .token(format!("oauth:{}", self.access_token().await))
+            .enable_all_capabilities()
+            .build()
+            .map_err(|e| ConfigError::InvalidUser(e.to_string()))
+    }
+
+    /// A Helix client with the current token, which has to be created again after a refresh.
+    pub async fn helix_client(&self) -> HelixClient {
+        HelixClient::new(self.client_id.clone(), self.access_token().await)
+    }
+
+    /// Receives an event whenever the token was refreshed or refreshing failed.
+    pub fn subscribe(&self) -> broadcast::Receiver<TokenEvent> {
+        self.events.subscribe()
+    }
+
+    /// Asks Twitch when the current token expires.
+    pub async fn validate(&self) -> anyhow::Result<SystemTime> {
+        let now = SystemTime::now();
+        let response: ValidateResponse = self
+            .client
+            .get(VALIDATE_URL)
+            .header(
+                "Authorization",
+                format!("OAuth {}", self.access_token().await),
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let expires_at = now + Duration::from_secs(response.expires_in);
+        self.token.write().await.expires_at = Some(expires_at);
+        Ok(expires_at)
+    }
+
+    /// Refreshes the token now and writes it to disk.
+    pub async fn refresh(&self) -> anyhow::Result<()> {
+        let now = SystemTime::now();
+        let refresh_token = self.token.read().await.refresh_token.clone();
+        let response: TokenResponse = self
+            .client
+            .post(TOKEN_URL)
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token.as_str()),
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let token = response.into_token(now);
+        let expires_at = token.expires_at.unwrap_or(now);
+        *self.token.write().await = token.clone();
+        log::info!("Refreshed the OAuth token");
+        if let Err(e) = store_in_root(&self.data_dir, Arc::new(token)).await {
+            log::error!("Error saving the refreshed token to disk: {:?}", e);
+        }
+        let _ = self.events.send(TokenEvent::Refreshed { expires_at });
+        Ok(())
+    }
+
+    /// Spawns a task which refreshes the token before it expires.
+    pub fn spawn_refresh(&self) -> tokio::task::JoinHandle<()> {
+        let credentials = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let token = credentials.token.read().await.clone();
+                let wait = match token.refresh_in(SystemTime::now()) {
+                    Some(wait) => wait,
+                    None => match credentials.validate().await {
+                        Ok(_) => continue,
+                        Err(e) => {
+                            // the token might already be invalid, which is fixed by refreshing it
+                            log::warn!("Error validating the OAuth token: {:?}", e);
+                            Duration::ZERO
+                        }
+                    },
+                };
+                tokio::time::sleep(wait.min(VALIDATE_INTERVAL)).await;
+                if wait > VALIDATE_INTERVAL {
+                    continue;
+                }
+                if let Err(e) = credentials.refresh().await {
+                    log::error!("Error refreshing the OAuth token: {:?}", e);
+                    let _ = credentials.events.send(TokenEvent::RefreshFailed {
+                        error: e.to_string(),
+                        expires_at: credentials.expires_at().await,
+                    });
+                    tokio::time::sleep(RETRY_INTERVAL).await;
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refresh_in() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let response: TokenResponse = serde_json::from_str(
+            r#"{"access_token":"a","refresh_token":"r","expires_in":14400,"scope":["chat:read"],"token_type":"bearer"}"#,
+        )
+        .unwrap();
+        let token = response.into_token(now);
+        assert_eq!(
+            token.refresh_in(now),
+            Some(Duration::from_secs(14400) - REFRESH_MARGIN)
+        );
+        assert_eq!(
+            token.refresh_in(now + Duration::from_secs(20000)),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(StoredToken::init("").refresh_in(now), None);
+    }
+}
@@ -0,0 +1,155 @@
+//! Runs a bot with the built-in commands and filters, set up by a config file.
+//!
+//! ```text
+//! chatbot-cli [config.toml]
+//! ```
+//!
+//! The config is read from `chatbot.toml` if no path is given, see
+//! [`chatbot_lib::config`] for its format and the environment variables replacing its values.
+//! The log level is set by `CHATBOT_LOG`, e.g. `CHATBOT_LOG=debug`.
+
+use chatbot_lib::command_stats::CommandStatsCommands;
+use chatbot_lib::config::Config;
+use chatbot_lib::counters::{CounterCommands, Counters};
+use chatbot_lib::ignore::{IgnoreCommands, IgnoredUsers};
+use chatbot_lib::moderation::{
+    BannedPhraseCommands, BannedPhraseFilter, BannedPhrases, FilterChain, FilterSettings,
+    FilterSettingsCommands, LinkFilter, ModerationLog, ModerationLogCommands, PermitCommands,
+    Permits, StrikeCommands, Strikes,
+};
+use chatbot_lib::quotes::{QuoteCommands, Quotes};
+use chatbot_lib::reminders::{ReminderCommands, Reminders};
+use chatbot_lib::request::{Role, TryFilterPredicate};
+use chatbot_lib::scripting::{ScriptCommands, ScriptProcessor, Scripts};
+use chatbot_lib::state::{ChannelConfig, ChannelConfigCommands, ChannelContainer};
+use chatbot_lib::ChatBot;
+use std::error::Error;
+use std::process::ExitCode;
+
+const DEFAULT_CONFIG: &str = "chatbot.toml";
+
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{} {}] {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn init_logger() {
+    let level = std::env::var("CHATBOT_LOG")
+        .ok()
+        .and_then(|level| level.parse().ok())
+        .unwrap_or(log::LevelFilter::Info);
+    if log::set_logger(&StderrLogger).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Deletes messages with banned phrases first, so users are not warned about links in them.
+fn filters() -> TryFilterPredicate {
+    FilterChain::new()
+        .with_filter(
+            "phrases",
+            &[Role::Moderator],
+            BannedPhraseFilter::new().into_predicate(),
+        )
+        .with_filter(
+            "links",
+            &[Role::Moderator, Role::Vip],
+            LinkFilter::new().into_predicate(),
+        )
+        .into_predicate()
+}
+
+async fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let channel_container = ChannelContainer::new(Box::new(|_channel, builder| {
+        builder.register_persisted_type::<ChannelConfig>();
+        builder.register_persisted_type::<Quotes>();
+        builder.register_persisted_type::<Counters>();
+        builder.register_persisted_type::<Reminders>();
+        builder.register_persisted_type::<Scripts>();
+        builder.register_persisted_type::<BannedPhrases>();
+        builder.register_persisted_type::<Strikes>();
+        builder.register_persisted_type::<IgnoredUsers>();
+        builder.register_persisted_type::<FilterSettings>();
+        builder.set(Permits::default());
+        builder.set(ModerationLog::default());
+    }))
+    .with_data_dir(config.data_dir());
+    channel_container.prewarm(&config.channels).await;
+    let bot = ChatBot::from_config(config)?
+        .with_command_processor(ChannelConfigCommands)
+        .with_command_processor(QuoteCommands)
+        .with_command_processor(CounterCommands)
+        .with_command_processor(ReminderCommands)
+        .with_command_processor(CommandStatsCommands)
+        .with_command_processor(BannedPhraseCommands)
+        .with_command_processor(PermitCommands)
+        .with_command_processor(StrikeCommands)
+        .with_command_processor(ModerationLogCommands)
+        .with_command_processor(IgnoreCommands)
+        .with_command_processor(FilterSettingsCommands)
+        .with_command_processor(ScriptCommands::new())
+        // scripts can not replace the built-in commands
+        .with_command_processor_priority(-1, ScriptProcessor::new())
+        .try_filter(filters())
+        .with_channel_state(&channel_container);
+    #[cfg(not(feature = "watch"))]
+    let result = bot.run(std::iter::empty()).await;
+    #[cfg(feature = "watch")]
+    let result = {
+        let watch = async {
+            if let Err(e) = channel_container.watch().await {
+                log::error!("Error watching the data directory: {}", e);
+            }
+            std::future::pending().await
+        };
+        let run = bot.run(std::iter::empty());
+        futures_util::future::select(Box::pin(run), Box::pin(watch))
+            .await
+            .factor_first()
+            .0
+    };
+    channel_container.flush().await;
+    result
+}
+
+fn main() -> ExitCode {
+    init_logger();
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_CONFIG.to_owned());
+    let config = match Config::load(&path) {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            log::error!("Error starting the runtime: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match runtime.block_on(run(config)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            log::error!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,35 +1,54 @@
-use crate::command::CommandProcessor;
+use crate::audit::{AuditEntry, AuditLog};
+use crate::command::{
+    CommandMetrics, CommandProcessor, CommandProcessors, RateLimitDecision, UserRateLimit,
+};
+use crate::command_stats::record_command;
+use crate::config::{Config, ConfigError};
+#[cfg(feature = "eventsub")]
+use crate::eventsub::{
+    listen_redemptions, EventSubConfig, Redemption, RedemptionProcessor, RedemptionRequest,
+};
+use crate::greeting::{GreetedUsers, Greeter};
+use crate::ignore::is_ignored;
+use crate::locale;
+use crate::moderation::{record_filtered, record_sent_command, ModerationLog};
+use crate::notification::{Notification, NotificationSink};
+use crate::platform::{
+    ChatEvent, ChatMessage, ChatPlatform, ChatWriter, ShardedPlatform, TwitchPlatform,
+};
+use crate::reminders::deliver_reminders;
 use crate::request::{
-    Bot, Channel, Command, CommandRequest, FilterPredicate, FilterRequest, FromCommandRequest,
-    Sender,
+    try_predicate, Bot, Channel, Command, CommandRequest, FilterErrorPolicy, FilterNotice,
+    FilterPredicate, FilterRequest, Sender, TryFilterPredicate,
 };
-use crate::response::Responder;
+use crate::response::{DeferredResponse, RequestResponder, RespondLater, Responder, Response};
 use crate::state::{
-    CachedChannelContainer, ChannelChatters, ChannelContainer, ChannelState, ChannelStateError,
+    has_command_prefix, prefixed_command, read_channel_config, CachedChannelContainer,
+    ChannelChatters, ChannelConfig, ChannelContainer, ChannelState, ChannelStateError,
+    CrossChannel, DataDir, GreetingScope, LazyState, Persisted,
 };
-use crate::user::User;
+use crate::status::BotStatus;
+use crate::user::{NameMatching, User, UserArgument, UserId, UserLookup};
+#[cfg(feature = "http")]
+use crate::webhook::{serve_webhooks, WebhookCommand, WebhookConfig};
 use async_trait::async_trait;
 use derive_more::{Deref, From};
 use fmt::Display;
-use futures_io::{AsyncRead, AsyncWrite};
+use futures_util::future::{select, try_join, Either};
+use futures_util::stream::{FuturesUnordered, StreamExt};
 use state::TypeMap;
-use std::convert::TryFrom;
-use std::convert::TryInto;
+use std::any::TypeId;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::fmt;
-use std::io::Write;
+use std::future::Future;
+use std::pin::pin;
 use std::sync::Arc;
-use tokio_compat_02::FutureExt;
-use twitchchat::commands::privmsg;
-use twitchchat::connector::Connector;
-use twitchchat::messages::{ClearChat, Commands};
-use twitchchat::messages::{ClearMsg, Privmsg};
-use twitchchat::runner::Identity;
-use twitchchat::writer::AsyncWriter;
-use twitchchat::writer::MpscWriter;
-use twitchchat::AsyncRunner;
-use twitchchat::Encodable;
-use twitchchat::Status;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender, WeakUnboundedSender};
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+use twitchchat::connector::tokio::ConnectorRustTls;
 use twitchchat::UserConfig;
 
 #[derive(Debug, Clone, Deref, From)]
@@ -38,6 +57,8 @@ pub struct State<'req, T: Send + Sync + 'static>(&'req T);
 pub enum StateError {
     NoContext,
     NoValue(&'static str),
+    /// The state was registered already with [`ChatBot::try_with_state`].
+    AlreadySet(&'static str),
 }
 
 impl Display for StateError {
@@ -45,40 +66,28 @@ impl Display for StateError {
         match self {
             StateError::NoContext => write!(f, "CommandRequest is missing context"),
             StateError::NoValue(type_name) => write!(f, "No value set for type {type_name}"),
+            StateError::AlreadySet(type_name) => {
+                write!(f, "A value was set already for type {type_name}")
+            }
         }
     }
 }
 
 impl std::error::Error for StateError {}
 
-impl<'a, 'req, T: Send + Sync + 'static> FromCommandRequest<'a, 'req> for State<'req, T> {
-    type Error = StateError;
-
-    fn from_command_request(request: &'a CommandRequest<'req>) -> Result<Self, Self::Error> {
-        request.context.ok_or(StateError::NoContext)?.state()
-    }
-}
-
-impl<'a, 'req, T: Send + Sync + 'static> FromCommandRequest<'a, 'req> for ChannelState<'req, T> {
-    type Error = ChannelStateError;
-
-    fn from_command_request(request: &'a CommandRequest<'req>) -> Result<Self, Self::Error> {
-        request
-            .context
-            .ok_or(ChannelStateError::NoContext)?
-            .channel_state()
-    }
-}
-
 #[derive(Clone)]
 pub(crate) struct ChatBotContext<'req> {
     container: &'req TypeMap![Send + Sync],
     channel_container: Option<&'req TypeMap![Send + Sync]>,
+    channels: Option<&'req ChannelContainer>,
     chatters: &'req ChannelChatters,
+    deferred: Option<&'req UnboundedSender<DeferredResponse>>,
+    message_id: Option<&'req str>,
+    language: Option<&'req str>,
 }
 
 impl<'req> ChatBotContext<'req> {
-    fn new(
+    pub(crate) fn new(
         container: &'req TypeMap![Send + Sync],
         channel_container: Option<&'req TypeMap![Send + Sync]>,
         chatters: &'req ChannelChatters,
@@ -86,10 +95,49 @@ impl<'req> ChatBotContext<'req> {
         Self {
             container,
             channel_container,
+            channels: None,
             chatters,
+            deferred: None,
+            message_id: None,
+            language: None,
+        }
+    }
+
+    pub(crate) fn with_channels(self, channels: Option<&'req ChannelContainer>) -> Self {
+        Self { channels, ..self }
+    }
+
+    pub(crate) fn with_language(self, language: Option<&'req str>) -> Self {
+        Self { language, ..self }
+    }
+
+    /// The language of the channel, see [`locale`](crate::locale).
+    pub fn language(&self) -> Option<&'req str> {
+        self.language
+    }
+
+    fn with_deferred(
+        self,
+        deferred: &'req UnboundedSender<DeferredResponse>,
+        message_id: Option<&'req str>,
+    ) -> Self {
+        Self {
+            deferred: Some(deferred),
+            message_id,
+            ..self
         }
     }
 
+    pub fn respond_later(&self, channel: &Channel<'_>) -> Option<RespondLater> {
+        self.deferred.map(|deferred| {
+            RespondLater::new(
+                deferred.clone(),
+                channel.username().to_owned(),
+                self.message_id.map(str::to_owned),
+            )
+        })
+    }
+
     pub fn chatters(&self) -> ChannelChatters {
         self.chatters.clone()
     }
@@ -110,6 +158,12 @@ impl<'req> ChatBotContext<'req> {
             .ok_or_else(|| ChannelStateError::NoValue(std::any::type_name::<T>()))
             .map(ChannelState::from)
     }
+
+    pub fn cross_channel(&self) -> Result<CrossChannel<'req>, ChannelStateError> {
+        self.channels
+            .map(CrossChannel::new)
+            .ok_or(ChannelStateError::NoChannelContainer)
+    }
 }
 
 impl<'req> std::fmt::Debug for ChatBotContext<'req> {
@@ -118,282 +172,834 @@ impl<'req> std::fmt::Debug for ChatBotContext<'req> {
     }
 }
 
-pub struct ChatBot<'a, C, P> {
-    connector: C,
-    command_processor: P,
-    user_config: &'a UserConfig,
-    container: TypeMap![Send + Sync],
+/// Receives the channel and the response instead of the chat when the chat bot runs in dry run mode.
+pub type DryRunSink = Arc<dyn Fn(&str, &Response<'_>) + Send + Sync>;
+
+/// Called for commands none of the command processors responded to.
+pub type UnknownCommandHandler =
+    Box<dyn for<'req> Fn(&CommandRequest<'req>) -> Option<Response<'static>> + Send + Sync>;
+
+/// Called after the bot connected to the chat.
+pub type ConnectHook = Box<dyn Fn(&Bot<'_>) + Send + Sync>;
+
+/// Called when the connection ended, with the error if it was lost.
+pub type DisconnectHook = Box<dyn Fn(Option<&dyn Error>) + Send + Sync>;
+
+/// Called after the bot joined a channel, the response is sent to the channel.
+pub type JoinHook = Box<dyn Fn(&str) -> Option<Response<'static>> + Send + Sync>;
+
+/// Called after the bot connected again with the same [`BotStatus`], with the number of reconnects.
+pub type ReconnectHook = Box<dyn Fn(u64) + Send + Sync>;
+
+#[derive(Default)]
+struct LifecycleHooks {
+    connect: Option<ConnectHook>,
+    disconnect: Option<DisconnectHook>,
+    join: Option<JoinHook>,
+    reconnect: Option<ReconnectHook>,
+}
+
+/// Twitch allows 20 messages per 30 seconds for users which are not moderators.
+const DEFAULT_DEFERRED_RESPONSE_INTERVAL: Duration = Duration::from_millis(1500);
+
+/// Commands of a user are ignored this long after one of their messages was deleted by default.
+const DEFAULT_SUPPRESSION_WINDOW: Duration = Duration::from_secs(2);
+
+/// How many messages are handled at the same time by default.
+const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Which messages are dropped if a channel has more queued messages than the capacity set with
+/// [`ChatBot::intake_capacity`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IntakePolicy {
+    /// Drops the received message.
+    DropNewest,
+    /// Drops the oldest queued message which is not a command, such that the bot keeps up with
+    /// the chat.
+    #[default]
+    DropOldest,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Intake {
+    capacity: usize,
+    policy: IntakePolicy,
+}
+
+/// How many message ids of each channel are remembered to skip messages which were delivered
+/// again after a reconnect.
+const SEEN_MESSAGES_PER_CHANNEL: usize = 500;
+
+/// Sets registered state in the container when the bot starts running.
+type RegisteredState = Box<dyn FnOnce(&TypeMap![Send + Sync]) + Send + Sync>;
+
+pub struct ChatBot<'a, C> {
+    platform: C,
+    command_processors: CommandProcessors,
+    states: HashMap<TypeId, RegisteredState>,
     channel_container: Option<&'a ChannelContainer>,
     chatters: ChannelChatters,
     ignore_self: bool,
-    filter: Option<FilterPredicate>,
+    filter: Option<TryFilterPredicate>,
+    filter_error_policy: FilterErrorPolicy,
+    chatters_snapshot_interval: Option<Duration>,
+    reminder_interval: Option<Duration>,
+    deferred_response_interval: Duration,
+    dry_run: Option<DryRunSink>,
+    notification_sink: Option<SharedNotificationSink>,
+    unknown_command: Option<UnknownCommandHandler>,
+    rate_limit: Option<UserRateLimit>,
+    suppression_window: Duration,
+    ignored_users: HashSet<String>,
+    echo_sent_messages: bool,
+    concurrency: usize,
+    intake: Option<Intake>,
+    prefix: Option<String>,
+    channels: Vec<String>,
+    status: BotStatus,
+    hooks: LifecycleHooks,
+    #[cfg(feature = "http")]
+    webhooks: Option<WebhookConfig>,
+    #[cfg(feature = "eventsub")]
+    eventsub: Option<EventSubConfig>,
 }
 
-impl<'a, C> ChatBot<'a, C, ()> {
+impl<'a, C> ChatBot<'a, TwitchPlatform<'a, C>> {
+    /// Creates a chat bot for Twitch chat.
     pub fn new(connector: C, user_config: &'a UserConfig) -> Self {
+        Self::with_platform(TwitchPlatform::new(connector, user_config))
+    }
+}
+
+impl<'a, C> ChatBot<'a, ShardedPlatform<TwitchPlatform<'a, C>>> {
+    /// Creates a chat bot for Twitch chat with one connection for each connector, which join at
+    /// most `channels_per_shard` channels each, see [`ShardedPlatform`].
+    pub fn sharded<I>(connectors: I, user_config: &'a UserConfig, channels_per_shard: usize) -> Self
+    where
+        I: IntoIterator<Item = C>,
+    {
+        let shards = connectors
+            .into_iter()
+            .map(|connector| TwitchPlatform::new(connector, user_config))
+            .collect();
+        Self::with_platform(ShardedPlatform::new(shards, channels_per_shard))
+    }
+}
+
+impl ChatBot<'static, TwitchPlatform<'static, ConnectorRustTls>> {
+    /// Creates a chat bot for Twitch chat which joins the channels of the config and has its
+    /// prefix and features, see [`Config`].
+    pub fn from_config(config: Config) -> Result<Self, ConfigError> {
+        let user_config = config.user_config()?;
+        let data_dir = config.data_dir();
+        let connector = ConnectorRustTls::twitch().map_err(ConfigError::Connector)?;
+        let mut bot =
+            Self::with_platform(TwitchPlatform::with_owned_config(connector, user_config))
+                .join_channels(config.channels)
+                .with_data_dir(data_dir.clone());
+        bot.prefix = config.prefix;
+        if let Some(key) = config.encryption_key {
+            #[cfg(feature = "encryption")]
+            {
+                let key = crate::state::EncryptionKey::from_hex(&key)
+                    .map_err(|e| ConfigError::InvalidEncryptionKey(e.to_string()))?;
+                if !crate::state::set_encryption_key(key) {
+                    log::warn!("The encryption key was set already, ignoring encryption_key");
+                }
+            }
+            #[cfg(not(feature = "encryption"))]
+            {
+                let _ = key;
+                log::warn!("Ignoring encryption_key, since the encryption feature is disabled");
+            }
+        }
+        let features = config.features;
+        bot.ignore_self = !features.process_self;
+        if features.dry_run {
+            bot = bot.dry_run();
+        }
+        if let Some(limit) = features.concurrency {
+            bot = bot.concurrency(limit);
+        }
+        if let Some(interval) = features.persist_chatters {
+            bot = bot.persist_chatters(interval);
+        }
+        if features.name_matching != NameMatching::default() {
+            bot = bot.name_matching(features.name_matching);
+        }
+        bot = bot.with_ignored_users(config.ignored_users);
+        if features.echo_sent_messages {
+            bot = bot.echo_sent_messages();
+        }
+        if features.audit_log {
+            bot = bot.with_audit_log(AuditLog::new().in_dir(data_dir.path()));
+        }
+        if let Some(interval) = features.reminders {
+            bot = bot.with_reminders(interval);
+        }
+        Ok(bot)
+    }
+}
+
+impl<'a, C> ChatBot<'a, C> {
+    /// Creates a chat bot for any chat, see [`ChatPlatform`].
+    pub fn with_platform(platform: C) -> Self {
         Self {
-            connector,
-            command_processor: (),
-            user_config,
-            container: <TypeMap![Send + Sync]>::new(),
+            platform,
+            command_processors: CommandProcessors::new(),
+            states: HashMap::new(),
             channel_container: Option::<&'a ChannelContainer>::None,
             chatters: ChannelChatters::new(),
             ignore_self: true,
             filter: None,
+            filter_error_policy: FilterErrorPolicy::default(),
+            chatters_snapshot_interval: None,
+            reminder_interval: None,
+            deferred_response_interval: DEFAULT_DEFERRED_RESPONSE_INTERVAL,
+            dry_run: None,
+            notification_sink: None,
+            unknown_command: None,
+            rate_limit: None,
+            suppression_window: DEFAULT_SUPPRESSION_WINDOW,
+            ignored_users: HashSet::new(),
+            echo_sent_messages: false,
+            concurrency: DEFAULT_CONCURRENCY,
+            intake: None,
+            prefix: None,
+            channels: Vec::new(),
+            status: BotStatus::new(),
+            hooks: LifecycleHooks::default(),
+            #[cfg(feature = "http")]
+            webhooks: None,
+            #[cfg(feature = "eventsub")]
+            eventsub: None,
         }
     }
 
-    pub fn with_command_processor<P>(self, command_processor: P) -> ChatBot<'a, C, P>
+    /// Adds a command processor with the priority `0`.
+    /// Requests are processed by every processor in order until one of them responds.
+    pub fn with_command_processor<P>(self, command_processor: P) -> Self
     where
-        P: CommandProcessor,
+        P: CommandProcessor + Send + Sync + 'static,
     {
-        ChatBot {
-            connector: self.connector,
-            command_processor,
-            user_config: self.user_config,
-            container: self.container,
-            channel_container: self.channel_container,
-            chatters: self.chatters,
-            ignore_self: self.ignore_self,
-            filter: self.filter,
-        }
+        self.with_command_processor_priority(0, command_processor)
     }
-}
 
-impl<'a, C, P> ChatBot<'a, C, P> {
+    /// Adds a command processor, processors with a higher `priority` are processed first.
+    pub fn with_command_processor_priority<P>(mut self, priority: i32, command_processor: P) -> Self
+    where
+        P: CommandProcessor + Send + Sync + 'static,
+    {
+        self.command_processors.push(priority, command_processor);
+        self
+    }
+
+    /// Registers state which is used with [`RequestParts::state`](crate::request::RequestParts::state).
+    ///
+    /// # Panics
+    ///
+    /// Panics if state of the same type was registered already, use
+    /// [`ChatBot::replace_state`] to replace it.
     pub fn with_state<T: Sync + Send + 'static>(self, state: T) -> Self {
-        self.container.set(state); // TODO: do something if the state was already set
+        match self.try_with_state(state) {
+            Ok(bot) => bot,
+            Err(e) => panic!("{}, use ChatBot::replace_state to replace it", e),
+        }
+    }
+
+    /// Registers state like [`ChatBot::with_state`], but returns an error if state of the same
+    /// type was registered already.
+    pub fn try_with_state<T: Sync + Send + 'static>(self, state: T) -> Result<Self, StateError> {
+        if self.states.contains_key(&TypeId::of::<T>()) {
+            return Err(StateError::AlreadySet(std::any::type_name::<T>()));
+        }
+        Ok(self.replace_state(state))
+    }
+
+    /// Registers state, replacing state of the same type which was registered already.
+    pub fn replace_state<T: Sync + Send + 'static>(mut self, state: T) -> Self {
+        self.states.insert(
+            TypeId::of::<T>(),
+            Box::new(move |container| {
+                container.set(state);
+            }),
+        );
         self
     }
 
+    /// Registers state which is built by `init` the first time it is used, e.g. a client which
+    /// has to connect to an external service. The state is used as [`LazyState<T>`].
+    pub fn with_state_init<T, F, Fut>(self, init: F) -> Self
+    where
+        T: Send + Sync + 'static,
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        self.with_state(LazyState::new(init))
+    }
+
+    /// Sets the fallback used by [`UserResolver`](crate::user::UserResolver) for users
+    /// which did not chat yet.
+    pub fn with_user_lookup<L: UserLookup + 'static>(self, lookup: L) -> Self {
+        self.with_state::<Arc<dyn UserLookup>>(Arc::new(lookup))
+    }
+
+    /// Sets the metrics which record every command handled by a generated processor.
+    pub fn with_command_metrics<M: CommandMetrics + 'static>(self, metrics: M) -> Self {
+        self.with_state::<Arc<dyn CommandMetrics>>(Arc::new(metrics))
+    }
+
+    /// Writes every command answered by a command processor to the audit log.
+    pub fn with_audit_log(self, audit_log: AuditLog) -> Self {
+        self.with_state(audit_log)
+    }
+
     pub fn with_channel_state<'b, 'c: 'b>(
         self,
         channel_container: &'c ChannelContainer,
-    ) -> ChatBot<'b, C, P>
+    ) -> ChatBot<'b, C>
     where
         'a: 'b,
     {
         ChatBot {
-            connector: self.connector,
-            command_processor: self.command_processor,
-            user_config: self.user_config,
-            container: self.container,
+            platform: self.platform,
+            command_processors: self.command_processors,
+            states: self.states,
             channel_container: Some(channel_container),
             chatters: self.chatters,
             ignore_self: self.ignore_self,
             filter: self.filter,
+            filter_error_policy: self.filter_error_policy,
+            chatters_snapshot_interval: self.chatters_snapshot_interval,
+            reminder_interval: self.reminder_interval,
+            deferred_response_interval: self.deferred_response_interval,
+            dry_run: self.dry_run,
+            notification_sink: self.notification_sink,
+            unknown_command: self.unknown_command,
+            rate_limit: self.rate_limit,
+            suppression_window: self.suppression_window,
+            ignored_users: self.ignored_users,
+            echo_sent_messages: self.echo_sent_messages,
+            concurrency: self.concurrency,
+            intake: self.intake,
+            prefix: self.prefix,
+            channels: self.channels,
+            status: self.status,
+            hooks: self.hooks,
+            #[cfg(feature = "http")]
+            webhooks: self.webhooks,
+            #[cfg(feature = "eventsub")]
+            eventsub: self.eventsub,
         }
     }
 
-    pub fn process_self<'b, 'c: 'b>(self) -> ChatBot<'b, C, P>
+    pub fn process_self<'b, 'c: 'b>(self) -> ChatBot<'b, C>
     where
         'a: 'b,
     {
         ChatBot {
-            connector: self.connector,
-            command_processor: self.command_processor,
-            user_config: self.user_config,
-            container: self.container,
+            platform: self.platform,
+            command_processors: self.command_processors,
+            states: self.states,
             channel_container: self.channel_container,
             chatters: self.chatters,
             ignore_self: false,
             filter: self.filter,
+            filter_error_policy: self.filter_error_policy,
+            chatters_snapshot_interval: self.chatters_snapshot_interval,
+            reminder_interval: self.reminder_interval,
+            deferred_response_interval: self.deferred_response_interval,
+            dry_run: self.dry_run,
+            notification_sink: self.notification_sink,
+            unknown_command: self.unknown_command,
+            rate_limit: self.rate_limit,
+            suppression_window: self.suppression_window,
+            ignored_users: self.ignored_users,
+            echo_sent_messages: self.echo_sent_messages,
+            concurrency: self.concurrency,
+            intake: self.intake,
+            prefix: self.prefix,
+            channels: self.channels,
+            status: self.status,
+            hooks: self.hooks,
+            #[cfg(feature = "http")]
+            webhooks: self.webhooks,
+            #[cfg(feature = "eventsub")]
+            eventsub: self.eventsub,
         }
     }
 
-    pub fn filter<'b, 'c: 'b>(self, predicate: FilterPredicate) -> ChatBot<'b, C, P>
+    /// Deletes the messages for which `predicate` returns a [`FilterDecision`](crate::request::FilterDecision)
+    /// with [`FilterAction::Delete`](crate::request::FilterAction::Delete), which are logged,
+    /// recorded in the [`ModerationLog`] and followed by the notice of the decision.
+    pub fn filter<'b, 'c: 'b>(self, predicate: FilterPredicate) -> ChatBot<'b, C>
+    where
+        'a: 'b,
+    {
+        self.try_filter(try_predicate(predicate))
+    }
+
+    /// Like [`ChatBot::filter`] for a predicate which can fail. Errors are
+    /// logged and sent to the notification sink, and the message is handled according to the
+    /// [`FilterErrorPolicy`], see [`ChatBot::filter_error_policy`].
+    pub fn try_filter<'b, 'c: 'b>(self, predicate: TryFilterPredicate) -> ChatBot<'b, C>
     where
         'a: 'b,
     {
         ChatBot {
-            connector: self.connector,
-            command_processor: self.command_processor,
-            user_config: self.user_config,
-            container: self.container,
+            platform: self.platform,
+            command_processors: self.command_processors,
+            states: self.states,
             channel_container: self.channel_container,
             chatters: self.chatters,
             ignore_self: self.ignore_self,
             filter: Some(predicate),
+            filter_error_policy: self.filter_error_policy,
+            chatters_snapshot_interval: self.chatters_snapshot_interval,
+            reminder_interval: self.reminder_interval,
+            deferred_response_interval: self.deferred_response_interval,
+            dry_run: self.dry_run,
+            notification_sink: self.notification_sink,
+            unknown_command: self.unknown_command,
+            rate_limit: self.rate_limit,
+            suppression_window: self.suppression_window,
+            ignored_users: self.ignored_users,
+            echo_sent_messages: self.echo_sent_messages,
+            concurrency: self.concurrency,
+            intake: self.intake,
+            prefix: self.prefix,
+            channels: self.channels,
+            status: self.status,
+            hooks: self.hooks,
+            #[cfg(feature = "http")]
+            webhooks: self.webhooks,
+            #[cfg(feature = "eventsub")]
+            eventsub: self.eventsub,
         }
     }
 
-    pub fn chatters(&self) -> ChannelChatters {
-        self.chatters.clone()
+    /// Decides what happens to messages if the filter failed, by default they are allowed.
+    pub fn filter_error_policy(mut self, policy: FilterErrorPolicy) -> Self {
+        self.filter_error_policy = policy;
+        self
     }
-}
 
-#[derive(Debug)]
-pub enum IdentityError {
-    Anonymous,
-}
+    /// Restores known chatters on startup and writes them to disk every `interval` and on shutdown.
+    pub fn persist_chatters(mut self, interval: Duration) -> Self {
+        self.chatters_snapshot_interval = Some(interval);
+        self
+    }
 
-impl fmt::Display for IdentityError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Anonymous user: No username found.")
+    /// Compares the names of chatters with `matching`, e.g. when looking up a user mentioned in a
+    /// command.
+    pub fn name_matching(mut self, matching: NameMatching) -> Self {
+        self.chatters = ChannelChatters::with_name_matching(matching)
+            .with_data_dir(self.chatters.data_dir().clone());
+        self
     }
-}
 
-impl Error for IdentityError {}
+    /// Writes the snapshots of the chatters to `data_dir` instead of `data`.
+    ///
+    /// The directories of the persisted state and of the audit log are set with
+    /// [`ChannelContainer::with_data_dir`] and [`AuditLog::in_dir`].
+    pub fn with_data_dir(mut self, data_dir: DataDir) -> Self {
+        self.chatters = self.chatters.with_data_dir(data_dir);
+        self
+    }
 
-impl<'a> TryFrom<&'a Identity> for Bot<'a> {
-    type Error = IdentityError;
-    fn try_from(value: &'a Identity) -> Result<Self, Self::Error> {
-        match value {
-            Identity::Anonymous { .. } => Err(IdentityError::Anonymous),
-            Identity::Basic { name, .. } => Ok(User::from_username(name).into()),
-            Identity::Full {
-                name,
-                user_id,
-                display_name,
-                ..
-            } => Ok(User::new(name, display_name.as_deref(), Some(*user_id)).into()),
-        }
+    /// Delivers the [`reminders`](crate::reminders) of the joined channels, which are checked every `interval`.
+    /// Reminders are only delivered if the channel state is used.
+    pub fn with_reminders(mut self, interval: Duration) -> Self {
+        self.reminder_interval = Some(interval);
+        self
     }
-}
 
-impl<'a> From<&'a UserConfig> for Bot<'a> {
-    fn from(value: &'a UserConfig) -> Self {
-        User::from_username(&value.name).into()
+    /// Sets the minimum time between two responses sent through [`RespondLater`] to the same channel.
+    pub fn deferred_response_interval(mut self, interval: Duration) -> Self {
+        self.deferred_response_interval = interval;
+        self
     }
-}
 
-#[derive(Debug)]
-pub enum PrivmsgCommandError {
-    DoesNotStartWithBang,
-}
+    /// Ignores the commands of a user for `window` after one of their messages was deleted or they
+    /// were timed out, e.g. by the filter, so commands sent right after a deleted message do not get
+    /// a response. `Duration::ZERO` turns this off.
+    pub fn suppression_window(mut self, window: Duration) -> Self {
+        self.suppression_window = window;
+        self
+    }
 
-impl fmt::Display for PrivmsgCommandError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Command does not start with `!`.")
+    /// Ignores the messages of the users in all channels, e.g. other bots. Their messages are
+    /// neither filtered nor processed as commands. See [`ignore`](crate::ignore) for ignoring
+    /// users in a single channel.
+    pub fn with_ignored_users<I, S>(mut self, usernames: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.ignored_users.extend(
+            usernames
+                .into_iter()
+                .map(|username| username.as_ref().to_lowercase()),
+        );
+        self
     }
-}
 
-impl Error for PrivmsgCommandError {}
+    /// Handles the messages sent by the bot like received messages, which are not sent back by the
+    /// chat. They are tracked by the chatters, e.g. as the last message of the bot, and timeouts and
+    /// bans sent by commands are recorded in the
+    /// [`ModerationLog`](crate::moderation::ModerationLog) of the channel.
+    /// Messages logged with [`ChatBot::dry_run`] are not echoed.
+    pub fn echo_sent_messages(mut self) -> Self {
+        self.echo_sent_messages = true;
+        self
+    }
 
-impl<'a> TryFrom<&'a Privmsg<'_>> for Command<'a> {
-    type Error = PrivmsgCommandError;
-    fn try_from(message: &'a Privmsg) -> Result<Self, Self::Error> {
-        let data = message.data().trim_start();
-        if data.starts_with('!') {
-            Ok(data.into())
-        } else {
-            Err(PrivmsgCommandError::DoesNotStartWithBang)
-        }
+    /// Logs responses instead of sending them to the chat, which allows testing commands against real chat.
+    pub fn dry_run(self) -> Self {
+        self.dry_run_with(|channel, response| {
+            if let Some(text) = response_text(response) {
+                log::info!("[dry run] #{}: {}", channel, text);
+            }
+        })
     }
-}
 
-impl<'a> From<&'a Privmsg<'_>> for Sender<'a> {
-    fn from(value: &'a Privmsg) -> Self {
-        let user_id = value.user_id().and_then(|value| value.try_into().ok()); // TODO: user_id is u64 instead of i64
-        Sender::new(
-            User::new(value.name(), value.display_name(), user_id),
-            value.is_moderator(),
-            value.is_broadcaster(),
-        )
+    /// Passes responses to `sink` instead of sending them to the chat.
+    pub fn dry_run_with<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(&str, &Response<'_>) + Send + Sync + 'static,
+    {
+        self.dry_run = Some(Arc::new(sink));
+        self
+    }
+
+    /// Mirrors responses and moderation events to `sink`, e.g. a [`DiscordWebhook`](crate::notification).
+    pub fn with_notification_sink<S>(mut self, sink: S) -> Self
+    where
+        S: NotificationSink + Send + Sync + 'static,
+    {
+        self.notification_sink = Some(Arc::new(sink));
+        self
     }
-}
 
-impl<'a> From<&'a Privmsg<'_>> for Channel<'a> {
-    fn from(value: &'a Privmsg) -> Self {
-        let user_id = value.room_id().and_then(|value| value.try_into().ok()); // TODO: user_id is u64 instead of i64
-        User::new(value.channel().trim_start_matches('#'), None, user_id).into()
+    /// Calls `handler` for messages starting with `!` which none of the command processors responded to,
+    /// e.g. to reply with `unknown command, try !help`.
+    pub fn on_unknown_command<F>(mut self, handler: F) -> Self
+    where
+        F: for<'req> Fn(&CommandRequest<'req>) -> Option<Response<'static>> + Send + Sync + 'static,
+    {
+        self.unknown_command = Some(Box::new(handler));
+        self
     }
-}
 
-impl<'a> From<&'a ClearChat<'_>> for Channel<'a> {
-    fn from(value: &'a ClearChat) -> Self {
-        let user_id = value.room_id().and_then(|value| value.parse().ok()); // TODO: user_id is u64 instead of i64
-        User::new(value.channel().trim_start_matches('#'), None, user_id).into()
+    /// Limits how many commands a single user can trigger, e.g.
+    /// `UserRateLimit::new(5, Duration::from_secs(30)).action(RateLimitAction::Warn)`.
+    pub fn rate_limit_users(mut self, rate_limit: UserRateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
     }
+
+    /// Replaces `!` as the prefix of commands in channels without a prefix in their [`ChannelConfig`].
+    pub fn with_prefix<P: Into<String>>(mut self, prefix: P) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Joins `channels` before the channels passed to [`run`](Self::run).
+    pub fn join_channels<I>(mut self, channels: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.channels.extend(channels.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets how many messages are handled at the same time.
+    /// Messages of the same channel are always handled in the order they were received.
+    pub fn concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = limit.max(1);
+        self
+    }
+
+    /// Queues at most `capacity` messages of each channel while the messages before them are
+    /// handled, the messages above the capacity which are not commands are dropped according to
+    /// `policy`. Commands and moderation events are never dropped. The dropped messages are counted
+    /// by the [`BotStatus`].
+    pub fn intake_capacity(mut self, capacity: usize, policy: IntakePolicy) -> Self {
+        self.intake = Some(Intake { capacity, policy });
+        self
+    }
+
+    pub fn chatters(&self) -> ChannelChatters {
+        self.chatters.clone()
+    }
+
+    /// Uses `status` instead of a new status, e.g. to count reconnects when the bot is started again.
+    pub fn with_status(mut self, status: BotStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// The status of the bot, which is also available to commands as [`State<BotStatus>`](State).
+    /// It can be served over HTTP with `status::serve_health` when the `health` feature is enabled.
+    pub fn status(&self) -> BotStatus {
+        self.status.clone()
+    }
+
+    /// Processes commands received through webhooks, see [`webhook`](crate::webhook).
+    #[cfg(feature = "http")]
+    pub fn with_webhooks(mut self, config: WebhookConfig) -> Self {
+        self.webhooks = Some(config);
+        self
+    }
+
+    /// Processes channel point redemptions, see [`eventsub`](crate::eventsub).
+    #[cfg(feature = "eventsub")]
+    pub fn with_eventsub(mut self, config: EventSubConfig) -> Self {
+        self.eventsub = Some(config);
+        self
+    }
+
+    pub fn on_connect<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Bot<'_>) + Send + Sync + 'static,
+    {
+        self.hooks.connect = Some(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` when [`run`](Self::run) stops after it connected, e.g. to page an operator.
+    pub fn on_disconnect<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(Option<&dyn Error>) + Send + Sync + 'static,
+    {
+        self.hooks.disconnect = Some(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` for every joined channel, e.g. to announce `back online`.
+    pub fn on_join<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&str) -> Option<Response<'static>> + Send + Sync + 'static,
+    {
+        self.hooks.join = Some(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` when the bot connects again, which requires passing the status of the previous
+    /// run to [`with_status`](Self::with_status).
+    pub fn on_reconnect<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(u64) + Send + Sync + 'static,
+    {
+        self.hooks.reconnect = Some(Box::new(hook));
+        self
+    }
+}
+
+/// A message sent by the bot, see [`ChatBot::echo_sent_messages`].
+struct SentMessage {
+    channel: String,
+    text: String,
+    /// Moderation commands of filters are recorded by the filters themselves.
+    from_filter: bool,
 }
 
-impl<'a> From<&'a ClearMsg<'_>> for Channel<'a> {
-    fn from(value: &'a ClearMsg) -> Self {
-        let user_id = value.tags().get_parsed("room-id"); // TODO: user_id is u64 instead of i64
-        User::new(value.channel().trim_start_matches('#'), None, user_id).into()
+/// A message which is handled in order with the other messages of its channel.
+enum ChannelMessage {
+    Chat(ChatEvent),
+    Sent(SentMessage),
+    #[cfg(feature = "http")]
+    Webhook(WebhookCommand),
+    #[cfg(feature = "eventsub")]
+    Redemption(Redemption),
+}
+
+impl ChannelMessage {
+    fn channel(&self) -> &str {
+        match self {
+            ChannelMessage::Chat(event) => event.channel().unwrap_or_default(),
+            ChannelMessage::Sent(message) => &message.channel,
+            #[cfg(feature = "http")]
+            ChannelMessage::Webhook(command) => &command.channel,
+            #[cfg(feature = "eventsub")]
+            ChannelMessage::Redemption(redemption) => redemption.channel.username(),
+        }
     }
 }
 
-struct MessageHandler<'msg, P> {
+struct MessageHandler<'msg> {
     bot: &'msg Bot<'msg>,
     containers: Containers<'msg>,
-    command_processor: &'msg P,
-    writer: AsyncWriter<MpscWriter>,
+    command_processors: &'msg CommandProcessors,
+    writer: Arc<dyn ChatWriter + Send + Sync>,
+    deferred: UnboundedSender<DeferredResponse>,
+    dry_run: Option<DryRunSink>,
+    notification_sink: Option<SharedNotificationSink>,
+    unknown_command: Option<&'msg UnknownCommandHandler>,
+    rate_limit: Option<&'msg UserRateLimit>,
     chatters: ChannelChatters,
+    status: BotStatus,
     ignore_self: bool,
-    filter: Option<FilterPredicate>,
+    prefix: Option<String>,
+    suppression_window: Duration,
+    ignored_users: HashSet<String>,
+    /// Receives the messages sent by the bot, see [`ChatBot::echo_sent_messages`].
+    echo: Option<WeakUnboundedSender<ChannelMessage>>,
+    /// The ids of the last messages of each channel, the oldest first.
+    seen: std::sync::Mutex<HashMap<String, VecDeque<String>>>,
+    /// When the last message of a user was deleted, by channel and username.
+    suppressed: std::sync::Mutex<HashMap<(String, String), Instant>>,
+    /// The prefix in the config of each channel when it was read last, see [`Self::may_be_command`].
+    channel_prefixes: std::sync::Mutex<HashMap<String, Option<String>>>,
+    filter: Option<tokio::sync::Mutex<TryFilterPredicate>>,
+    filter_error_policy: FilterErrorPolicy,
+    // messages waiting for the message before them in the same channel
+    queues: std::sync::Mutex<HashMap<String, VecDeque<ChannelMessage>>>,
+    intake: Option<Intake>,
+    concurrency: Semaphore,
+    greeter: Greeter,
+    #[cfg(feature = "eventsub")]
+    redemption_processors: &'msg [Box<dyn RedemptionProcessor + Send + Sync>],
 }
 
-pub struct PrivmsgReply<'a> {
-    pub(crate) reply_to: &'a Privmsg<'a>,
-    pub(crate) msg: &'a str,
+/// The text which can be sent for the response.
+fn response_text<'r>(response: &'r Response<'_>) -> Option<&'r str> {
+    response
+        .response()
+        // TODO: check if filter is necessary
+        .filter(|response_text| response.command() || !response_text.trim_start().starts_with('/'))
+        .filter(|response_text| response.command() || !response_text.trim_start().starts_with('.'))
+        .filter(|response_text| !response_text.is_empty() && !response_text.trim().is_empty())
 }
 
-macro_rules! write_nl {
-    ($w:expr, $fmt:expr, $($args:expr),* $(,)?) => {{
-        write!($w, $fmt, $($args),*)?;
-        write!($w, "\r\n")
-    }};
+/// Commands like `.timeout` can only be sent by responses which are [`Response::as_command`].
+fn is_chat_command(text: &str) -> bool {
+    let text = text.trim_start();
+    text.starts_with('/') || text.starts_with('.')
 }
 
-impl<'a> Encodable for PrivmsgReply<'a> {
-    fn encode<W>(&self, buf: &mut W) -> std::io::Result<()>
-    where
-        W: Write + ?Sized,
-    {
-        log::trace!("reply message");
-        if !self.msg.trim_start().starts_with(|c| c == '.' || c == '/') {
-            // do not reply when using a twitch command
-            if let Some(id) = self.reply_to.tags().get("id") {
-                // find message id to reply to
-                return write_nl!(
-                    buf,
-                    "@reply-parent-msg-id={} PRIVMSG {} :{}",
-                    id,
-                    twitchchat::commands::Channel::new(self.reply_to.channel()),
-                    self.msg
-                );
+type SharedNotificationSink = Arc<dyn NotificationSink + Send + Sync>;
+
+/// Passes the notification to the sink without waiting for it.
+fn notify(sink: Option<&SharedNotificationSink>, notification: Notification) {
+    if let Some(sink) = sink {
+        let sink = sink.clone();
+        tokio::spawn(async move {
+            if let Err(e) = sink.notify(&notification).await {
+                log::error!("Error sending notification: {:?}", e);
             }
-        }
-        write_nl!(
-            buf,
-            "PRIVMSG {} :{}",
-            twitchchat::commands::Channel::new(self.reply_to.channel()),
-            self.msg
-        )
+        });
+    }
+}
+
+/// Queues the message sent by the bot to be handled like a received message, if enabled.
+fn echo(
+    echo: Option<&WeakUnboundedSender<ChannelMessage>>,
+    channel: &str,
+    text: &str,
+    from_filter: bool,
+) {
+    // the weak sender does not keep the bot running after the connection was closed
+    if let Some(echo) = echo.and_then(WeakUnboundedSender::upgrade) {
+        let _ = echo.send(ChannelMessage::Sent(SentMessage {
+            channel: channel.trim_start_matches('#').to_owned(),
+            text: text.to_owned(),
+            from_filter,
+        }));
+    }
+}
+
+/// Aborts a spawned task when it is dropped, such that returning early does not leave it running.
+struct AbortOnDrop<T>(tokio::task::JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
     }
 }
 
-pub const fn privmsg_reply<'a>(reply_to: &'a Privmsg<'a>, msg: &'a str) -> PrivmsgReply<'a> {
-    PrivmsgReply { reply_to, msg }
+/// Writes the responses sent through [`RespondLater`], at most one per `interval` and channel.
+async fn write_deferred_responses(
+    mut receiver: UnboundedReceiver<DeferredResponse>,
+    writer: Arc<dyn ChatWriter + Send + Sync>,
+    interval: Duration,
+    dry_run: Option<DryRunSink>,
+    notification_sink: Option<SharedNotificationSink>,
+    echo_sender: Option<WeakUnboundedSender<ChannelMessage>>,
+) {
+    let mut last_sent: HashMap<String, Instant> = HashMap::new();
+    while let Some(deferred) = receiver.recv().await {
+        let text = match response_text(&deferred.response) {
+            Some(text) => text,
+            None => continue,
+        };
+        if let Some(last_sent) = last_sent.get(&deferred.channel) {
+            tokio::time::sleep_until(*last_sent + interval).await;
+        }
+        if let Some(dry_run) = &dry_run {
+            dry_run(&deferred.channel, &deferred.response);
+            last_sent.insert(deferred.channel, Instant::now());
+            continue;
+        }
+        let reply_to = match deferred.response.reply() {
+            true => deferred
+                .response
+                .reply_to_message()
+                .or(deferred.reply_to.as_deref()),
+            false => None,
+        };
+        let result = writer.send(&deferred.channel, text, reply_to).await;
+        match result {
+            Ok(()) => {
+                echo(echo_sender.as_ref(), &deferred.channel, text, false);
+                notify(
+                    notification_sink.as_ref(),
+                    Notification::Response {
+                        channel: deferred.channel.clone(),
+                        text: text.to_owned(),
+                    },
+                )
+            }
+            Err(e) => log::error!("Error sending deferred response: {:?}", e),
+        }
+        last_sent.insert(deferred.channel, Instant::now());
+    }
 }
 
 struct MessageResponder<'a> {
-    message: &'a Privmsg<'a>,
-    writer: &'a mut AsyncWriter<MpscWriter>,
+    channel: &'a str,
+    /// the message replies are sent to
+    reply_to: Option<&'a str>,
+    writer: Arc<dyn ChatWriter + Send + Sync>,
+    dry_run: Option<&'a DryRunSink>,
+    notification_sink: Option<&'a SharedNotificationSink>,
+    echo: Option<&'a WeakUnboundedSender<ChannelMessage>>,
+    /// true while the filter responds
+    from_filter: bool,
 }
 
 #[async_trait]
 impl<'a> Responder for MessageResponder<'a> {
-    async fn respond(&mut self, response: &crate::response::Response<'_>) -> tokio::io::Result<()> {
-        if let Some(text) = response
-            .response()
-            // TODO: check if filter is necessary
-            .filter(|response_text| {
-                response.command() || !response_text.trim_start().starts_with('/')
-            })
-            .filter(|response_text| {
-                response.command() || !response_text.trim_start().starts_with('.')
-            })
-            .filter(|response_text| !response_text.is_empty() && !response_text.trim().is_empty())
-        {
-            if response.reply() {
-                let message = privmsg_reply(self.message, text);
-                self.writer.encode(message).compat().await?;
-            } else {
-                let message = privmsg(self.message.channel(), text);
-                self.writer.encode(message).compat().await?;
-            }
+    async fn respond(&mut self, response: &Response<'_>) -> tokio::io::Result<()> {
+        if let Some(dry_run) = self.dry_run {
+            dry_run(self.channel.trim_start_matches('#'), response);
+            return Ok(());
+        }
+        if let Some(text) = response_text(response) {
+            let reply_to = match response.reply() {
+                true => response.reply_to_message().or(self.reply_to),
+                false => None,
+            };
+            self.writer.send(self.channel, text, reply_to).await?;
+            echo(self.echo, self.channel, text, self.from_filter);
+            notify(
+                self.notification_sink,
+                Notification::Response {
+                    channel: self.channel.trim_start_matches('#').to_owned(),
+                    text: text.to_owned(),
+                },
+            );
         }
         Ok(())
     }
@@ -401,204 +1007,1016 @@ impl<'a> Responder for MessageResponder<'a> {
 
 struct Containers<'msg> {
     container: &'msg TypeMap![Send + Sync],
-    channel_container: Option<CachedChannelContainer<'msg>>,
+    channels: Option<&'msg ChannelContainer>,
+    channel_container: Option<tokio::sync::Mutex<CachedChannelContainer<'msg>>>,
 }
 
-impl<'msg, P> MessageHandler<'msg, P>
-where
-    P: CommandProcessor,
-{
+impl<'msg> Containers<'msg> {
+    async fn channel_container(&self, channel: &str) -> Option<Arc<TypeMap![Send + Sync]>> {
+        match &self.channel_container {
+            Some(channel_container) => Some(channel_container.lock().await.get(channel).await),
+            None => None,
+        }
+    }
+}
+
+impl<'msg> MessageHandler<'msg> {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         bot: &'msg Bot<'msg>,
         containers: Containers<'msg>,
-        command_processor: &'msg P,
-        writer: AsyncWriter<MpscWriter>,
+        command_processors: &'msg CommandProcessors,
+        writer: Arc<dyn ChatWriter + Send + Sync>,
+        deferred: UnboundedSender<DeferredResponse>,
+        dry_run: Option<DryRunSink>,
+        notification_sink: Option<SharedNotificationSink>,
+        unknown_command: Option<&'msg UnknownCommandHandler>,
+        rate_limit: Option<&'msg UserRateLimit>,
         chatters: ChannelChatters,
+        status: BotStatus,
         ignore_self: bool,
-        filter: Option<FilterPredicate>,
+        filter: Option<TryFilterPredicate>,
+        concurrency: usize,
     ) -> Self {
         Self {
             bot,
             containers,
-            command_processor,
+            command_processors,
             writer,
+            deferred,
+            dry_run,
+            notification_sink,
+            unknown_command,
+            rate_limit,
             chatters,
+            status,
             ignore_self,
-            filter,
+            prefix: None,
+            suppression_window: Duration::ZERO,
+            ignored_users: HashSet::new(),
+            echo: None,
+            seen: Default::default(),
+            suppressed: Default::default(),
+            channel_prefixes: Default::default(),
+            filter: filter.map(tokio::sync::Mutex::new),
+            filter_error_policy: FilterErrorPolicy::default(),
+            queues: Default::default(),
+            intake: None,
+            concurrency: Semaphore::new(concurrency),
+            greeter: Greeter::default(),
+            #[cfg(feature = "eventsub")]
+            redemption_processors: &[],
         }
     }
 
-    async fn clear_chat(&mut self, message: &'_ ClearChat<'_>) -> Result<(), Box<dyn Error>> {
-        let channel: Channel = message.into();
-        self.chatters
-            .clear_chat(
-                &channel,
-                message.tags().get_parsed("target-user-id"),
-                message.name(),
-            )
-            .await;
+    fn with_prefix(self, prefix: Option<String>) -> Self {
+        Self { prefix, ..self }
+    }
+
+    fn with_suppression_window(self, suppression_window: Duration) -> Self {
+        Self {
+            suppression_window,
+            ..self
+        }
+    }
+
+    fn with_ignored_users(self, ignored_users: HashSet<String>) -> Self {
+        Self {
+            ignored_users,
+            ..self
+        }
+    }
+
+    fn with_echo(self, echo: Option<WeakUnboundedSender<ChannelMessage>>) -> Self {
+        Self { echo, ..self }
+    }
+
+    fn with_filter_error_policy(self, filter_error_policy: FilterErrorPolicy) -> Self {
+        Self {
+            filter_error_policy,
+            ..self
+        }
+    }
+
+    fn with_intake(self, intake: Option<Intake>) -> Self {
+        Self { intake, ..self }
+    }
+
+    #[cfg(feature = "eventsub")]
+    fn with_redemption_processors(
+        self,
+        redemption_processors: &'msg [Box<dyn RedemptionProcessor + Send + Sync>],
+    ) -> Self {
+        Self {
+            redemption_processors,
+            ..self
+        }
+    }
+
+    /// Queues `message` behind the pending messages of its channel.
+    /// Returns the message if there are none, which then has to be handled by [`Self::handle_channel`].
+    fn enqueue(&self, message: ChannelMessage) -> Option<ChannelMessage> {
+        let mut queues = self.queues.lock().unwrap_or_else(|e| e.into_inner());
+        match queues.get_mut(message.channel()) {
+            Some(queue) => {
+                let channel = message.channel().to_owned();
+                match self.intake {
+                    Some(intake) if queue.len() >= intake.capacity => {
+                        if self.make_room(queue, message, intake.policy) {
+                            log::trace!("Dropped a message in {}", channel);
+                            self.status.dropped(&channel);
+                        }
+                    }
+                    _ => queue.push_back(message),
+                }
+                self.status.set_queue_depth(&channel, queue.len());
+                None
+            }
+            None => {
+                queues.insert(message.channel().to_owned(), VecDeque::new());
+                Some(message)
+            }
+        }
+    }
+
+    /// Queues `message` into the full `queue`, unless a message has to be dropped according to
+    /// `policy`. Returns `false` if all messages are commands or events which can not be dropped.
+    fn make_room(
+        &self,
+        queue: &mut VecDeque<ChannelMessage>,
+        message: ChannelMessage,
+        policy: IntakePolicy,
+    ) -> bool {
+        let droppable = |message: &ChannelMessage| match message {
+            ChannelMessage::Chat(ChatEvent::Message(message)) => !self.may_be_command(message),
+            _ => false,
+        };
+        if policy == IntakePolicy::DropNewest && droppable(&message) {
+            return true;
+        }
+        let dropped = match policy {
+            IntakePolicy::DropOldest => queue
+                .iter()
+                .position(droppable)
+                .and_then(|index| queue.remove(index))
+                .is_some(),
+            IntakePolicy::DropNewest => false,
+        };
+        queue.push_back(message);
+        dropped
+    }
+
+    /// Handles `message` and all messages which are queued for the same channel in the meantime.
+    async fn handle_channel(&self, mut message: ChannelMessage) -> Result<(), Box<dyn Error>> {
+        let channel = message.channel().to_owned();
+        loop {
+            {
+                let _permit = self.concurrency.acquire().await?;
+                self.dispatch(&message).await?;
+            }
+            let mut queues = self.queues.lock().unwrap_or_else(|e| e.into_inner());
+            let queue = queues.get_mut(&channel);
+            let depth = queue
+                .as_ref()
+                .map_or(0, |queue| queue.len().saturating_sub(1));
+            self.status.set_queue_depth(&channel, depth);
+            match queue.and_then(VecDeque::pop_front) {
+                Some(next) => message = next,
+                None => {
+                    queues.remove(&channel);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    async fn dispatch(&self, message: &ChannelMessage) -> Result<(), Box<dyn Error>> {
+        match message {
+            ChannelMessage::Chat(ChatEvent::Message(message)) => self.handle(message).await,
+            ChannelMessage::Chat(event) => self.moderation(event).await,
+            ChannelMessage::Sent(message) => self.sent(message).await,
+            #[cfg(feature = "http")]
+            ChannelMessage::Webhook(command) => self.webhook(command).await,
+            #[cfg(feature = "eventsub")]
+            ChannelMessage::Redemption(redemption) => self.redemption(redemption).await,
+        }
+    }
+
+    /// Keeps the chatters up to date with the events other than chat messages.
+    async fn moderation(&self, event: &ChatEvent) -> Result<(), Box<dyn Error>> {
+        match event {
+            ChatEvent::ChatCleared {
+                channel,
+                channel_id,
+                user,
+                user_id,
+                duration,
+            } => {
+                let channel = Channel::from(User::new(channel, None, channel_id.map(UserId::from)));
+                self.chatters
+                    .clear_chat(&channel, *user_id, user.as_deref())
+                    .await;
+                if let Some(user) = user {
+                    self.suppress(channel.username(), user);
+                }
+                notify(
+                    self.notification_sink.as_ref(),
+                    Notification::ChatCleared {
+                        channel: channel.username().to_owned(),
+                        user: user.clone(),
+                        duration: *duration,
+                    },
+                );
+            }
+            ChatEvent::MessageDeleted {
+                channel,
+                channel_id,
+                message_id,
+                user,
+                message,
+            } => {
+                let channel = Channel::from(User::new(channel, None, channel_id.map(UserId::from)));
+                self.chatters
+                    .clear_message(&channel, message_id.as_deref(), user.as_deref())
+                    .await;
+                if let Some(user) = user {
+                    self.suppress(channel.username(), user);
+                }
+                notify(
+                    self.notification_sink.as_ref(),
+                    Notification::MessageDeleted {
+                        channel: channel.username().to_owned(),
+                        user: user.clone(),
+                        message: message.clone(),
+                    },
+                );
+            }
+            ChatEvent::Join { channel, user } => {
+                let channel = Channel::from(User::from_username(channel));
+                self.chatters.join(&channel, user).await;
+            }
+            ChatEvent::Part { channel, user } => {
+                let channel = Channel::from(User::from_username(channel));
+                if user == self.bot.username() {
+                    // the bot left the channel
+                    self.status.part(channel.username());
+                    self.chatters.clear_presence(&channel).await;
+                } else {
+                    self.chatters.part(&channel, user).await;
+                }
+            }
+            ChatEvent::Message(_) | ChatEvent::Activity => {}
+        }
         Ok(())
     }
 
-    async fn clear_msg(&mut self, message: &'_ ClearMsg<'_>) -> Result<(), Box<dyn Error>> {
-        let channel: Channel = message.into();
-        self.chatters
-            .clear_message(&channel, message.target_msg_id(), message.login())
-            .await;
+    /// Tracks a message sent by the bot like a received message, see [`ChatBot::echo_sent_messages`].
+    async fn sent(&self, message: &SentMessage) -> Result<(), Box<dyn Error>> {
+        let channel = Channel::from(User::from_username(&message.channel));
+        let sender = Sender::from((self.bot as &User).clone());
+        if is_chat_command(&message.text) {
+            if message.from_filter {
+                return Ok(());
+            }
+            if let Some(channel_container) =
+                self.containers.channel_container(&message.channel).await
+            {
+                if let Some(log) = channel_container.try_get::<ModerationLog>() {
+                    record_sent_command(log, &message.text);
+                }
+            }
+        } else {
+            // sent messages do not have an id
+            self.chatters
+                .notice_chatter(&channel, &sender, &message.text, "")
+                .await;
+        }
         Ok(())
     }
 
-    async fn handle(&mut self, message: &'_ Privmsg<'_>) -> Result<(), Box<dyn Error>> {
+    /// Whether the message starts with the prefix the channel had when its config was read last.
+    /// Returns `true` if the prefix is not known yet, such that the config is read.
+    fn may_be_command(&self, message: &ChatMessage) -> bool {
+        let prefixes = self
+            .channel_prefixes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        match prefixes.get(&message.channel) {
+            Some(prefix) => {
+                has_command_prefix(prefix.as_deref().or(self.prefix.as_deref()), &message.text)
+            }
+            None => true,
+        }
+    }
+
+    /// Returns `false` if the message was seen before, e.g. because Twitch delivered it again after
+    /// a reconnect.
+    fn first_seen(&self, channel: &str, message_id: &str) -> bool {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let ids = seen.entry(channel.to_owned()).or_default();
+        if ids.iter().any(|id| id == message_id) {
+            return false;
+        }
+        if ids.len() >= SEEN_MESSAGES_PER_CHANNEL {
+            ids.pop_front();
+        }
+        ids.push_back(message_id.to_owned());
+        true
+    }
+
+    /// Ignores the commands of the user for the suppression window.
+    fn suppress(&self, channel: &str, username: &str) {
+        if self.suppression_window.is_zero() {
+            return;
+        }
+        self.suppressed
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert((channel.to_owned(), username.to_owned()), Instant::now());
+    }
+
+    fn is_suppressed(&self, channel: &str, username: &str) -> bool {
+        let mut suppressed = self.suppressed.lock().unwrap_or_else(|e| e.into_inner());
+        if suppressed.is_empty() {
+            return false;
+        }
+        suppressed.retain(|_, deleted_at| deleted_at.elapsed() < self.suppression_window);
+        suppressed.contains_key(&(channel.to_owned(), username.to_owned()))
+    }
+
+    fn set_channel_prefix(&self, channel: &str, prefix: Option<&str>) {
+        let mut prefixes = self
+            .channel_prefixes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        match prefixes.get_mut(channel) {
+            Some(known) if known.as_deref() == prefix => {}
+            Some(known) => *known = prefix.map(str::to_owned),
+            None => {
+                prefixes.insert(channel.to_owned(), prefix.map(str::to_owned));
+            }
+        }
+    }
+
+    async fn handle(&self, message: &ChatMessage) -> Result<(), Box<dyn Error>> {
         let bot = self.bot;
         let container = self.containers.container;
 
-        let channel: Channel = message.into();
-        let sender: Sender = message.into();
+        let channel = message.channel();
+        let sender = message.sender();
 
-        self.chatters
-            .notice_chatter(&channel, &sender, message.data(), "id")
+        if let Some(id) = &message.id {
+            if !self.first_seen(&message.channel, id) {
+                log::debug!(
+                    "Skipping message {} of {:?}, which was seen before",
+                    id,
+                    sender
+                );
+                return Ok(());
+            }
+        }
+        let first_message = self
+            .chatters
+            .notice_chatter(&channel, &sender, &message.text, "id")
             .await;
 
-        let mut responder = MessageResponder {
-            message,
-            writer: &mut self.writer,
+        let filter = match (&self.filter, message.id.as_deref()) {
+            (Some(filter), Some(msg_id)) => Some((filter, msg_id)),
+            _ => None,
+        };
+        if self.ignored_users.contains(sender.username()) {
+            log::trace!("Ignoring message from {:?}", sender);
+            return Ok(());
+        }
+        // most messages are not commands, which only need to be noticed by the chatters
+        if filter.is_none() && !first_message && !self.may_be_command(message) {
+            return Ok(());
+        }
+
+        // the channel container and the context are shared by the filter and the command
+        let channel_container = self.containers.channel_container(&message.channel).await;
+        if let Some(channel_container) = &channel_container {
+            if is_ignored(channel_container, channel.username(), sender.username()).await {
+                log::trace!("Ignoring message from {:?}", sender);
+                return Ok(());
+            }
+        }
+        // the channel config can replace the prefix of commands
+        let config = match &channel_container {
+            Some(channel_container) => {
+                read_channel_config(channel_container, channel.username()).await
+            }
+            None => None,
         };
+        let context = ChatBotContext::new(container, channel_container.as_deref(), &self.chatters)
+            .with_deferred(&self.deferred, message.id.as_deref())
+            .with_channels(self.containers.channels)
+            .with_language(
+                config
+                    .as_ref()
+                    .and_then(|config| config.language.as_deref()),
+            );
+
+        let mut responder = tokio::sync::Mutex::new(MessageResponder {
+            channel: &message.channel,
+            reply_to: message.id.as_deref(),
+            writer: self.writer.clone(),
+            dry_run: self.dry_run.as_ref(),
+            notification_sink: self.notification_sink.as_ref(),
+            echo: self.echo.as_ref(),
+            from_filter: false,
+        });
 
-        if let Some(msg_id) = message.tags().get("id") {
-            if let Some(filter) = self.filter.as_mut() {
-                // TODO: create context only once
-                let channel: Channel = message.into();
-                let sender: Sender = message.into();
-                let mut channel_container_rc = None;
-                if let Some(channel_container) = &mut self.containers.channel_container {
-                    channel_container_rc = Some(channel_container.get(message.channel()).await);
+        if let Some((filter, msg_id)) = filter {
+            let filter_request = FilterRequest::new(
+                &message.text,
+                sender.clone(),
+                channel.clone(),
+                bot,
+                &context,
+            );
+            let mut filter = filter.lock().await;
+            responder.get_mut().from_filter = true;
+            let decision = match (filter)(filter_request, responder.get_mut()).await {
+                Ok(decision) => decision,
+                Err(e) => {
+                    log::error!("Error filtering message of {:?}: {:?}", sender, e);
+                    notify(
+                        self.notification_sink.as_ref(),
+                        Notification::FilterFailed {
+                            channel: channel.username().to_owned(),
+                            user: sender.username().to_owned(),
+                            error: e.to_string(),
+                        },
+                    );
+                    self.filter_error_policy.decision()
                 }
-                let context = ChatBotContext::new(
-                    container,
-                    channel_container_rc
-                        .as_ref()
-                        .map(|rc| rc as &Arc<TypeMap![Send + Sync]> as &TypeMap![Send + Sync]),
-                    &self.chatters,
+            };
+            if decision.is_delete() {
+                log::info!(
+                    "Deleting message of {:?} ({}: {})",
+                    sender,
+                    decision.tag.as_deref().unwrap_or("filter"),
+                    decision.reason.as_deref().unwrap_or("no reason")
                 );
-                let filter_request =
-                    FilterRequest::new(message.data(), sender, channel, bot, &context);
-                if !(filter)(filter_request, &mut responder).await {
-                    self.chatters
-                        .clear_message(&message.into(), Some(msg_id), Some(message.name()))
-                        .await;
-                    responder
-                        .respond(
-                            &crate::response::Response::new(format!(".delete {msg_id}"))
-                                .as_command(),
-                        )
-                        .await?;
-                    return Ok(());
+                if let Some(log) = channel_container
+                    .as_deref()
+                    .and_then(|container| container.try_get::<ModerationLog>())
+                {
+                    record_filtered(log, sender.username(), &message.text, &decision);
                 }
+                self.chatters
+                    .clear_message(&channel, Some(msg_id), Some(sender.username()))
+                    .await;
+                self.suppress(channel.username(), sender.username());
+                let responder = responder.get_mut();
+                responder
+                    .respond(&Response::new(format!(".delete {msg_id}")).as_command())
+                    .await?;
+                let notice = match decision.notice {
+                    Some(FilterNotice::Reply(text)) => {
+                        Response::new(format!("{} {}", UserArgument::from(&sender as &User), text))
+                    }
+                    Some(FilterNotice::Whisper(text)) => {
+                        Response::new(format!(".w {} {}", sender.username(), text)).as_command()
+                    }
+                    None => return Ok(()),
+                };
+                responder.respond(&notice).await?;
+                return Ok(());
             }
+            responder.get_mut().from_filter = false;
         }
 
-        if let Ok(command) = Command::try_from(message) {
+        if let (true, Some(config), Some(channel_container)) =
+            (first_message, &config, &channel_container)
+        {
+            self.greet(message, config, channel_container).await;
+        }
+        let channel_prefix = config.as_ref().and_then(|config| config.prefix.as_deref());
+        self.set_channel_prefix(&message.channel, channel_prefix);
+        let command = prefixed_command(channel_prefix.or(self.prefix.as_deref()), &message.text);
+
+        if command.is_some() && self.is_suppressed(channel.username(), sender.username()) {
+            log::debug!("Ignoring command of {:?} after a deleted message", sender);
+            return Ok(());
+        }
+        if let Some(command) = &command {
             log::trace!("Command found");
+            let command = Command::from(command.as_ref());
+            let request = CommandRequest::new(command, sender, channel, bot, &context, &responder);
+
+            let result = self.process_request(&request, config.as_deref()).await;
+            // commands can change the prefix, which is read again with the next message
+            self.channel_prefixes
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&message.channel);
+            result?;
+        }
+        Ok(())
+    }
 
-            // unpack channel container at the last moment possible
-            let mut channel_container_rc = None;
-            if let Some(channel_container) = &mut self.containers.channel_container {
-                channel_container_rc = Some(channel_container.get(message.channel()).await);
+    /// Greets the sender of the first message if the channel has a greeting.
+    async fn greet(
+        &self,
+        message: &ChatMessage,
+        config: &ChannelConfig,
+        channel_container: &TypeMap![Send + Sync],
+    ) {
+        let template = match &config.greeting {
+            Some(template) => template,
+            None => return,
+        };
+        let sender = message.sender();
+        if &sender as &User == self.bot as &User || sender.is_broadcaster() {
+            return;
+        }
+        if config.greeting_scope == GreetingScope::Ever {
+            let (persisted, user_id) = match (
+                channel_container.try_get::<Persisted<GreetedUsers>>(),
+                sender.user_id(),
+            ) {
+                (Some(persisted), Some(user_id)) => (persisted, user_id),
+                _ => {
+                    log::warn!("The greeted users are not registered or the user id is missing");
+                    return;
+                }
+            };
+            let (_, new) = persisted
+                .for_channel(&message.channel)
+                .maybe_update(|greeted| {
+                    let mut greeted = greeted.clone();
+                    greeted.insert(user_id).then_some(greeted)
+                })
+                .await;
+            if new.is_none() {
+                return;
             }
+        }
+        self.greeter.greet(
+            &message.channel,
+            sender.display_name().unwrap_or(sender.username()),
+            template,
+            config.greeting_cooldown,
+            &self.deferred,
+        );
+    }
 
-            let context = ChatBotContext::new(
-                container,
-                channel_container_rc
-                    .as_ref()
-                    .map(|rc| rc as &Arc<TypeMap![Send + Sync]> as &TypeMap![Send + Sync]),
-                &self.chatters,
-            );
-            let request = CommandRequest::new(command, sender, channel, bot, &context);
+    #[cfg(feature = "http")]
+    async fn webhook(&self, webhook: &WebhookCommand) -> Result<(), Box<dyn Error>> {
+        let channel = Channel::from(User::from_username(&webhook.channel));
+        let sender = Sender::new(User::from_owned(&webhook.sender), webhook.moderator, false);
+        let responder = tokio::sync::Mutex::new(MessageResponder {
+            channel: &webhook.channel,
+            reply_to: None,
+            writer: self.writer.clone(),
+            dry_run: self.dry_run.as_ref(),
+            notification_sink: self.notification_sink.as_ref(),
+            echo: self.echo.as_ref(),
+            from_filter: false,
+        });
+        let channel_container = self.containers.channel_container(&webhook.channel).await;
+        let config = match &channel_container {
+            Some(channel_container) => {
+                read_channel_config(channel_container, &webhook.channel).await
+            }
+            None => None,
+        };
+        let context = ChatBotContext::new(
+            self.containers.container,
+            channel_container.as_deref(),
+            &self.chatters,
+        )
+        .with_deferred(&self.deferred, None)
+        .with_channels(self.containers.channels)
+        .with_language(
+            config
+                .as_ref()
+                .and_then(|config| config.language.as_deref()),
+        );
+        let command = Command::from(webhook.command.as_str());
+        let request = CommandRequest::new(command, sender, channel, self.bot, &context, &responder);
+        self.process_request(&request, config.as_deref()).await
+    }
 
-            log::trace!("request: {:?}", request);
+    #[cfg(feature = "eventsub")]
+    async fn redemption(&self, redemption: &Redemption) -> Result<(), Box<dyn Error>> {
+        let channel = redemption.channel.username();
+        let channel_container = self.containers.channel_container(channel).await;
+        let config = match &channel_container {
+            Some(channel_container) => read_channel_config(channel_container, channel).await,
+            None => None,
+        };
+        let context = ChatBotContext::new(
+            self.containers.container,
+            channel_container.as_deref(),
+            &self.chatters,
+        )
+        .with_deferred(&self.deferred, None)
+        .with_channels(self.containers.channels)
+        .with_language(
+            config
+                .as_ref()
+                .and_then(|config| config.language.as_deref()),
+        );
+        let request = RedemptionRequest::new(redemption, self.bot, &context);
+        for processor in self.redemption_processors {
+            if let Some(response) = processor.process(&request).await {
+                let mut responder = MessageResponder {
+                    channel,
+                    reply_to: None,
+                    writer: self.writer.clone(),
+                    dry_run: self.dry_run.as_ref(),
+                    notification_sink: self.notification_sink.as_ref(),
+                    echo: self.echo.as_ref(),
+                    from_filter: false,
+                };
+                responder.respond(&response).await?;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks if the command is allowed and processes it with the command processors.
+    async fn process_request(
+        &self,
+        request: &CommandRequest<'_>,
+        config: Option<&ChannelConfig>,
+    ) -> Result<(), Box<dyn Error>> {
+        log::trace!("request: {:?}", request);
 
-            if self.ignore_self && request.sender() as &User == bot as &User {
-                log::debug!("Ignoring message from bot {:?}", bot);
-                return Ok(()); // do not handle messages from the bot
+        if self.ignore_self && request.sender() as &User == self.bot as &User {
+            log::debug!("Ignoring message from bot {:?}", self.bot);
+            return Ok(()); // do not handle messages from the bot
+        }
+        let privileged = request.sender().is_moderator() || request.sender().is_broadcaster();
+        if !privileged && config.is_some_and(|config| config.is_quiet(chrono::Utc::now().time())) {
+            log::debug!("Ignoring command during quiet hours {:?}", request);
+            return Ok(());
+        }
+        if let Some(rate_limit) = self.rate_limit {
+            match rate_limit.check(request.sender()) {
+                RateLimitDecision::Allow => {}
+                RateLimitDecision::Drop => {
+                    log::debug!("Rate limited {:?}", request.sender());
+                    return Ok(());
+                }
+                RateLimitDecision::Warn => {
+                    log::debug!("Rate limited {:?}", request.sender());
+                    let warning = Response::new(format!(
+                        "{} {}",
+                        UserArgument::from(request.sender() as &User),
+                        locale::translate_or(
+                            request,
+                            "chatbot.rate_limited",
+                            "you are using commands too fast, please slow down",
+                            &[],
+                        )
+                    ));
+                    RequestResponder::from(request).respond(&warning).await?;
+                    return Ok(());
+                }
             }
-            if let Some(response) = self.command_processor.process(&request).await.as_ref() {
-                responder.respond(response).await?;
+        }
+        if let Some(response) = self.command_processors.process(request).await.as_ref() {
+            record_command(request).await;
+            record_audit(request, response).await;
+            RequestResponder::from(request).respond(response).await?;
+        } else if let Some(unknown_command) = self.unknown_command {
+            if let Some(response) = unknown_command(request) {
+                RequestResponder::from(request).respond(&response).await?;
             }
         }
         Ok(())
     }
 }
 
-impl<'a, C, P> ChatBot<'a, C, P>
-where
-    C: Connector,
-    for<'o> &'o C::Output: AsyncRead + AsyncWrite + Send + Sync + Unpin,
-    P: CommandProcessor,
-{
+/// Writes the command of `request` to the [`AuditLog`] if it is set.
+async fn record_audit(request: &CommandRequest<'_>, response: &Response<'_>) {
+    let audit_log = match request.context.map(|context| context.state::<AuditLog>()) {
+        Some(Ok(audit_log)) => audit_log,
+        _ => return,
+    };
+    if let Err(e) = audit_log.record(&AuditEntry::new(request, response)).await {
+        log::error!("Error writing the audit log: {:?}", e);
+    }
+}
+
+impl<'a, C: ChatPlatform> ChatBot<'a, C> {
     #[allow(clippy::needless_late_init)]
     pub async fn run(
         self,
         channels: impl std::iter::IntoIterator<Item = &str>,
     ) -> Result<(), Box<dyn Error>> {
-        let user_config = self.user_config;
-        let command_processor = self.command_processor;
+        let mut platform = self.platform;
+        let command_processors = self.command_processors;
         let channel_container = self.channel_container;
         let bot: Bot;
-        let mut container = self.container;
-        let mut runner;
-        let mut handler;
+        let mut container = <TypeMap![Send + Sync]>::new();
+        for (_, set) in self.states {
+            set(&container);
+        }
+        let handler;
 
+        container.set(self.status.clone());
         container.freeze();
-        runner = AsyncRunner::connect(self.connector, user_config)
-            .compat()
-            .await?;
-        let identity = runner.identity.clone(); // TODO: store bot user somewhere in memeory
-        bot = (&identity)
-            .try_into()
-            .unwrap_or_else(|_| user_config.into());
+        let bot_user = platform.connect().await?; // TODO: store bot user somewhere in memeory
+        bot = User::from_owned(&bot_user).into();
 
         log::info!("Connected as {}", bot.username());
+        self.status.set_connected(true);
+        if let Some(hook) = &self.hooks.connect {
+            hook(&bot);
+        }
+        let reconnects = self.status.reconnects();
+        if reconnects > 0 {
+            log::info!("Reconnected {} times", reconnects);
+            if let Some(hook) = &self.hooks.reconnect {
+                hook(reconnects);
+            }
+        }
+
+        let (messages, mut received) = mpsc::unbounded_channel();
+        let echo = self.echo_sent_messages.then(|| messages.downgrade());
+        let (deferred, receiver) = mpsc::unbounded_channel();
+        let deferred_responses = AbortOnDrop(tokio::spawn(write_deferred_responses(
+            receiver,
+            platform.writer(),
+            self.deferred_response_interval,
+            self.dry_run.clone(),
+            self.notification_sink.clone(),
+            echo.clone(),
+        )));
 
         // TODO: join channels
         //runner.join(bot.username()).compat().await?;
         //log::info!("Joined channel {}", bot.username());
+        let channels = self
+            .channels
+            .into_iter()
+            .chain(channels.into_iter().map(str::to_owned));
         for channel in channels {
-            runner.join(channel).compat().await?;
+            let channel = channel.as_str();
+            if let Err(e) = platform.join(channel).await {
+                self.status.set_connected(false);
+                if let Some(hook) = &self.hooks.disconnect {
+                    hook(Some(e.as_ref()));
+                }
+                return Err(e);
+            }
+            self.status.join(channel);
             log::info!("Joined channel {}", channel);
+            if let Some(response) = self.hooks.join.as_ref().and_then(|hook| hook(channel)) {
+                let _ = deferred.send(DeferredResponse {
+                    channel: channel.to_owned(),
+                    reply_to: None,
+                    response,
+                });
+            }
         }
 
         let containers = Containers {
             container: &container,
-            channel_container: channel_container.map(ChannelContainer::create_local_cache),
+            channels: channel_container,
+            channel_container: channel_container
+                .map(ChannelContainer::create_local_cache)
+                .map(tokio::sync::Mutex::new),
         };
 
         handler = MessageHandler::new(
             &bot,
             containers,
-            &command_processor,
-            runner.writer(),
+            &command_processors,
+            platform.writer(),
+            deferred.clone(),
+            self.dry_run,
+            self.notification_sink,
+            self.unknown_command.as_ref(),
+            self.rate_limit.as_ref(),
             self.chatters.clone(),
+            self.status.clone(),
             self.ignore_self,
             self.filter,
-        );
+            self.concurrency,
+        )
+        .with_prefix(self.prefix)
+        .with_suppression_window(self.suppression_window)
+        .with_ignored_users(self.ignored_users)
+        .with_echo(echo)
+        .with_filter_error_policy(self.filter_error_policy)
+        .with_intake(self.intake);
+        #[cfg(feature = "eventsub")]
+        let (eventsub, redemption_processors) = match self.eventsub {
+            Some(mut config) => {
+                let processors = std::mem::take(&mut config.processors);
+                (Some(config), processors)
+            }
+            None => (None, Vec::new()),
+        };
+        #[cfg(feature = "eventsub")]
+        let handler = handler.with_redemption_processors(&redemption_processors);
 
-        loop {
-            // TODO: add CTRL+C detection!
-            let message = runner.next_message().compat().await?;
-            match message {
-                Status::Message(commands) => {
-                    log::trace!("Message: {:#?}", commands);
-                    match commands {
-                        Commands::Privmsg(message) => handler.handle(&message).await?,
-                        Commands::ClearChat(message) => handler.clear_chat(&message).await?,
-                        Commands::ClearMsg(message) => handler.clear_msg(&message).await?,
-                        Commands::Ping(_) | Commands::Pong(_) => {}
-                        _ => {}
+        #[cfg(feature = "http")]
+        let webhooks = match self.webhooks {
+            Some(config) => {
+                let listener = match tokio::net::TcpListener::bind(config.address()).await {
+                    Ok(listener) => listener,
+                    Err(e) => {
+                        self.status.set_connected(false);
+                        if let Some(hook) = &self.hooks.disconnect {
+                            hook(Some(&e));
+                        }
+                        return Err(e.into());
+                    }
+                };
+                log::info!("Listening for webhooks on {}", config.address());
+                // the weak sender does not keep the bot running after the connection was closed
+                let messages = messages.downgrade();
+                Some(tokio::spawn(serve_webhooks(
+                    listener,
+                    config,
+                    self.status.clone(),
+                    move |command| {
+                        messages.upgrade().is_some_and(|messages| {
+                            messages.send(ChannelMessage::Webhook(command)).is_ok()
+                        })
+                    },
+                )))
+            }
+            None => None,
+        };
+        // started once nothing can fail anymore, such that the task does not outlive a failed start
+        let snapshots = if let Some(interval) = self.chatters_snapshot_interval {
+            if let Err(e) = self.chatters.restore().await {
+                log::error!("Error loading chatters from disk: {:?}", e);
+            }
+            Some(self.chatters.spawn_snapshots(interval))
+        } else {
+            None
+        };
+        #[cfg(feature = "eventsub")]
+        let redemptions = eventsub.map(|config| {
+            // the weak sender does not keep the bot running after the connection was closed
+            let messages = messages.downgrade();
+            let channels = self.status.channels();
+            tokio::spawn(async move {
+                let dispatch = move |redemption| {
+                    messages.upgrade().is_some_and(|messages| {
+                        messages
+                            .send(ChannelMessage::Redemption(redemption))
+                            .is_ok()
+                    })
+                };
+                if let Err(e) = listen_redemptions(config, channels, dispatch).await {
+                    log::error!("Error listening for redemptions: {:?}", e);
+                }
+            })
+        });
+        let status = self.status.clone();
+        let reader = async move {
+            loop {
+                // TODO: add CTRL+C detection!
+                let event = match platform.next_event().await? {
+                    Some(event) => event,
+                    None => break,
+                };
+                if event.channel().is_none() {
+                    // pings show that the connection is still alive
+                    status.received(None);
+                    continue;
+                }
+                status.received(event.channel());
+                if messages.send(ChannelMessage::Chat(event)).is_err() {
+                    break;
+                }
+            }
+            Ok::<_, Box<dyn Error>>(())
+        };
+        let handler = &handler;
+        let dispatcher = async move {
+            // every channel with pending messages is handled by one of the workers
+            let mut workers = FuturesUnordered::new();
+            loop {
+                let message = if workers.is_empty() {
+                    received.recv().await
+                } else {
+                    match select(pin!(received.recv()), workers.next()).await {
+                        Either::Left((message, _)) => message,
+                        Either::Right((result, _)) => {
+                            result.transpose()?;
+                            continue;
+                        }
                     }
+                };
+                match message {
+                    Some(message) => {
+                        if let Some(message) = handler.enqueue(message) {
+                            workers.push(handler.handle_channel(message));
+                        }
+                    }
+                    None => break,
                 }
-                Status::Quit | Status::Eof => break,
+            }
+            while let Some(result) = workers.next().await {
+                result?;
+            }
+            Ok::<_, Box<dyn Error>>(())
+        };
+        let reminders = async {
+            match (self.reminder_interval, channel_container) {
+                (Some(interval), Some(channel_container)) => {
+                    let chatters = self.chatters.clone();
+                    let status = self.status.clone();
+                    deliver_reminders(interval, channel_container, chatters, status, deferred).await
+                }
+                _ => std::future::pending().await,
+            }
+        };
+        let result = match select(pin!(try_join(reader, dispatcher)), pin!(reminders)).await {
+            Either::Left((result, _)) => result.map(|_| ()),
+            Either::Right((never, _)) => match never {},
+        };
+        self.status.set_connected(false);
+        #[cfg(feature = "http")]
+        if let Some(webhooks) = webhooks {
+            webhooks.abort();
+        }
+        #[cfg(feature = "eventsub")]
+        if let Some(redemptions) = redemptions {
+            redemptions.abort();
+        }
+        if let Some(hook) = &self.hooks.disconnect {
+            hook(result.as_ref().err().map(|e| e.as_ref() as &dyn Error));
+        }
+        drop(deferred_responses);
+        // write debounced persisted state before stopping
+        if let Some(channel_container) = channel_container {
+            channel_container.flush().await;
+        }
+        if let Some(snapshots) = snapshots {
+            snapshots.abort();
+            if let Err(e) = self.chatters.snapshot().await {
+                log::error!("Error saving chatters to disk: {:?}", e);
             }
         }
-        Ok(())
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::ReplayPlatform;
+
+    fn bot() -> ChatBot<'static, ReplayPlatform> {
+        ChatBot::with_platform(ReplayPlatform::new(Vec::new(), "bot"))
+    }
+
+    #[test]
+    fn test_state_registered_twice() {
+        let bot = bot().with_state(1u32).replace_state(2u32);
+        assert!(matches!(
+            bot.try_with_state(3u32),
+            Err(StateError::AlreadySet("u32"))
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "A value was set already for type u32")]
+    fn test_with_state_twice() {
+        let _ = bot().with_state(1u32).with_state(2u32);
+    }
+
+    fn run_until_closed(bot: ChatBot<'static, ReplayPlatform>) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let run = tokio::time::timeout(Duration::from_secs(5), bot.run([]));
+            run.await
+                .expect("the bot did not stop after the connection was closed")
+                .unwrap();
+        });
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_stops_with_webhooks() {
+        let config = WebhookConfig::new(([127, 0, 0, 1], 0), "token");
+        run_until_closed(bot().with_webhooks(config));
+    }
+
+    #[cfg(feature = "eventsub")]
+    #[test]
+    fn test_stops_with_eventsub() {
+        // without channels no users are looked up, and the websocket handshake is never answered,
+        // such that the listener keeps waiting
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let client = crate::helix::HelixClient::new("client".to_owned(), "token".to_owned());
+        run_until_closed(bot().with_eventsub(EventSubConfig::new(client).url(url)));
     }
 }
@@ -0,0 +1,116 @@
+use super::FromArgument;
+use core::fmt::{Display, Formatter};
+
+/// The argument was parsed successfully, but is not within `min..=max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfRange {
+    pub min: i128,
+    pub max: i128,
+}
+
+impl Display for OutOfRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "must be between {} and {}", self.min, self.max)
+    }
+}
+
+impl std::error::Error for OutOfRange {}
+
+#[derive(Debug)]
+pub enum BoundedError<E> {
+    Parsing(E),
+    OutOfRange(OutOfRange),
+}
+
+impl<E: Display> Display for BoundedError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BoundedError::Parsing(error) => error.fmt(f),
+            BoundedError::OutOfRange(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for BoundedError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BoundedError::Parsing(error) => Some(error),
+            BoundedError::OutOfRange(error) => Some(error),
+        }
+    }
+}
+
+/// An integer argument that has to be within `MIN..=MAX`, e.g. `Bounded<u32, 1, 10>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, derive_more::Deref)]
+pub struct Bounded<T, const MIN: i128, const MAX: i128>(T);
+
+impl<T, const MIN: i128, const MAX: i128> Bounded<T, MIN, MAX> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<'a, T, const MIN: i128, const MAX: i128> FromArgument<'a> for Bounded<T, MIN, MAX>
+where
+    T: FromArgument<'a> + Copy + TryInto<i128>,
+{
+    type Error = BoundedError<<T as FromArgument<'a>>::Error>;
+    fn from_argument(argument: &'a str) -> Result<Self, Self::Error> {
+        let value = T::from_argument(argument).map_err(BoundedError::Parsing)?;
+        match value.try_into() {
+            Ok(number) if (MIN..=MAX).contains(&number) => Ok(Self(value)),
+            _ => Err(BoundedError::OutOfRange(OutOfRange { min: MIN, max: MAX })),
+        }
+    }
+}
+
+/// A percentage between `0` and `100`, with an optional `%` suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, derive_more::Deref)]
+pub struct Percent(u8);
+
+impl Percent {
+    pub fn value(self) -> u8 {
+        self.0
+    }
+
+    pub fn as_fraction(self) -> f32 {
+        f32::from(self.0) / 100.0
+    }
+}
+
+impl FromArgument<'_> for Percent {
+    type Error = BoundedError<core::num::ParseIntError>;
+    fn from_argument(argument: &str) -> Result<Self, Self::Error> {
+        let argument = argument.strip_suffix('%').unwrap_or(argument);
+        let value = Bounded::<u32, 0, 100>::from_argument(argument)?;
+        Ok(Self(value.into_inner() as u8))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounded() {
+        assert_eq!(*Bounded::<u32, 1, 10>::from_argument("10").unwrap(), 10);
+        assert!(matches!(
+            Bounded::<u32, 1, 10>::from_argument("0"),
+            Err(BoundedError::OutOfRange(OutOfRange { min: 1, max: 10 }))
+        ));
+        assert!(matches!(
+            Bounded::<i8, -5, 5>::from_argument("x"),
+            Err(BoundedError::Parsing(_))
+        ));
+    }
+
+    #[test]
+    fn test_percent() {
+        assert_eq!(Percent::from_argument("50%").unwrap().value(), 50);
+        assert_eq!(Percent::from_argument("100").unwrap().as_fraction(), 1.0);
+        assert!(matches!(
+            Percent::from_argument("300%"),
+            Err(BoundedError::OutOfRange(_))
+        ));
+    }
+}
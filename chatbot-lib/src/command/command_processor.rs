@@ -6,3 +6,91 @@ use async_trait::async_trait;
 pub trait CommandProcessor {
     async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>>;
 }
+
+/// Processes a request with every processor in order of their priority until one responds.
+/// Processors with the same priority are processed in the order they were added.
+#[derive(Default)]
+pub struct CommandProcessors {
+    processors: Vec<(i32, Box<dyn CommandProcessor + Send + Sync>)>,
+}
+
+impl CommandProcessors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a processor, processors with a higher `priority` are processed first.
+    pub fn push<P>(&mut self, priority: i32, command_processor: P)
+    where
+        P: CommandProcessor + Send + Sync + 'static,
+    {
+        let index = self
+            .processors
+            .partition_point(|(other, _)| *other >= priority);
+        self.processors
+            .insert(index, (priority, Box::new(command_processor)));
+    }
+
+    pub fn len(&self) -> usize {
+        self.processors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for CommandProcessors {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        for (_, command_processor) in &self.processors {
+            if let Some(response) = command_processor.process(request).await {
+                return Some(response);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::Bot;
+    use crate::user::User;
+
+    struct Respond(&'static str);
+
+    #[async_trait]
+    impl CommandProcessor for Respond {
+        async fn process<'a>(&self, _request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+            Some(Response::new(self.0))
+        }
+    }
+
+    struct Ignore;
+
+    #[async_trait]
+    impl CommandProcessor for Ignore {
+        async fn process<'a>(&self, _request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_priorities() {
+        let mut processors = CommandProcessors::new();
+        processors.push(0, Respond("fallback"));
+        processors.push(10, Ignore);
+        processors.push(5, Respond("first"));
+        processors.push(5, Respond("second"));
+
+        let user = User::from_username("user");
+        let bot = Bot::from(User::from_username("bot"));
+        let request = CommandRequest::from_parts("!song", user.clone(), user, &bot);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let response = runtime.block_on(processors.process(&request));
+        assert_eq!(response.unwrap().response(), Some("first"));
+    }
+}
@@ -1,4 +1,24 @@
-use core::fmt::Debug;
+use super::OutOfRange;
+use core::fmt::{Debug, Display, Formatter};
+use std::borrow::Cow;
+
+/// The argument which could not be parsed, e.g. to tell the user which value was wrong.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidArgument {
+    /// The name of the argument in the pattern of the command.
+    pub name: &'static str,
+    /// The text of the argument as it was written by the user.
+    pub value: String,
+}
+
+impl InvalidArgument {
+    pub fn new<V: Into<String>>(name: &'static str, value: V) -> Self {
+        Self {
+            name,
+            value: value.into(),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum CommandError<Error> {
@@ -7,8 +27,10 @@ pub enum CommandError<Error> {
     ArgumentMissing,
     ArgumentParsing(Error),
     ArgumentsLeftOver,
-    NamedArgumentParsing(&'static str, Error),
+    NamedArgumentParsing(InvalidArgument, Error),
     RequestError(Error),
+    /// An expected mistake of the user, the message is sent to the sender.
+    UserError(Cow<'static, str>),
 }
 
 impl<Error> CommandError<Error> {
@@ -22,10 +44,30 @@ impl<Error> CommandError<Error> {
             CommandError::ArgumentMissing => CommandError::ArgumentMissing,
             CommandError::ArgumentParsing(error) => CommandError::ArgumentParsing(op(error)),
             CommandError::ArgumentsLeftOver => CommandError::ArgumentsLeftOver,
-            CommandError::NamedArgumentParsing(name, error) => {
-                CommandError::NamedArgumentParsing(name, op(error))
+            CommandError::NamedArgumentParsing(argument, error) => {
+                CommandError::NamedArgumentParsing(argument, op(error))
             }
             CommandError::RequestError(error) => CommandError::RequestError(op(error)),
+            CommandError::UserError(message) => CommandError::UserError(message),
+        }
+    }
+
+    pub fn user_error<M: Into<Cow<'static, str>>>(message: M) -> Self {
+        CommandError::UserError(message.into())
+    }
+
+    pub fn user_message(&self) -> Option<&str> {
+        match self {
+            CommandError::UserError(message) => Some(message),
+            _ => None,
+        }
+    }
+
+    /// The argument which could not be parsed, if the error was caused by one.
+    pub fn invalid_argument(&self) -> Option<&InvalidArgument> {
+        match self {
+            CommandError::NamedArgumentParsing(argument, _) => Some(argument),
+            _ => None,
         }
     }
 
@@ -55,3 +97,111 @@ impl<Error> CommandError<Error> {
         self.map_err(|_| ())
     }
 }
+
+impl<Error: Display> Display for CommandError<Error> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CommandError::CommandMismatch => write!(f, "the command did not match"),
+            CommandError::SubcommandMismatch => write!(f, "the subcommand did not match"),
+            CommandError::ArgumentMissing => write!(f, "an argument is missing"),
+            CommandError::ArgumentParsing(error) => write!(f, "invalid argument: {}", error),
+            CommandError::ArgumentsLeftOver => write!(f, "too many arguments"),
+            CommandError::NamedArgumentParsing(argument, error) => write!(
+                f,
+                "invalid argument {} \"{}\": {}",
+                argument.name, argument.value, error
+            ),
+            CommandError::RequestError(error) => write!(f, "request failed: {}", error),
+            CommandError::UserError(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+// the error is not required to implement `Error`, such that `anyhow::Error` can be used
+impl<Error: Debug + Display> std::error::Error for CommandError<Error> {}
+
+/// The error type of generated commands, set with the `error = ...` option of `#[command]`.
+/// Argument and request errors are converted into it.
+pub trait CommandErrorType: Debug + Sized {
+    fn from_error<E: std::error::Error + Send + Sync + 'static>(error: E) -> Self;
+
+    /// Finds the range an argument was not within, to show it next to the syntax.
+    fn out_of_range(&self) -> Option<OutOfRange> {
+        None
+    }
+}
+
+impl CommandErrorType for anyhow::Error {
+    fn from_error<E: std::error::Error + Send + Sync + 'static>(error: E) -> Self {
+        anyhow::Error::new(error)
+    }
+
+    fn out_of_range(&self) -> Option<OutOfRange> {
+        self.chain()
+            .find_map(|error| error.downcast_ref::<OutOfRange>())
+            .copied()
+    }
+}
+
+impl CommandErrorType for Box<dyn std::error::Error + Send + Sync> {
+    fn from_error<E: std::error::Error + Send + Sync + 'static>(error: E) -> Self {
+        Box::new(error)
+    }
+
+    fn out_of_range(&self) -> Option<OutOfRange> {
+        let mut error: Option<&(dyn std::error::Error + 'static)> = Some(self.as_ref());
+        while let Some(current) = error {
+            if let Some(out_of_range) = current.downcast_ref::<OutOfRange>() {
+                return Some(*out_of_range);
+            }
+            error = current.source();
+        }
+        None
+    }
+}
+
+impl<Error: CommandErrorType> CommandError<Error> {
+    /// Describes why an argument was rejected, e.g. `volume must be between 0 and 100`.
+    pub fn argument_hint(&self) -> Option<String> {
+        let (name, out_of_range) = self.out_of_range_argument()?;
+        Some(match name {
+            Some(name) => format!("{} {}", name, out_of_range),
+            None => out_of_range.to_string(),
+        })
+    }
+
+    /// The name of the argument and the range it was not within.
+    pub fn out_of_range_argument(&self) -> Option<(Option<&'static str>, OutOfRange)> {
+        let (name, error) = match self {
+            CommandError::NamedArgumentParsing(argument, error) => (Some(argument.name), error),
+            CommandError::ArgumentParsing(error) => (None, error),
+            _ => return None,
+        };
+        Some((name, error.out_of_range()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let error = CommandError::NamedArgumentParsing(
+            InvalidArgument::new("volume", "loud"),
+            anyhow::anyhow!("not a number"),
+        );
+        assert_eq!(
+            error.to_string(),
+            "invalid argument volume \"loud\": not a number"
+        );
+        assert_eq!(error.invalid_argument().unwrap().value, "loud");
+        let error = CommandError::<anyhow::Error>::ArgumentMissing;
+        assert_eq!(error.to_string(), "an argument is missing");
+        let error = CommandError::<anyhow::Error>::user_error("The queue is full");
+        assert_eq!(error.to_string(), "The queue is full");
+        let error: Box<dyn std::error::Error> =
+            Box::new(CommandError::RequestError(anyhow::anyhow!("no channel")));
+        assert_eq!(error.to_string(), "request failed: no channel");
+    }
+}
@@ -71,6 +71,10 @@ impl_from_argument! {
     chrono::NaiveDate
     chrono::NaiveDateTime
     chrono::NaiveTime
+    chrono::DateTime<chrono::Utc>
+    chrono::DateTime<chrono::FixedOffset>
+    chrono::Weekday
+    chrono::Month
 
     http::uri::Uri
     url::Url
@@ -0,0 +1,51 @@
+use super::CommandError;
+use crate::request::CommandRequest;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommandOutcome {
+    Success,
+    ArgumentError,
+    RequestError,
+    UserError,
+}
+
+impl CommandOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommandOutcome::Success => "success",
+            CommandOutcome::ArgumentError => "argument_error",
+            CommandOutcome::RequestError => "request_error",
+            CommandOutcome::UserError => "user_error",
+        }
+    }
+}
+
+/// Receives a record for every command invocation of a generated processor.
+/// Requests for other commands are not recorded.
+pub trait CommandMetrics: Send + Sync {
+    fn record(&self, command: &str, latency: Duration, outcome: CommandOutcome);
+}
+
+/// Called by the code generated by `commands!` and `#[subcommands]`.
+pub fn record_metrics<T, E>(
+    request: &CommandRequest<'_>,
+    command: &str,
+    start: Instant,
+    result: &Result<T, CommandError<E>>,
+) {
+    let outcome = match result {
+        Ok(_) => CommandOutcome::Success,
+        Err(CommandError::RequestError(_)) => CommandOutcome::RequestError,
+        Err(CommandError::UserError(_)) => CommandOutcome::UserError,
+        Err(error) if error.is_argument_error() => CommandOutcome::ArgumentError,
+        Err(_) => return,
+    };
+    let metrics = request
+        .context
+        .and_then(|context| context.state::<Arc<dyn CommandMetrics>>().ok());
+    if let Some(metrics) = metrics {
+        metrics.record(command, start.elapsed(), outcome);
+    }
+}
@@ -1,31 +1,61 @@
+mod bounded;
 mod command_processor;
 mod error;
 mod from_argument;
+mod metrics;
+mod pattern;
+mod rate_limit;
 mod split;
 mod subcommand;
 
+pub use self::bounded::{Bounded, BoundedError, OutOfRange, Percent};
 pub use self::command_processor::CommandProcessor;
-pub use self::error::CommandError;
+pub use self::command_processor::CommandProcessors;
+pub use self::error::{CommandError, CommandErrorType, InvalidArgument};
 pub use self::from_argument::FromArgument;
+pub use self::metrics::{record_metrics, CommandMetrics, CommandOutcome};
+pub use self::pattern::{Captures, Pattern, PatternError, Segment};
+pub use self::rate_limit::{RateLimitAction, RateLimitDecision, UserRateLimit};
 pub use self::split::CommandArguments;
-pub use self::subcommand::FindSharedSyntax;
+pub use self::subcommand::{same_prefix, same_syntax, FindSharedSyntax};
 
 use crate::request::{CommandRequest, FromCommandRequest};
 use core::fmt::Debug;
+use std::borrow::Cow;
+
+/// Replaces `prefix` of the command with `!` and lowercases the command name if
+/// `case_insensitive` is set, which is used by `commands!` with the options of the same name.
+/// Returns `None` if the command does not start with `prefix`.
+pub fn normalize_command<'a>(
+    command: &'a str,
+    prefix: Option<&str>,
+    case_insensitive: bool,
+) -> Option<Cow<'a, str>> {
+    let command = match prefix {
+        Some(prefix) => Cow::Owned(format!("!{}", command.strip_prefix(prefix)?)),
+        None => Cow::Borrowed(command),
+    };
+    if !case_insensitive {
+        return Some(command);
+    }
+    let name_end = command.find(char::is_whitespace).unwrap_or(command.len());
+    let (name, rest) = command.split_at(name_end);
+    if !name.chars().any(char::is_uppercase) {
+        return Some(command);
+    }
+    Some(Cow::Owned(format!("{}{}", name.to_lowercase(), rest)))
+}
 
 pub fn next_argument<'req, T: FromArgument<'req> + 'req>(
     arg: Option<&'req str>,
     name: &'static str,
 ) -> Result<T, CommandError<<T as FromArgument<'req>>::Error>> {
-    let to_parsing = move |err| -> CommandError<<T as FromArgument<'req>>::Error> {
-        CommandError::NamedArgumentParsing(name, err)
+    let to_parsing = move |arg, err| -> CommandError<<T as FromArgument<'req>>::Error> {
+        CommandError::NamedArgumentParsing(InvalidArgument::new(name, arg), err)
     };
     match arg {
         None => Err(CommandError::ArgumentMissing),
-        Some(arg) => {
-            let arg = <T as FromArgument>::from_argument(arg);
-            arg.map_err(to_parsing)
-        }
+        Some(arg) => <T as FromArgument>::from_argument(arg).map_err(|err| to_parsing(arg, err)),
     }
 }
 
@@ -58,17 +88,53 @@ pub fn next_argument_anyhow<'req, T: FromArgument<'req> + 'req>(
     arg: Option<&'req str>,
     name: &'static str,
 ) -> Result<T, CommandError<anyhow::Error>> {
-    next_argument(arg, name).map_err(|err| err.map_err(anyhow::Error::new))
+    next_argument_with(arg, name)
 }
 
 pub fn next_optional_argument_anyhow<'req, T: FromArgument<'req> + 'req>(
     arg: Option<&'req str>,
     name: &'static str,
 ) -> Result<Option<T>, CommandError<anyhow::Error>> {
+    next_optional_argument_with(arg, name)
+}
+
+pub fn next_argument_with<'req, T: FromArgument<'req> + 'req, E: CommandErrorType>(
+    arg: Option<&'req str>,
+    name: &'static str,
+) -> Result<T, CommandError<E>> {
+    next_argument(arg, name).map_err(|err| err.map_err(E::from_error))
+}
+
+pub fn next_optional_argument_with<'req, T: FromArgument<'req> + 'req, E: CommandErrorType>(
+    arg: Option<&'req str>,
+    name: &'static str,
+) -> Result<Option<T>, CommandError<E>> {
     match next_argument(arg, name) {
         Ok(value) => Ok(Some(value)),
         Err(CommandError::ArgumentMissing) => Ok(None),
-        Err(err) => Err(err.map_err(anyhow::Error::new)),
+        Err(err) => Err(err.map_err(E::from_error)),
+    }
+}
+
+/// Parses every argument of a take-all argument like `<users..>` and collects them into a `Vec`.
+pub fn next_arguments_with<'req, T: FromArgument<'req> + 'req, E: CommandErrorType>(
+    arg: Option<&'req str>,
+    name: &'static str,
+) -> Result<Vec<T>, CommandError<E>> {
+    let arg = arg.ok_or(CommandError::ArgumentMissing)?;
+    CommandArguments::from(arg)
+        .map(|argument| next_argument_with(Some(argument), name))
+        .collect()
+}
+
+/// Like [`next_arguments_with`], but a missing argument like `[users..]` is an empty `Vec`.
+pub fn next_optional_arguments_with<'req, T: FromArgument<'req> + 'req, E: CommandErrorType>(
+    arg: Option<&'req str>,
+    name: &'static str,
+) -> Result<Vec<T>, CommandError<E>> {
+    match arg {
+        None => Ok(Vec::new()),
+        arg => next_arguments_with(arg, name),
     }
 }
 
@@ -89,6 +155,12 @@ pub fn from_command_request_option<'a, T: FromCommandRequest<'a, 'a> + 'a>(
 pub fn from_command_request_anyhow<'a, T: FromCommandRequest<'a, 'a> + 'a>(
     request: &'a CommandRequest<'a>,
 ) -> Result<T, CommandError<anyhow::Error>> {
+    from_command_request_with(request)
+}
+
+pub fn from_command_request_with<'a, T: FromCommandRequest<'a, 'a> + 'a, E: CommandErrorType>(
+    request: &'a CommandRequest<'a>,
+) -> Result<T, CommandError<E>> {
     let value = <T as FromCommandRequest>::from_command_request(request);
-    value.map_err(|err| CommandError::RequestError(anyhow::Error::new(err)))
+    value.map_err(|err| CommandError::RequestError(E::from_error(err)))
 }
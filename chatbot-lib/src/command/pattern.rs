@@ -0,0 +1,453 @@
+use super::{CommandArguments, CommandError, FromArgument};
+use core::fmt::{Display, Formatter};
+use core::str::FromStr;
+
+/// A whitespace separated segment of a command pattern, parsed the same way as the patterns of
+/// `#[command(...)]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment<'a> {
+    /// `!song` or `(!so|!shoutout)`
+    Command(&'a str),
+    /// `add` or `(add|create)`
+    Subcommand(&'a str),
+    /// `<name>`, `[name]`, `<name..>`, `[name..]` or `<name:Type>`
+    Argument {
+        name: &'a str,
+        take_all: bool,
+        optional: bool,
+        ty: Option<&'a str>,
+    },
+    /// `--name=<placeholder>` or `name:<placeholder>`, may appear in any order at the end
+    Flag {
+        name: &'a str,
+        prefix: &'a str,
+        placeholder: &'a str,
+    },
+    /// `..`, which ignores the rest of the arguments
+    TakeAll,
+}
+
+impl<'a> Segment<'a> {
+    pub fn parse(value: &'a str) -> Self {
+        if value.starts_with('!') || value.starts_with("(!") {
+            Self::Command(value)
+        } else if value == ".." {
+            Self::TakeAll
+        } else if let Some(value) = value
+            .strip_prefix('<')
+            .and_then(|value| value.strip_suffix('>'))
+        {
+            parse_argument(value, false)
+        } else if let Some(value) = value
+            .strip_prefix('[')
+            .and_then(|value| value.strip_suffix(']'))
+        {
+            parse_argument(value, true)
+        } else if let Some(flag) = parse_flag(value) {
+            flag
+        } else {
+            Self::Subcommand(value)
+        }
+    }
+
+    /// The name of an argument or flag, or the text of a command or subcommand.
+    pub fn key(&self) -> &'a str {
+        match self {
+            Segment::Command(value)
+            | Segment::Subcommand(value)
+            | Segment::Argument { name: value, .. }
+            | Segment::Flag { name: value, .. } => value,
+            Segment::TakeAll => "",
+        }
+    }
+
+    /// The spellings a command or subcommand matches, e.g. `add` and `create` for `(add|create)`.
+    pub fn alternatives(&self) -> Vec<&'a str> {
+        match self {
+            Segment::Command(value) | Segment::Subcommand(value) => value
+                .strip_prefix('(')
+                .and_then(|value| value.strip_suffix(')'))
+                .map_or_else(|| vec![*value], |value| value.split('|').collect()),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn is_taking_all(&self) -> bool {
+        matches!(
+            self,
+            Segment::Argument { take_all: true, .. } | Segment::TakeAll
+        )
+    }
+
+    pub fn is_optional(&self) -> bool {
+        matches!(
+            self,
+            Segment::Argument { optional: true, .. } | Segment::TakeAll
+        )
+    }
+
+    pub fn is_flag(&self) -> bool {
+        matches!(self, Segment::Flag { .. })
+    }
+}
+
+impl Display for Segment<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Segment::Command(value) | Segment::Subcommand(value) => value.fmt(f),
+            Segment::TakeAll => "..".fmt(f),
+            Segment::Argument {
+                name,
+                take_all,
+                optional,
+                ty,
+            } => {
+                let (open, close) = if *optional { ('[', ']') } else { ('<', '>') };
+                let rest = if *take_all { ".." } else { "" };
+                match ty {
+                    Some(ty) => write!(f, "{}{}:{}{}{}", open, name, ty, rest, close),
+                    None => write!(f, "{}{}{}{}", open, name, rest, close),
+                }
+            }
+            Segment::Flag {
+                prefix,
+                placeholder,
+                ..
+            } => write!(f, "{}<{}>", prefix, placeholder),
+        }
+    }
+}
+
+/// `name`, `name..`, `name:Type` or `name:Type..` of an argument
+fn parse_argument(value: &str, optional: bool) -> Segment<'_> {
+    let (value, take_all) = match value.strip_suffix("..") {
+        Some(value) => (value, true),
+        None => (value, false),
+    };
+    let (name, ty) = match value.split_once(':') {
+        Some((name, ty)) => (name, Some(ty.trim())),
+        None => (value, None),
+    };
+    Segment::Argument {
+        name,
+        take_all,
+        optional,
+        ty,
+    }
+}
+
+fn parse_flag(value: &str) -> Option<Segment<'_>> {
+    let index = value.find(['=', ':'])?;
+    let (prefix, placeholder) = value.split_at(index + 1);
+    let placeholder = placeholder.strip_prefix('<')?.strip_suffix('>')?;
+    let name = &value[..index];
+    let name = name.strip_prefix("--").unwrap_or(name);
+    if name.is_empty() {
+        return None;
+    }
+    Some(Segment::Flag {
+        name,
+        prefix,
+        placeholder,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+    Empty,
+    /// The segment follows an optional segment, but is required.
+    RequiredAfterOptional {
+        segment: String,
+        optional: String,
+    },
+    /// The segment is taken from the back after a greedy `<name..>`, so it can not be optional.
+    OptionalAfterTakeAll {
+        segment: String,
+        take_all: String,
+    },
+    MultipleTakeAll {
+        segment: String,
+        take_all: String,
+    },
+    Duplicate(String),
+}
+
+impl Display for PatternError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PatternError::Empty => write!(f, "the pattern is empty"),
+            PatternError::RequiredAfterOptional { segment, optional } => write!(
+                f,
+                "`{}` has to be optional, since it follows the optional `{}`",
+                segment, optional
+            ),
+            PatternError::OptionalAfterTakeAll { segment, take_all } => write!(
+                f,
+                "`{}` can not be optional, since it follows `{}`",
+                segment, take_all
+            ),
+            PatternError::MultipleTakeAll { segment, take_all } => write!(
+                f,
+                "only one `..` is allowed, but `{}` follows `{}`",
+                segment, take_all
+            ),
+            PatternError::Duplicate(segment) => {
+                write!(f, "`{}` appears twice in the pattern", segment)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+/// A command pattern like `!timer add <name..> <duration> --reply=<bool>`, which is parsed at
+/// runtime, e.g. for custom commands which are defined in the chat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pattern: String,
+}
+
+impl Pattern {
+    pub fn parse(pattern: &str) -> Result<Self, PatternError> {
+        let segments: Vec<_> = pattern.split_whitespace().map(Segment::parse).collect();
+        if segments.is_empty() {
+            return Err(PatternError::Empty);
+        }
+        let mut optional: Option<&Segment> = None;
+        let mut take_all: Option<&Segment> = None;
+        for (index, segment) in segments.iter().enumerate() {
+            if !matches!(segment, Segment::TakeAll)
+                && segments[..index]
+                    .iter()
+                    .any(|other| other.key() == segment.key())
+            {
+                return Err(PatternError::Duplicate(segment.to_string()));
+            }
+            if segment.is_flag() {
+                continue;
+            }
+            if let (Some(take_all), true) = (take_all, segment.is_optional()) {
+                return Err(PatternError::OptionalAfterTakeAll {
+                    segment: segment.to_string(),
+                    take_all: take_all.to_string(),
+                });
+            }
+            match optional {
+                None if segment.is_optional() => optional = Some(segment),
+                Some(optional) if !segment.is_optional() => {
+                    return Err(PatternError::RequiredAfterOptional {
+                        segment: segment.to_string(),
+                        optional: optional.to_string(),
+                    })
+                }
+                _ => {}
+            }
+            if segment.is_taking_all() {
+                if let Some(take_all) = take_all {
+                    return Err(PatternError::MultipleTakeAll {
+                        segment: segment.to_string(),
+                        take_all: take_all.to_string(),
+                    });
+                }
+                take_all = Some(segment);
+            }
+        }
+        Ok(Self {
+            pattern: pattern.split_whitespace().collect::<Vec<_>>().join(" "),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = Segment<'_>> {
+        self.pattern.split_whitespace().map(Segment::parse)
+    }
+
+    /// Matches the arguments like a `#[command(...)]` with this pattern would: flags are taken from
+    /// the end, the segments after a greedy `<name..>` are taken from the back and no arguments
+    /// may be left over.
+    pub fn match_arguments<'a>(
+        &self,
+        mut arguments: CommandArguments<'a>,
+    ) -> Result<Captures<'_, 'a>, CommandError<()>> {
+        let segments: Vec<_> = self.segments().collect();
+        let mut values = vec![None; segments.len()];
+
+        let flags: Vec<_> = segments
+            .iter()
+            .enumerate()
+            .filter_map(|(index, segment)| match segment {
+                Segment::Flag { prefix, .. } => Some((index, *prefix)),
+                _ => None,
+            })
+            .collect();
+        let prefixes: Vec<_> = flags.iter().map(|(_, prefix)| *prefix).collect();
+        let mut flag_values = vec![None; flags.len()];
+        arguments.take_flags_into(&prefixes, &mut flag_values);
+        for ((index, _), value) in flags.iter().zip(flag_values) {
+            values[*index] = value;
+        }
+
+        let positional: Vec<_> = (0..segments.len())
+            .filter(|index| !segments[*index].is_flag())
+            .collect();
+        let split = positional
+            .iter()
+            .position(|index| segments[*index].is_taking_all())
+            .unwrap_or(positional.len());
+        for index in &positional[..split] {
+            values[*index] = match_segment(&segments[*index], arguments.next())?;
+        }
+        for index in positional[split..].iter().skip(1).rev() {
+            values[*index] = match_segment(&segments[*index], arguments.next_back())?;
+        }
+        if let Some(index) = positional.get(split) {
+            let rest = arguments.next_rest();
+            values[*index] = match segments[*index] {
+                Segment::Argument { .. } => match_segment(&segments[*index], rest)?,
+                _ => None,
+            };
+        }
+        if arguments.next_rest().is_some() {
+            return Err(CommandError::ArgumentsLeftOver);
+        }
+
+        let values = segments
+            .iter()
+            .zip(values)
+            .filter_map(|(segment, value)| match (segment, value) {
+                (Segment::Argument { name, .. } | Segment::Flag { name, .. }, Some(value)) => {
+                    Some((*name, value))
+                }
+                _ => None,
+            })
+            .collect();
+        Ok(Captures { values })
+    }
+
+    /// Matches a command like `!timer add drink water 30m`, see [`Pattern::match_arguments`].
+    pub fn matches<'a>(&self, command: &'a str) -> Result<Captures<'_, 'a>, CommandError<()>> {
+        self.match_arguments(CommandArguments::from(command))
+    }
+}
+
+/// Returns the captured value of an argument.
+fn match_segment<'a>(
+    segment: &Segment<'_>,
+    value: Option<&'a str>,
+) -> Result<Option<&'a str>, CommandError<()>> {
+    match segment {
+        Segment::Command(_) => match value {
+            Some(value) if segment.alternatives().contains(&value) => Ok(None),
+            _ => Err(CommandError::CommandMismatch),
+        },
+        Segment::Subcommand(_) => match value {
+            Some(value) if segment.alternatives().contains(&value) => Ok(None),
+            _ => Err(CommandError::SubcommandMismatch),
+        },
+        Segment::Argument { optional, .. } => match value {
+            None if !optional => Err(CommandError::ArgumentMissing),
+            value => Ok(value),
+        },
+        Segment::Flag { .. } | Segment::TakeAll => Ok(None),
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = PatternError;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        Self::parse(pattern)
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        self.pattern.fmt(f)
+    }
+}
+
+/// The values of the arguments and flags of a matched [`Pattern`], missing optional arguments
+/// are not captured.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Captures<'p, 'a> {
+    values: Vec<(&'p str, &'a str)>,
+}
+
+impl<'p, 'a> Captures<'p, 'a> {
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.values
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// Parses the value of an argument, `None` if it was not captured.
+    pub fn parse<T: FromArgument<'a>>(&self, name: &str) -> Option<Result<T, T::Error>> {
+        self.get(name).map(T::from_argument)
+    }
+
+    /// The names and values in the order of the pattern.
+    pub fn iter(&self) -> impl Iterator<Item = (&'p str, &'a str)> + '_ {
+        self.values.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern() {
+        let pattern =
+            Pattern::parse("!timer (add|create) <name..> <duration> reply:<bool>").unwrap();
+        let captures = pattern
+            .matches("!timer create drink some water 30m reply:yes")
+            .unwrap();
+        assert_eq!(
+            captures.iter().collect::<Vec<_>>(),
+            [
+                ("name", "drink some water"),
+                ("duration", "30m"),
+                ("reply", "yes")
+            ]
+        );
+        assert!(matches!(
+            pattern.matches("!timer remove water 30m"),
+            Err(CommandError::SubcommandMismatch)
+        ));
+        assert!(matches!(
+            pattern.matches("!timer add 30m"),
+            Err(CommandError::ArgumentMissing)
+        ));
+
+        let pattern: Pattern = "!so <user> [count:u32]".parse().unwrap();
+        let captures = pattern.matches("!so liquidnya 2").unwrap();
+        assert_eq!(captures.parse::<u32>("count").unwrap(), Ok(2));
+        assert!(matches!(
+            pattern.matches("!so liquidnya 2 3"),
+            Err(CommandError::ArgumentsLeftOver)
+        ));
+
+        assert_eq!(
+            Pattern::parse("!song [title] <url>")
+                .unwrap_err()
+                .to_string(),
+            "`<url>` has to be optional, since it follows the optional `[title]`"
+        );
+        assert!(matches!(
+            Pattern::parse("!song <a..> <b..>"),
+            Err(PatternError::MultipleTakeAll { .. })
+        ));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::request::Sender;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitAction {
+    /// Commands over the limit are ignored.
+    Drop,
+    /// Commands over the limit are ignored, the first one within a window is answered with a warning.
+    Warn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitDecision {
+    Allow,
+    Drop,
+    Warn,
+}
+
+#[derive(Debug, Default)]
+struct UserHistory {
+    commands: VecDeque<Instant>,
+    warned: bool,
+}
+
+/// Limits how many commands a single user can trigger within `window`.
+/// Moderators and the broadcaster are not limited.
+#[derive(Debug)]
+pub struct UserRateLimit {
+    max_commands: usize,
+    window: Duration,
+    action: RateLimitAction,
+    history: Mutex<HashMap<String, UserHistory>>,
+}
+
+impl UserRateLimit {
+    pub fn new(max_commands: usize, window: Duration) -> Self {
+        Self {
+            max_commands,
+            window,
+            action: RateLimitAction::Drop,
+            history: Mutex::default(),
+        }
+    }
+
+    pub fn action(self, action: RateLimitAction) -> Self {
+        Self { action, ..self }
+    }
+
+    pub fn check(&self, sender: &Sender<'_>) -> RateLimitDecision {
+        self.check_at(sender, Instant::now())
+    }
+
+    fn check_at(&self, sender: &Sender<'_>, now: Instant) -> RateLimitDecision {
+        if sender.is_moderator() || sender.is_broadcaster() {
+            return RateLimitDecision::Allow;
+        }
+        let key = match sender.user_id() {
+            Some(user_id) => user_id.to_string(),
+            None => sender.username().to_owned(),
+        };
+        let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        // forget users which did not use a command within the window
+        history.retain(|_, user| {
+            user.commands
+                .back()
+                .is_some_and(|last| now.duration_since(*last) < self.window)
+        });
+        let user = history.entry(key).or_default();
+        while user
+            .commands
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= self.window)
+        {
+            user.commands.pop_front();
+            user.warned = false;
+        }
+        if user.commands.len() < self.max_commands {
+            user.commands.push_back(now);
+            return RateLimitDecision::Allow;
+        }
+        match self.action {
+            RateLimitAction::Warn if !user.warned => {
+                user.warned = true;
+                RateLimitDecision::Warn
+            }
+            _ => RateLimitDecision::Drop,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::User;
+
+    #[test]
+    fn test_user_rate_limit() {
+        let rate_limit =
+            UserRateLimit::new(2, Duration::from_secs(10)).action(RateLimitAction::Warn);
+        let user = Sender::from(User::from_username("user"));
+        let moderator = Sender::new(User::from_username("moderator"), true, false);
+        let start = Instant::now();
+        assert_eq!(rate_limit.check_at(&user, start), RateLimitDecision::Allow);
+        assert_eq!(rate_limit.check_at(&user, start), RateLimitDecision::Allow);
+        assert_eq!(rate_limit.check_at(&user, start), RateLimitDecision::Warn);
+        assert_eq!(rate_limit.check_at(&user, start), RateLimitDecision::Drop);
+        for _ in 0..5 {
+            assert_eq!(
+                rate_limit.check_at(&moderator, start),
+                RateLimitDecision::Allow
+            );
+        }
+        let later = start + Duration::from_secs(10);
+        assert_eq!(rate_limit.check_at(&user, later), RateLimitDecision::Allow);
+    }
+}
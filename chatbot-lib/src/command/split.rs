@@ -63,6 +63,36 @@ impl<'a> CommandArguments<'a> {
         none_if_empty(result)
     }
 
+    /// Takes `prefix<value>` arguments from the end in any order, each prefix at most once.
+    pub fn take_flags<const N: usize>(&mut self, prefixes: [&str; N]) -> [Option<&'a str>; N] {
+        let mut flags = [None; N];
+        self.take_flags_into(&prefixes, &mut flags);
+        flags
+    }
+
+    /// Like [`CommandArguments::take_flags`] for a number of prefixes only known at runtime, the
+    /// value of `prefixes[i]` is stored in `flags[i]`.
+    pub fn take_flags_into(&mut self, prefixes: &[&str], flags: &mut [Option<&'a str>]) {
+        loop {
+            let mut rest = self.clone();
+            let matched = rest.next_back().and_then(|argument| {
+                prefixes.iter().enumerate().find_map(|(index, prefix)| {
+                    argument
+                        .strip_prefix(prefix)
+                        .filter(|_| flags[index].is_none())
+                        .map(|value| (index, value))
+                })
+            });
+            match matched {
+                Some((index, value)) => {
+                    flags[index] = Some(value);
+                    *self = rest;
+                }
+                None => return,
+            }
+        }
+    }
+
     pub fn consumed_begin(&self) -> Self {
         Self::from(&self.str[..self.range.start])
     }
@@ -105,7 +135,8 @@ impl<'a> DoubleEndedIterator for CommandArguments<'a> {
             .rfind(|c: char| !c.is_whitespace())
             .map(|i| start + i + 1)
             .and_then(|end| {
-                let index = rfind_index_plus_one(&self.str[start..end], char::is_whitespace);
+                let index =
+                    start + rfind_index_plus_one(&self.str[start..end], char::is_whitespace);
                 self.range = start..index;
                 none_if_empty(&self.str[index..end])
             })
@@ -150,6 +181,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_take_flags() {
+        let test = "!timer set name --cooldown=5m reply:yes --cooldown=1m";
+        let mut iter = CommandArguments::from(test);
+        let flags = iter.take_flags(["--cooldown=", "reply:"]);
+        assert_eq!(flags, [Some("1m"), Some("yes")]);
+        assert_eq!(iter.as_str(), "!timer set name --cooldown=5m");
+
+        let mut iter = CommandArguments::from("!timer --cooldown=5m set");
+        assert_eq!(iter.take_flags(["--cooldown="]), [None]);
+        assert_eq!(iter.next_rest(), Some("!timer --cooldown=5m set"));
+    }
+
     #[test]
     fn test() {
         let test = "Hello World!";
@@ -183,6 +227,16 @@ mod tests {
         assert_eq!(iter.next_back(), None);
     }
 
+    #[test]
+    fn test_greedy_middle() {
+        let test = "!timer add drink some water 30m";
+        let mut iter = CommandArguments::from(test);
+        assert_eq!(iter.next(), Some("!timer"));
+        assert_eq!(iter.next(), Some("add"));
+        assert_eq!(iter.next_back(), Some("30m"));
+        assert_eq!(iter.next_rest(), Some("drink some water"));
+    }
+
     #[test]
     fn test_back_more_spaces() {
         let test = "   Hello    World!   ";
@@ -66,6 +66,62 @@ impl<'a> FindSharedSyntax<'a> {
     }
 }
 
+const fn skip_whitespace(value: &[u8], mut index: usize, len: usize) -> usize {
+    while index < len && value[index].is_ascii_whitespace() {
+        index += 1;
+    }
+    index
+}
+
+/// Compares two patterns while ignoring differences in whitespace, usable in constants.
+pub const fn same_syntax(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    same_words(a, a.len(), b, b.len())
+}
+
+/// Compares the words of two patterns up to their first argument, e.g. `!song add <url>` and
+/// `!song add <command>`, which are both tried for the same messages. Usable in constants.
+pub const fn same_prefix(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    same_words(a, literal_prefix_len(a), b, literal_prefix_len(b))
+}
+
+// the start of the first word which contains an argument like `<url>` or `[reason..]`
+const fn literal_prefix_len(value: &[u8]) -> usize {
+    let mut word_start = 0;
+    let mut index = 0;
+    while index < value.len() {
+        if value[index].is_ascii_whitespace() {
+            word_start = index + 1;
+        } else if value[index] == b'<' || value[index] == b'[' {
+            return word_start;
+        }
+        index += 1;
+    }
+    value.len()
+}
+
+// compares `a[..a_len]` and `b[..b_len]` while ignoring differences in whitespace
+const fn same_words(a: &[u8], a_len: usize, b: &[u8], b_len: usize) -> bool {
+    let mut i = skip_whitespace(a, 0, a_len);
+    let mut j = skip_whitespace(b, 0, b_len);
+    while i < a_len && j < b_len {
+        let separator_a = a[i].is_ascii_whitespace();
+        let separator_b = b[j].is_ascii_whitespace();
+        if separator_a != separator_b || (!separator_a && a[i] != b[j]) {
+            return false;
+        }
+        if separator_a {
+            i = skip_whitespace(a, i, a_len);
+            j = skip_whitespace(b, j, b_len);
+        } else {
+            i += 1;
+            j += 1;
+        }
+    }
+    skip_whitespace(a, i, a_len) == a_len && skip_whitespace(b, j, b_len) == b_len
+}
+
 #[cfg(test)]
 mod test {
     /*use super::find_prefix_index;
@@ -76,7 +132,24 @@ mod test {
         assert_eq!(find_prefix_index("aba", "abä"), 2);
     }*/
 
-    use super::FindSharedSyntax;
+    use super::{same_prefix, same_syntax, FindSharedSyntax};
+
+    #[test]
+    fn test_same_syntax() {
+        assert!(same_syntax("!song add <url>", "  !song   add <url> "));
+        assert!(!same_syntax("!song add <url>", "!song add <command>"));
+        assert!(!same_syntax("!song add", "!song add <url>"));
+        assert!(!same_syntax("!songadd", "!song add"));
+    }
+
+    #[test]
+    fn test_same_prefix() {
+        assert!(same_prefix("!song add <url>", "!song add <command>"));
+        assert!(same_prefix("!song skip [count]", " !song  skip"));
+        assert!(!same_prefix("!song add <url>", "!song remove <url>"));
+        assert!(!same_prefix("!song <url>", "!song list"));
+        assert!(!same_prefix("!songadd <url>", "!song add <url>"));
+    }
 
     #[test]
     fn test_find_prefix_index() {
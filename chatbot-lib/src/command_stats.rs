@@ -0,0 +1,208 @@
+//! How often each command of a channel was used, which helps finding commands nobody uses.
+//!
+//! [`CommandStats`] has to be registered with
+//! [`ContainerBuilder::register_persisted_type`](crate::state::ContainerBuilder::register_persisted_type),
+//! then every command which was answered by a command processor is counted.
+
+use crate::command::{CommandArguments, CommandProcessor};
+use crate::request::{CommandRequest, FromCommandRequest};
+use crate::response::Response;
+use crate::state::{PersistedChannelState, PersistedType, WritePolicy};
+use crate::user::UserArgument;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use core::fmt::{Display, Formatter};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CommandUsage {
+    pub count: u64,
+    pub last_used: DateTime<Utc>,
+    /// The user who used the command last.
+    pub last_user: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct CommandStats {
+    commands: BTreeMap<String, CommandUsage>,
+}
+
+impl CommandStats {
+    pub fn get(&self, command: &str) -> Option<&CommandUsage> {
+        self.commands.get(command)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &CommandUsage)> {
+        self.commands
+            .iter()
+            .map(|(command, usage)| (command.as_str(), usage))
+    }
+
+    pub fn record(&mut self, command: &str, user: &str, used_at: DateTime<Utc>) {
+        let usage = self
+            .commands
+            .entry(command.to_owned())
+            .or_insert_with(|| CommandUsage {
+                count: 0,
+                last_used: used_at,
+                last_user: String::new(),
+            });
+        usage.count = usage.count.saturating_add(1);
+        usage.last_used = used_at;
+        usage.last_user = user.to_owned();
+    }
+
+    /// The `n` most used commands, ordered by count.
+    pub fn most_used(&self, n: usize) -> Vec<(&str, &CommandUsage)> {
+        let mut commands: Vec<_> = self.iter().collect();
+        commands.sort_by_key(|(_, usage)| core::cmp::Reverse(usage.count));
+        commands.truncate(n);
+        commands
+    }
+}
+
+impl PersistedType for CommandStats {
+    const FILENAME: &'static str = "command_stats";
+    // every command changes the stats
+    const WRITE_POLICY: WritePolicy = WritePolicy::Debounced(Duration::from_secs(60));
+
+    fn init(_channel: &str) -> Self {
+        Self::default()
+    }
+}
+
+pub type CommandStatsState<'req> = PersistedChannelState<'req, CommandStats>;
+
+/// The name of the command, e.g. `!quote`.
+fn command_name(command: &str) -> Option<String> {
+    CommandArguments::from(command)
+        .next()
+        .map(|name| name.to_lowercase())
+}
+
+/// Counts the command of `request` if [`CommandStats`] is registered.
+pub(crate) async fn record_command(request: &CommandRequest<'_>) {
+    let stats = match CommandStatsState::from_command_request(request) {
+        Ok(stats) => stats,
+        Err(_) => return,
+    };
+    let name = match command_name(request.command()) {
+        Some(name) => name,
+        None => return,
+    };
+    let sender = request.sender();
+    let user = sender.display_name().unwrap_or(sender.username());
+    let now = Utc::now();
+    stats
+        .update(|stats| {
+            let mut stats = stats.clone();
+            stats.record(&name, user, now);
+            stats
+        })
+        .await;
+}
+
+struct Usage<'a>(&'a str, &'a CommandUsage);
+
+impl Display for Usage<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} was used {} times, last by {} on {}",
+            self.0,
+            self.1.count,
+            self.1.last_user,
+            self.1.last_used.format("%Y-%m-%d")
+        )
+    }
+}
+
+/// Processes `!stats` and `!stats <command>` for moderators.
+pub struct CommandStatsCommands;
+
+impl CommandStatsCommands {
+    async fn process_stats(
+        request: &CommandRequest<'_>,
+        arguments: &mut CommandArguments<'_>,
+    ) -> Result<String, Cow<'static, str>> {
+        let stats = CommandStatsState::from_command_request(request)
+            .map_err(|_| "the command stats are not registered for this channel")?;
+        let stats = stats.read().await;
+        let command = arguments.next();
+        if !arguments.as_str().is_empty() {
+            return Err("!stats [command]".into());
+        }
+        match command {
+            None => {
+                let most_used = stats.most_used(5);
+                if most_used.is_empty() {
+                    return Err("no commands were used yet".into());
+                }
+                let most_used: Vec<_> = most_used
+                    .into_iter()
+                    .map(|(command, usage)| format!("{} ({})", command, usage.count))
+                    .collect();
+                Ok(format!("most used commands: {}", most_used.join(", ")))
+            }
+            Some(command) => {
+                let command = match command.starts_with('!') {
+                    true => command.to_lowercase(),
+                    false => format!("!{}", command.to_lowercase()),
+                };
+                match stats.get(&command) {
+                    Some(usage) => Ok(Usage(&command, usage).to_string()),
+                    None => Err(format!("{} was never used", command).into()),
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for CommandStatsCommands {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        if arguments.next() != Some("!stats") {
+            return None;
+        }
+        let sender = request.sender();
+        if !sender.is_moderator() && !sender.is_broadcaster() {
+            return None;
+        }
+        let text = match Self::process_stats(request, &mut arguments).await {
+            Ok(text) => text,
+            Err(error) => error.into_owned(),
+        };
+        Some(Response::new(format!(
+            "{} {}",
+            UserArgument::from(sender as &crate::user::User),
+            text
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_command_stats() {
+        let used_at = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+        let mut stats = CommandStats::default();
+        stats.record("!quote", "user", used_at);
+        stats.record("!quote", "LiquidNya", used_at);
+        stats.record("!count", "user", used_at);
+        let most_used = stats.most_used(1);
+        assert_eq!(most_used.len(), 1);
+        assert_eq!(
+            Usage(most_used[0].0, most_used[0].1).to_string(),
+            "!quote was used 2 times, last by LiquidNya on 2023-04-01"
+        );
+        assert_eq!(command_name("!Quote add nya"), Some("!quote".to_owned()));
+        assert_eq!(command_name(""), None);
+    }
+}
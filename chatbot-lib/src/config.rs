@@ -0,0 +1,353 @@
+//! Loads the setup of a bot from a TOML or RON file, see [`Config`] and
+//! [`ChatBot::from_config`](crate::ChatBot::from_config).
+//!
+//! ```toml
+//! login = "helperblock"
+//! token = "oauth:..."
+//! channels = ["liquidnya"]
+//! prefix = "?"
+//!
+//! [features]
+//! persist_chatters = "5m"
+//! reminders = "30s"
+//! ```
+//!
+//! Every value of the file can be replaced by an environment variable, e.g. the token by
+//! `CHATBOT_TOKEN`, so secrets do not have to be stored in the file. See [`Config::with_env`].
+
+use crate::state::DataDir;
+use crate::user::NameMatching;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use twitchchat::UserConfig;
+
+/// The prefix of the environment variables which replace the values of the file.
+pub const ENV_PREFIX: &str = "CHATBOT_";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The username of the bot.
+    pub login: String,
+    /// The OAuth token of the bot, `oauth:` is added if it is missing.
+    pub token: String,
+    pub refresh_token: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    /// The key of persisted types which are encrypted, 64 hex digits.
+    /// Requires the `encryption` feature.
+    pub encryption_key: Option<String>,
+    /// The channels which are joined after connecting.
+    pub channels: Vec<String>,
+    /// Replaces `!` as the prefix of commands in channels without a prefix in their
+    /// [`ChannelConfig`](crate::state::ChannelConfig).
+    pub prefix: Option<String>,
+    /// Users whose messages are ignored in all channels, e.g. other bots.
+    pub ignored_users: Vec<String>,
+    /// The directory of the persisted state, `data` by default.
+    /// Every bot on the same host needs its own directory.
+    pub data_dir: Option<PathBuf>,
+    pub features: Features,
+}
+
+/// The optional parts of [`ChatBot`](crate::ChatBot) which are turned on by the config.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Features {
+    /// Processes the messages of the bot itself, see
+    /// [`ChatBot::process_self`](crate::ChatBot::process_self).
+    pub process_self: bool,
+    /// Logs responses instead of sending them, see [`ChatBot::dry_run`](crate::ChatBot::dry_run).
+    pub dry_run: bool,
+    /// Tracks the messages of the bot, see
+    /// [`ChatBot::echo_sent_messages`](crate::ChatBot::echo_sent_messages).
+    pub echo_sent_messages: bool,
+    /// Writes the commands to the audit log, see
+    /// [`ChatBot::with_audit_log`](crate::ChatBot::with_audit_log).
+    pub audit_log: bool,
+    pub concurrency: Option<usize>,
+    /// How often the chatters are written to disk, e.g. `"5m"`.
+    #[serde(with = "humantime_option")]
+    pub persist_chatters: Option<Duration>,
+    /// How often reminders are checked, e.g. `"30s"`.
+    #[serde(with = "humantime_option")]
+    pub reminders: Option<Duration>,
+    /// How the names of chatters are compared, e.g. `"ascii_case_insensitive"`.
+    pub name_matching: NameMatching,
+}
+
+/// The format of a config file, which is chosen by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Ron,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(ConfigFormat::Toml),
+            "ron" => Some(ConfigFormat::Ron),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    UnknownFormat(PathBuf),
+    Toml(toml::de::Error),
+    Ron(ron::error::SpannedError),
+    /// An environment variable could not be parsed.
+    InvalidEnv(String),
+    Missing(&'static str),
+    InvalidUser(String),
+    Connector(std::io::Error),
+    InvalidEncryptionKey(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "could not read {}: {}", path.display(), e),
+            ConfigError::UnknownFormat(path) => write!(
+                f,
+                "unknown format of {}, expected a .toml or .ron file",
+                path.display()
+            ),
+            ConfigError::Toml(e) => write!(f, "invalid config: {}", e),
+            ConfigError::Ron(e) => write!(f, "invalid config: {}", e),
+            ConfigError::InvalidEnv(name) => write!(f, "invalid value of {}", name),
+            ConfigError::Missing(key) => write!(f, "missing {} in the config", key),
+            ConfigError::InvalidUser(e) => write!(f, "invalid login: {}", e),
+            ConfigError::Connector(e) => write!(f, "could not create the connector: {}", e),
+            ConfigError::InvalidEncryptionKey(e) => write!(f, "invalid encryption_key: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(_, e) | ConfigError::Connector(e) => Some(e),
+            ConfigError::Toml(e) => Some(e),
+            ConfigError::Ron(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the file at `path` and replaces its values with the environment variables.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let format =
+            ConfigFormat::from_path(path).ok_or_else(|| ConfigError::UnknownFormat(path.into()))?;
+        let text =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        Self::parse(&text, format)?.with_env(|name| std::env::var(name).ok())
+    }
+
+    /// Parses a config without looking at environment variables.
+    pub fn parse(text: &str, format: ConfigFormat) -> Result<Self, ConfigError> {
+        match format {
+            ConfigFormat::Toml => toml::from_str(text).map_err(ConfigError::Toml),
+            ConfigFormat::Ron => ron::from_str(text).map_err(ConfigError::Ron),
+        }
+    }
+
+    /// Replaces values with the variables returned by `var`, which is called with names like
+    /// `CHATBOT_LOGIN`, `CHATBOT_TOKEN` or `CHATBOT_FEATURES_DRY_RUN`.
+    /// Channels and ignored users are separated by commas.
+    pub fn with_env<F>(mut self, var: F) -> Result<Self, ConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let var = |name: &str| var(&format!("{}{}", ENV_PREFIX, name));
+        let parse = |name: &str, value: String| {
+            let invalid = || ConfigError::InvalidEnv(format!("{}{}", ENV_PREFIX, name));
+            match value.as_str() {
+                "1" | "true" | "on" => Ok(true),
+                "0" | "false" | "off" => Ok(false),
+                _ => Err(invalid()),
+            }
+        };
+        let duration = |name: &str, value: String| {
+            humantime::parse_duration(&value)
+                .map_err(|_| ConfigError::InvalidEnv(format!("{}{}", ENV_PREFIX, name)))
+        };
+        if let Some(login) = var("LOGIN") {
+            self.login = login;
+        }
+        if let Some(token) = var("TOKEN") {
+            self.token = token;
+        }
+        for (name, value) in [
+            ("REFRESH_TOKEN", &mut self.refresh_token),
+            ("CLIENT_ID", &mut self.client_id),
+            ("CLIENT_SECRET", &mut self.client_secret),
+            ("ENCRYPTION_KEY", &mut self.encryption_key),
+            ("PREFIX", &mut self.prefix),
+        ] {
+            if let Some(var) = var(name) {
+                *value = Some(var);
+            }
+        }
+        let list = |value: String| -> Vec<String> {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_owned)
+                .collect()
+        };
+        if let Some(channels) = var("CHANNELS") {
+            self.channels = list(channels);
+        }
+        if let Some(users) = var("IGNORED_USERS") {
+            self.ignored_users = list(users);
+        }
+        if let Some(dir) = var("DATA_DIR") {
+            self.data_dir = Some(dir.into());
+        }
+        let features = &mut self.features;
+        if let Some(value) = var("FEATURES_PROCESS_SELF") {
+            features.process_self = parse("FEATURES_PROCESS_SELF", value)?;
+        }
+        if let Some(value) = var("FEATURES_DRY_RUN") {
+            features.dry_run = parse("FEATURES_DRY_RUN", value)?;
+        }
+        if let Some(value) = var("FEATURES_ECHO_SENT_MESSAGES") {
+            features.echo_sent_messages = parse("FEATURES_ECHO_SENT_MESSAGES", value)?;
+        }
+        if let Some(value) = var("FEATURES_AUDIT_LOG") {
+            features.audit_log = parse("FEATURES_AUDIT_LOG", value)?;
+        }
+        if let Some(value) = var("FEATURES_CONCURRENCY") {
+            features.concurrency = Some(value.parse().map_err(|_| {
+                ConfigError::InvalidEnv(format!("{}FEATURES_CONCURRENCY", ENV_PREFIX))
+            })?);
+        }
+        if let Some(value) = var("FEATURES_PERSIST_CHATTERS") {
+            features.persist_chatters = Some(duration("FEATURES_PERSIST_CHATTERS", value)?);
+        }
+        if let Some(value) = var("FEATURES_REMINDERS") {
+            features.reminders = Some(duration("FEATURES_REMINDERS", value)?);
+        }
+        if let Some(value) = var("FEATURES_NAME_MATCHING") {
+            features.name_matching = value.parse().map_err(|_| {
+                ConfigError::InvalidEnv(format!("{}FEATURES_NAME_MATCHING", ENV_PREFIX))
+            })?;
+        }
+        Ok(self)
+    }
+
+    pub fn data_dir(&self) -> DataDir {
+        self.data_dir.clone().map(DataDir::new).unwrap_or_default()
+    }
+
+    /// The login of the bot for Twitch chat.
+    pub fn user_config(&self) -> Result<UserConfig, ConfigError> {
+        if self.login.is_empty() {
+            return Err(ConfigError::Missing("login"));
+        }
+        if self.token.is_empty() {
+            return Err(ConfigError::Missing("token"));
+        }
+        let token = if self.token.starts_with("oauth:") {
+            self.token.clone()
+        } else {
+            format!("oauth:{}", self.token)
+        };
+        UserConfig::builder()
+            .name(&self.login)
+            .token(token)
+            .enable_all_capabilities()
+            .build()
+            .map_err(|e| ConfigError::InvalidUser(e.to_string()))
+    }
+}
+
+/// Durations like `"5m"` instead of seconds and nanoseconds.
+mod humantime_option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => {
+                serializer.serialize_some(&humantime::format_duration(*duration).to_string())
+            }
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|text| humantime::parse_duration(&text).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let text = r#"
+            login = "helperblock"
+            token = "abc"
+            channels = ["liquidnya"]
+
+            [features]
+            dry_run = true
+            reminders = "30s"
+        "#;
+        let config = Config::parse(text, ConfigFormat::Toml).unwrap();
+        assert_eq!(config.channels, ["liquidnya"]);
+        assert!(config.features.dry_run);
+        assert_eq!(config.features.reminders, Some(Duration::from_secs(30)));
+        let ron = Config::parse(
+            r#"(login: "helperblock", token: "abc", channels: ["liquidnya"], features: (dry_run: true, reminders: Some("30s")))"#,
+            ConfigFormat::Ron,
+        )
+        .unwrap();
+        assert_eq!(ron, config);
+
+        let config = config
+            .with_env(|name| match name {
+                "CHATBOT_CHANNELS" => Some("liquidnya, helperblock".to_owned()),
+                "CHATBOT_PREFIX" => Some("?".to_owned()),
+                "CHATBOT_IGNORED_USERS" => Some("Nightbot,".to_owned()),
+                "CHATBOT_FEATURES_DRY_RUN" => Some("off".to_owned()),
+                "CHATBOT_FEATURES_NAME_MATCHING" => Some("exact".to_owned()),
+                "CHATBOT_DATA_DIR" => Some("data/helperblock".to_owned()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(config.channels, ["liquidnya", "helperblock"]);
+        assert_eq!(config.prefix.as_deref(), Some("?"));
+        assert_eq!(config.ignored_users, ["Nightbot"]);
+        assert!(!config.features.dry_run);
+        assert_eq!(config.features.name_matching, NameMatching::Exact);
+        assert_eq!(config.data_dir(), DataDir::new("data/helperblock"));
+        assert_eq!(Config::default().data_dir(), DataDir::new("data"));
+        assert_eq!(config.user_config().unwrap().token, "oauth:abc");
+        assert!(matches!(
+            Config::default().user_config(),
+            Err(ConfigError::Missing("login"))
+        ));
+        assert!(matches!(
+            Config::default().with_env(|_| Some("maybe".to_owned())),
+            Err(ConfigError::InvalidEnv(_))
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+//! Named counters of a channel like death counters, which are stored with the persisted state.
+//!
+//! [`Counters`] has to be registered with
+//! [`ContainerBuilder::register_persisted_type`](crate::state::ContainerBuilder::register_persisted_type).
+//! Handlers can use [`CountersState`] to change counters, e.g. with `counters.increment("deaths", 1)`.
+
+use crate::command::{CommandArguments, CommandProcessor};
+use crate::request::{CommandRequest, FromCommandRequest};
+use crate::response::Response;
+use crate::state::{PersistedChannelState, PersistedType};
+use async_trait::async_trait;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Counters {
+    counters: BTreeMap<String, i64>,
+}
+
+impl Counters {
+    /// The value of a counter, counters which were never changed are `0`.
+    pub fn get(&self, name: &str) -> i64 {
+        self.counters.get(name).copied().unwrap_or(0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, i64)> {
+        self.counters
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+    }
+
+    /// Adds `delta` to a counter and returns the new value.
+    pub fn add(&mut self, name: &str, delta: i64) -> i64 {
+        let value = self.counters.entry(name.to_owned()).or_default();
+        *value = value.saturating_add(delta);
+        *value
+    }
+
+    /// Removes a counter, which resets it to `0`.
+    pub fn reset(&mut self, name: &str) -> Option<i64> {
+        self.counters.remove(name)
+    }
+}
+
+impl PersistedType for Counters {
+    const FILENAME: &'static str = "counters";
+
+    fn init(_channel: &str) -> Self {
+        Self::default()
+    }
+}
+
+pub type CountersState<'req> = PersistedChannelState<'req, Counters>;
+
+impl PersistedChannelState<'_, Counters> {
+    pub async fn get(&self, name: &str) -> i64 {
+        self.read().await.get(name)
+    }
+
+    /// Adds `delta` to a counter and returns the new value.
+    pub async fn increment(&self, name: &str, delta: i64) -> i64 {
+        let mut value = 0;
+        self.update(|counters| {
+            let mut counters = counters.clone();
+            value = counters.add(name, delta);
+            counters
+        })
+        .await;
+        value
+    }
+
+    pub async fn reset(&self, name: &str) {
+        self.maybe_update(|counters| {
+            let mut counters = counters.clone();
+            counters.reset(name).map(|_| counters)
+        })
+        .await;
+    }
+}
+
+/// Counter names are case insensitive and only contain letters, digits, `_` and `-`.
+fn counter_name(name: &str) -> Option<String> {
+    name.chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        .then(|| name.to_lowercase())
+}
+
+/// Processes `!count <name>` for everyone and `!count <name> +n`, `!count <name> -n` and
+/// `!count <name> reset` for moderators.
+pub struct CounterCommands;
+
+impl CounterCommands {
+    async fn process_count(
+        request: &CommandRequest<'_>,
+        arguments: &mut CommandArguments<'_>,
+    ) -> Result<String, Cow<'static, str>> {
+        let syntax = "!count <name> [+n|-n|reset]";
+        let counters = CountersState::from_command_request(request)
+            .map_err(|_| "the counters are not registered for this channel")?;
+        let name = arguments.next().and_then(counter_name).ok_or(syntax)?;
+        let change = arguments.next();
+        if !arguments.as_str().is_empty() {
+            return Err(syntax.into());
+        }
+        let change = match change {
+            None => return Ok(format!("{} is {}", name, counters.get(&name).await)),
+            Some(change) => change,
+        };
+        let sender = request.sender();
+        if !sender.is_moderator() && !sender.is_broadcaster() {
+            return Err("only moderators can change counters".into());
+        }
+        if change == "reset" {
+            counters.reset(&name).await;
+            return Ok(format!("{} is 0", name));
+        }
+        let delta: i64 = match change.strip_prefix('+') {
+            Some(delta) => delta.parse(),
+            None if change.starts_with('-') => change.parse(),
+            None => return Err(syntax.into()),
+        }
+        .map_err(|_| syntax)?;
+        let value = counters.increment(&name, delta).await;
+        Ok(format!("{} is {}", name, value))
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for CounterCommands {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        if arguments.next() != Some("!count") {
+            return None;
+        }
+        let text = match Self::process_count(request, &mut arguments).await {
+            Ok(text) => text,
+            Err(error) => error.into_owned(),
+        };
+        Some(Response::new(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters() {
+        let mut counters = Counters::default();
+        assert_eq!(counters.get("deaths"), 0);
+        assert_eq!(counters.add("deaths", 1), 1);
+        assert_eq!(counters.add("deaths", 5), 6);
+        assert_eq!(counters.add("deaths", -2), 4);
+        assert_eq!(counters.add("wins", i64::MAX), i64::MAX);
+        assert_eq!(counters.add("wins", 1), i64::MAX);
+        assert_eq!(counters.reset("deaths"), Some(4));
+        assert_eq!(counters.get("deaths"), 0);
+        assert_eq!(counter_name("Deaths"), Some("deaths".to_owned()));
+        assert_eq!(counter_name("a b"), None);
+    }
+}
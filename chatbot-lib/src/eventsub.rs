@@ -0,0 +1,407 @@
+//! Receives channel point redemptions through an EventSub websocket, see
+//! <https://dev.twitch.tv/docs/eventsub/handling-websocket-events/>.
+//!
+//! Redemptions are handled by [`RedemptionProcessor`]s in order with the chat messages of their
+//! channel, which allows commands and redemptions to share state like points or queues.
+//! The token of the [`HelixClient`] needs the scope `channel:read:redemptions` of the broadcaster.
+
+use crate::chat_bot::{ChatBotContext, StateError};
+use crate::helix::HelixClient;
+use crate::request::{Bot, Channel, Sender};
+use crate::response::{RespondLater, Response};
+use crate::state::{ChannelChatters, ChannelState, ChannelStateError};
+use crate::user::{OwnedUser, User};
+use crate::State;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::stream::StreamExt;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+const EVENTSUB_URL: &str = "wss://eventsub.wss.twitch.tv/ws";
+const REDEMPTION_SUBSCRIPTION: &str = "channel.channel_points_custom_reward_redemption.add";
+
+/// Used until the welcome message tells how often keepalive messages are sent.
+const DEFAULT_KEEPALIVE: Duration = Duration::from_secs(30);
+/// Messages may be late by this much before the connection is considered lost.
+const KEEPALIVE_GRACE: Duration = Duration::from_secs(5);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// A viewer redeemed a custom channel point reward.
+#[derive(Debug, Clone)]
+pub struct Redemption {
+    pub id: String,
+    pub channel: OwnedUser,
+    pub user: OwnedUser,
+    pub reward_id: String,
+    pub reward_title: String,
+    pub cost: u64,
+    /// The text entered by the viewer, empty if the reward does not require any.
+    pub user_input: String,
+    pub redeemed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RedemptionRequest<'req> {
+    redemption: &'req Redemption,
+    channel: Channel<'req>,
+    user: Sender<'req>,
+    bot: &'req Bot<'req>,
+    context: &'req ChatBotContext<'req>,
+}
+
+impl<'req> RedemptionRequest<'req> {
+    pub(crate) fn new(
+        redemption: &'req Redemption,
+        bot: &'req Bot<'req>,
+        context: &'req ChatBotContext<'req>,
+    ) -> Self {
+        Self {
+            redemption,
+            channel: User::from_owned(&redemption.channel).into(),
+            user: User::from_owned(&redemption.user).into(),
+            bot,
+            context,
+        }
+    }
+
+    pub fn redemption(&self) -> &'req Redemption {
+        self.redemption
+    }
+
+    pub fn channel(&self) -> &Channel<'req> {
+        &self.channel
+    }
+
+    /// The user who redeemed the reward.
+    pub fn user(&self) -> &Sender<'req> {
+        &self.user
+    }
+
+    pub fn bot(&self) -> &Bot<'req> {
+        self.bot
+    }
+
+    pub fn chatters(&self) -> ChannelChatters {
+        self.context.chatters()
+    }
+
+    pub fn respond_later(&self) -> Option<RespondLater> {
+        self.context.respond_later(&self.channel)
+    }
+
+    pub fn state<T: Send + Sync + 'static>(&self) -> Result<State<'req, T>, StateError> {
+        self.context.state()
+    }
+
+    pub fn channel_state<T: Send + Sync + 'static>(
+        &self,
+    ) -> Result<ChannelState<'req, T>, ChannelStateError> {
+        self.context.channel_state()
+    }
+}
+
+/// Handles redemptions, the response is sent to the channel of the redemption.
+#[async_trait]
+pub trait RedemptionProcessor {
+    async fn process<'a>(&self, request: &'a RedemptionRequest<'a>) -> Option<Response<'a>>;
+}
+
+/// Which client subscribes to the redemptions and which processors handle them.
+pub struct EventSubConfig {
+    client: HelixClient,
+    url: String,
+    pub(crate) processors: Vec<Box<dyn RedemptionProcessor + Send + Sync>>,
+}
+
+impl EventSubConfig {
+    pub fn new(client: HelixClient) -> Self {
+        Self {
+            client,
+            url: EVENTSUB_URL.to_owned(),
+            processors: Vec::new(),
+        }
+    }
+
+    /// Adds a processor, redemptions are processed by every processor in order until one responds.
+    pub fn with_processor<P>(mut self, processor: P) -> Self
+    where
+        P: RedemptionProcessor + Send + Sync + 'static,
+    {
+        self.processors.push(Box::new(processor));
+        self
+    }
+
+    /// Connects to another websocket, e.g. the Twitch CLI for testing.
+    pub fn url<T: Into<String>>(self, url: T) -> Self {
+        Self {
+            url: url.into(),
+            ..self
+        }
+    }
+}
+
+impl std::fmt::Debug for EventSubConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSubConfig")
+            .field("url", &self.url)
+            .field("processors", &self.processors.len())
+            .finish()
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct EventSubMessage {
+    metadata: Metadata,
+    payload: Payload,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Metadata {
+    message_type: String,
+    subscription_type: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Payload {
+    session: Option<Session>,
+    event: Option<serde_json::Value>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Session {
+    id: String,
+    keepalive_timeout_seconds: Option<u64>,
+    reconnect_url: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct RedemptionEvent {
+    id: String,
+    broadcaster_user_id: String,
+    broadcaster_user_login: String,
+    broadcaster_user_name: String,
+    user_id: String,
+    user_login: String,
+    user_name: String,
+    #[serde(default)]
+    user_input: String,
+    reward: Reward,
+    redeemed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Reward {
+    id: String,
+    title: String,
+    cost: u64,
+}
+
+impl From<RedemptionEvent> for Redemption {
+    fn from(event: RedemptionEvent) -> Self {
+        Self {
+            id: event.id,
+            channel: OwnedUser::new(
+                event.broadcaster_user_login,
+                Some(event.broadcaster_user_name),
+                event.broadcaster_user_id.parse().ok(),
+            ),
+            user: OwnedUser::new(
+                event.user_login,
+                Some(event.user_name),
+                event.user_id.parse().ok(),
+            ),
+            reward_id: event.reward.id,
+            reward_title: event.reward.title,
+            cost: event.reward.cost,
+            user_input: event.user_input,
+            redeemed_at: event.redeemed_at,
+        }
+    }
+}
+
+fn parse_redemption(message: &EventSubMessage) -> anyhow::Result<Option<Redemption>> {
+    if message.metadata.subscription_type.as_deref() != Some(REDEMPTION_SUBSCRIPTION) {
+        return Ok(None);
+    }
+    match &message.payload.event {
+        Some(event) => Ok(Some(
+            serde_json::from_value::<RedemptionEvent>(event.clone())?.into(),
+        )),
+        None => Ok(None),
+    }
+}
+
+enum SessionEnd {
+    /// Twitch asked to connect to another url, which keeps the subscriptions.
+    Reconnect(String),
+    Closed,
+    Stopped,
+}
+
+async fn session<F>(
+    url: &str,
+    client: Option<&HelixClient>,
+    broadcasters: &[String],
+    dispatch: &F,
+) -> anyhow::Result<SessionEnd>
+where
+    F: Fn(Redemption) -> bool,
+{
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await?;
+    let mut keepalive = DEFAULT_KEEPALIVE;
+    loop {
+        let message = match tokio::time::timeout(keepalive, socket.next()).await {
+            Ok(Some(message)) => message?,
+            Ok(None) => return Ok(SessionEnd::Closed),
+            Err(_) => {
+                log::warn!("No EventSub message received for {:?}", keepalive);
+                return Ok(SessionEnd::Closed);
+            }
+        };
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => return Ok(SessionEnd::Closed),
+            _ => continue,
+        };
+        let message: EventSubMessage = serde_json::from_str(&text)?;
+        match message.metadata.message_type.as_str() {
+            "session_welcome" => {
+                let session = message
+                    .payload
+                    .session
+                    .ok_or_else(|| anyhow::anyhow!("welcome message without session"))?;
+                if let Some(seconds) = session.keepalive_timeout_seconds {
+                    keepalive = Duration::from_secs(seconds) + KEEPALIVE_GRACE;
+                }
+                if let Some(client) = client {
+                    for broadcaster in broadcasters {
+                        client
+                            .create_eventsub_subscription(
+                                REDEMPTION_SUBSCRIPTION,
+                                "1",
+                                serde_json::json!({ "broadcaster_user_id": broadcaster }),
+                                &session.id,
+                            )
+                            .await?;
+                    }
+                    log::info!(
+                        "Subscribed to redemptions of {} channels",
+                        broadcasters.len()
+                    );
+                }
+            }
+            "session_reconnect" => {
+                if let Some(url) = message.payload.session.and_then(|s| s.reconnect_url) {
+                    return Ok(SessionEnd::Reconnect(url));
+                }
+            }
+            "notification" => match parse_redemption(&message) {
+                Ok(Some(redemption)) => {
+                    log::debug!("Redemption: {:?}", redemption);
+                    if !dispatch(redemption) {
+                        return Ok(SessionEnd::Stopped);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => log::error!("Error parsing redemption: {:?}", e),
+            },
+            "revocation" => log::warn!(
+                "EventSub subscription revoked: {:?}",
+                message.metadata.subscription_type
+            ),
+            _ => {}
+        }
+    }
+}
+
+/// Passes the redemptions of `channels` to `dispatch`, which returns `false` if the bot stopped.
+pub(crate) async fn listen_redemptions<F>(
+    config: EventSubConfig,
+    channels: Vec<String>,
+    dispatch: F,
+) -> anyhow::Result<()>
+where
+    F: Fn(Redemption) -> bool + Send + Sync,
+{
+    let logins: Vec<_> = channels.iter().map(String::as_str).collect();
+    let broadcasters: Vec<_> = if logins.is_empty() {
+        // there is nothing to look up
+        Vec::new()
+    } else {
+        config
+            .client
+            .get_users_by_login(&logins)
+            .await?
+            .into_iter()
+            .map(|user| user.id)
+            .collect()
+    };
+    let mut url = config.url.clone();
+    let mut subscribe = true;
+    loop {
+        let client = Some(&config.client).filter(|_| subscribe);
+        match session(&url, client, &broadcasters, &dispatch).await {
+            Ok(SessionEnd::Reconnect(reconnect_url)) => {
+                url = reconnect_url;
+                subscribe = false;
+                continue;
+            }
+            Ok(SessionEnd::Stopped) => return Ok(()),
+            Ok(SessionEnd::Closed) => log::warn!("EventSub connection closed"),
+            Err(e) => log::error!("EventSub error: {:?}", e),
+        }
+        // the subscriptions of a closed session are deleted
+        url = config.url.clone();
+        subscribe = true;
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::UserId;
+
+    #[test]
+    fn test_parse_redemption() {
+        let message = r#"{
+            "metadata": {
+                "message_id": "befa7b53-d79d-478f-86b9-120f112b044e",
+                "message_type": "notification",
+                "message_timestamp": "2022-11-16T10:11:12.464757833Z",
+                "subscription_type": "channel.channel_points_custom_reward_redemption.add",
+                "subscription_version": "1"
+            },
+            "payload": {
+                "subscription": { "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4" },
+                "event": {
+                    "id": "17fa2df1-ad76-4804-bfa5-a40ef63efe63",
+                    "broadcaster_user_id": "1337",
+                    "broadcaster_user_login": "liquidnya",
+                    "broadcaster_user_name": "LiquidNya",
+                    "user_id": "9001",
+                    "user_login": "cooler_user",
+                    "user_name": "Cooler_User",
+                    "user_input": "nya",
+                    "status": "unfulfilled",
+                    "reward": {
+                        "id": "92af127c-7326-4483-a52b-b0da0be61c01",
+                        "title": "Hydrate",
+                        "cost": 100,
+                        "prompt": "drink water"
+                    },
+                    "redeemed_at": "2020-07-15T17:16:03.17106713Z"
+                }
+            }
+        }"#;
+        let message: EventSubMessage = serde_json::from_str(message).unwrap();
+        let redemption = parse_redemption(&message).unwrap().unwrap();
+        assert_eq!(redemption.channel.username(), "liquidnya");
+        assert_eq!(redemption.channel.user_id(), Some(UserId::new(1337)));
+        assert_eq!(redemption.user.username(), "cooler_user");
+        assert_eq!(redemption.reward_title, "Hydrate");
+        assert_eq!(redemption.cost, 100);
+        assert_eq!(redemption.user_input, "nya");
+    }
+}
@@ -0,0 +1,144 @@
+//! Greetings for users who chat for the first time, which are enabled per channel with
+//! `!config set greeting <text>`.
+//!
+//! `{user}` and `{channel}` are replaced in the greeting. With `greeting_scope` set to `ever`
+//! users are greeted only once, which requires [`GreetedUsers`] to be registered with
+//! [`ContainerBuilder::register_persisted_type`](crate::state::ContainerBuilder::register_persisted_type).
+//! During `greeting_cooldown` after a greeting the users are greeted together afterwards,
+//! so a raid does not cause a greeting for every single user.
+
+use crate::locale;
+use crate::response::{DeferredResponse, Response};
+use crate::state::PersistedType;
+use crate::user::UserId;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time::Instant;
+
+/// The users of a channel who were greeted already.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct GreetedUsers {
+    users: BTreeSet<UserId>,
+}
+
+impl GreetedUsers {
+    pub fn contains(&self, user_id: UserId) -> bool {
+        self.users.contains(&user_id)
+    }
+
+    /// Returns `false` if the user was greeted already.
+    pub fn insert(&mut self, user_id: UserId) -> bool {
+        self.users.insert(user_id)
+    }
+}
+
+impl PersistedType for GreetedUsers {
+    const FILENAME: &'static str = "greeted";
+
+    fn init(_channel: &str) -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Debug, Default)]
+struct ChannelGreetings {
+    last_greeting: Option<Instant>,
+    /// Users who are greeted when the cooldown ends.
+    pending: Vec<String>,
+}
+
+/// Sends the greetings of all channels, at most one per cooldown and channel.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Greeter {
+    channels: Arc<Mutex<HashMap<String, ChannelGreetings>>>,
+}
+
+impl Greeter {
+    /// Greets `user` now or after the cooldown of the channel ended.
+    pub(crate) fn greet(
+        &self,
+        channel: &str,
+        user: &str,
+        template: &str,
+        cooldown: Option<Duration>,
+        deferred: &UnboundedSender<DeferredResponse>,
+    ) {
+        let now = Instant::now();
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        let greetings = channels.entry(channel.to_owned()).or_default();
+        if !greetings.pending.is_empty() {
+            // the greeting is sent once the cooldown ends
+            greetings.pending.push(user.to_owned());
+            return;
+        }
+        let ends = cooldown
+            .zip(greetings.last_greeting)
+            .map(|(cooldown, last_greeting)| last_greeting + cooldown)
+            .filter(|ends| *ends > now);
+        let ends = match ends {
+            Some(ends) => ends,
+            None => {
+                greetings.last_greeting = Some(now);
+                send_greeting(channel, &[user.to_owned()], template, deferred);
+                return;
+            }
+        };
+        greetings.pending.push(user.to_owned());
+        let greeter = self.clone();
+        let channel = channel.to_owned();
+        let template = template.to_owned();
+        let deferred = deferred.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep_until(ends).await;
+            let users = {
+                let mut channels = greeter.channels.lock().unwrap_or_else(|e| e.into_inner());
+                let greetings = channels.entry(channel.clone()).or_default();
+                greetings.last_greeting = Some(Instant::now());
+                std::mem::take(&mut greetings.pending)
+            };
+            send_greeting(&channel, &users, &template, &deferred);
+        });
+    }
+}
+
+fn send_greeting(
+    channel: &str,
+    users: &[String],
+    template: &str,
+    deferred: &UnboundedSender<DeferredResponse>,
+) {
+    if users.is_empty() {
+        return;
+    }
+    let _ = deferred.send(DeferredResponse {
+        channel: channel.to_owned(),
+        reply_to: None,
+        response: Response::new(greeting_text(template, users, channel)),
+    });
+}
+
+fn greeting_text(template: &str, users: &[String], channel: &str) -> String {
+    let users = users.join(", ");
+    locale::format_message(template, &[("user", &users), ("channel", &channel)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_greeting_text() {
+        let users = ["LiquidNya".to_owned(), "user".to_owned()];
+        assert_eq!(
+            greeting_text("Welcome to {channel}, {user}!", &users[..1], "liquidnya"),
+            "Welcome to liquidnya, LiquidNya!"
+        );
+        assert_eq!(
+            greeting_text("Welcome {user}!", &users, "liquidnya"),
+            "Welcome LiquidNya, user!"
+        );
+    }
+}
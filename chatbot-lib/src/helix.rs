@@ -0,0 +1,125 @@
+use crate::user::{OwnedUser, UserArgument, UserLookup};
+use async_trait::async_trait;
+
+const HELIX_URL: &str = "https://api.twitch.tv/helix";
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct HelixUser {
+    pub id: String,
+    pub login: String,
+    pub display_name: String,
+}
+
+impl From<HelixUser> for OwnedUser {
+    fn from(user: HelixUser) -> Self {
+        OwnedUser::new(user.login, Some(user.display_name), user.id.parse().ok())
+    }
+}
+
+/// The channel information of a broadcaster, which keeps the last category after the stream ended.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct HelixChannel {
+    pub broadcaster_id: String,
+    pub broadcaster_login: String,
+    pub broadcaster_name: String,
+    /// Empty if the broadcaster never set a category.
+    pub game_name: String,
+    pub title: String,
+}
+
+#[derive(serde::Deserialize)]
+struct HelixData<T> {
+    data: Vec<T>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HelixClient {
+    client: reqwest::Client,
+    client_id: String,
+    token: String,
+}
+
+impl HelixClient {
+    pub fn new(client_id: String, token: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            client_id,
+            token: token.trim_start_matches("oauth:").to_owned(),
+        }
+    }
+
+    async fn get<T: for<'de> serde::Deserialize<'de>>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> anyhow::Result<Vec<T>> {
+        let response = self
+            .client
+            .get(format!("{}/{}", HELIX_URL, path))
+            .query(query)
+            .header("Client-Id", &self.client_id)
+            .bearer_auth(&self.token)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json::<HelixData<T>>().await?.data)
+    }
+
+    pub async fn get_channel_information(
+        &self,
+        broadcaster_id: &str,
+    ) -> anyhow::Result<Option<HelixChannel>> {
+        let channels = self
+            .get("channels", &[("broadcaster_id", broadcaster_id)])
+            .await?;
+        Ok(channels.into_iter().next())
+    }
+
+    /// Subscribes the EventSub websocket `session_id` to events of `kind`, see
+    /// <https://dev.twitch.tv/docs/eventsub/eventsub-subscription-types/>.
+    #[cfg(feature = "eventsub")]
+    pub async fn create_eventsub_subscription(
+        &self,
+        kind: &str,
+        version: &str,
+        condition: serde_json::Value,
+        session_id: &str,
+    ) -> anyhow::Result<()> {
+        self.client
+            .post(format!("{}/eventsub/subscriptions", HELIX_URL))
+            .header("Client-Id", &self.client_id)
+            .bearer_auth(&self.token)
+            .json(&serde_json::json!({
+                "type": kind,
+                "version": version,
+                "condition": condition,
+                "transport": { "method": "websocket", "session_id": session_id },
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    pub async fn get_users_by_login(&self, logins: &[&str]) -> anyhow::Result<Vec<HelixUser>> {
+        let query: Vec<_> = logins.iter().map(|login| ("login", *login)).collect();
+        self.get("users", &query).await
+    }
+}
+
+fn is_login(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[async_trait]
+impl UserLookup for HelixClient {
+    async fn lookup(&self, user: &UserArgument<'_>) -> anyhow::Result<Option<OwnedUser>> {
+        // display names only differ in case from the login, unless they are localized
+        let login = user.as_argument().to_ascii_lowercase();
+        if !is_login(&login) {
+            return Ok(None);
+        }
+        let users = self.get_users_by_login(&[&login]).await?;
+        Ok(users.into_iter().next().map(OwnedUser::from))
+    }
+}
@@ -0,0 +1,183 @@
+//! A minimal HTTP/1.1 server for the health endpoint and webhooks, which answers one request per connection.
+
+use std::future::Future;
+use std::time::Duration;
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Requests with a larger head or body are rejected.
+const MAX_HEAD_LEN: usize = 8 * 1024;
+const MAX_BODY_LEN: usize = 64 * 1024;
+/// Connections which do not send anything for this long are closed.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub(crate) struct HttpRequest {
+    pub(crate) method: String,
+    pub(crate) path: String,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl HttpRequest {
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct HttpResponse {
+    pub(crate) status: &'static str,
+    pub(crate) content_type: &'static str,
+    pub(crate) body: String,
+}
+
+impl HttpResponse {
+    pub(crate) fn new(status: &'static str, content_type: &'static str, body: String) -> Self {
+        Self {
+            status,
+            content_type,
+            body,
+        }
+    }
+
+    pub(crate) fn text(status: &'static str, body: &str) -> Self {
+        Self::new(status, "text/plain", body.to_owned())
+    }
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+async fn read_with_timeout(
+    stream: &mut TcpStream,
+    buf: &mut [u8],
+    timeout: Duration,
+) -> io::Result<usize> {
+    tokio::time::timeout(timeout, stream.read(buf))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "reading the request timed out"))?
+}
+
+async fn read_request(stream: &mut TcpStream, timeout: Duration) -> io::Result<HttpRequest> {
+    let mut data = Vec::new();
+    let mut buf = [0; 1024];
+    let head_len = loop {
+        if let Some(index) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+            break index + 4;
+        }
+        if data.len() > MAX_HEAD_LEN {
+            return Err(invalid_data("request head too large"));
+        }
+        let len = read_with_timeout(stream, &mut buf, timeout).await?;
+        if len == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        data.extend_from_slice(&buf[..len]);
+    };
+    let head = String::from_utf8_lossy(&data[..head_len]).into_owned();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_owned();
+    let path = request_line.next().unwrap_or_default().to_owned();
+    let headers: Vec<_> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_owned(), value.trim().to_owned()))
+        .collect();
+    let mut request = HttpRequest {
+        method,
+        path,
+        headers,
+        body: data.split_off(head_len),
+    };
+    let content_length = match request.header("content-length") {
+        Some(length) => length
+            .parse()
+            .map_err(|_| invalid_data("invalid content length"))?,
+        None => 0,
+    };
+    if content_length > MAX_BODY_LEN {
+        return Err(invalid_data("request body too large"));
+    }
+    while request.body.len() < content_length {
+        let len = read_with_timeout(stream, &mut buf, timeout).await?;
+        if len == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        request.body.extend_from_slice(&buf[..len]);
+    }
+    request.body.truncate(content_length);
+    Ok(request)
+}
+
+async fn write_response(stream: &mut TcpStream, response: &HttpResponse) -> io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Answers every request on `listener` with `handler` until accepting a connection fails.
+pub(crate) async fn serve<F, Fut>(listener: TcpListener, handler: F) -> io::Result<()>
+where
+    F: Fn(HttpRequest) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = HttpResponse> + Send,
+{
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            let response = match read_request(&mut stream, READ_TIMEOUT).await {
+                Ok(request) => handler(request).await,
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                    log::debug!("Closing idle HTTP connection");
+                    return;
+                }
+                Err(e) => {
+                    log::debug!("Error reading HTTP request: {:?}", e);
+                    HttpResponse::text("400 Bad Request", "bad request")
+                }
+            };
+            if let Err(e) = write_response(&mut stream, &response).await {
+                log::debug!("Error writing HTTP response: {:?}", e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_timeout() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let mut client = TcpStream::connect(address).await.unwrap();
+            let (mut stream, _) = listener.accept().await.unwrap();
+            // the head is never finished
+            client
+                .write_all(b"POST /webhook HTTP/1.1\r\n")
+                .await
+                .unwrap();
+            let error = read_request(&mut stream, Duration::from_millis(50))
+                .await
+                .unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        });
+    }
+}
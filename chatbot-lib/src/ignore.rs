@@ -0,0 +1,169 @@
+//! Users whose messages are neither filtered nor processed as commands, e.g. other bots like
+//! Nightbot.
+//!
+//! Users are ignored in all channels with [`ChatBot::with_ignored_users`](crate::ChatBot::with_ignored_users)
+//! or in a single channel with `!ignore <user>`, which requires [`IgnoredUsers`] to be registered
+//! with
+//! [`ContainerBuilder::register_persisted_type`](crate::state::ContainerBuilder::register_persisted_type).
+
+use crate::command::{CommandArguments, CommandProcessor};
+use crate::request::{CommandRequest, FromCommandRequest};
+use crate::response::Response;
+use crate::state::{Persisted, PersistedChannelState, PersistedType};
+use crate::user::{NameMatching, User, UserArgument};
+use async_trait::async_trait;
+use state::TypeMap;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+/// The usernames of the users which are ignored in a channel.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct IgnoredUsers {
+    users: BTreeSet<String>,
+}
+
+impl IgnoredUsers {
+    pub fn contains(&self, username: &str) -> bool {
+        // usernames are lowercase ASCII, so this only allocates for names typed by users
+        let username = NameMatching::AsciiCaseInsensitive.key(username);
+        self.users.contains(username.as_ref())
+    }
+
+    /// Returns `false` if the user was ignored already.
+    pub fn insert(&mut self, username: &str) -> bool {
+        self.users.insert(
+            NameMatching::AsciiCaseInsensitive
+                .key(username)
+                .into_owned(),
+        )
+    }
+
+    /// Returns `false` if the user was not ignored.
+    pub fn remove(&mut self, username: &str) -> bool {
+        self.users
+            .remove(NameMatching::AsciiCaseInsensitive.key(username).as_ref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.users.iter().map(String::as_str)
+    }
+}
+
+impl PersistedType for IgnoredUsers {
+    const FILENAME: &'static str = "ignored_users";
+
+    fn init(_channel: &str) -> Self {
+        Self::default()
+    }
+}
+
+pub type IgnoredUsersState<'req> = PersistedChannelState<'req, IgnoredUsers>;
+
+/// Returns `true` if the user is on the ignore list of the channel.
+pub(crate) async fn is_ignored(
+    channel_container: &TypeMap![Send + Sync],
+    channel: &str,
+    username: &str,
+) -> bool {
+    match channel_container.try_get::<Persisted<IgnoredUsers>>() {
+        Some(persisted) => persisted
+            .for_channel(channel)
+            .read()
+            .await
+            .contains(username),
+        None => false,
+    }
+}
+
+/// Processes `!ignore <user>`, `!unignore <user>` and `!ignore` listing the ignored users for
+/// moderators.
+pub struct IgnoreCommands;
+
+impl IgnoreCommands {
+    async fn process_ignore(
+        request: &CommandRequest<'_>,
+        command: &str,
+        arguments: &mut CommandArguments<'_>,
+    ) -> Result<String, Cow<'static, str>> {
+        let syntax = "!ignore <user>, !unignore <user> or !ignore";
+        let ignored = IgnoredUsersState::from_command_request(request)
+            .map_err(|_| "the ignored users are not registered for this channel")?;
+        let user = match arguments.next() {
+            Some(user) if arguments.as_str().is_empty() => UserArgument::new(user),
+            None if command == "!ignore" => {
+                let ignored = ignored.read().await;
+                if ignored.is_empty() {
+                    return Ok("no users are ignored".to_owned());
+                }
+                let list: Vec<_> = ignored.iter().collect();
+                return Ok(format!("ignored users: {}", list.join(", ")));
+            }
+            _ => return Err(syntax.into()),
+        };
+        let username = user.as_argument();
+        let ignore = command == "!ignore";
+        let (_, new) = ignored
+            .maybe_update(|ignored| {
+                let mut ignored = ignored.clone();
+                let changed = if ignore {
+                    ignored.insert(username)
+                } else {
+                    ignored.remove(username)
+                };
+                changed.then_some(ignored)
+            })
+            .await;
+        match (ignore, new.is_some()) {
+            (true, true) => Ok(format!("ignoring {}", user)),
+            (true, false) => Err(format!("{} is ignored already", user).into()),
+            (false, true) => Ok(format!("no longer ignoring {}", user)),
+            (false, false) => Err(format!("{} is not ignored", user).into()),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for IgnoreCommands {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        let command = match arguments.next() {
+            Some(command @ ("!ignore" | "!unignore")) => command,
+            _ => return None,
+        };
+        let sender = request.sender();
+        if !sender.is_moderator() && !sender.is_broadcaster() {
+            return None;
+        }
+        let text = match Self::process_ignore(request, command, &mut arguments).await {
+            Ok(text) => text,
+            Err(error) => error.into_owned(),
+        };
+        Some(Response::new(format!(
+            "{} {}",
+            UserArgument::from(sender as &User),
+            text
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignored_users() {
+        let mut ignored = IgnoredUsers::default();
+        assert!(ignored.insert("Nightbot"));
+        assert!(!ignored.insert("nightbot"));
+        assert!(ignored.contains("NightBot"));
+        assert_eq!(ignored.iter().collect::<Vec<_>>(), ["nightbot"]);
+        assert!(ignored.remove("nightbot"));
+        assert!(!ignored.remove("nightbot"));
+        assert!(ignored.is_empty());
+    }
+}
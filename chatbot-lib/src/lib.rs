@@ -2,13 +2,46 @@
 
 mod chat_bot;
 
+pub mod audit;
+#[cfg(feature = "helix")]
+pub mod auth;
 pub mod command;
+pub mod command_stats;
+pub mod config;
+pub mod counters;
+#[cfg(feature = "eventsub")]
+pub mod eventsub;
+pub mod greeting;
+#[cfg(feature = "helix")]
+pub mod helix;
+#[cfg(any(feature = "health", feature = "http"))]
+mod http_server;
+pub mod ignore;
+pub mod locale;
+pub mod moderation;
+pub mod notification;
+pub mod platform;
+pub mod quotes;
+pub mod reminders;
+pub mod replay;
 pub mod request;
 pub mod response;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "helix")]
+pub mod shoutout;
 pub mod state;
+pub mod status;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod user;
+#[cfg(feature = "http")]
+pub mod webhook;
 
-pub use self::chat_bot::{ChatBot, State};
+pub use self::chat_bot::{
+    ChatBot, ConnectHook, DisconnectHook, DryRunSink, IntakePolicy, JoinHook, ReconnectHook, State,
+    UnknownCommandHandler,
+};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,268 @@
+//! Translated responses, the language of a channel is selected with `!config set language <language>`.
+
+use crate::command::{CommandError, CommandErrorType};
+use crate::request::CommandRequest;
+use core::fmt::Display;
+use std::collections::HashMap;
+
+/// Messages by language and key, e.g. `"de"` and `"song.added"`.
+///
+/// Messages can contain placeholders like `{name}`, which are replaced by the arguments.
+#[derive(Debug, Clone)]
+pub struct Catalog {
+    default_language: String,
+    messages: HashMap<String, HashMap<String, String>>,
+}
+
+impl Catalog {
+    pub fn new<T: Into<String>>(default_language: T) -> Self {
+        Self {
+            default_language: default_language.into(),
+            messages: HashMap::new(),
+        }
+    }
+
+    pub fn insert<L, K, M>(&mut self, language: L, key: K, message: M)
+    where
+        L: Into<String>,
+        K: Into<String>,
+        M: Into<String>,
+    {
+        self.messages
+            .entry(language.into())
+            .or_default()
+            .insert(key.into(), message.into());
+    }
+
+    pub fn with<L, K, M>(mut self, language: L, key: K, message: M) -> Self
+    where
+        L: Into<String>,
+        K: Into<String>,
+        M: Into<String>,
+    {
+        self.insert(language, key, message);
+        self
+    }
+
+    pub fn default_language(&self) -> &str {
+        &self.default_language
+    }
+
+    /// The message in `language`, or in the default language if there is no translation.
+    pub fn message(&self, language: Option<&str>, key: &str) -> Option<&str> {
+        language
+            .and_then(|language| self.messages.get(language))
+            .and_then(|messages| messages.get(key))
+            .or_else(|| {
+                self.messages
+                    .get(&self.default_language)
+                    .and_then(|messages| messages.get(key))
+            })
+            .map(String::as_str)
+    }
+
+    /// Formats the message, or the key itself if there is no message.
+    pub fn translate(
+        &self,
+        language: Option<&str>,
+        key: &str,
+        args: &[(&str, &dyn Display)],
+    ) -> String {
+        format_message(self.message(language, key).unwrap_or(key), args)
+    }
+}
+
+/// Replaces placeholders like `{name}` with the arguments, unknown placeholders are kept.
+pub fn format_message(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let argument = rest.find('}').and_then(|end| {
+            let name = &rest[1..end];
+            args.iter()
+                .find(|(arg, _)| *arg == name)
+                .map(|(_, value)| (end, value))
+        });
+        match argument {
+            Some((end, value)) => {
+                result.push_str(&value.to_string());
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// The language of the channel the request was sent in.
+pub fn language<'a>(request: &'a CommandRequest<'_>) -> Option<&'a str> {
+    request.context?.language()
+}
+
+/// Translates `key` into the language of the channel, see [`tr!`](crate::tr).
+pub fn translate(request: &CommandRequest<'_>, key: &str, args: &[(&str, &dyn Display)]) -> String {
+    translate_or(request, key, key, args)
+}
+
+/// Translates `key` into the language of the channel, `default` is used if there is no message.
+pub fn translate_or(
+    request: &CommandRequest<'_>,
+    key: &str,
+    default: &str,
+    args: &[(&str, &dyn Display)],
+) -> String {
+    let catalog = request
+        .context
+        .and_then(|context| context.state::<Catalog>().ok());
+    let message = catalog.as_ref().and_then(|catalog| {
+        let language = language(request).or(Some(catalog.default_language()));
+        catalog.message(language, key)
+    });
+    format_message(message.unwrap_or(default), args)
+}
+
+/// Translates the response showing the syntax of a command, uses the key `chatbot.syntax`.
+pub fn syntax(request: &CommandRequest<'_>, syntax: &str) -> String {
+    translate_or(
+        request,
+        "chatbot.syntax",
+        "{syntax}",
+        &[("syntax", &syntax)],
+    )
+}
+
+/// Translates the response showing the syntax of a command with the reason the arguments were
+/// rejected, uses the keys `chatbot.syntax_hint`, `chatbot.out_of_range` and
+/// `chatbot.invalid_argument`.
+pub fn syntax_hint<E: CommandErrorType>(
+    request: &CommandRequest<'_>,
+    syntax_pattern: &str,
+    error: &CommandError<E>,
+) -> String {
+    let hint = if let Some((name, out_of_range)) = error.out_of_range_argument() {
+        translate_or(
+            request,
+            "chatbot.out_of_range",
+            "{name} must be between {min} and {max}",
+            &[
+                ("name", &name.unwrap_or_default()),
+                ("min", &out_of_range.min),
+                ("max", &out_of_range.max),
+            ],
+        )
+    } else if let Some(argument) = error.invalid_argument() {
+        translate_or(
+            request,
+            "chatbot.invalid_argument",
+            "\"{value}\" is not a valid {name}",
+            &[("name", &argument.name), ("value", &argument.value)],
+        )
+    } else {
+        return syntax(request, syntax_pattern);
+    };
+    translate_or(
+        request,
+        "chatbot.syntax_hint",
+        "{syntax} ({hint})",
+        &[("syntax", &syntax_pattern), ("hint", &hint.trim_start())],
+    )
+}
+
+/// Translates a message into the language of the channel of a request, e.g.
+/// `tr!(request, "song.added", title = song.title)`.
+#[macro_export]
+macro_rules! tr {
+    ($request:expr, $key:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::locale::translate(
+            $request,
+            $key,
+            &[$((stringify!($name), &$value as &dyn ::core::fmt::Display)),*],
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_message() {
+        assert_eq!(
+            format_message("{user} added {song}", &[("user", &"@a"), ("song", &3)]),
+            "@a added 3"
+        );
+        assert_eq!(format_message("{unknown} {", &[]), "{unknown} {");
+    }
+
+    #[test]
+    fn test_catalog() {
+        let catalog = Catalog::new("en")
+            .with("en", "hello", "Hello {name}")
+            .with("de", "hello", "Hallo {name}")
+            .with("en", "bye", "Bye");
+        assert_eq!(
+            catalog.translate(Some("de"), "hello", &[("name", &"Nya")]),
+            "Hallo Nya"
+        );
+        assert_eq!(catalog.translate(Some("de"), "bye", &[]), "Bye");
+        assert_eq!(catalog.translate(None, "missing", &[]), "missing");
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_syntax_hint() {
+        use crate::command::{CommandProcessor, InvalidArgument, OutOfRange};
+        use crate::response::Response;
+        use crate::testing::TestRequestBuilder;
+        use async_trait::async_trait;
+
+        struct Volume;
+
+        #[async_trait]
+        impl CommandProcessor for Volume {
+            async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+                let error = CommandError::<anyhow::Error>::NamedArgumentParsing(
+                    InvalidArgument::new("volume", "200"),
+                    anyhow::Error::new(OutOfRange { min: 0, max: 100 }),
+                );
+                Some(Response::new(syntax_hint(
+                    request,
+                    "!volume <volume>",
+                    &error,
+                )))
+            }
+        }
+
+        let catalog = Catalog::new("en")
+            .with("de", "chatbot.syntax_hint", "{syntax} ({hint})")
+            .with(
+                "de",
+                "chatbot.out_of_range",
+                "{name} muss zwischen {min} und {max} liegen",
+            );
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let request = TestRequestBuilder::new("!volume 200")
+            .state(catalog.clone())
+            .language("de")
+            .build();
+        assert_eq!(
+            runtime.block_on(request.process_text(&Volume)),
+            vec!["!volume <volume> (volume muss zwischen 0 und 100 liegen)"]
+        );
+        let request = TestRequestBuilder::new("!volume 200")
+            .state(catalog)
+            .build();
+        assert_eq!(
+            runtime.block_on(request.process_text(&Volume)),
+            vec!["!volume <volume> (volume must be between 0 and 100)"]
+        );
+    }
+}
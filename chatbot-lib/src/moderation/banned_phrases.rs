@@ -0,0 +1,379 @@
+use super::strikes::strike_filtered;
+use crate::command::{CommandArguments, CommandProcessor};
+use crate::request::{
+    CommandRequest, FilterDecision, FilterPredicate, FilterRequest, FromCommandRequest,
+    RequestParts,
+};
+use crate::response::{Responder, Response};
+use crate::state::{PersistedChannelState, PersistedType};
+use crate::user::{User, UserArgument};
+use async_trait::async_trait;
+use core::fmt::{Display, Formatter};
+use regex::{Regex, RegexSet};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// What happens to users who send a banned phrase, ordered by severity.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub enum PhraseAction {
+    Delete,
+    Timeout(Duration),
+    Ban,
+}
+
+impl PhraseAction {
+    /// Parses `delete`, `ban`, `timeout` or `timeout:<duration>`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "delete" => Some(PhraseAction::Delete),
+            "ban" => Some(PhraseAction::Ban),
+            "timeout" => Some(PhraseAction::Timeout(DEFAULT_TIMEOUT)),
+            _ => value
+                .strip_prefix("timeout:")
+                .and_then(|duration| humantime::parse_duration(duration).ok())
+                .map(PhraseAction::Timeout),
+        }
+    }
+
+    /// The chat command which times out or bans `user`, deleting needs the id of the message.
+    pub(crate) fn command(&self, user: &str, reason: &str) -> Option<String> {
+        match self {
+            PhraseAction::Delete => None,
+            PhraseAction::Timeout(duration) => Some(format!(
+                ".timeout {} {} {}",
+                user,
+                duration.as_secs().max(1),
+                reason
+            )),
+            PhraseAction::Ban => Some(format!(".ban {} {}", user, reason)),
+        }
+    }
+}
+
+impl Display for PhraseAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PhraseAction::Delete => f.write_str("delete"),
+            PhraseAction::Timeout(duration) => {
+                write!(f, "timeout:{}", humantime::format_duration(*duration))
+            }
+            PhraseAction::Ban => f.write_str("ban"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BannedPhrase {
+    pub pattern: String,
+    /// Literal patterns match the text anywhere in the message, ignoring the case.
+    pub regex: bool,
+    pub action: PhraseAction,
+}
+
+impl BannedPhrase {
+    fn regex_pattern(&self) -> String {
+        match self.regex {
+            true => self.pattern.clone(),
+            false => format!("(?i){}", regex::escape(&self.pattern)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct BannedPhrases {
+    phrases: Vec<BannedPhrase>,
+}
+
+impl BannedPhrases {
+    pub fn len(&self) -> usize {
+        self.phrases.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.phrases.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BannedPhrase> {
+        self.phrases.iter()
+    }
+
+    /// Adds a phrase and returns its number, which starts at `1`.
+    pub fn add(&mut self, phrase: BannedPhrase) -> Result<usize, regex::Error> {
+        Regex::new(&phrase.regex_pattern())?;
+        self.phrases.push(phrase);
+        Ok(self.phrases.len())
+    }
+
+    /// Removes the phrase with the given number.
+    pub fn remove(&mut self, number: usize) -> Option<BannedPhrase> {
+        let index = number.checked_sub(1).filter(|index| *index < self.len())?;
+        Some(self.phrases.remove(index))
+    }
+}
+
+impl PersistedType for BannedPhrases {
+    const FILENAME: &'static str = "banned_phrases";
+
+    fn init(_channel: &str) -> Self {
+        Self::default()
+    }
+}
+
+pub type BannedPhrasesState<'req> = PersistedChannelState<'req, BannedPhrases>;
+
+/// The banned phrases of a channel compiled into one [`RegexSet`].
+#[derive(Debug)]
+struct CompiledPhrases {
+    source: Arc<BannedPhrases>,
+    set: RegexSet,
+    actions: Vec<PhraseAction>,
+}
+
+impl CompiledPhrases {
+    fn new(source: Arc<BannedPhrases>) -> Self {
+        // patterns which were edited by hand and are invalid are skipped
+        let (patterns, actions): (Vec<_>, Vec<_>) = source
+            .iter()
+            .map(|phrase| (phrase.regex_pattern(), phrase.action))
+            .filter(|(pattern, _)| match Regex::new(pattern) {
+                Ok(_) => true,
+                Err(e) => {
+                    log::warn!("Skipping invalid banned phrase {}: {}", pattern, e);
+                    false
+                }
+            })
+            .unzip();
+        let set = RegexSet::new(patterns).unwrap_or_else(|_| RegexSet::empty());
+        Self {
+            source,
+            set,
+            actions,
+        }
+    }
+
+    /// The most severe action of the phrases in `message`.
+    fn action(&self, message: &str) -> Option<PhraseAction> {
+        self.set
+            .matches(message)
+            .into_iter()
+            .map(|index| self.actions[index])
+            .max()
+    }
+}
+
+/// Deletes messages containing a phrase of the [`BannedPhrases`] of the channel, which have to be
+/// registered with
+/// [`ContainerBuilder::register_persisted_type`](crate::state::ContainerBuilder::register_persisted_type).
+///
+/// Senders are timed out or banned depending on the most severe [`PhraseAction`] which matched.
+/// Moderators are exempted by adding the filter to a [`FilterChain`](super::FilterChain) with
+/// [`Role::Moderator`](crate::request::Role::Moderator) as a bypass role.
+#[derive(Debug, Clone, Default)]
+pub struct BannedPhraseFilter {
+    compiled: Arc<Mutex<HashMap<String, Arc<CompiledPhrases>>>>,
+}
+
+impl BannedPhraseFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compiles the phrases again only if they changed since the last message of the channel.
+    fn compiled(&self, channel: &str, phrases: Arc<BannedPhrases>) -> Arc<CompiledPhrases> {
+        let mut compiled = self.compiled.lock().unwrap_or_else(|e| e.into_inner());
+        match compiled.get(channel) {
+            Some(cached) if Arc::ptr_eq(&cached.source, &phrases) => cached.clone(),
+            _ => {
+                let cached = Arc::new(CompiledPhrases::new(phrases));
+                compiled.insert(channel.to_owned(), cached.clone());
+                cached
+            }
+        }
+    }
+
+    /// Deletes the message if it contains a banned phrase, tagged with `banned phrases`.
+    pub async fn check(
+        &self,
+        request: &FilterRequest<'_>,
+        responder: &mut dyn Responder,
+    ) -> FilterDecision {
+        let sender = request.sender();
+        let phrases = match request.persisted_state::<BannedPhrases>() {
+            Ok(phrases) => phrases.read().await,
+            Err(_) => return FilterDecision::allow(),
+        };
+        if phrases.is_empty() {
+            return FilterDecision::allow();
+        }
+        let compiled = self.compiled(request.channel().username(), phrases);
+        let action = match compiled.action(request.message()) {
+            None => return FilterDecision::allow(),
+            Some(action) => action,
+        };
+        let decision = FilterDecision::delete(action.to_string()).with_tag("banned phrases");
+        let escalation =
+            strike_filtered(request, "banned phrases", "banned phrase".to_owned()).await;
+        let action = escalation.map_or(action, |escalation| action.max(escalation.into()));
+        if let Some(command) = action.command(sender.username(), "banned phrase") {
+            if let Err(e) = responder
+                .respond(&Response::new(command).as_command())
+                .await
+            {
+                log::error!("Error sending moderation command: {:?}", e);
+            }
+        }
+        decision
+    }
+
+    pub fn into_predicate(self) -> FilterPredicate {
+        Box::new(move |request, responder| {
+            let filter = self.clone();
+            Box::pin(async move { filter.check(&request, responder).await })
+        })
+    }
+}
+
+/// Processes `!banphrase add <action> <phrase..>`, `!banphrase regex <action> <pattern..>`,
+/// `!banphrase remove <number>` and `!banphrase list` for moderators.
+///
+/// The action is `delete`, `ban`, `timeout` or `timeout:<duration>`.
+pub struct BannedPhraseCommands;
+
+impl BannedPhraseCommands {
+    async fn process_banphrase(
+        request: &CommandRequest<'_>,
+        arguments: &mut CommandArguments<'_>,
+    ) -> Result<String, Cow<'static, str>> {
+        let syntax = "!banphrase add|regex <delete|timeout[:duration]|ban> <phrase>, !banphrase remove <number> or !banphrase list";
+        let phrases = BannedPhrasesState::from_command_request(request)
+            .map_err(|_| "the banned phrases are not registered for this channel")?;
+        match arguments.next() {
+            Some(kind @ ("add" | "regex")) => {
+                let action = arguments
+                    .next()
+                    .and_then(PhraseAction::parse)
+                    .ok_or(syntax)?;
+                let pattern = arguments.next_rest().ok_or(syntax)?;
+                let phrase = BannedPhrase {
+                    pattern: pattern.to_owned(),
+                    regex: kind == "regex",
+                    action,
+                };
+                let mut result = Ok(0);
+                phrases
+                    .maybe_update(|phrases| {
+                        let mut phrases = phrases.clone();
+                        result = phrases.add(phrase.clone());
+                        result.is_ok().then_some(phrases)
+                    })
+                    .await;
+                match result {
+                    Ok(number) => Ok(format!("added banned phrase #{} ({})", number, action)),
+                    Err(e) => Err(format!("the pattern is invalid: {}", e).into()),
+                }
+            }
+            Some("remove") => {
+                let number = arguments
+                    .next()
+                    .and_then(|number| number.trim_start_matches('#').parse().ok())
+                    .filter(|_| arguments.as_str().is_empty())
+                    .ok_or(syntax)?;
+                let (_, new) = phrases
+                    .maybe_update(|phrases| {
+                        let mut phrases = phrases.clone();
+                        phrases.remove(number).map(|_| phrases)
+                    })
+                    .await;
+                match new {
+                    Some(_) => Ok(format!("removed banned phrase #{}", number)),
+                    None => Err(format!("there is no banned phrase #{}", number).into()),
+                }
+            }
+            Some("list") if arguments.as_str().is_empty() => {
+                let phrases = phrases.read().await;
+                if phrases.is_empty() {
+                    return Ok("there are no banned phrases".to_owned());
+                }
+                let list: Vec<_> = phrases
+                    .iter()
+                    .enumerate()
+                    .map(|(index, phrase)| {
+                        format!("#{} {} ({})", index + 1, phrase.pattern, phrase.action)
+                    })
+                    .collect();
+                Ok(list.join(", "))
+            }
+            _ => Err(syntax.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for BannedPhraseCommands {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        if arguments.next() != Some("!banphrase") {
+            return None;
+        }
+        let sender = request.sender();
+        if !sender.is_moderator() && !sender.is_broadcaster() {
+            return None;
+        }
+        let text = match Self::process_banphrase(request, &mut arguments).await {
+            Ok(text) => text,
+            Err(error) => error.into_owned(),
+        };
+        Some(Response::new(format!(
+            "{} {}",
+            UserArgument::from(sender as &User),
+            text
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_banned_phrases() {
+        let mut phrases = BannedPhrases::default();
+        let phrase = |pattern: &str, regex, action| BannedPhrase {
+            pattern: pattern.to_owned(),
+            regex,
+            action,
+        };
+        assert_eq!(
+            phrases.add(phrase("buy followers", false, PhraseAction::Ban)),
+            Ok(1)
+        );
+        assert_eq!(
+            phrases.add(phrase(r"\bspam+\b", true, PhraseAction::Delete)),
+            Ok(2)
+        );
+        assert!(phrases
+            .add(phrase("(", true, PhraseAction::Delete))
+            .is_err());
+        assert_eq!(
+            PhraseAction::parse("timeout:5m"),
+            Some(PhraseAction::Timeout(Duration::from_secs(300)))
+        );
+
+        let compiled = CompiledPhrases::new(Arc::new(phrases));
+        assert_eq!(compiled.action("hello"), None);
+        assert_eq!(compiled.action("spammm"), Some(PhraseAction::Delete));
+        assert_eq!(
+            compiled.action("spam: BUY FOLLOWERS now"),
+            Some(PhraseAction::Ban)
+        );
+        // the literal pattern is escaped
+        assert_eq!(compiled.action("buy.followers"), None);
+    }
+}
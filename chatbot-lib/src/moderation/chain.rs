@@ -0,0 +1,225 @@
+use crate::command::{CommandArguments, CommandProcessor};
+use crate::request::{
+    try_predicate, CommandRequest, FilterDecision, FilterPredicate, FromCommandRequest,
+    RequestParts, Role, TryFilterPredicate,
+};
+use crate::response::Response;
+use crate::state::{PersistedChannelState, PersistedType};
+use crate::user::{User, UserArgument};
+use async_trait::async_trait;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+/// Which filters of a [`FilterChain`] are turned off in a channel, all filters are on by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct FilterSettings {
+    disabled: BTreeSet<String>,
+}
+
+impl FilterSettings {
+    pub fn is_enabled(&self, filter: &str) -> bool {
+        !self.disabled.contains(filter)
+    }
+
+    /// Returns `false` if the filter was enabled or disabled already.
+    pub fn set_enabled(&mut self, filter: &str, enabled: bool) -> bool {
+        match enabled {
+            true => self.disabled.remove(filter),
+            false => self.disabled.insert(filter.to_owned()),
+        }
+    }
+
+    pub fn disabled(&self) -> impl Iterator<Item = &str> {
+        self.disabled.iter().map(String::as_str)
+    }
+}
+
+impl PersistedType for FilterSettings {
+    const FILENAME: &'static str = "filters";
+
+    fn init(_channel: &str) -> Self {
+        Self::default()
+    }
+}
+
+pub type FilterSettingsState<'req> = PersistedChannelState<'req, FilterSettings>;
+
+struct ChainedFilter {
+    name: String,
+    bypass: Vec<Role>,
+    predicate: RefCell<TryFilterPredicate>,
+}
+
+/// Runs filters one after another until one of them deletes the message, whose decision is
+/// tagged with the name of the filter unless it has a tag already.
+///
+/// Filters are skipped for senders with one of their bypass roles and in channels which disabled
+/// them in their [`FilterSettings`], if the settings are registered with
+/// [`ContainerBuilder::register_persisted_type`](crate::state::ContainerBuilder::register_persisted_type).
+///
+/// ```ignore
+/// let filters = FilterChain::new()
+///     .with_filter("phrases", &[Role::Moderator], BannedPhraseFilter::new().into_predicate())
+///     .with_filter("links", &[Role::Moderator, Role::Vip], LinkFilter::new().into_predicate());
+/// let bot = bot.try_filter(filters.into_predicate());
+/// ```
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<ChainedFilter>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a filter which is not applied to senders with one of the `bypass` roles.
+    /// The broadcaster always bypasses the filters.
+    pub fn with_filter<N: Into<String>>(
+        self,
+        name: N,
+        bypass: &[Role],
+        predicate: FilterPredicate,
+    ) -> Self {
+        self.with_try_filter(name, bypass, try_predicate(predicate))
+    }
+
+    pub fn with_try_filter<N: Into<String>>(
+        mut self,
+        name: N,
+        bypass: &[Role],
+        predicate: TryFilterPredicate,
+    ) -> Self {
+        self.filters.push(ChainedFilter {
+            name: name.into(),
+            bypass: bypass.to_vec(),
+            predicate: RefCell::new(predicate),
+        });
+        self
+    }
+
+    /// The names of the filters in the order they are applied.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.filters.iter().map(|filter| filter.name.as_str())
+    }
+
+    pub fn into_predicate(self) -> TryFilterPredicate {
+        let filters = Rc::new(self.filters);
+        Box::new(move |request, responder| {
+            let filters = filters.clone();
+            Box::pin(async move {
+                let sender = request.sender();
+                if sender.is_broadcaster() {
+                    return Ok(FilterDecision::allow());
+                }
+                let settings = match request.persisted_state::<FilterSettings>() {
+                    Ok(settings) => Some(settings.read().await),
+                    Err(_) => None,
+                };
+                for filter in filters.iter() {
+                    if filter.bypass.iter().any(|role| sender.has_role(*role)) {
+                        continue;
+                    }
+                    if settings
+                        .as_ref()
+                        .is_some_and(|settings| !settings.is_enabled(&filter.name))
+                    {
+                        continue;
+                    }
+                    // the predicate is only borrowed to create the future
+                    let decision =
+                        (filter.predicate.borrow_mut())(request.clone(), &mut *responder);
+                    let mut decision = decision.await?;
+                    if decision.is_delete() {
+                        decision.tag.get_or_insert_with(|| filter.name.clone());
+                        return Ok(decision);
+                    }
+                }
+                Ok(FilterDecision::allow())
+            })
+        })
+    }
+}
+
+/// Processes `!filter enable <name>`, `!filter disable <name>` and `!filter` listing the disabled
+/// filters for moderators.
+pub struct FilterSettingsCommands;
+
+impl FilterSettingsCommands {
+    async fn process_filter(
+        request: &CommandRequest<'_>,
+        arguments: &mut CommandArguments<'_>,
+    ) -> Result<String, Cow<'static, str>> {
+        let syntax = "!filter enable <name>, !filter disable <name> or !filter";
+        let settings = FilterSettingsState::from_command_request(request)
+            .map_err(|_| "the filter settings are not registered for this channel")?;
+        let (enable, name) = match (arguments.next(), arguments.next()) {
+            (None, _) => {
+                let settings = settings.read().await;
+                let disabled: Vec<_> = settings.disabled().collect();
+                if disabled.is_empty() {
+                    return Ok("all filters are enabled".to_owned());
+                }
+                return Ok(format!("disabled filters: {}", disabled.join(", ")));
+            }
+            (Some("enable"), Some(name)) if arguments.as_str().is_empty() => (true, name),
+            (Some("disable"), Some(name)) if arguments.as_str().is_empty() => (false, name),
+            _ => return Err(syntax.into()),
+        };
+        let (_, new) = settings
+            .maybe_update(|settings| {
+                let mut settings = settings.clone();
+                settings.set_enabled(name, enable).then_some(settings)
+            })
+            .await;
+        match (enable, new.is_some()) {
+            (true, true) => Ok(format!("enabled the {} filter", name)),
+            (false, true) => Ok(format!("disabled the {} filter", name)),
+            (true, false) => Err(format!("the {} filter is enabled already", name).into()),
+            (false, false) => Err(format!("the {} filter is disabled already", name).into()),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for FilterSettingsCommands {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        if arguments.next() != Some("!filter") {
+            return None;
+        }
+        let sender = request.sender();
+        if !sender.is_moderator() && !sender.is_broadcaster() {
+            return None;
+        }
+        let text = match Self::process_filter(request, &mut arguments).await {
+            Ok(text) => text,
+            Err(error) => error.into_owned(),
+        };
+        Some(Response::new(format!(
+            "{} {}",
+            UserArgument::from(sender as &User),
+            text
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_settings() {
+        let mut settings = FilterSettings::default();
+        assert!(settings.is_enabled("links"));
+        assert!(settings.set_enabled("links", false));
+        assert!(!settings.set_enabled("links", false));
+        assert!(!settings.is_enabled("links"));
+        assert_eq!(settings.disabled().collect::<Vec<_>>(), ["links"]);
+        assert!(settings.set_enabled("links", true));
+        assert!(settings.is_enabled("links"));
+    }
+}
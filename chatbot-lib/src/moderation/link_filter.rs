@@ -0,0 +1,207 @@
+use super::strikes::strike_filtered;
+use super::PhraseAction;
+use crate::command::{CommandArguments, CommandProcessor};
+use crate::request::{
+    CommandRequest, FilterDecision, FilterNotice, FilterPredicate, FilterRequest,
+    FromCommandRequest, RequestParts,
+};
+use crate::response::{Responder, Response};
+use crate::state::ChannelState;
+use crate::user::{User, UserArgument};
+use async_trait::async_trait;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const DEFAULT_PERMIT: Duration = Duration::from_secs(60);
+const MAX_PERMIT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Users who may post links in a channel until their permit expires.
+///
+/// Has to be set in the channel container, e.g. with `builder.set(Permits::default())`.
+#[derive(Debug, Default)]
+pub struct Permits {
+    permits: Mutex<HashMap<String, Instant>>,
+}
+
+impl Permits {
+    /// Allows `user` to post links for `duration`, replacing an earlier permit.
+    pub fn permit(&self, user: &str, duration: Duration) {
+        let mut permits = self.permits.lock().unwrap_or_else(|e| e.into_inner());
+        permits.insert(user.to_lowercase(), Instant::now() + duration);
+    }
+
+    /// Returns `false` if the user had no permit.
+    pub fn revoke(&self, user: &str) -> bool {
+        let mut permits = self.permits.lock().unwrap_or_else(|e| e.into_inner());
+        permits.remove(&user.to_lowercase()).is_some()
+    }
+
+    pub fn is_permitted(&self, user: &str) -> bool {
+        let now = Instant::now();
+        let mut permits = self.permits.lock().unwrap_or_else(|e| e.into_inner());
+        // expired permits are removed
+        permits.retain(|_, expires| *expires > now);
+        permits.contains_key(&user.to_lowercase())
+    }
+}
+
+/// Whether `message` contains something which looks like a link, e.g. `https://…` or `example.com`.
+pub fn contains_link(message: &str) -> bool {
+    message.split_whitespace().any(|word| {
+        let word = word.trim_matches(|c: char| !c.is_alphanumeric() && c != '/');
+        if word.contains("://") {
+            return true;
+        }
+        let host = word.split('/').next().unwrap_or_default();
+        match host.rsplit_once('.') {
+            Some((name, tld)) => {
+                !name.is_empty()
+                    && !name.ends_with('.')
+                    && name
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '-' || c == '.')
+                    && (2..=24).contains(&tld.len())
+                    && tld.chars().all(|c| c.is_ascii_alphabetic())
+            }
+            None => false,
+        }
+    })
+}
+
+/// Deletes messages with links, unless they were sent by users with a permit. Moderators are
+/// exempted by adding the filter to a [`FilterChain`](super::FilterChain) with
+/// [`Role::Moderator`](crate::request::Role::Moderator) as a bypass role.
+///
+/// The [`Permits`] of the channel are used if they are set in the channel container.
+#[derive(Debug, Clone, Default)]
+pub struct LinkFilter {
+    warning: Option<String>,
+}
+
+impl LinkFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The warning which is sent to users whose message was deleted.
+    pub fn with_warning<T: Into<String>>(self, warning: T) -> Self {
+        Self {
+            warning: Some(warning.into()),
+        }
+    }
+
+    /// Deletes the message if it contains a link, tagged with `links`, and warns the sender.
+    pub async fn check(
+        &self,
+        request: &FilterRequest<'_>,
+        responder: &mut dyn Responder,
+    ) -> FilterDecision {
+        let sender = request.sender();
+        if !contains_link(request.message()) {
+            return FilterDecision::allow();
+        }
+        let permits = request.channel_state::<Permits>();
+        if permits.is_ok_and(|permits| permits.is_permitted(sender.username())) {
+            return FilterDecision::allow();
+        }
+        let escalation = strike_filtered(request, "links", "link without permit".to_owned()).await;
+        let command = escalation
+            .and_then(|action| PhraseAction::from(action).command(sender.username(), "links"));
+        if let Some(command) = command {
+            if let Err(e) = responder
+                .respond(&Response::new(command).as_command())
+                .await
+            {
+                log::error!("Error sending moderation command: {:?}", e);
+            }
+        }
+        let warning = self
+            .warning
+            .as_deref()
+            .unwrap_or("please ask a moderator for a !permit before posting links");
+        FilterDecision::delete("link without permit")
+            .with_tag("links")
+            .with_notice(FilterNotice::Reply(warning.to_owned()))
+    }
+
+    pub fn into_predicate(self) -> FilterPredicate {
+        Box::new(move |request, responder| {
+            let filter = self.clone();
+            Box::pin(async move { filter.check(&request, responder).await })
+        })
+    }
+}
+
+/// Processes `!permit <user> [duration]` for moderators, the default duration is one minute.
+pub struct PermitCommands;
+
+impl PermitCommands {
+    fn process_permit(
+        request: &CommandRequest<'_>,
+        arguments: &mut CommandArguments<'_>,
+    ) -> Result<String, Cow<'static, str>> {
+        let syntax = "!permit <user> [duration]";
+        let permits = ChannelState::<Permits>::from_command_request(request)
+            .map_err(|_| "permits are not enabled for this channel")?;
+        let target = arguments.next().map(UserArgument::new).ok_or(syntax)?;
+        let duration = match arguments.next() {
+            Some(duration) => humantime::parse_duration(duration).map_err(|_| syntax)?,
+            None => DEFAULT_PERMIT,
+        };
+        if !arguments.as_str().is_empty() {
+            return Err(syntax.into());
+        }
+        if duration > MAX_PERMIT {
+            return Err("permits can last at most one day".into());
+        }
+        permits.permit(target.as_argument(), duration);
+        Ok(format!(
+            "{} may post links for {}",
+            target,
+            humantime::format_duration(duration)
+        ))
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for PermitCommands {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        if arguments.next() != Some("!permit") {
+            return None;
+        }
+        let sender = request.sender();
+        if !sender.is_moderator() && !sender.is_broadcaster() {
+            return None;
+        }
+        let text = match Self::process_permit(request, &mut arguments) {
+            Ok(text) => text,
+            Err(error) => format!("{} {}", UserArgument::from(sender as &User), error),
+        };
+        Some(Response::new(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_filter() {
+        assert!(contains_link("check out https://twitch.tv/liquidnya"));
+        assert!(contains_link("go to example.com/nya!"));
+        assert!(contains_link("(twitch.tv)"));
+        assert!(!contains_link("hello... world"));
+        assert!(!contains_link("e.g. this is 1.5 times better"));
+
+        let permits = Permits::default();
+        permits.permit("LiquidNya", Duration::from_secs(60));
+        permits.permit("user", Duration::ZERO);
+        assert!(permits.is_permitted("liquidnya"));
+        assert!(!permits.is_permitted("user"));
+        assert!(permits.revoke("liquidnya"));
+        assert!(!permits.is_permitted("liquidnya"));
+    }
+}
@@ -0,0 +1,242 @@
+use crate::command::{CommandArguments, CommandProcessor};
+use crate::request::{CommandRequest, FilterDecision, FromCommandRequest};
+use crate::response::Response;
+use crate::state::ChannelState;
+use crate::user::{User, UserArgument};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use core::fmt::{Display, Formatter};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// How many characters of a message are shown by `!modlog`.
+const MAX_MESSAGE_CHARS: usize = 40;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModerationEntry {
+    pub time: DateTime<Utc>,
+    /// The user whose message was moderated.
+    pub user: String,
+    /// The filter which moderated the message, e.g. `links`.
+    pub filter: Cow<'static, str>,
+    pub reason: String,
+    pub message: String,
+}
+
+impl Display for ModerationEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let mut message: String = self.message.chars().take(MAX_MESSAGE_CHARS).collect();
+        if message.len() < self.message.len() {
+            message.push('…');
+        }
+        write!(
+            f,
+            "{} {} ({}: {}) \"{}\"",
+            self.time.format("%H:%M"),
+            self.user,
+            self.filter,
+            self.reason,
+            message
+        )
+    }
+}
+
+/// The last moderation actions of the filters in a channel.
+///
+/// Has to be set in the channel container, e.g. with `builder.set(ModerationLog::new(50))`,
+/// and can be extracted with [`ChannelState<ModerationLog>`](ChannelState).
+#[derive(Debug)]
+pub struct ModerationLog {
+    entries: Mutex<VecDeque<ModerationEntry>>,
+    capacity: usize,
+}
+
+impl Default for ModerationLog {
+    fn default() -> Self {
+        Self::new(50)
+    }
+}
+
+impl ModerationLog {
+    /// Keeps the last `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    pub fn record(&self, entry: ModerationEntry) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        if self.capacity > 0 {
+            entries.push_back(entry);
+        }
+    }
+
+    /// The last `n` entries, the newest first.
+    pub fn last(&self, n: usize) -> Vec<ModerationEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.iter().rev().take(n).cloned().collect()
+    }
+
+    /// The last `n` entries of `user`, the newest first.
+    pub fn last_of(&self, user: &str, n: usize) -> Vec<ModerationEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.user.eq_ignore_ascii_case(user))
+            .take(n)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Records a message which was deleted because of `decision`.
+pub(crate) fn record_filtered(
+    log: &ModerationLog,
+    user: &str,
+    message: &str,
+    decision: &FilterDecision,
+) {
+    log.record(ModerationEntry {
+        time: Utc::now(),
+        user: user.to_owned(),
+        filter: decision
+            .tag
+            .clone()
+            .map_or(Cow::Borrowed("filter"), Cow::Owned),
+        reason: decision
+            .reason
+            .clone()
+            .unwrap_or_else(|| "deleted".to_owned()),
+        message: message.to_owned(),
+    });
+}
+
+/// Records a timeout or ban sent by the bot, e.g. by a command. Returns `false` for other
+/// messages.
+pub(crate) fn record_sent_command(log: &ModerationLog, text: &str) -> bool {
+    let mut arguments = text.trim_start_matches(['.', '/']).split_whitespace();
+    let (action, user) = match (arguments.next(), arguments.next()) {
+        (Some(action @ ("timeout" | "ban")), Some(user)) => (action, user),
+        _ => return false,
+    };
+    log.record(ModerationEntry {
+        time: Utc::now(),
+        user: user.trim_start_matches('@').to_owned(),
+        filter: "bot".into(),
+        reason: action.to_owned(),
+        message: text.to_owned(),
+    });
+    true
+}
+
+/// Processes `!modlog [user]` for moderators, which shows the last three moderation actions.
+pub struct ModerationLogCommands;
+
+impl ModerationLogCommands {
+    fn process_modlog(
+        request: &CommandRequest<'_>,
+        arguments: &mut CommandArguments<'_>,
+    ) -> Result<String, Cow<'static, str>> {
+        let log = ChannelState::<ModerationLog>::from_command_request(request)
+            .map_err(|_| "the moderation log is not enabled for this channel")?;
+        let user = arguments.next().map(UserArgument::new);
+        if !arguments.as_str().is_empty() {
+            return Err("!modlog [user]".into());
+        }
+        let entries = match &user {
+            Some(user) => log.last_of(user.as_argument(), 3),
+            None => log.last(3),
+        };
+        if entries.is_empty() {
+            return Ok("nothing was moderated yet".to_owned());
+        }
+        let entries: Vec<_> = entries.iter().map(ModerationEntry::to_string).collect();
+        Ok(entries.join(" | "))
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for ModerationLogCommands {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        if arguments.next() != Some("!modlog") {
+            return None;
+        }
+        let sender = request.sender();
+        if !sender.is_moderator() && !sender.is_broadcaster() {
+            return None;
+        }
+        let text = match Self::process_modlog(request, &mut arguments) {
+            Ok(text) => text,
+            Err(error) => error.into_owned(),
+        };
+        Some(Response::new(format!(
+            "{} {}",
+            UserArgument::from(sender as &User),
+            text
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_moderation_log() {
+        let log = ModerationLog::new(2);
+        let entry = |user: &str, message: &str| ModerationEntry {
+            time: Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap(),
+            user: user.to_owned(),
+            filter: "links".into(),
+            reason: "no permit".to_owned(),
+            message: message.to_owned(),
+        };
+        log.record(entry("a", "example.com"));
+        log.record(entry("b", "example.org"));
+        log.record(entry("A", &"nya ".repeat(20)));
+        let last = log.last(5);
+        assert_eq!(last.len(), 2);
+        assert_eq!(
+            last[0].to_string(),
+            "12:00 A (links: no permit) \"nya nya nya nya nya nya nya nya nya nya …\""
+        );
+        // the first entry was dropped
+        assert_eq!(log.last_of("a", 5).len(), 1);
+    }
+
+    #[test]
+    fn test_record_sent_command() {
+        let log = ModerationLog::new(5);
+        assert!(record_sent_command(&log, ".timeout @spammer 600 spam"));
+        assert!(!record_sent_command(&log, ".delete 123"));
+        assert!(!record_sent_command(&log, "timeouts are not fun"));
+        let last = log.last(5);
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].user, "spammer");
+        assert_eq!(last[0].reason, "timeout");
+    }
+
+    #[test]
+    fn test_record_filtered() {
+        let log = ModerationLog::new(5);
+        let decision = FilterDecision::delete("link without permit").with_tag("links");
+        record_filtered(&log, "spammer", "example.com", &decision);
+        record_filtered(&log, "spammer", "nya", &FilterDecision::from(false));
+        let last = log.last(5);
+        assert_eq!(last.len(), 2);
+        assert_eq!(
+            (&*last[1].filter, &*last[1].reason),
+            ("links", "link without permit")
+        );
+        assert_eq!((&*last[0].filter, &*last[0].reason), ("filter", "deleted"));
+    }
+}
@@ -0,0 +1,19 @@
+//! Filters which delete messages in chat, to be used with [`ChatBot::filter`](crate::ChatBot::filter).
+
+mod banned_phrases;
+mod chain;
+mod link_filter;
+mod log;
+mod strikes;
+
+pub use self::banned_phrases::{
+    BannedPhrase, BannedPhraseCommands, BannedPhraseFilter, BannedPhrases, BannedPhrasesState,
+    PhraseAction,
+};
+pub use self::chain::{FilterChain, FilterSettings, FilterSettingsCommands, FilterSettingsState};
+pub use self::link_filter::{contains_link, LinkFilter, PermitCommands, Permits};
+pub(crate) use self::log::{record_filtered, record_sent_command};
+pub use self::log::{ModerationEntry, ModerationLog, ModerationLogCommands};
+pub use self::strikes::{
+    EscalationPolicy, Strike, StrikeAction, StrikeCommands, Strikes, StrikesState,
+};
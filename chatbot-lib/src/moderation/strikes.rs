@@ -0,0 +1,279 @@
+use super::PhraseAction;
+use crate::command::{CommandArguments, CommandProcessor};
+use crate::request::{CommandRequest, FilterRequest, FromCommandRequest, RequestParts};
+use crate::response::{RequestResponder, Responder, Response};
+use crate::state::{ChannelState, PersistedChannelState, PersistedType};
+use crate::user::{User, UserArgument};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Strike {
+    pub reason: String,
+    /// The moderator or the filter which gave the strike.
+    pub by: String,
+    pub at: DateTime<Utc>,
+}
+
+/// The strikes of the users of a channel.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Strikes {
+    users: BTreeMap<String, Vec<Strike>>,
+}
+
+impl Strikes {
+    pub fn get(&self, user: &str) -> &[Strike] {
+        self.users
+            .get(&user.to_lowercase())
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Adds a strike and returns the number of strikes of the user.
+    pub fn add(&mut self, user: &str, strike: Strike) -> usize {
+        let strikes = self.users.entry(user.to_lowercase()).or_default();
+        strikes.push(strike);
+        strikes.len()
+    }
+
+    /// Removes all strikes of the user and returns how many there were.
+    pub fn clear(&mut self, user: &str) -> usize {
+        self.users
+            .remove(&user.to_lowercase())
+            .map_or(0, |strikes| strikes.len())
+    }
+}
+
+impl PersistedType for Strikes {
+    const FILENAME: &'static str = "strikes";
+
+    fn init(_channel: &str) -> Self {
+        Self::default()
+    }
+}
+
+pub type StrikesState<'req> = PersistedChannelState<'req, Strikes>;
+
+impl PersistedChannelState<'_, Strikes> {
+    /// Adds a strike and returns the number of strikes of the user.
+    pub async fn strike(&self, user: &str, strike: Strike) -> usize {
+        let mut count = 0;
+        self.update(|strikes| {
+            let mut strikes = strikes.clone();
+            count = strikes.add(user, strike.clone());
+            strikes
+        })
+        .await;
+        count
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrikeAction {
+    Timeout(Duration),
+    Ban,
+}
+
+impl From<StrikeAction> for PhraseAction {
+    fn from(action: StrikeAction) -> Self {
+        match action {
+            StrikeAction::Timeout(duration) => PhraseAction::Timeout(duration),
+            StrikeAction::Ban => PhraseAction::Ban,
+        }
+    }
+}
+
+/// What happens to users once they have a number of strikes.
+///
+/// Has to be set in the channel container, e.g. with
+/// `builder.set(EscalationPolicy::new().with_step(2, StrikeAction::Timeout(duration)).with_step(3, StrikeAction::Ban))`.
+#[derive(Debug, Clone, Default)]
+pub struct EscalationPolicy {
+    steps: BTreeMap<usize, StrikeAction>,
+}
+
+impl EscalationPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_step(mut self, strikes: usize, action: StrikeAction) -> Self {
+        self.steps.insert(strikes, action);
+        self
+    }
+
+    /// The action of the highest step the number of strikes reached.
+    pub fn action(&self, strikes: usize) -> Option<StrikeAction> {
+        self.steps
+            .range(..=strikes)
+            .next_back()
+            .map(|(_, action)| *action)
+    }
+}
+
+/// Gives the sender of a filtered message a strike if [`Strikes`] are registered for the channel
+/// and returns the action of the [`EscalationPolicy`].
+pub(crate) async fn strike_filtered(
+    request: &FilterRequest<'_>,
+    filter: &str,
+    reason: String,
+) -> Option<StrikeAction> {
+    let strikes = request.persisted_state::<Strikes>().ok()?;
+    let strike = Strike {
+        reason,
+        by: filter.to_owned(),
+        at: Utc::now(),
+    };
+    let count = strikes.strike(request.sender().username(), strike).await;
+    request
+        .channel_state::<EscalationPolicy>()
+        .ok()
+        .and_then(|policy| policy.action(count))
+}
+
+/// Processes `!strike <user> <reason..>`, `!strikes <user>` and `!strikes <user> clear` for
+/// moderators.
+pub struct StrikeCommands;
+
+impl StrikeCommands {
+    async fn process_strike(
+        request: &CommandRequest<'_>,
+        arguments: &mut CommandArguments<'_>,
+    ) -> Result<String, Cow<'static, str>> {
+        let syntax = "!strike <user> <reason>";
+        let strikes = StrikesState::from_command_request(request)
+            .map_err(|_| "strikes are not registered for this channel")?;
+        let target = arguments.next().map(UserArgument::new).ok_or(syntax)?;
+        let reason = arguments.next_rest().ok_or(syntax)?;
+        let sender = request.sender();
+        let strike = Strike {
+            reason: reason.to_owned(),
+            by: sender.username().to_owned(),
+            at: Utc::now(),
+        };
+        let count = strikes.strike(target.as_argument(), strike).await;
+        let policy = ChannelState::<EscalationPolicy>::from_command_request(request).ok();
+        let action = policy.and_then(|policy| policy.action(count));
+        if let Some(command) = action
+            .and_then(|action| PhraseAction::from(action).command(target.as_argument(), reason))
+        {
+            RequestResponder::from(request)
+                .respond(&Response::new(command).as_command())
+                .await
+                .map_err(|e| {
+                    log::error!("Error sending moderation command: {:?}", e);
+                    "the strike was added, but the moderation command failed"
+                })?;
+        }
+        Ok(match action {
+            Some(action) => format!(
+                "{} has {} strikes ({})",
+                target,
+                count,
+                PhraseAction::from(action)
+            ),
+            None => format!("{} has {} strikes", target, count),
+        })
+    }
+
+    async fn process_strikes(
+        request: &CommandRequest<'_>,
+        arguments: &mut CommandArguments<'_>,
+    ) -> Result<String, Cow<'static, str>> {
+        let syntax = "!strikes <user> [clear]";
+        let strikes = StrikesState::from_command_request(request)
+            .map_err(|_| "strikes are not registered for this channel")?;
+        let target = arguments.next().map(UserArgument::new).ok_or(syntax)?;
+        match arguments.next() {
+            None => {
+                let strikes = strikes.read().await;
+                let strikes = strikes.get(target.as_argument());
+                if strikes.is_empty() {
+                    return Ok(format!("{} has no strikes", target));
+                }
+                let reasons: Vec<_> = strikes
+                    .iter()
+                    .map(|strike| strike.reason.as_str())
+                    .collect();
+                Ok(format!(
+                    "{} has {} strikes: {}",
+                    target,
+                    strikes.len(),
+                    reasons.join(", ")
+                ))
+            }
+            Some("clear") if arguments.as_str().is_empty() => {
+                let mut count = 0;
+                strikes
+                    .maybe_update(|strikes| {
+                        let mut strikes = strikes.clone();
+                        count = strikes.clear(target.as_argument());
+                        (count > 0).then_some(strikes)
+                    })
+                    .await;
+                Ok(format!("removed {} strikes of {}", count, target))
+            }
+            _ => Err(syntax.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for StrikeCommands {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        let command = arguments.next();
+        if !matches!(command, Some("!strike" | "!strikes")) {
+            return None;
+        }
+        let sender = request.sender();
+        if !sender.is_moderator() && !sender.is_broadcaster() {
+            return None;
+        }
+        let result = match command {
+            Some("!strike") => Self::process_strike(request, &mut arguments).await,
+            _ => Self::process_strikes(request, &mut arguments).await,
+        };
+        let text = match result {
+            Ok(text) => text,
+            Err(error) => error.into_owned(),
+        };
+        Some(Response::new(format!(
+            "{} {}",
+            UserArgument::from(sender as &User),
+            text
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strikes() {
+        let strike = |reason: &str| Strike {
+            reason: reason.to_owned(),
+            by: "links".to_owned(),
+            at: Utc::now(),
+        };
+        let mut strikes = Strikes::default();
+        assert_eq!(strikes.add("User", strike("link")), 1);
+        assert_eq!(strikes.add("user", strike("spam")), 2);
+        assert_eq!(strikes.get("USER").len(), 2);
+
+        let timeout = StrikeAction::Timeout(Duration::from_secs(600));
+        let policy = EscalationPolicy::new()
+            .with_step(2, timeout)
+            .with_step(3, StrikeAction::Ban);
+        assert_eq!(policy.action(1), None);
+        assert_eq!(policy.action(2), Some(timeout));
+        assert_eq!(policy.action(5), Some(StrikeAction::Ban));
+
+        assert_eq!(strikes.clear("user"), 2);
+        assert!(strikes.get("user").is_empty());
+    }
+}
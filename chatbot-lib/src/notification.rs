@@ -0,0 +1,177 @@
+//! Mirrors responses and moderation events to other platforms, e.g. deleted messages to a Discord mod channel.
+
+use async_trait::async_trait;
+use core::fmt::{Display, Formatter};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notification {
+    /// The bot sent a message to the chat.
+    Response { channel: String, text: String },
+    /// A message was deleted by a moderator or a filter.
+    MessageDeleted {
+        channel: String,
+        user: Option<String>,
+        message: Option<String>,
+    },
+    /// A user was timed out or banned, or the chat of the channel was cleared if there is no user.
+    ChatCleared {
+        channel: String,
+        user: Option<String>,
+        duration: Option<Duration>,
+    },
+    /// The filter failed to check a message of `user`.
+    FilterFailed {
+        channel: String,
+        user: String,
+        error: String,
+    },
+}
+
+impl Notification {
+    pub fn channel(&self) -> &str {
+        match self {
+            Notification::Response { channel, .. }
+            | Notification::MessageDeleted { channel, .. }
+            | Notification::ChatCleared { channel, .. }
+            | Notification::FilterFailed { channel, .. } => channel,
+        }
+    }
+
+    pub fn is_moderation(&self) -> bool {
+        !matches!(self, Notification::Response { .. })
+    }
+}
+
+impl Display for Notification {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#{}: ", self.channel())?;
+        match self {
+            Notification::Response { text, .. } => f.write_str(text),
+            Notification::MessageDeleted { user, message, .. } => {
+                f.write_str("deleted a message")?;
+                if let Some(user) = user {
+                    write!(f, " of {}", user)?;
+                }
+                if let Some(message) = message {
+                    write!(f, ": {}", message)?;
+                }
+                Ok(())
+            }
+            Notification::ChatCleared {
+                user: Some(user),
+                duration: Some(duration),
+                ..
+            } => write!(
+                f,
+                "{} was timed out for {}",
+                user,
+                humantime::format_duration(*duration)
+            ),
+            Notification::ChatCleared {
+                user: Some(user), ..
+            } => write!(f, "{} was banned", user),
+            Notification::ChatCleared { user: None, .. } => f.write_str("the chat was cleared"),
+            Notification::FilterFailed { user, error, .. } => {
+                write!(
+                    f,
+                    "the filter failed to check a message of {}: {}",
+                    user, error
+                )
+            }
+        }
+    }
+}
+
+/// Receives the notifications of a chat bot, see
+/// [`ChatBot::with_notification_sink`](crate::ChatBot::with_notification_sink).
+#[async_trait]
+pub trait NotificationSink {
+    async fn notify(&self, notification: &Notification) -> anyhow::Result<()>;
+}
+
+/// Posts notifications to a Discord channel through a webhook.
+#[cfg(feature = "discord")]
+#[derive(Debug, Clone)]
+pub struct DiscordWebhook {
+    client: reqwest::Client,
+    url: String,
+    moderation_only: bool,
+}
+
+/// Discord rejects messages with more characters.
+#[cfg(feature = "discord")]
+const DISCORD_MAX_LEN: usize = 2000;
+
+#[cfg(feature = "discord")]
+impl DiscordWebhook {
+    pub fn new<T: Into<String>>(url: T) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+            moderation_only: false,
+        }
+    }
+
+    /// Only posts moderation events and no responses.
+    pub fn moderation_only(self) -> Self {
+        Self {
+            moderation_only: true,
+            ..self
+        }
+    }
+}
+
+#[cfg(feature = "discord")]
+#[async_trait]
+impl NotificationSink for DiscordWebhook {
+    async fn notify(&self, notification: &Notification) -> anyhow::Result<()> {
+        if self.moderation_only && !notification.is_moderation() {
+            return Ok(());
+        }
+        let content: String = notification
+            .to_string()
+            .chars()
+            .take(DISCORD_MAX_LEN)
+            .collect();
+        self.client
+            .post(&self.url)
+            .json(&serde_json::json!({
+                "content": content,
+                // chat messages must not ping anyone on Discord
+                "allowed_mentions": { "parse": [] },
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let deleted = Notification::MessageDeleted {
+            channel: "liquidnya".to_owned(),
+            user: Some("user".to_owned()),
+            message: Some("spam".to_owned()),
+        };
+        assert_eq!(
+            deleted.to_string(),
+            "#liquidnya: deleted a message of user: spam"
+        );
+        let timeout = Notification::ChatCleared {
+            channel: "liquidnya".to_owned(),
+            user: Some("user".to_owned()),
+            duration: Some(Duration::from_secs(600)),
+        };
+        assert_eq!(
+            timeout.to_string(),
+            "#liquidnya: user was timed out for 10m"
+        );
+        assert!(timeout.is_moderation());
+    }
+}
@@ -0,0 +1,119 @@
+//! The chat the bot is connected to, Twitch is the default implementation.
+//!
+//! Another chat like Discord or YouTube can drive the command processors, the state and the
+//! responses by implementing [`ChatPlatform`] and running the bot with
+//! [`ChatBot::with_platform`](crate::ChatBot::with_platform).
+
+mod sharded;
+mod twitch;
+
+pub use self::sharded::ShardedPlatform;
+pub use self::twitch::{TwitchPlatform, TwitchWriter};
+
+use crate::request::{Channel, Sender};
+use crate::user::{ChannelId, OwnedUser, User, UserId};
+use async_trait::async_trait;
+use std::error::Error;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub type PlatformError = Box<dyn Error>;
+
+/// A chat message received in a channel.
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+    pub channel: String,
+    pub channel_id: Option<ChannelId>,
+    /// Used to reply to or to delete the message.
+    pub id: Option<String>,
+    pub sender: OwnedUser,
+    pub moderator: bool,
+    pub broadcaster: bool,
+    pub vip: bool,
+    pub subscriber: bool,
+    pub text: String,
+}
+
+impl ChatMessage {
+    pub fn channel(&self) -> Channel<'_> {
+        User::new(&self.channel, None, self.channel_id.map(UserId::from)).into()
+    }
+
+    pub fn sender(&self) -> Sender<'_> {
+        Sender::new(
+            User::from_owned(&self.sender),
+            self.moderator,
+            self.broadcaster,
+        )
+        .with_vip(self.vip)
+        .with_subscriber(self.subscriber)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ChatEvent {
+    Message(ChatMessage),
+    /// A message was deleted by a moderator.
+    MessageDeleted {
+        channel: String,
+        channel_id: Option<ChannelId>,
+        message_id: Option<String>,
+        user: Option<String>,
+        message: Option<String>,
+    },
+    /// A user was timed out or banned, or the whole chat was cleared if there is no user.
+    ChatCleared {
+        channel: String,
+        channel_id: Option<ChannelId>,
+        user: Option<String>,
+        user_id: Option<UserId>,
+        duration: Option<Duration>,
+    },
+    Join {
+        channel: String,
+        user: String,
+    },
+    Part {
+        channel: String,
+        user: String,
+    },
+    /// Anything else which shows that the connection is alive, e.g. a ping.
+    Activity,
+}
+
+impl ChatEvent {
+    /// The channel of the event, events without a channel are not handled by the chat bot.
+    pub fn channel(&self) -> Option<&str> {
+        match self {
+            ChatEvent::Message(message) => Some(&message.channel),
+            ChatEvent::MessageDeleted { channel, .. }
+            | ChatEvent::ChatCleared { channel, .. }
+            | ChatEvent::Join { channel, .. }
+            | ChatEvent::Part { channel, .. } => Some(channel),
+            ChatEvent::Activity => None,
+        }
+    }
+}
+
+/// Sends messages to the chat, which can be shared by every task of the chat bot.
+#[async_trait]
+pub trait ChatWriter {
+    /// Sends `text` to `channel`, as a reply to the message with the id `reply_to` if it is set.
+    async fn send(&self, channel: &str, text: &str, reply_to: Option<&str>) -> io::Result<()>;
+}
+
+/// The connection to a chat, which produces the events handled by the chat bot.
+#[async_trait(?Send)]
+pub trait ChatPlatform {
+    /// Connects to the chat and returns the user of the bot.
+    async fn connect(&mut self) -> Result<OwnedUser, PlatformError>;
+
+    async fn join(&mut self, channel: &str) -> Result<(), PlatformError>;
+
+    /// Waits for the next event, `None` if the connection was closed.
+    async fn next_event(&mut self) -> Result<Option<ChatEvent>, PlatformError>;
+
+    /// The writer for responses, which is requested after the bot connected.
+    fn writer(&self) -> Arc<dyn ChatWriter + Send + Sync>;
+}
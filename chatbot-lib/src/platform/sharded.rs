@@ -0,0 +1,278 @@
+use super::{ChatEvent, ChatPlatform, ChatWriter, PlatformError};
+use crate::user::OwnedUser;
+use async_trait::async_trait;
+use futures_util::future::select_all;
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, RwLock};
+
+/// Which shard joined which channel, shared with the writer.
+type Routes = Arc<RwLock<HashMap<String, usize>>>;
+
+fn normalize(channel: &str) -> &str {
+    channel.trim_start_matches('#')
+}
+
+/// Spreads the channels over several connections, e.g. to stay below the join rate limit of a
+/// single connection when joining hundreds of channels.
+///
+/// The events of all connections are handled like the events of one connection, and responses
+/// are sent through the connection which joined the channel. The bot stops once any of the
+/// connections is closed.
+///
+/// Waiting for the next event of a connection is cancelled whenever another connection produces
+/// an event first, so [`ChatPlatform::next_event`] of the shards has to be cancel safe.
+///
+/// ```ignore
+/// let connectors = (0..4)
+///     .map(|_| ConnectorRustTls::twitch())
+///     .collect::<Result<Vec<_>, _>>()?;
+/// let bot = ChatBot::sharded(connectors, &user_config, 50);
+/// ```
+pub struct ShardedPlatform<P> {
+    shards: Vec<P>,
+    channels_per_shard: usize,
+    routes: Routes,
+    /// The shard which is polled first, which changes with every event so no shard starves.
+    next: usize,
+}
+
+impl<P> ShardedPlatform<P> {
+    /// Joins at most `channels_per_shard` channels with each shard, as long as there is a shard
+    /// with fewer channels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no shards.
+    pub fn new(shards: Vec<P>, channels_per_shard: usize) -> Self {
+        assert!(!shards.is_empty(), "at least one shard is needed");
+        Self {
+            shards,
+            channels_per_shard: channels_per_shard.max(1),
+            routes: Default::default(),
+            next: 0,
+        }
+    }
+
+    /// How many channels each shard joined.
+    pub fn channels_per_shard(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.shards.len()];
+        for shard in self
+            .routes
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+        {
+            counts[*shard] += 1;
+        }
+        counts
+    }
+
+    fn least_loaded(&self) -> usize {
+        let counts = self.channels_per_shard();
+        // the first shard with the fewest channels, such that shards are filled in order
+        let (shard, count) = counts
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, count)| **count)
+            .unwrap_or((0, &0));
+        if *count >= self.channels_per_shard {
+            log::warn!(
+                "All {} shards joined {} channels, joining more with shard {}",
+                counts.len(),
+                self.channels_per_shard,
+                shard
+            );
+        }
+        shard
+    }
+}
+
+#[async_trait(?Send)]
+impl<P: ChatPlatform> ChatPlatform for ShardedPlatform<P> {
+    /// Connects all shards, which have to log in as the same user.
+    async fn connect(&mut self) -> Result<OwnedUser, PlatformError> {
+        let mut bot = None;
+        for (index, shard) in self.shards.iter_mut().enumerate() {
+            let user = shard.connect().await?;
+            log::debug!("Connected shard {} as {}", index, user.username());
+            bot.get_or_insert(user);
+        }
+        Ok(bot.expect("at least one shard is needed"))
+    }
+
+    async fn join(&mut self, channel: &str) -> Result<(), PlatformError> {
+        let channel = normalize(channel);
+        let known = self
+            .routes
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(channel)
+            .copied();
+        let shard = match known {
+            Some(shard) => shard,
+            None => self.least_loaded(),
+        };
+        self.shards[shard].join(channel).await?;
+        self.routes
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(channel.to_owned(), shard);
+        Ok(())
+    }
+
+    async fn next_event(&mut self) -> Result<Option<ChatEvent>, PlatformError> {
+        let start = self.next % self.shards.len();
+        let (first, second) = self.shards.split_at_mut(start);
+        let events = second
+            .iter_mut()
+            .chain(first.iter_mut())
+            .map(|shard| shard.next_event());
+        let (event, index, _) = select_all(events).await;
+        let shard = (start + index) % self.shards.len();
+        self.next = shard + 1;
+        let event = event?;
+        if event.is_none() {
+            log::info!("The connection of shard {} was closed", shard);
+        }
+        Ok(event)
+    }
+
+    fn writer(&self) -> Arc<dyn ChatWriter + Send + Sync> {
+        Arc::new(ShardedWriter {
+            writers: self.shards.iter().map(P::writer).collect(),
+            routes: self.routes.clone(),
+        })
+    }
+}
+
+/// Sends messages through the shard which joined the channel.
+struct ShardedWriter {
+    writers: Vec<Arc<dyn ChatWriter + Send + Sync>>,
+    routes: Routes,
+}
+
+#[async_trait]
+impl ChatWriter for ShardedWriter {
+    async fn send(&self, channel: &str, text: &str, reply_to: Option<&str>) -> io::Result<()> {
+        let shard = self
+            .routes
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(normalize(channel))
+            .copied();
+        // channels which were not joined are sent through the first connection
+        let writer = &self.writers[shard.unwrap_or_default()];
+        writer.send(channel, text, reply_to).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::ChatMessage;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder {
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl ChatWriter for Recorder {
+        async fn send(&self, channel: &str, text: &str, _: Option<&str>) -> io::Result<()> {
+            self.sent.lock().unwrap().push(format!("{channel}: {text}"));
+            Ok(())
+        }
+    }
+
+    struct TestShard {
+        joined: Vec<String>,
+        events: VecDeque<ChatEvent>,
+        writer: Arc<Recorder>,
+    }
+
+    impl TestShard {
+        fn new(messages: &[&str]) -> Self {
+            let events = messages.iter().map(|text| {
+                ChatEvent::Message(ChatMessage {
+                    channel: "channel".to_owned(),
+                    channel_id: None,
+                    id: None,
+                    sender: OwnedUser::from_username("user".to_owned()),
+                    moderator: false,
+                    broadcaster: false,
+                    vip: false,
+                    subscriber: false,
+                    text: text.to_string(),
+                })
+            });
+            Self {
+                joined: Vec::new(),
+                events: events.collect(),
+                writer: Default::default(),
+            }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl ChatPlatform for TestShard {
+        async fn connect(&mut self) -> Result<OwnedUser, PlatformError> {
+            Ok(OwnedUser::from_username("bot".to_owned()))
+        }
+
+        async fn join(&mut self, channel: &str) -> Result<(), PlatformError> {
+            self.joined.push(channel.to_owned());
+            Ok(())
+        }
+
+        async fn next_event(&mut self) -> Result<Option<ChatEvent>, PlatformError> {
+            match self.events.pop_front() {
+                Some(event) => Ok(Some(event)),
+                None => std::future::pending().await,
+            }
+        }
+
+        fn writer(&self) -> Arc<dyn ChatWriter + Send + Sync> {
+            self.writer.clone()
+        }
+    }
+
+    fn text(event: Option<ChatEvent>) -> String {
+        match event {
+            Some(ChatEvent::Message(message)) => message.text,
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn test_sharded_platform() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let shards = vec![TestShard::new(&["a1", "a2"]), TestShard::new(&["b1"])];
+            let writers: Vec<_> = shards.iter().map(|shard| shard.writer.clone()).collect();
+            let mut platform = ShardedPlatform::new(shards, 2);
+            assert_eq!(platform.connect().await.unwrap().username(), "bot");
+            let writer = platform.writer();
+            for channel in ["#one", "two", "three", "one"] {
+                platform.join(channel).await.unwrap();
+            }
+            assert_eq!(platform.shards[0].joined, ["one", "three", "one"]);
+            assert_eq!(platform.shards[1].joined, ["two"]);
+            assert_eq!(platform.channels_per_shard(), [2, 1]);
+
+            // the shards take turns
+            assert_eq!(text(platform.next_event().await.unwrap()), "a1");
+            assert_eq!(text(platform.next_event().await.unwrap()), "b1");
+            assert_eq!(text(platform.next_event().await.unwrap()), "a2");
+
+            writer.send("two", "hi", None).await.unwrap();
+            writer.send("#three", "hello", None).await.unwrap();
+            assert_eq!(*writers[0].sent.lock().unwrap(), ["#three: hello"]);
+            assert_eq!(*writers[1].sent.lock().unwrap(), ["two: hi"]);
+        });
+    }
+}
@@ -0,0 +1,328 @@
+use super::{ChatEvent, ChatMessage, ChatPlatform, ChatWriter, PlatformError};
+use crate::request::{Bot, Channel, Command, Sender};
+use crate::user::{OwnedUser, User, UserId};
+use async_trait::async_trait;
+use futures_io::{AsyncRead, AsyncWrite};
+use std::borrow::Cow;
+use std::convert::{TryFrom, TryInto};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_compat_02::FutureExt;
+use twitchchat::commands::privmsg;
+use twitchchat::connector::Connector;
+use twitchchat::messages::{ClearChat, ClearMsg, Commands, Join, Part, Privmsg};
+use twitchchat::runner::Identity;
+use twitchchat::writer::{AsyncWriter, MpscWriter};
+use twitchchat::{AsyncRunner, Encodable, Status, UserConfig};
+
+#[derive(Debug)]
+pub enum IdentityError {
+    Anonymous,
+}
+
+impl fmt::Display for IdentityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Anonymous user: No username found.")
+    }
+}
+
+impl Error for IdentityError {}
+
+impl<'a> TryFrom<&'a Identity> for Bot<'a> {
+    type Error = IdentityError;
+    fn try_from(value: &'a Identity) -> Result<Self, Self::Error> {
+        match value {
+            Identity::Anonymous { .. } => Err(IdentityError::Anonymous),
+            Identity::Basic { name, .. } => Ok(User::from_username(name).into()),
+            Identity::Full {
+                name,
+                user_id,
+                display_name,
+                ..
+            } => Ok(User::new(name, display_name.as_deref(), Some(UserId::from(*user_id))).into()),
+        }
+    }
+}
+
+impl<'a> From<&'a UserConfig> for Bot<'a> {
+    fn from(value: &'a UserConfig) -> Self {
+        User::from_username(&value.name).into()
+    }
+}
+
+#[derive(Debug)]
+pub enum PrivmsgCommandError {
+    DoesNotStartWithBang,
+}
+
+impl fmt::Display for PrivmsgCommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Command does not start with `!`.")
+    }
+}
+
+impl Error for PrivmsgCommandError {}
+
+impl<'a> TryFrom<&'a Privmsg<'_>> for Command<'a> {
+    type Error = PrivmsgCommandError;
+    fn try_from(message: &'a Privmsg) -> Result<Self, Self::Error> {
+        let data = message.data().trim_start();
+        if data.starts_with('!') {
+            Ok(data.into())
+        } else {
+            Err(PrivmsgCommandError::DoesNotStartWithBang)
+        }
+    }
+}
+
+impl<'a> From<&'a Privmsg<'_>> for Sender<'a> {
+    fn from(value: &'a Privmsg) -> Self {
+        let user_id = value
+            .user_id()
+            .and_then(|value| i64::try_from(value).ok())
+            .map(UserId::from);
+        Sender::new(
+            User::new(value.name(), value.display_name(), user_id),
+            value.is_moderator(),
+            value.is_broadcaster(),
+        )
+        .with_vip(value.is_vip())
+        .with_subscriber(value.is_subscriber())
+    }
+}
+
+impl<'a> From<&'a Privmsg<'_>> for Channel<'a> {
+    fn from(value: &'a Privmsg) -> Self {
+        let user_id = value
+            .room_id()
+            .and_then(|value| i64::try_from(value).ok())
+            .map(UserId::from);
+        User::new(value.channel().trim_start_matches('#'), None, user_id).into()
+    }
+}
+
+impl<'a> From<&'a ClearChat<'_>> for Channel<'a> {
+    fn from(value: &'a ClearChat) -> Self {
+        let user_id = value.room_id().and_then(|value| value.parse().ok()); // TODO: user_id is u64 instead of i64
+        User::new(value.channel().trim_start_matches('#'), None, user_id).into()
+    }
+}
+
+impl<'a> From<&'a ClearMsg<'_>> for Channel<'a> {
+    fn from(value: &'a ClearMsg) -> Self {
+        let user_id = value.tags().get_parsed("room-id"); // TODO: user_id is u64 instead of i64
+        User::new(value.channel().trim_start_matches('#'), None, user_id).into()
+    }
+}
+
+impl<'a> From<&'a Join<'_>> for Channel<'a> {
+    fn from(value: &'a Join) -> Self {
+        User::from_username(value.channel().trim_start_matches('#')).into()
+    }
+}
+
+impl<'a> From<&'a Part<'_>> for Channel<'a> {
+    fn from(value: &'a Part) -> Self {
+        User::from_username(value.channel().trim_start_matches('#')).into()
+    }
+}
+
+macro_rules! write_nl {
+    ($w:expr, $fmt:expr, $($args:expr),* $(,)?) => {{
+        write!($w, $fmt, $($args),*)?;
+        write!($w, "\r\n")
+    }};
+}
+
+/// A message to `channel`, which replies to the message with the id `reply_to`.
+struct ChannelReply<'a> {
+    channel: &'a str,
+    reply_to: Option<&'a str>,
+    msg: &'a str,
+}
+
+impl<'a> Encodable for ChannelReply<'a> {
+    fn encode<W>(&self, buf: &mut W) -> std::io::Result<()>
+    where
+        W: Write + ?Sized,
+    {
+        log::trace!("reply message");
+        if !self.msg.trim_start().starts_with(|c| c == '.' || c == '/') {
+            // do not reply when using a twitch command
+            if let Some(id) = self.reply_to {
+                return write_nl!(
+                    buf,
+                    "@reply-parent-msg-id={} PRIVMSG {} :{}",
+                    id,
+                    twitchchat::commands::Channel::new(self.channel),
+                    self.msg
+                );
+            }
+        }
+        write_nl!(
+            buf,
+            "PRIVMSG {} :{}",
+            twitchchat::commands::Channel::new(self.channel),
+            self.msg
+        )
+    }
+}
+
+impl From<&Privmsg<'_>> for ChatMessage {
+    fn from(message: &Privmsg<'_>) -> Self {
+        let channel = Channel::from(message);
+        let sender = Sender::from(message);
+        Self {
+            channel: channel.username().to_owned(),
+            channel_id: channel.channel_id(),
+            id: message.tags().get("id").map(str::to_owned),
+            sender: OwnedUser::from_user(&sender),
+            moderator: sender.is_moderator(),
+            broadcaster: sender.is_broadcaster(),
+            vip: sender.is_vip(),
+            subscriber: sender.is_subscriber(),
+            text: message.data().to_owned(),
+        }
+    }
+}
+
+impl From<Commands<'_>> for ChatEvent {
+    fn from(commands: Commands<'_>) -> Self {
+        match commands {
+            Commands::Privmsg(message) => ChatEvent::Message((&message).into()),
+            Commands::ClearChat(message) => {
+                let channel = Channel::from(&message);
+                ChatEvent::ChatCleared {
+                    channel: channel.username().to_owned(),
+                    channel_id: channel.channel_id(),
+                    user: message.name().map(str::to_owned),
+                    user_id: message.tags().get_parsed("target-user-id"),
+                    duration: message.ban_duration().map(Duration::from_secs),
+                }
+            }
+            Commands::ClearMsg(message) => {
+                let channel = Channel::from(&message);
+                ChatEvent::MessageDeleted {
+                    channel: channel.username().to_owned(),
+                    channel_id: channel.channel_id(),
+                    message_id: message.target_msg_id().map(str::to_owned),
+                    user: message.login().map(str::to_owned),
+                    message: message.message().map(str::to_owned),
+                }
+            }
+            Commands::Join(message) => ChatEvent::Join {
+                channel: Channel::from(&message).username().to_owned(),
+                user: message.name().to_owned(),
+            },
+            Commands::Part(message) => ChatEvent::Part {
+                channel: Channel::from(&message).username().to_owned(),
+                user: message.name().to_owned(),
+            },
+            _ => ChatEvent::Activity,
+        }
+    }
+}
+
+/// Writes to the connection of a [`TwitchPlatform`].
+#[derive(Clone)]
+pub struct TwitchWriter {
+    writer: Option<AsyncWriter<MpscWriter>>,
+}
+
+#[async_trait]
+impl ChatWriter for TwitchWriter {
+    async fn send(&self, channel: &str, text: &str, reply_to: Option<&str>) -> io::Result<()> {
+        let mut writer = self
+            .writer
+            .clone()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+        if reply_to.is_some() {
+            let message = ChannelReply {
+                channel,
+                reply_to,
+                msg: text,
+            };
+            writer.encode(message).compat().await
+        } else {
+            writer.encode(privmsg(channel, text)).compat().await
+        }
+    }
+}
+
+/// Connects to Twitch chat through a [`Connector`], e.g. `twitchchat::connector::tokio::ConnectorRustTls`.
+pub struct TwitchPlatform<'a, C> {
+    connector: Option<C>,
+    user_config: Cow<'a, UserConfig>,
+    runner: Option<AsyncRunner>,
+}
+
+impl<'a, C> TwitchPlatform<'a, C> {
+    pub fn new(connector: C, user_config: &'a UserConfig) -> Self {
+        Self {
+            connector: Some(connector),
+            user_config: Cow::Borrowed(user_config),
+            runner: None,
+        }
+    }
+
+    /// Creates the platform with a login which is not borrowed, e.g. from a [`Config`](crate::config::Config).
+    pub fn with_owned_config(connector: C, user_config: UserConfig) -> TwitchPlatform<'static, C> {
+        TwitchPlatform {
+            connector: Some(connector),
+            user_config: Cow::Owned(user_config),
+            runner: None,
+        }
+    }
+
+    fn runner(&mut self) -> Result<&mut AsyncRunner, PlatformError> {
+        self.runner
+            .as_mut()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected).into())
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a, C> ChatPlatform for TwitchPlatform<'a, C>
+where
+    C: Connector,
+    for<'o> &'o C::Output: AsyncRead + AsyncWrite + Send + Sync + Unpin,
+{
+    async fn connect(&mut self) -> Result<OwnedUser, PlatformError> {
+        let connector = self
+            .connector
+            .take()
+            .ok_or("the connector can only be used once")?;
+        let runner = AsyncRunner::connect(connector, &self.user_config)
+            .compat()
+            .await?;
+        let bot = (&runner.identity)
+            .try_into()
+            .unwrap_or_else(|_| Bot::from(self.user_config.as_ref()));
+        let bot = OwnedUser::from_user(&bot);
+        self.runner = Some(runner);
+        Ok(bot)
+    }
+
+    async fn join(&mut self, channel: &str) -> Result<(), PlatformError> {
+        Ok(self.runner()?.join(channel).compat().await?)
+    }
+
+    async fn next_event(&mut self) -> Result<Option<ChatEvent>, PlatformError> {
+        match self.runner()?.next_message().compat().await? {
+            Status::Message(commands) => {
+                log::trace!("Message: {:#?}", commands);
+                Ok(Some(commands.into()))
+            }
+            Status::Quit | Status::Eof => Ok(None),
+        }
+    }
+
+    fn writer(&self) -> Arc<dyn ChatWriter + Send + Sync> {
+        Arc::new(TwitchWriter {
+            writer: self.runner.as_ref().map(AsyncRunner::writer),
+        })
+    }
+}
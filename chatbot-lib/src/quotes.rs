@@ -0,0 +1,231 @@
+//! Quotes of a channel, which are stored with the persisted state.
+//!
+//! [`Quotes`] has to be registered with
+//! [`ContainerBuilder::register_persisted_type`](crate::state::ContainerBuilder::register_persisted_type)
+//! before [`QuoteCommands`] can process `!quote`.
+
+use crate::command::{CommandArguments, CommandProcessor};
+use crate::request::{CommandRequest, FromCommandRequest};
+use crate::response::Response;
+use crate::state::{PersistedChannelState, PersistedType};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use core::fmt::{Display, Formatter};
+use rand::seq::SliceRandom;
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Quote {
+    pub id: u32,
+    pub text: String,
+    /// The user who added the quote.
+    pub added_by: String,
+    pub added_at: DateTime<Utc>,
+}
+
+impl Display for Quote {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "#{}: \"{}\" (added by {} on {})",
+            self.id,
+            self.text,
+            self.added_by,
+            self.added_at.format("%Y-%m-%d")
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Quotes {
+    quotes: Vec<Quote>,
+    /// Ids are never reused, even if the newest quote was removed.
+    next_id: u32,
+}
+
+impl Quotes {
+    pub fn len(&self) -> usize {
+        self.quotes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.quotes.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Quote> {
+        self.quotes.iter()
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Quote> {
+        self.quotes.iter().find(|quote| quote.id == id)
+    }
+
+    /// Adds a quote and returns its id, which starts at `1`.
+    pub fn add<T: Into<String>, U: Into<String>>(
+        &mut self,
+        text: T,
+        added_by: U,
+        added_at: DateTime<Utc>,
+    ) -> u32 {
+        self.next_id = self.next_id.max(1);
+        let id = self.next_id;
+        self.next_id += 1;
+        self.quotes.push(Quote {
+            id,
+            text: text.into(),
+            added_by: added_by.into(),
+            added_at,
+        });
+        id
+    }
+
+    pub fn remove(&mut self, id: u32) -> Option<Quote> {
+        let index = self.quotes.iter().position(|quote| quote.id == id)?;
+        Some(self.quotes.remove(index))
+    }
+
+    pub fn random(&self) -> Option<&Quote> {
+        self.quotes.choose(&mut rand::thread_rng())
+    }
+
+    /// A random quote which contains `text`, ignoring the case.
+    pub fn search(&self, text: &str) -> Option<&Quote> {
+        let text = text.to_lowercase();
+        let matches: Vec<_> = self
+            .quotes
+            .iter()
+            .filter(|quote| quote.text.to_lowercase().contains(&text))
+            .collect();
+        matches.choose(&mut rand::thread_rng()).copied()
+    }
+}
+
+impl PersistedType for Quotes {
+    const FILENAME: &'static str = "quotes";
+
+    fn init(_channel: &str) -> Self {
+        Self::default()
+    }
+}
+
+pub type QuotesState<'req> = PersistedChannelState<'req, Quotes>;
+
+/// Processes `!quote`, `!quote <id>`, `!quote random` and `!quote search <text>` for everyone
+/// and `!quote add <text>` and `!quote remove <id>` for moderators.
+pub struct QuoteCommands;
+
+impl QuoteCommands {
+    async fn process_quote(
+        request: &CommandRequest<'_>,
+        arguments: &mut CommandArguments<'_>,
+    ) -> Result<String, Cow<'static, str>> {
+        let quotes = QuotesState::from_command_request(request)
+            .map_err(|_| "the quotes are not registered for this channel")?;
+        let sender = request.sender();
+        let is_moderator = sender.is_moderator() || sender.is_broadcaster();
+        let not_found = |id| Cow::Owned(format!("there is no quote #{}", id));
+        match arguments.next() {
+            None | Some("random") if arguments.as_str().is_empty() => quotes
+                .read()
+                .await
+                .random()
+                .map(Quote::to_string)
+                .ok_or("there are no quotes yet".into()),
+            Some("search") => {
+                let text = arguments.next_rest().ok_or("!quote search <text>")?;
+                quotes
+                    .read()
+                    .await
+                    .search(text)
+                    .map(Quote::to_string)
+                    .ok_or_else(|| format!("there is no quote containing {}", text).into())
+            }
+            Some("add") if is_moderator => {
+                let text = arguments.next_rest().ok_or("!quote add <text>")?;
+                let added_by = sender.display_name().unwrap_or(sender.username());
+                let mut id = 0;
+                quotes
+                    .update(|quotes| {
+                        let mut quotes = quotes.clone();
+                        id = quotes.add(text, added_by, Utc::now());
+                        quotes
+                    })
+                    .await;
+                Ok(format!("added quote #{}", id))
+            }
+            Some("remove") if is_moderator => {
+                let id = arguments
+                    .next()
+                    .and_then(|id| id.trim_start_matches('#').parse().ok())
+                    .filter(|_| arguments.as_str().is_empty())
+                    .ok_or("!quote remove <id>")?;
+                let (_, new) = quotes
+                    .maybe_update(|quotes| {
+                        let mut quotes = quotes.clone();
+                        quotes.remove(id).map(|_| quotes)
+                    })
+                    .await;
+                match new {
+                    Some(_) => Ok(format!("removed quote #{}", id)),
+                    None => Err(not_found(id)),
+                }
+            }
+            Some("add" | "remove") => Err("only moderators can add or remove quotes".into()),
+            Some(id) if arguments.as_str().is_empty() => {
+                let id = id
+                    .trim_start_matches('#')
+                    .parse()
+                    .map_err(|_| "!quote, !quote <id> or !quote search <text>")?;
+                quotes
+                    .read()
+                    .await
+                    .get(id)
+                    .map(Quote::to_string)
+                    .ok_or_else(|| not_found(id))
+            }
+            _ => Err("!quote, !quote <id> or !quote search <text>".into()),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for QuoteCommands {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        if arguments.next() != Some("!quote") {
+            return None;
+        }
+        let text = match Self::process_quote(request, &mut arguments).await {
+            Ok(text) => text,
+            Err(error) => error.into_owned(),
+        };
+        Some(Response::new(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_quotes() {
+        let added_at = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+        let mut quotes = Quotes::default();
+        assert_eq!(quotes.add("nya", "LiquidNya", added_at), 1);
+        assert_eq!(quotes.add("Hello World", "user", added_at), 2);
+        assert_eq!(
+            quotes.get(2).unwrap().to_string(),
+            "#2: \"Hello World\" (added by user on 2023-04-01)"
+        );
+        assert_eq!(quotes.search("WORLD").map(|quote| quote.id), Some(2));
+        assert!(quotes.search("bye").is_none());
+
+        assert!(quotes.remove(2).is_some());
+        assert!(quotes.remove(2).is_none());
+        // removed ids are not reused
+        assert_eq!(quotes.add("again", "user", added_at), 3);
+        assert_eq!(quotes.len(), 2);
+    }
+}
@@ -0,0 +1,289 @@
+//! Reminders for other users, which are stored with the persisted state and survive restarts.
+//!
+//! `!remind <user> in <duration> <text..>` delivers the reminder when it is due and
+//! `!remind <user> <text..>` delivers it when the user chats the next time.
+//! [`Reminders`] has to be registered with
+//! [`ContainerBuilder::register_persisted_type`](crate::state::ContainerBuilder::register_persisted_type)
+//! and the delivery has to be enabled with [`ChatBot::with_reminders`](crate::ChatBot::with_reminders).
+
+use crate::command::{CommandArguments, CommandProcessor};
+use crate::request::{CommandRequest, FromCommandRequest};
+use crate::response::{DeferredResponse, Response};
+use crate::state::{
+    ChannelChatters, ChannelContainer, Persisted, PersistedChannelState, PersistedType,
+};
+use crate::status::BotStatus;
+use crate::user::{ChannelId, UserArgument};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use core::convert::Infallible;
+use core::fmt::{Display, Formatter};
+use std::borrow::Cow;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Reminders can not be due later than this.
+const MAX_DELAY: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Reminder {
+    /// The user who is reminded.
+    pub target: String,
+    /// The user who created the reminder.
+    pub from: String,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+    /// `None` if the reminder is delivered when the target chats the next time.
+    pub due: Option<DateTime<Utc>>,
+    /// Used to find out if the target chatted.
+    pub channel_id: Option<ChannelId>,
+}
+
+impl Display for Reminder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} reminder from {:#}: {}",
+            UserArgument::new(&self.target),
+            UserArgument::new(&self.from),
+            self.text
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Reminders {
+    reminders: Vec<Reminder>,
+}
+
+impl Reminders {
+    pub fn len(&self) -> usize {
+        self.reminders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reminders.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Reminder> {
+        self.reminders.iter()
+    }
+
+    pub fn add(&mut self, reminder: Reminder) {
+        self.reminders.push(reminder);
+    }
+
+    /// Removes and returns the reminders which are due at `now` or whose target chatted since
+    /// the reminder was created, according to `last_chatted`.
+    pub fn take_due<F>(&mut self, now: DateTime<Utc>, mut last_chatted: F) -> Vec<Reminder>
+    where
+        F: FnMut(&Reminder) -> Option<DateTime<Utc>>,
+    {
+        let (due, pending) = self
+            .reminders
+            .drain(..)
+            .partition(|reminder| match reminder.due {
+                Some(due) => due <= now,
+                None => last_chatted(reminder).is_some_and(|time| time > reminder.created_at),
+            });
+        self.reminders = pending;
+        due
+    }
+}
+
+impl PersistedType for Reminders {
+    const FILENAME: &'static str = "reminders";
+
+    fn init(_channel: &str) -> Self {
+        Self::default()
+    }
+}
+
+pub type RemindersState<'req> = PersistedChannelState<'req, Reminders>;
+
+/// Processes `!remind <user> [in <duration>] <text..>`.
+pub struct ReminderCommands;
+
+impl ReminderCommands {
+    async fn process_remind(
+        request: &CommandRequest<'_>,
+        arguments: &mut CommandArguments<'_>,
+    ) -> Result<String, Cow<'static, str>> {
+        let syntax = "!remind <user> [in <duration>] <text>";
+        let reminders = RemindersState::from_command_request(request)
+            .map_err(|_| "the reminders are not registered for this channel")?;
+        let target = arguments.next().map(UserArgument::new).ok_or(syntax)?;
+        let now = Utc::now();
+        let mut due = None;
+        // `in` is part of the text unless a duration follows
+        let mut delay_arguments = arguments.clone();
+        if delay_arguments.next() == Some("in") {
+            if let Some(delay) = delay_arguments
+                .next()
+                .and_then(|delay| humantime::parse_duration(delay).ok())
+            {
+                if delay > MAX_DELAY {
+                    return Err("reminders can be at most 30 days in the future".into());
+                }
+                due = Some(now + chrono::Duration::from_std(delay).map_err(|_| syntax)?);
+                *arguments = delay_arguments;
+            }
+        }
+        let text = arguments.next_rest().ok_or(syntax)?;
+        let sender = request.sender();
+        let reminder = Reminder {
+            target: target.as_argument().to_owned(),
+            from: sender
+                .display_name()
+                .unwrap_or(sender.username())
+                .to_owned(),
+            text: text.to_owned(),
+            created_at: now,
+            due,
+            channel_id: request.channel().channel_id(),
+        };
+        reminders
+            .update(|reminders| {
+                let mut reminders = reminders.clone();
+                reminders.add(reminder.clone());
+                reminders
+            })
+            .await;
+        Ok(match due {
+            Some(due) => format!(
+                "I will remind {} at {}",
+                target,
+                due.format("%Y-%m-%d %H:%M UTC")
+            ),
+            None => format!("I will remind {} when they chat", target),
+        })
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for ReminderCommands {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        if arguments.next() != Some("!remind") {
+            return None;
+        }
+        let text = match Self::process_remind(request, &mut arguments).await {
+            Ok(text) => text,
+            Err(error) => error.into_owned(),
+        };
+        Some(Response::new(format!(
+            "{} {}",
+            UserArgument::from(request.sender() as &crate::user::User),
+            text
+        )))
+    }
+}
+
+async fn deliver_channel(
+    channel: &str,
+    persisted: &Persisted<Reminders>,
+    chatters: &ChannelChatters,
+    deferred: &UnboundedSender<DeferredResponse>,
+) {
+    let reminders = persisted.for_channel(channel);
+    let current = reminders.read().await;
+    if current.is_empty() {
+        return;
+    }
+    // the stats are looked up before taking the lock of the persisted state
+    let mut last_chatted = Vec::new();
+    for reminder in current.iter().filter(|reminder| reminder.due.is_none()) {
+        if let Some(channel_id) = reminder.channel_id {
+            let stats = chatters
+                .stats(channel_id, UserArgument::new(&reminder.target))
+                .await;
+            if let Some(stats) = stats {
+                last_chatted.push((reminder.clone(), DateTime::from(stats.last_chatted())));
+            }
+        }
+    }
+    let now = Utc::now();
+    let mut due = Vec::new();
+    reminders
+        .maybe_update(|reminders| {
+            let mut reminders = reminders.clone();
+            due = reminders.take_due(now, |reminder| {
+                last_chatted
+                    .iter()
+                    .find(|(other, _)| other == reminder)
+                    .map(|(_, time)| *time)
+            });
+            (!due.is_empty()).then_some(reminders)
+        })
+        .await;
+    for reminder in due {
+        let _ = deferred.send(DeferredResponse {
+            channel: channel.to_owned(),
+            reply_to: None,
+            response: Response::new(reminder.to_string()),
+        });
+    }
+}
+
+/// Delivers the reminders of every joined channel, which are checked every `interval`.
+pub(crate) async fn deliver_reminders(
+    interval: Duration,
+    channel_container: &ChannelContainer,
+    chatters: ChannelChatters,
+    status: BotStatus,
+    deferred: UnboundedSender<DeferredResponse>,
+) -> Infallible {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        for channel in status.channels() {
+            let container = channel_container.get_arc(channel.as_str()).await;
+            if let Some(persisted) = container.try_get::<Persisted<Reminders>>() {
+                deliver_channel(&channel, persisted, &chatters, &deferred).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn reminder(due: Option<DateTime<Utc>>) -> Reminder {
+        Reminder {
+            target: "liquidnya".to_owned(),
+            from: "user".to_owned(),
+            text: "drink water".to_owned(),
+            created_at: Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap(),
+            due,
+            channel_id: Some(ChannelId::new(1)),
+        }
+    }
+
+    #[test]
+    fn test_take_due() {
+        let now = Utc.with_ymd_and_hms(2023, 4, 1, 13, 0, 0).unwrap();
+        let mut reminders = Reminders::default();
+        reminders.add(reminder(Some(now)));
+        reminders.add(reminder(Some(now + chrono::Duration::minutes(5))));
+        reminders.add(reminder(None));
+
+        let due = reminders.take_due(now, |_| None);
+        assert_eq!(due.len(), 1);
+        assert_eq!(
+            due[0].to_string(),
+            "@liquidnya reminder from user: drink water"
+        );
+        assert_eq!(reminders.len(), 2);
+
+        // chatting before the reminder was created does not count
+        let before = Utc.with_ymd_and_hms(2023, 4, 1, 11, 0, 0).unwrap();
+        assert!(reminders.take_due(now, |_| Some(before)).is_empty());
+        let due = reminders.take_due(now, |_| Some(now));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].due, None);
+        assert_eq!(reminders.len(), 1);
+    }
+}
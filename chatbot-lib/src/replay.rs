@@ -0,0 +1,365 @@
+//! Runs the bot against a recorded chat log instead of a live chat, e.g. to check how changed
+//! filters or commands would have handled real messages.
+//!
+//! A log of raw IRC lines from Twitch or an [audit log](crate::audit) is turned into a
+//! [`ReplayPlatform`], which is run like any other platform. The bot stops after the last
+//! event and the responses are read from the [`ReplayHandle`].
+//!
+//! ```ignore
+//! let platform = ReplayPlatform::from_irc_log(&std::fs::read_to_string("chat.log")?, "bot");
+//! let handle = platform.handle();
+//! ChatBot::with_platform(platform)
+//!     .with_command_processor(QuoteCommands)
+//!     .filter(LinkFilter::new().into_predicate())
+//!     .run(["channel"])
+//!     .await?;
+//! for sent in handle.sent() {
+//!     println!("{}: {}", sent.channel, sent.text);
+//! }
+//! ```
+
+use crate::audit::AuditEntry;
+use crate::platform::{ChatEvent, ChatMessage, ChatPlatform, ChatWriter, PlatformError};
+use crate::user::{OwnedUser, UserId};
+use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A message the bot would have sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentLine {
+    pub channel: String,
+    pub text: String,
+    pub reply_to: Option<String>,
+}
+
+/// Captures the messages sent by the bot during a replay.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayHandle {
+    sent: Arc<Mutex<Vec<SentLine>>>,
+}
+
+impl ReplayHandle {
+    /// The messages sent so far, in the order they were sent.
+    pub fn sent(&self) -> Vec<SentLine> {
+        self.sent.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+#[async_trait]
+impl ChatWriter for ReplayHandle {
+    async fn send(&self, channel: &str, text: &str, reply_to: Option<&str>) -> io::Result<()> {
+        self.sent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(SentLine {
+                channel: channel.to_owned(),
+                text: text.to_owned(),
+                reply_to: reply_to.map(str::to_owned),
+            });
+        Ok(())
+    }
+}
+
+/// A [`ChatPlatform`] which produces recorded events and captures the responses.
+#[derive(Debug)]
+pub struct ReplayPlatform {
+    bot: OwnedUser,
+    events: VecDeque<ChatEvent>,
+    handle: ReplayHandle,
+}
+
+impl ReplayPlatform {
+    pub fn new<I: IntoIterator<Item = ChatEvent>>(events: I, bot: &str) -> Self {
+        Self {
+            bot: OwnedUser::from_username(bot.to_owned()),
+            events: events.into_iter().collect(),
+            handle: ReplayHandle::default(),
+        }
+    }
+
+    /// Replays the messages, deletions, timeouts, joins and parts of a log with one raw IRC line
+    /// per line, other lines are skipped.
+    pub fn from_irc_log(log: &str, bot: &str) -> Self {
+        Self::new(log.lines().filter_map(parse_irc_line), bot)
+    }
+
+    /// Replays the commands of an audit log, which was written as JSON lines. Invalid lines are
+    /// skipped.
+    pub fn from_audit_log(log: &str, bot: &str) -> Self {
+        let events =
+            log.lines()
+                .filter_map(|line| match serde_json::from_str::<AuditEntry>(line) {
+                    Ok(entry) => Some(ChatEvent::Message(audit_message(entry))),
+                    Err(e) => {
+                        log::warn!("Skipping invalid audit log entry: {:?}", e);
+                        None
+                    }
+                });
+        Self::new(events, bot)
+    }
+
+    pub fn handle(&self) -> ReplayHandle {
+        self.handle.clone()
+    }
+}
+
+#[async_trait(?Send)]
+impl ChatPlatform for ReplayPlatform {
+    async fn connect(&mut self) -> Result<OwnedUser, PlatformError> {
+        Ok(self.bot.clone())
+    }
+
+    async fn join(&mut self, _channel: &str) -> Result<(), PlatformError> {
+        Ok(())
+    }
+
+    async fn next_event(&mut self) -> Result<Option<ChatEvent>, PlatformError> {
+        Ok(self.events.pop_front())
+    }
+
+    fn writer(&self) -> Arc<dyn ChatWriter + Send + Sync> {
+        Arc::new(self.handle.clone())
+    }
+}
+
+/// The audit log does not know the roles of the sender, so the command is replayed as a message
+/// of a user without roles.
+fn audit_message(entry: AuditEntry) -> ChatMessage {
+    let text = match entry.arguments.is_empty() {
+        true => entry.command,
+        false => format!("{} {}", entry.command, entry.arguments),
+    };
+    ChatMessage {
+        channel: entry.channel,
+        channel_id: None,
+        id: None,
+        sender: OwnedUser::from_username(entry.sender),
+        moderator: false,
+        broadcaster: false,
+        vip: false,
+        subscriber: false,
+        text,
+    }
+}
+
+/// The parts of a raw IRC line, e.g.
+/// `@id=1;mod=0 :nick!nick@nick.tmi.twitch.tv PRIVMSG #channel :hello`.
+struct IrcLine<'a> {
+    tags: HashMap<&'a str, String>,
+    nick: Option<&'a str>,
+    command: &'a str,
+    channel: Option<&'a str>,
+    trailing: Option<&'a str>,
+}
+
+impl<'a> IrcLine<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let mut rest = line.trim_end_matches(['\r', '\n']);
+        let mut tags = HashMap::new();
+        if let Some(tagged) = rest.strip_prefix('@') {
+            let (raw_tags, remaining) = tagged.split_once(' ')?;
+            for tag in raw_tags.split(';') {
+                let (key, value) = tag.split_once('=').unwrap_or((tag, ""));
+                tags.insert(key, unescape_tag(value));
+            }
+            rest = remaining;
+        }
+        let mut nick = None;
+        if let Some(prefixed) = rest.strip_prefix(':') {
+            let (prefix, remaining) = prefixed.split_once(' ')?;
+            nick = prefix.split_once('!').map(|(nick, _)| nick);
+            rest = remaining;
+        }
+        let (params, trailing) = match rest.split_once(" :") {
+            Some((params, trailing)) => (params, Some(trailing)),
+            None => (rest, None),
+        };
+        let mut params = params.split_whitespace();
+        let command = params.next()?;
+        let channel = params.next().and_then(|channel| channel.strip_prefix('#'));
+        Some(Self {
+            tags,
+            nick,
+            command,
+            channel,
+            trailing,
+        })
+    }
+
+    fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .get(key)
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        self.tag(key) == Some("1")
+    }
+
+    fn has_badge(&self, badge: &str) -> bool {
+        self.tag("badges").is_some_and(|badges| {
+            badges
+                .split(',')
+                .any(|b| b.split_once('/').map_or(b, |(name, _)| name) == badge)
+        })
+    }
+}
+
+// see https://ircv3.net/specs/extensions/message-tags#escaping-values
+fn unescape_tag(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => unescaped.push(';'),
+            Some('s') => unescaped.push(' '),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// Turns a raw IRC line from Twitch into the event the Twitch platform would produce, `None` for
+/// lines which are not handled by the chat bot.
+pub fn parse_irc_line(line: &str) -> Option<ChatEvent> {
+    let line = IrcLine::parse(line)?;
+    let channel = line.channel?.to_owned();
+    let channel_id = line.tag("room-id").and_then(|id| id.parse().ok());
+    match line.command {
+        "PRIVMSG" => {
+            let username = line.nick?.to_owned();
+            let user_id = line.tag("user-id").and_then(|id| id.parse::<UserId>().ok());
+            let display_name = line.tag("display-name").map(str::to_owned);
+            Some(ChatEvent::Message(ChatMessage {
+                channel,
+                channel_id,
+                id: line.tag("id").map(str::to_owned),
+                sender: OwnedUser::new(username, display_name, user_id),
+                moderator: line.flag("mod"),
+                broadcaster: line.has_badge("broadcaster"),
+                vip: line.has_badge("vip") || line.flag("vip"),
+                subscriber: line.flag("subscriber"),
+                text: line.trailing?.to_owned(),
+            }))
+        }
+        "CLEARMSG" => Some(ChatEvent::MessageDeleted {
+            channel,
+            channel_id,
+            message_id: line.tag("target-msg-id").map(str::to_owned),
+            user: line.tag("login").map(str::to_owned),
+            message: line.trailing.map(str::to_owned),
+        }),
+        "CLEARCHAT" => Some(ChatEvent::ChatCleared {
+            channel,
+            channel_id,
+            user: line.trailing.map(str::to_owned),
+            user_id: line.tag("target-user-id").and_then(|id| id.parse().ok()),
+            duration: line
+                .tag("ban-duration")
+                .and_then(|duration| duration.parse().ok())
+                .map(Duration::from_secs),
+        }),
+        "JOIN" => Some(ChatEvent::Join {
+            channel,
+            user: line.nick?.to_owned(),
+        }),
+        "PART" => Some(ChatEvent::Part {
+            channel,
+            user: line.nick?.to_owned(),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::CommandProcessor;
+    use crate::request::CommandRequest;
+    use crate::response::Response;
+    use crate::ChatBot;
+
+    struct Ping;
+
+    #[async_trait]
+    impl CommandProcessor for Ping {
+        async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+            (request.command() as &str == "!ping").then(|| Response::new("pong"))
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let log = "@id=1 :user!user@user.tmi.twitch.tv PRIVMSG #channel :!ping\n\
+            @id=2 :user!user@user.tmi.twitch.tv PRIVMSG #channel :hello\n\
+            @id=1 :user!user@user.tmi.twitch.tv PRIVMSG #channel :!ping\n\
+            @id=3 :user!user@user.tmi.twitch.tv PRIVMSG #channel :!ping";
+        let platform = ReplayPlatform::from_irc_log(log, "bot");
+        let handle = platform.handle();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime
+            .block_on(
+                ChatBot::with_platform(platform)
+                    .with_command_processor(Ping)
+                    .run(["channel"]),
+            )
+            .unwrap();
+        // the message delivered again is skipped
+        let sent = handle.sent();
+        assert_eq!(sent.len(), 2);
+        assert!(sent.iter().all(|sent| sent.text == "pong"));
+    }
+
+    #[test]
+    fn test_parse_irc_line() {
+        let line =
+            "@badges=vip/1,subscriber/12;display-name=Liquid\\sBlock;id=abc;mod=0;room-id=1;\
+            subscriber=1;user-id=2 :liquidblock!liquidblock@liquidblock.tmi.twitch.tv \
+            PRIVMSG #channel :!quote 3";
+        let message = match parse_irc_line(line) {
+            Some(ChatEvent::Message(message)) => message,
+            event => panic!("unexpected event {:?}", event),
+        };
+        assert_eq!(message.channel, "channel");
+        assert_eq!(message.id.as_deref(), Some("abc"));
+        assert_eq!(message.sender.username(), "liquidblock");
+        assert_eq!(message.sender.display_name(), Some("Liquid Block"));
+        assert_eq!(message.sender.user_id(), Some(UserId::new(2)));
+        assert!(message.vip && message.subscriber && !message.moderator);
+        assert_eq!(message.text, "!quote 3");
+
+        let line = "@ban-duration=600;room-id=1;target-user-id=2 :tmi.twitch.tv CLEARCHAT #channel :spammer";
+        assert!(matches!(
+            parse_irc_line(line),
+            Some(ChatEvent::ChatCleared { user: Some(user), duration: Some(duration), .. })
+                if user == "spammer" && duration == Duration::from_secs(600)
+        ));
+        assert!(parse_irc_line(":tmi.twitch.tv PONG tmi.twitch.tv :token").is_none());
+        assert!(parse_irc_line("not irc").is_none());
+    }
+
+    #[test]
+    fn test_audit_message() {
+        let line = r#"{"time":"2024-01-01T12:00:00Z","channel":"channel","sender":"user","command":"!quote","arguments":"3","response":"nya"}"#;
+        let mut platform = ReplayPlatform::from_audit_log(&format!("{line}\ninvalid\n"), "bot");
+        assert_eq!(platform.events.len(), 1);
+        match platform.events.pop_front() {
+            Some(ChatEvent::Message(message)) => assert_eq!(message.text, "!quote 3"),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use super::{Bot, Channel, Sender};
+use crate::response::SharedResponder;
+use crate::user::{OwnedUser, User};
 use derive_more::{Deref, From};
 
 #[derive(Debug, Clone)]
@@ -8,6 +10,16 @@ pub struct CommandRequest<'req> {
     channel: Channel<'req>,
     bot: &'req Bot<'req>,
     pub(crate) context: Option<&'req crate::chat_bot::ChatBotContext<'req>>,
+    pub(crate) responder: Option<ResponderRef<'req>>,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct ResponderRef<'req>(pub(crate) &'req dyn SharedResponder);
+
+impl std::fmt::Debug for ResponderRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ResponderRef")
+    }
 }
 
 impl<'req> CommandRequest<'req> {
@@ -17,6 +29,7 @@ impl<'req> CommandRequest<'req> {
         channel: Ch,
         bot: &'req Bot<'req>,
         context: &'req crate::chat_bot::ChatBotContext<'req>,
+        responder: &'req dyn SharedResponder,
     ) -> Self {
         CommandRequest {
             command: command.into(),
@@ -24,6 +37,7 @@ impl<'req> CommandRequest<'req> {
             channel: channel.into(),
             bot,
             context: Some(context),
+            responder: Some(ResponderRef(responder)),
         }
     }
 
@@ -39,6 +53,7 @@ impl<'req> CommandRequest<'req> {
             channel: channel.into(),
             bot,
             context: None,
+            responder: None,
         }
     }
 }
@@ -59,4 +74,100 @@ impl<'req> CommandRequest<'req> {
     pub fn bot(&self) -> &Bot<'req> {
         self.bot
     }
+
+    /// Copies the request, such that it can be moved into a spawned task or a queue.
+    ///
+    /// The owned request can not respond or access state, use
+    /// [`RespondLater`](crate::response::RespondLater) to respond to it.
+    pub fn to_owned(&self) -> OwnedCommandRequest {
+        OwnedCommandRequest {
+            command: self.command.0.to_owned(),
+            sender: OwnedUser::from_user(&self.sender),
+            moderator: self.sender.is_moderator(),
+            broadcaster: self.sender.is_broadcaster(),
+            vip: self.sender.is_vip(),
+            subscriber: self.sender.is_subscriber(),
+            channel: OwnedUser::from_user(&self.channel),
+            bot: OwnedUser::from_user(self.bot),
+        }
+    }
+
+    /// The same request with another command, e.g. after the prefix was replaced.
+    pub fn with_command<'b>(&self, command: &'b str) -> CommandRequest<'b>
+    where
+        'req: 'b,
+    {
+        CommandRequest {
+            command: Command(command),
+            ..self.clone()
+        }
+    }
+}
+
+/// A [`CommandRequest`] which owns its data, created with [`CommandRequest::to_owned`].
+#[derive(Debug, Clone)]
+pub struct OwnedCommandRequest {
+    command: String,
+    sender: OwnedUser,
+    moderator: bool,
+    broadcaster: bool,
+    vip: bool,
+    subscriber: bool,
+    channel: OwnedUser,
+    bot: OwnedUser,
+}
+
+impl OwnedCommandRequest {
+    pub fn command(&self) -> Command<'_> {
+        Command(&self.command)
+    }
+    pub fn sender(&self) -> Sender<'_> {
+        Sender::new(
+            User::from_owned(&self.sender),
+            self.moderator,
+            self.broadcaster,
+        )
+        .with_vip(self.vip)
+        .with_subscriber(self.subscriber)
+    }
+    pub fn channel(&self) -> Channel<'_> {
+        Channel(User::from_owned(&self.channel))
+    }
+    pub fn bot(&self) -> Bot<'_> {
+        Bot::from(User::from_owned(&self.bot))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::UserId;
+
+    #[test]
+    fn test_to_owned() {
+        let bot = Bot::from(User::from_username("chatbot"));
+        let owned = {
+            let command = String::from("!queue add");
+            let sender = String::from("liquidnya");
+            let sender = Sender::new(User::new(&sender, None, Some(UserId::new(1))), true, false)
+                .with_subscriber(true);
+            let request = CommandRequest::from_parts(
+                command.as_str(),
+                sender,
+                User::from_username("streamer"),
+                &bot,
+            );
+            request.to_owned()
+        };
+        let handle = std::thread::spawn(move || owned);
+        let owned = handle.join().unwrap();
+        assert_eq!(*owned.command(), "!queue add");
+        assert_eq!(owned.sender().username(), "liquidnya");
+        assert_eq!(owned.sender().user_id(), Some(UserId::new(1)));
+        assert!(owned.sender().is_moderator());
+        assert!(!owned.sender().is_broadcaster());
+        assert!(owned.sender().is_subscriber());
+        assert_eq!(owned.channel().username(), "streamer");
+        assert_eq!(owned.bot().username(), "chatbot");
+    }
 }
@@ -1,20 +1,138 @@
 use super::{Bot, Channel, Sender};
-use crate::{
-    chat_bot::StateError,
-    response::Responder,
-    state::{ChannelChatters, ChannelState, ChannelStateError},
-    State,
-};
+use crate::response::Responder;
 use std::future::Future;
 use std::pin::Pin;
 
+/// Decides whether a message is deleted, see [`FilterDecision`].
 pub type FilterPredicate = Box<
     dyn for<'req> FnMut(
         FilterRequest<'req>,
         &'req mut dyn Responder,
-    ) -> Pin<Box<dyn Future<Output = bool> + 'req>>,
+    ) -> Pin<Box<dyn Future<Output = FilterDecision> + 'req>>,
 >;
 
+/// A filter which can fail, e.g. if a moderation API is not available. What happens to the
+/// message then is decided by the
+/// [`FilterErrorPolicy`](crate::request::FilterErrorPolicy) of the chat bot.
+pub type TryFilterPredicate = Box<
+    dyn for<'req> FnMut(
+        FilterRequest<'req>,
+        &'req mut dyn Responder,
+    )
+        -> Pin<Box<dyn Future<Output = anyhow::Result<FilterDecision>> + 'req>>,
+>;
+
+/// What happens to a filtered message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterAction {
+    Allow,
+    Delete,
+}
+
+/// How the sender of a deleted message is told why it was deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterNotice {
+    /// Mentions the sender in the channel.
+    Reply(String),
+    /// Whispers to the sender.
+    Whisper(String),
+}
+
+/// The result of a filter. Deleted messages are logged with their reason and tag, and recorded in
+/// the [`ModerationLog`](crate::moderation::ModerationLog) if it is set in the channel container.
+///
+/// ```
+/// # use chatbot_lib::request::{FilterDecision, FilterNotice};
+/// let decision = FilterDecision::delete("link without permit")
+///     .with_tag("links")
+///     .with_notice(FilterNotice::Reply("please ask for a !permit".to_owned()));
+/// assert!(decision.is_delete());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterDecision {
+    pub action: FilterAction,
+    pub reason: Option<String>,
+    /// Which filter made the decision, e.g. `links`.
+    pub tag: Option<String>,
+    pub notice: Option<FilterNotice>,
+}
+
+impl FilterDecision {
+    pub fn allow() -> Self {
+        Self {
+            action: FilterAction::Allow,
+            reason: None,
+            tag: None,
+            notice: None,
+        }
+    }
+
+    pub fn delete<T: Into<String>>(reason: T) -> Self {
+        Self {
+            action: FilterAction::Delete,
+            reason: Some(reason.into()),
+            tag: None,
+            notice: None,
+        }
+    }
+
+    pub fn with_tag<T: Into<String>>(mut self, tag: T) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /// Sends `notice` to the sender if the message is deleted.
+    pub fn with_notice(mut self, notice: FilterNotice) -> Self {
+        self.notice = Some(notice);
+        self
+    }
+
+    pub fn is_delete(&self) -> bool {
+        self.action == FilterAction::Delete
+    }
+}
+
+impl From<bool> for FilterDecision {
+    /// `true` allows the message, `false` deletes it without a reason.
+    fn from(allow: bool) -> Self {
+        match allow {
+            true => FilterDecision::allow(),
+            false => FilterDecision {
+                action: FilterAction::Delete,
+                ..FilterDecision::allow()
+            },
+        }
+    }
+}
+
+/// What happens to a message if the filter failed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FilterErrorPolicy {
+    /// Keeps the message, such that a broken filter does not delete every message.
+    #[default]
+    Allow,
+    /// Deletes the message, such that nothing passes a broken filter.
+    Delete,
+}
+
+impl FilterErrorPolicy {
+    pub fn decision(&self) -> FilterDecision {
+        match self {
+            FilterErrorPolicy::Allow => FilterDecision::allow(),
+            FilterErrorPolicy::Delete => FilterDecision::delete("the filter failed"),
+        }
+    }
+}
+
+/// Turns a filter which can not fail into a [`TryFilterPredicate`].
+pub fn try_predicate(mut predicate: FilterPredicate) -> TryFilterPredicate {
+    Box::new(move |request, responder| {
+        let decision = predicate(request, responder);
+        Box::pin(async move { Ok(decision.await) })
+    })
+}
+
+/// The state and the chatters can be accessed with [`RequestParts`](super::RequestParts).
 #[derive(Debug, Clone)]
 pub struct FilterRequest<'req> {
     message: &'req str,
@@ -49,27 +167,11 @@ impl<'req> FilterRequest<'req> {
         &self.sender
     }
 
-    pub fn channel(&self) -> &Channel {
+    pub fn channel(&self) -> &Channel<'req> {
         &self.channel
     }
 
-    pub fn bot(&self) -> &Bot {
+    pub fn bot(&self) -> &Bot<'req> {
         self.bot
     }
-
-    pub fn chatters(&self) -> Option<ChannelChatters> {
-        self.context.map(|c| c.chatters())
-    }
-
-    pub fn state<'a, T: Send + Sync + 'static>(&'a self) -> Result<State<'req, T>, StateError> {
-        self.context.ok_or(StateError::NoContext)?.state()
-    }
-
-    pub fn channel_state<'a, T: Send + Sync + 'static>(
-        &'a self,
-    ) -> Result<ChannelState<'req, T>, ChannelStateError> {
-        self.context
-            .ok_or(ChannelStateError::NoContext)?
-            .channel_state()
-    }
 }
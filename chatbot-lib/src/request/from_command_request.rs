@@ -1,8 +1,14 @@
-use crate::state::ChannelChatters;
+use crate::state::{ChannelChatters, ChannelState, PersistedChannelState, PersistedType};
+use crate::State;
 
-use super::{Bot, Channel, Command, CommandRequest, Sender};
+use super::{Bot, Channel, Command, CommandRequest, FromRequestParts, Sender};
 use core::fmt::Debug;
 
+/// Values which can be stored in the state, used as a bound in [`impl_from_request_parts`].
+trait StateType: Send + Sync + 'static {}
+
+impl<T: Send + Sync + 'static> StateType for T {}
+
 pub trait FromCommandRequest<'a, 'req>: Sized {
     type Error: std::error::Error + Send + Sync + 'static;
 
@@ -63,14 +69,34 @@ macro_rules! impl_from_command_request {
 }
 
 impl_from_command_request! {
-    impl<'a, 'req> |request| -> &'a Sender<'req> { request.sender() }
-    impl<'a, 'req> |request| -> &'a Channel<'req> { request.channel() }
-    impl<'a, 'req> |request| -> &'a Bot<'req> { request.bot() }
     impl<'a, 'req> |request| -> &'a Command<'req> { request.command() }
-    impl<'a, 'req> |request| -> Sender<'req> { request.sender().clone() }
-    impl<'a, 'req> |request| -> Channel<'req> { request.channel().clone() }
-    impl<'a, 'req> |request| -> Bot<'req> { request.bot().clone() }
     impl<'a, 'req> |request| -> Command<'req> { request.command().clone() }
+}
+
+/// Implements [`FromCommandRequest`] for extractors which implement [`FromRequestParts`].
+macro_rules! impl_from_request_parts {
+    ($(impl<$a:lifetime, $req:lifetime $(, $t:ident: $bound:path)?> $ty:ty;) +) => {
+        $(
+            impl<$a, $req $(, $t: $bound)?> FromCommandRequest<$a, $req> for $ty {
+                type Error = <$ty as FromRequestParts<$a, $req>>::Error;
+
+                fn from_command_request(request: &$a CommandRequest<$req>) -> Result<Self, Self::Error> {
+                    <$ty as FromRequestParts>::from_request_parts(request)
+                }
+            }
+        )+
+    };
+}
 
-    impl<'a, 'req> |request| -> ChannelChatters { request.context.map(|c| c.chatters()).unwrap_or_else(ChannelChatters::new) }
+impl_from_request_parts! {
+    impl<'a, 'req> &'a Sender<'req>;
+    impl<'a, 'req> &'a Channel<'req>;
+    impl<'a, 'req> &'a Bot<'req>;
+    impl<'a, 'req> Sender<'req>;
+    impl<'a, 'req> Channel<'req>;
+    impl<'a, 'req> Bot<'req>;
+    impl<'a, 'req> ChannelChatters;
+    impl<'a, 'req, T: StateType> State<'req, T>;
+    impl<'a, 'req, T: StateType> ChannelState<'req, T>;
+    impl<'a, 'req, T: PersistedType> PersistedChannelState<'req, T>;
 }
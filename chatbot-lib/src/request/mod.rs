@@ -1,20 +1,39 @@
-use crate::user::User;
+use crate::user::{ChannelId, User};
 use derive_more::{Deref, From};
 
 mod command_request;
 mod filter_request;
 mod from_command_request;
+mod request_parts;
 
 #[derive(Debug, Clone, Deref, From)]
 pub struct Channel<'a>(pub(crate) User<'a>);
 #[derive(Debug, Clone, Deref, From)]
 pub struct Bot<'a>(User<'a>);
 
+impl<'a> Channel<'a> {
+    /// The [`UserId`](crate::user::UserId) of the broadcaster as the id of the channel.
+    pub fn channel_id(&self) -> Option<ChannelId> {
+        self.user_id().map(ChannelId::from)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Sender<'a> {
     user: User<'a>,
     moderator: bool,
     broadcaster: bool,
+    vip: bool,
+    subscriber: bool,
+}
+
+/// The roles of a user in a channel, e.g. which roles bypass a filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    Broadcaster,
+    Moderator,
+    Vip,
+    Subscriber,
 }
 
 impl<'a> Sender<'a> {
@@ -23,9 +42,19 @@ impl<'a> Sender<'a> {
             user,
             moderator,
             broadcaster,
+            vip: false,
+            subscriber: false,
         }
     }
 
+    pub fn with_vip(self, vip: bool) -> Self {
+        Self { vip, ..self }
+    }
+
+    pub fn with_subscriber(self, subscriber: bool) -> Self {
+        Self { subscriber, ..self }
+    }
+
     pub fn is_moderator(&self) -> bool {
         self.moderator
     }
@@ -33,6 +62,23 @@ impl<'a> Sender<'a> {
     pub fn is_broadcaster(&self) -> bool {
         self.broadcaster
     }
+
+    pub fn is_vip(&self) -> bool {
+        self.vip
+    }
+
+    pub fn is_subscriber(&self) -> bool {
+        self.subscriber
+    }
+
+    pub fn has_role(&self, role: Role) -> bool {
+        match role {
+            Role::Broadcaster => self.broadcaster,
+            Role::Moderator => self.moderator,
+            Role::Vip => self.vip,
+            Role::Subscriber => self.subscriber,
+        }
+    }
 }
 
 impl<'a> From<User<'a>> for Sender<'a> {
@@ -49,6 +95,10 @@ impl<'a> std::ops::Deref for Sender<'a> {
     }
 }
 
-pub use self::command_request::{Command, CommandRequest};
-pub use self::filter_request::{FilterPredicate, FilterRequest};
+pub use self::command_request::{Command, CommandRequest, OwnedCommandRequest};
+pub use self::filter_request::{
+    try_predicate, FilterAction, FilterDecision, FilterErrorPolicy, FilterNotice, FilterPredicate,
+    FilterRequest, TryFilterPredicate,
+};
 pub use self::from_command_request::FromCommandRequest;
+pub use self::request_parts::{FromRequestParts, RequestParts};
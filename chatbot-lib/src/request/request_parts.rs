@@ -0,0 +1,219 @@
+use super::{Bot, Channel, CommandRequest, FilterRequest, Sender};
+use crate::chat_bot::StateError;
+use crate::state::{
+    ChannelChatters, ChannelState, ChannelStateError, CrossChannel, Persisted,
+    PersistedChannelState, PersistedType,
+};
+use crate::State;
+
+pub(crate) mod sealed {
+    use crate::chat_bot::ChatBotContext;
+
+    /// Hides the context from the public interface.
+    pub struct Context<'req>(pub(crate) Option<&'req ChatBotContext<'req>>);
+
+    pub trait Sealed<'req> {
+        fn context(&self) -> Context<'req>;
+    }
+}
+
+/// The parts which [`CommandRequest`] and [`FilterRequest`] have in common.
+pub trait RequestParts<'req>: sealed::Sealed<'req> {
+    fn sender(&self) -> &Sender<'req>;
+
+    fn channel(&self) -> &Channel<'req>;
+
+    fn bot(&self) -> &Bot<'req>;
+
+    /// The chatters of all channels, which are empty if the request has no context.
+    fn chatters(&self) -> ChannelChatters {
+        self.context()
+            .0
+            .map(|context| context.chatters())
+            .unwrap_or_else(ChannelChatters::new)
+    }
+
+    fn state<T: Send + Sync + 'static>(&self) -> Result<State<'req, T>, StateError> {
+        self.context().0.ok_or(StateError::NoContext)?.state()
+    }
+
+    fn channel_state<T: Send + Sync + 'static>(
+        &self,
+    ) -> Result<ChannelState<'req, T>, ChannelStateError> {
+        self.context()
+            .0
+            .ok_or(ChannelStateError::NoContext)?
+            .channel_state()
+    }
+
+    /// A read-only view of the state of all channels.
+    fn cross_channel(&self) -> Result<CrossChannel<'req>, ChannelStateError> {
+        self.context()
+            .0
+            .ok_or(ChannelStateError::NoContext)?
+            .cross_channel()
+    }
+
+    fn persisted_state<T: PersistedType>(
+        &self,
+    ) -> Result<PersistedChannelState<'req, T>, ChannelStateError> {
+        let channel_state = self.channel_state::<Persisted<T>>()?;
+        Ok(channel_state.for_channel(self.channel().username()))
+    }
+
+    #[cfg(feature = "redis")]
+    fn shared_state<T: PersistedType>(
+        &self,
+    ) -> Result<crate::state::SharedChannelState<'req, T>, ChannelStateError> {
+        let channel_state = self.channel_state::<crate::state::SharedState<T>>()?;
+        Ok(channel_state.for_channel(self.channel().username()))
+    }
+
+    fn extract<'a, T: FromRequestParts<'a, 'req>>(&'a self) -> Result<T, T::Error>
+    where
+        Self: Sized,
+    {
+        T::from_request_parts(self)
+    }
+}
+
+/// Extractors which only need the [`RequestParts`], which makes them usable for commands and
+/// filters. Every extractor implementing it also implements
+/// [`FromCommandRequest`](super::FromCommandRequest).
+pub trait FromRequestParts<'a, 'req>: Sized {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    fn from_request_parts<R: RequestParts<'req>>(request: &'a R) -> Result<Self, Self::Error>;
+}
+
+impl<'req> sealed::Sealed<'req> for CommandRequest<'req> {
+    fn context(&self) -> sealed::Context<'req> {
+        sealed::Context(self.context)
+    }
+}
+
+impl<'req> RequestParts<'req> for CommandRequest<'req> {
+    fn sender(&self) -> &Sender<'req> {
+        CommandRequest::sender(self)
+    }
+
+    fn channel(&self) -> &Channel<'req> {
+        CommandRequest::channel(self)
+    }
+
+    fn bot(&self) -> &Bot<'req> {
+        CommandRequest::bot(self)
+    }
+}
+
+impl<'req> sealed::Sealed<'req> for FilterRequest<'req> {
+    fn context(&self) -> sealed::Context<'req> {
+        sealed::Context(self.context)
+    }
+}
+
+impl<'req> RequestParts<'req> for FilterRequest<'req> {
+    fn sender(&self) -> &Sender<'req> {
+        FilterRequest::sender(self)
+    }
+
+    fn channel(&self) -> &Channel<'req> {
+        FilterRequest::channel(self)
+    }
+
+    fn bot(&self) -> &Bot<'req> {
+        FilterRequest::bot(self)
+    }
+}
+
+macro_rules! impl_from_request_parts {
+    ($(impl<$a:lifetime, $req:lifetime> |$request:ident| -> $ty:ty $value:block) +) => {
+        $(
+            impl<$a, $req> FromRequestParts<$a, $req> for $ty {
+                type Error = core::convert::Infallible;
+
+                fn from_request_parts<R: RequestParts<$req>>(
+                    $request: &$a R,
+                ) -> Result<Self, Self::Error> {
+                    Ok($value)
+                }
+            }
+        )+
+    };
+}
+
+impl_from_request_parts! {
+    impl<'a, 'req> |request| -> &'a Sender<'req> { request.sender() }
+    impl<'a, 'req> |request| -> &'a Channel<'req> { request.channel() }
+    impl<'a, 'req> |request| -> &'a Bot<'req> { request.bot() }
+    impl<'a, 'req> |request| -> Sender<'req> { request.sender().clone() }
+    impl<'a, 'req> |request| -> Channel<'req> { request.channel().clone() }
+    impl<'a, 'req> |request| -> Bot<'req> { request.bot().clone() }
+    impl<'a, 'req> |request| -> ChannelChatters { request.chatters() }
+}
+
+impl<'a, 'req, T: Send + Sync + 'static> FromRequestParts<'a, 'req> for State<'req, T> {
+    type Error = StateError;
+
+    fn from_request_parts<R: RequestParts<'req>>(request: &'a R) -> Result<Self, Self::Error> {
+        request.state()
+    }
+}
+
+impl<'a, 'req, T: Send + Sync + 'static> FromRequestParts<'a, 'req> for ChannelState<'req, T> {
+    type Error = ChannelStateError;
+
+    fn from_request_parts<R: RequestParts<'req>>(request: &'a R) -> Result<Self, Self::Error> {
+        request.channel_state()
+    }
+}
+
+impl<'a, 'req> FromRequestParts<'a, 'req> for CrossChannel<'req> {
+    type Error = ChannelStateError;
+
+    fn from_request_parts<R: RequestParts<'req>>(request: &'a R) -> Result<Self, Self::Error> {
+        request.cross_channel()
+    }
+}
+
+impl<'a, 'req, T: PersistedType> FromRequestParts<'a, 'req> for PersistedChannelState<'req, T> {
+    type Error = ChannelStateError;
+
+    fn from_request_parts<R: RequestParts<'req>>(request: &'a R) -> Result<Self, Self::Error> {
+        request.persisted_state()
+    }
+}
+
+#[cfg(feature = "redis")]
+impl<'a, 'req, T: PersistedType> FromRequestParts<'a, 'req>
+    for crate::state::SharedChannelState<'req, T>
+{
+    type Error = ChannelStateError;
+
+    fn from_request_parts<R: RequestParts<'req>>(request: &'a R) -> Result<Self, Self::Error> {
+        request.shared_state()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::FromCommandRequest;
+    use crate::user::User;
+
+    #[test]
+    fn test_request_parts() {
+        let bot = Bot::from(User::from_username("bot"));
+        let request = CommandRequest::from_parts(
+            "!test",
+            User::from_username("liquidnya"),
+            User::from_username("channel"),
+            &bot,
+        );
+        let sender: &Sender = request.extract().unwrap();
+        assert_eq!(sender.username(), "liquidnya");
+        let channel = <Channel as FromCommandRequest>::from_command_request(&request).unwrap();
+        assert_eq!(channel.username(), "channel");
+        assert!(matches!(request.state::<u32>(), Err(StateError::NoContext)));
+    }
+}
@@ -1,9 +1,12 @@
 use std::borrow::Cow;
 
+use crate::request::CommandRequest;
 use async_trait::async_trait;
 use tokio::io;
+use tokio::sync::Mutex;
 
-pub struct Response<'a>(Option<Cow<'a, str>>, bool, bool);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response<'a>(Option<Cow<'a, str>>, bool, bool, Option<Cow<'a, str>>);
 
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct ReplyResponse<T>(pub(super) T);
@@ -23,26 +26,104 @@ impl<T> From<T> for CommandResponse<T> {
     }
 }
 
+/// Prefixes the response with the mention of the sender, e.g. `@sender response`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Mention<T>(pub(super) T);
+
+impl<T> From<T> for Mention<T> {
+    fn from(value: T) -> Self {
+        Mention(value)
+    }
+}
+
 #[async_trait]
-pub trait Responder {
+pub trait Responder: Send {
     async fn respond(&mut self, response: &Response<'_>) -> io::Result<()>;
+
+    /// Sends the responses in order and stops at the first error.
+    async fn respond_all(&mut self, responses: &[Response<'_>]) -> io::Result<()> {
+        for response in responses {
+            self.respond(response).await?;
+        }
+        Ok(())
+    }
+}
+
+/// A [`Responder`] which can be used by everyone handling the same request.
+#[async_trait]
+pub trait SharedResponder: Send + Sync {
+    async fn respond(&self, response: &Response<'_>) -> io::Result<()>;
+
+    /// Sends the responses in order and stops at the first error.
+    async fn respond_all(&self, responses: &[Response<'_>]) -> io::Result<()> {
+        for response in responses {
+            self.respond(response).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<R: Responder> SharedResponder for Mutex<R> {
+    async fn respond(&self, response: &Response<'_>) -> io::Result<()> {
+        self.lock().await.respond(response).await
+    }
+
+    /// Locks the responder once, so the responses are not interleaved with other responses.
+    async fn respond_all(&self, responses: &[Response<'_>]) -> io::Result<()> {
+        self.lock().await.respond_all(responses).await
+    }
+}
+
+/// Sends intermediate messages while a command is still running.
+/// Command functions can take it as `&mut dyn Responder`.
+pub struct RequestResponder<'a>(Option<&'a dyn SharedResponder>);
+
+impl<'a> From<&'a CommandRequest<'_>> for RequestResponder<'a> {
+    fn from(request: &'a CommandRequest<'_>) -> Self {
+        Self(request.responder.map(|responder| responder.0))
+    }
+}
+
+impl RequestResponder<'_> {
+    fn shared(&self) -> io::Result<&dyn SharedResponder> {
+        self.0.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotConnected, "the request has no responder")
+        })
+    }
+}
+
+#[async_trait]
+impl Responder for RequestResponder<'_> {
+    async fn respond(&mut self, response: &Response<'_>) -> io::Result<()> {
+        self.shared()?.respond(response).await
+    }
+
+    async fn respond_all(&mut self, responses: &[Response<'_>]) -> io::Result<()> {
+        self.shared()?.respond_all(responses).await
+    }
 }
 
 impl<'a> Response<'a> {
     pub fn new<T: Into<Cow<'a, str>>>(response: T) -> Self {
-        Self(Some(response.into()), false, false)
+        Self(Some(response.into()), false, false, None)
     }
 
     pub fn as_reply(self) -> Self {
-        Self(self.0, true, self.2)
+        Self(self.0, true, self.2, self.3)
     }
 
     pub fn as_command(self) -> Self {
-        Self(self.0, self.1, true)
+        Self(self.0, self.1, true, self.3)
+    }
+
+    /// Replies to the message with the id `message_id` instead of the message which triggered the command.
+    pub fn reply_to<T: Into<Cow<'a, str>>>(self, message_id: T) -> Self {
+        Self(self.0, true, self.2, Some(message_id.into()))
     }
 
     pub fn none() -> Self {
-        Self(None, false, false)
+        Self(None, false, false, None)
     }
 
     pub fn response(&self) -> Option<&str> {
@@ -56,4 +137,63 @@ impl<'a> Response<'a> {
     pub fn command(&self) -> bool {
         self.2
     }
+
+    /// The id of the message to reply to, if it is not the message which triggered the command.
+    pub fn reply_to_message(&self) -> Option<&str> {
+        self.3.as_deref()
+    }
+
+    pub fn into_owned(self) -> Response<'static> {
+        Response(
+            self.0.map(|response| Cow::Owned(response.into_owned())),
+            self.1,
+            self.2,
+            self.3.map(|message_id| Cow::Owned(message_id.into_owned())),
+        )
+    }
+
+    pub(super) fn map_response<F: FnOnce(Cow<'a, str>) -> Cow<'a, str>>(self, f: F) -> Self {
+        Self(self.0.map(f), self.1, self.2, self.3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Sent(Vec<String>);
+
+    #[async_trait]
+    impl Responder for Sent {
+        async fn respond(&mut self, response: &Response<'_>) -> io::Result<()> {
+            match response.response() {
+                Some("fail") => Err(io::Error::new(io::ErrorKind::Other, "fail")),
+                Some(text) => {
+                    self.0.push(text.to_owned());
+                    Ok(())
+                }
+                None => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_respond_all() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let shared = Mutex::new(Sent::default());
+        runtime.block_on(async {
+            let responses = [Response::new("a"), Response::none(), Response::new("b")];
+            SharedResponder::respond_all(&shared, &responses)
+                .await
+                .unwrap();
+            let responses = [Response::new("fail"), Response::new("c")];
+            assert!(SharedResponder::respond_all(&shared, &responses)
+                .await
+                .is_err());
+        });
+        assert_eq!(shared.into_inner().0, ["a", "b"]);
+    }
 }
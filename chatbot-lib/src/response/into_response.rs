@@ -1,5 +1,11 @@
-use super::{command_response::CommandResponse, command_response::ReplyResponse, Response};
+use super::{
+    command_response::CommandResponse, command_response::Mention, command_response::ReplyResponse,
+    Response,
+};
 use crate::request::CommandRequest;
+use crate::user::{User, UserArgument};
+use core::fmt::Display;
+use itertools::Itertools;
 
 pub trait IntoResponse<'a> {
     fn into_response(self, request: &CommandRequest<'_>) -> Response<'a>;
@@ -29,6 +35,18 @@ where
     }
 }
 
+impl<'a, T> IntoResponse<'a> for Mention<T>
+where
+    T: IntoResponse<'a>,
+{
+    fn into_response(self, request: &CommandRequest<'_>) -> Response<'a> {
+        let mention = UserArgument::from(request.sender() as &User);
+        self.0
+            .into_response(request)
+            .map_response(|text| format!("{} {}", mention, text).into())
+    }
+}
+
 impl<'a> IntoResponse<'a> for () {
     fn into_response(self, _request: &CommandRequest<'_>) -> Response<'a> {
         Response::none()
@@ -44,6 +62,67 @@ impl<'a, T: IntoResponse<'a>> IntoResponse<'a> for Option<T> {
     }
 }
 
+/// Errors are sent to the sender of the command, e.g. `@sender could not find song`.
+impl<'a, T: IntoResponse<'a>, E: Display> IntoResponse<'a> for Result<T, E> {
+    fn into_response(self, request: &CommandRequest<'_>) -> Response<'a> {
+        match self {
+            Ok(value) => value.into_response(request),
+            Err(error) => Response::new(format!(
+                "{} {}",
+                UserArgument::from(request.sender() as &User),
+                error
+            )),
+        }
+    }
+}
+
+/// Joins the items of an iterator with a separator, e.g. `Joined::new(queue).separator(" | ")`.
+/// Empty iterators do not respond.
+#[derive(Debug, Clone)]
+pub struct Joined<I> {
+    items: I,
+    separator: &'static str,
+}
+
+impl<I> Joined<I>
+where
+    I: IntoIterator,
+    I::Item: Display,
+{
+    /// Joins the items with `", "`.
+    pub fn new(items: I) -> Self {
+        Self {
+            items,
+            separator: ", ",
+        }
+    }
+
+    pub fn separator(self, separator: &'static str) -> Self {
+        Self { separator, ..self }
+    }
+}
+
+impl<'a, I> IntoResponse<'a> for Joined<I>
+where
+    I: IntoIterator,
+    I::Item: Display,
+{
+    fn into_response(self, _request: &CommandRequest<'_>) -> Response<'a> {
+        let mut items = self.items.into_iter().peekable();
+        if items.peek().is_none() {
+            return Response::none();
+        }
+        Response::new(items.join(self.separator))
+    }
+}
+
+/// Items are joined with `", "`, see [`Joined`] for other separators.
+impl<'a, T: Display> IntoResponse<'a> for Vec<T> {
+    fn into_response(self, request: &CommandRequest<'_>) -> Response<'a> {
+        Joined::new(self).into_response(request)
+    }
+}
+
 impl<'a> IntoResponse<'a> for Box<str> {
     fn into_response(self, _request: &CommandRequest<'_>) -> Response<'a> {
         Response::new(self.into_string())
@@ -114,3 +193,67 @@ impl_into_response! {
     std::num::NonZeroU128
     std::num::NonZeroUsize
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_into_response() {
+        let bot = User::from_username("helperblock").into();
+        let request = CommandRequest::from_parts(
+            "!song",
+            User::from_username("liquidblock"),
+            User::from_username("liquidblock"),
+            &bot,
+        );
+        let ok: Result<&str, String> = Ok("song");
+        assert_eq!(ok.into_response(&request).response(), Some("song"));
+        let err: Result<&str, String> = Err("no song is playing".into());
+        assert_eq!(
+            err.into_response(&request).response(),
+            Some("@liquidblock no song is playing")
+        );
+    }
+
+    #[test]
+    fn test_wrappers_into_response() {
+        let bot = User::from_username("helperblock").into();
+        let request = CommandRequest::from_parts(
+            "!song",
+            User::from_username("liquidblock"),
+            User::from_username("liquidblock"),
+            &bot,
+        );
+        let response = ReplyResponse::from(Mention::from("song")).into_response(&request);
+        assert_eq!(response.response(), Some("@liquidblock song"));
+        assert!(response.reply());
+        assert!(!response.command());
+        let response = CommandResponse::from(".clear").into_response(&request);
+        assert!(response.command());
+        let response = Mention::from(()).into_response(&request);
+        assert_eq!(response.response(), None);
+        let response =
+            Mention::from(Response::new("song").reply_to("parent-id")).into_response(&request);
+        assert_eq!(response.response(), Some("@liquidblock song"));
+        assert_eq!(response.reply_to_message(), Some("parent-id"));
+        assert!(response.reply());
+    }
+
+    #[test]
+    fn test_joined_into_response() {
+        let bot = User::from_username("helperblock").into();
+        let request = CommandRequest::from_parts(
+            "!queue",
+            User::from_username("liquidblock"),
+            User::from_username("liquidblock"),
+            &bot,
+        );
+        let queue = vec!["a", "b", "c"];
+        assert_eq!(queue.into_response(&request).response(), Some("a, b, c"));
+        let joined = Joined::new(1..=3).separator(" | ");
+        assert_eq!(joined.into_response(&request).response(), Some("1 | 2 | 3"));
+        let empty: Vec<String> = Vec::new();
+        assert_eq!(empty.into_response(&request).response(), None);
+    }
+}
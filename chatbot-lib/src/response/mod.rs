@@ -1,8 +1,17 @@
 mod command_response;
 mod into_response;
+mod respond_later;
 
 pub use self::command_response::CommandResponse;
+pub use self::command_response::Mention;
 pub use self::command_response::ReplyResponse;
+pub use self::command_response::RequestResponder;
 pub use self::command_response::Responder;
 pub use self::command_response::Response;
+pub use self::command_response::SharedResponder;
 pub use self::into_response::IntoResponse;
+pub use self::into_response::Joined;
+pub(crate) use self::respond_later::DeferredResponse;
+pub use self::respond_later::NoDeferredResponses;
+pub use self::respond_later::RespondLater;
+pub use self::respond_later::RespondLaterError;
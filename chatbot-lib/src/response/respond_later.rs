@@ -0,0 +1,113 @@
+use super::Response;
+use crate::request::{CommandRequest, FromCommandRequest};
+use core::fmt::{Display, Formatter};
+use std::future::Future;
+use tokio::sync::mpsc::UnboundedSender;
+
+pub(crate) struct DeferredResponse {
+    pub(crate) channel: String,
+    pub(crate) reply_to: Option<String>,
+    pub(crate) response: Response<'static>,
+}
+
+/// Sends responses after the command function returned, e.g. when an external API answers.
+/// The messages are written by the chat bot, which limits how fast they are sent per channel.
+#[derive(Debug, Clone)]
+pub struct RespondLater {
+    sender: UnboundedSender<DeferredResponse>,
+    channel: String,
+    reply_to: Option<String>,
+}
+
+/// The chat bot stopped before the response was sent.
+#[derive(Debug)]
+pub struct RespondLaterError;
+
+impl Display for RespondLaterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "the chat bot is not running")
+    }
+}
+
+impl std::error::Error for RespondLaterError {}
+
+/// The request was not created by a running chat bot.
+#[derive(Debug)]
+pub struct NoDeferredResponses;
+
+impl Display for NoDeferredResponses {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "CommandRequest can not respond later")
+    }
+}
+
+impl std::error::Error for NoDeferredResponses {}
+
+impl RespondLater {
+    pub(crate) fn new(
+        sender: UnboundedSender<DeferredResponse>,
+        channel: String,
+        reply_to: Option<String>,
+    ) -> Self {
+        Self {
+            sender,
+            channel,
+            reply_to,
+        }
+    }
+
+    pub fn send(&self, response: Response<'static>) -> Result<(), RespondLaterError> {
+        self.sender
+            .send(DeferredResponse {
+                channel: self.channel.clone(),
+                reply_to: self.reply_to.clone(),
+                response,
+            })
+            .map_err(|_| RespondLaterError)
+    }
+
+    /// Sends the response of `future` once it completes.
+    pub fn spawn<F>(self, future: F)
+    where
+        F: Future<Output = Response<'static>> + Send + 'static,
+    {
+        tokio::spawn(async move {
+            let response = future.await;
+            if let Err(e) = self.send(response) {
+                log::warn!("Deferred response was dropped: {}", e);
+            }
+        });
+    }
+}
+
+impl<'a, 'req> FromCommandRequest<'a, 'req> for RespondLater {
+    type Error = NoDeferredResponses;
+
+    fn from_command_request(request: &'a CommandRequest<'req>) -> Result<Self, Self::Error> {
+        request
+            .context
+            .and_then(|context| context.respond_later(request.channel()))
+            .ok_or(NoDeferredResponses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send() {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let respond_later = RespondLater::new(sender, "channel".into(), Some("id".into()));
+        respond_later
+            .send(Response::new("done").as_reply())
+            .unwrap();
+        let deferred = receiver.try_recv().unwrap();
+        assert_eq!(deferred.channel, "channel");
+        assert_eq!(deferred.reply_to.as_deref(), Some("id"));
+        assert_eq!(deferred.response.response(), Some("done"));
+        assert!(deferred.response.reply());
+        drop(receiver);
+        assert!(respond_later.send(Response::new("dropped")).is_err());
+    }
+}
@@ -0,0 +1,443 @@
+//! Commands of a channel whose bodies are [Rhai](https://rhai.rs) scripts, which are stored with
+//! the persisted state.
+//!
+//! [`Scripts`] has to be registered with
+//! [`ContainerBuilder::register_persisted_type`](crate::state::ContainerBuilder::register_persisted_type)
+//! before [`ScriptCommands`] can process `!script` and [`ScriptProcessor`] can run the scripts.
+//! Scripts can use [`Counters`] if they are registered as well.
+//!
+//! Scripts run in a sandbox without access to files or the network and with limits on the number
+//! of operations, so a script can not stall the bot. They can use:
+//! - `sender.name`, `sender.display_name`, `sender.is_moderator` and `sender.is_broadcaster`
+//! - `channel`, `args` (the arguments as an array) and `text` (the arguments as a string)
+//! - `respond(text)`, which sends a message after the script finished
+//! - `counter(name)`, `add_counter(name, delta)` and `set_counter(name, value)`
+//! - `random(min, max)`, a random number within `min..=max`
+//!
+//! The value of the script is sent as the response unless it is `()`, e.g.
+//! `!script set !hug "${sender.name} hugs ${text}!"`.
+
+use crate::command::{CommandArguments, CommandProcessor};
+use crate::counters::{Counters, CountersState};
+use crate::request::{CommandRequest, FromCommandRequest};
+use crate::response::{RequestResponder, Responder, Response};
+use crate::state::{PersistedChannelState, PersistedType};
+use crate::user::{User, UserArgument};
+use async_trait::async_trait;
+use rand::Rng;
+use rhai::packages::{Package, StandardPackage};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+const MAX_OPERATIONS: u64 = 50_000;
+const MAX_CALL_LEVELS: usize = 16;
+const MAX_STRING_SIZE: usize = 2_000;
+const MAX_COLLECTION_SIZE: usize = 1_000;
+/// How many messages a script may send with `respond`.
+const MAX_RESPONSES: usize = 3;
+
+/// The scripts of a channel by their command, e.g. `!hug`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Scripts {
+    scripts: BTreeMap<String, String>,
+}
+
+impl Scripts {
+    pub fn get(&self, command: &str) -> Option<&str> {
+        self.scripts.get(command).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.scripts
+            .iter()
+            .map(|(command, source)| (command.as_str(), source.as_str()))
+    }
+
+    /// Sets the script of a command and returns the script it replaced.
+    pub fn set<T: Into<String>>(&mut self, command: &str, source: T) -> Option<String> {
+        self.scripts.insert(command.to_owned(), source.into())
+    }
+
+    pub fn remove(&mut self, command: &str) -> Option<String> {
+        self.scripts.remove(command)
+    }
+}
+
+impl PersistedType for Scripts {
+    const FILENAME: &'static str = "scripts";
+
+    fn init(_channel: &str) -> Self {
+        Self::default()
+    }
+}
+
+pub type ScriptsState<'req> = PersistedChannelState<'req, Scripts>;
+
+/// What a script did, which is applied after it finished.
+#[derive(Debug, Default)]
+struct Execution {
+    responses: Vec<String>,
+    /// The counters with the changes of the script, `None` if they are not registered.
+    counters: Option<Counters>,
+    changes: Vec<CounterChange>,
+}
+
+#[derive(Debug, Clone)]
+enum CounterChange {
+    Add(String, i64),
+    Set(String, i64),
+}
+
+impl Execution {
+    fn counters(&mut self) -> Result<&mut Counters, Box<EvalAltResult>> {
+        self.counters
+            .as_mut()
+            .ok_or_else(|| "the counters are not registered for this channel".into())
+    }
+
+    fn change(&mut self, change: CounterChange) -> Result<i64, Box<EvalAltResult>> {
+        let value = change.apply(self.counters()?);
+        self.changes.push(change);
+        Ok(value)
+    }
+}
+
+impl CounterChange {
+    fn apply(&self, counters: &mut Counters) -> i64 {
+        match self {
+            CounterChange::Add(name, delta) => counters.add(name, *delta),
+            CounterChange::Set(name, value) => {
+                let current = counters.get(name);
+                counters.add(name, value.saturating_sub(current))
+            }
+        }
+    }
+}
+
+fn lock(execution: &Mutex<Execution>) -> std::sync::MutexGuard<'_, Execution> {
+    execution.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Creates the sandboxed engines which run the scripts.
+#[derive(Clone)]
+struct Sandbox {
+    packages: rhai::Shared<rhai::Module>,
+}
+
+impl Sandbox {
+    fn new() -> Self {
+        Self {
+            packages: StandardPackage::new().as_shared_module(),
+        }
+    }
+
+    fn engine(&self) -> Engine {
+        let mut engine = Engine::new_raw();
+        engine.register_global_module(self.packages.clone());
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        engine.set_max_string_size(MAX_STRING_SIZE);
+        engine.set_max_array_size(MAX_COLLECTION_SIZE);
+        engine.set_max_map_size(MAX_COLLECTION_SIZE);
+        engine.disable_symbol("eval");
+        engine.on_print(|text| log::debug!("script: {}", text));
+        engine.on_debug(|text, _, _| log::debug!("script: {}", text));
+        engine
+    }
+
+    /// The engine with the functions of the API, which change `execution`.
+    fn engine_for(&self, execution: &Arc<Mutex<Execution>>) -> Engine {
+        let mut engine = self.engine();
+        let state = execution.clone();
+        engine.register_fn("respond", move |text: &str| {
+            let mut execution = lock(&state);
+            if execution.responses.len() >= MAX_RESPONSES {
+                return Err::<(), Box<EvalAltResult>>(
+                    format!("a script can respond at most {} times", MAX_RESPONSES).into(),
+                );
+            }
+            execution.responses.push(text.to_owned());
+            Ok(())
+        });
+        let state = execution.clone();
+        engine.register_fn("counter", move |name: &str| {
+            let mut execution = lock(&state);
+            execution
+                .counters()
+                .map(|counters| counters.get(&name.to_lowercase()))
+        });
+        let state = execution.clone();
+        engine.register_fn("add_counter", move |name: &str, delta: i64| {
+            lock(&state).change(CounterChange::Add(name.to_lowercase(), delta))
+        });
+        let state = execution.clone();
+        engine.register_fn("set_counter", move |name: &str, value: i64| {
+            lock(&state).change(CounterChange::Set(name.to_lowercase(), value))
+        });
+        engine.register_fn("random", |min: i64, max: i64| {
+            if min > max {
+                return Err::<i64, Box<EvalAltResult>>("`min` is greater than `max`".into());
+            }
+            Ok(rand::thread_rng().gen_range(min..=max))
+        });
+        engine
+    }
+
+    /// Checks whether the script can be compiled.
+    fn check(&self, source: &str) -> Result<(), String> {
+        self.engine()
+            .compile(source)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Runs the script and returns its value as a string if it is not `()`.
+    fn run(
+        &self,
+        source: &str,
+        request: &CommandRequest<'_>,
+        arguments: &CommandArguments<'_>,
+        execution: &Arc<Mutex<Execution>>,
+    ) -> Result<Option<String>, String> {
+        let engine = self.engine_for(execution);
+        let sender = request.sender();
+        let mut sender_map = Map::new();
+        sender_map.insert("name".into(), sender.username().into());
+        sender_map.insert(
+            "display_name".into(),
+            sender.display_name().unwrap_or(sender.username()).into(),
+        );
+        sender_map.insert("is_moderator".into(), sender.is_moderator().into());
+        sender_map.insert("is_broadcaster".into(), sender.is_broadcaster().into());
+        let args: Array = arguments
+            .clone()
+            .map(|argument| Dynamic::from(argument.to_owned()))
+            .collect();
+        let mut scope = Scope::new();
+        scope.push_constant("sender", sender_map);
+        scope.push_constant("channel", request.channel().username().to_owned());
+        scope.push_constant("args", args);
+        scope.push_constant("text", arguments.as_str().to_owned());
+        let value = engine
+            .eval_with_scope::<Dynamic>(&mut scope, source)
+            .map_err(|e| e.to_string())?;
+        Ok((!value.is_unit()).then(|| value.to_string()))
+    }
+}
+
+/// Runs the scripts of the channel, see the [module documentation](self).
+pub struct ScriptProcessor {
+    sandbox: Sandbox,
+}
+
+impl Default for ScriptProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScriptProcessor {
+    pub fn new() -> Self {
+        Self {
+            sandbox: Sandbox::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for ScriptProcessor {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        let command = arguments.next()?;
+        let scripts = ScriptsState::from_command_request(request).ok()?;
+        let source = scripts.read().await.get(command)?.to_owned();
+        let counters = CountersState::from_command_request(request).ok();
+        let execution = Arc::new(Mutex::new(Execution {
+            counters: match &counters {
+                Some(counters) => Some(Counters::clone(&*counters.read().await)),
+                None => None,
+            },
+            ..Execution::default()
+        }));
+        let result = self.sandbox.run(&source, request, &arguments, &execution);
+        let Execution {
+            responses, changes, ..
+        } = std::mem::take(&mut *lock(&execution));
+        if let (Some(counters), false) = (counters, changes.is_empty()) {
+            // the changes are applied to the current counters, which could have changed meanwhile
+            counters
+                .update(|counters| {
+                    let mut counters = counters.clone();
+                    for change in &changes {
+                        change.apply(&mut counters);
+                    }
+                    counters
+                })
+                .await;
+        }
+        let responses: Vec<_> = responses.into_iter().map(Response::new).collect();
+        if let Err(e) = RequestResponder::from(request)
+            .respond_all(&responses)
+            .await
+        {
+            log::error!("Error sending the responses of {}: {:?}", command, e);
+        }
+        match result {
+            Ok(Some(value)) => Some(Response::new(value)),
+            Ok(None) => Some(Response::none()),
+            Err(e) => {
+                log::warn!("Error running the script of {}: {}", command, e);
+                Some(Response::new(format!(
+                    "{} {} failed: {}",
+                    UserArgument::from(request.sender() as &User),
+                    command,
+                    e
+                )))
+            }
+        }
+    }
+}
+
+/// Processes `!script set <command> <source..>`, `!script remove <command>`,
+/// `!script show <command>` and `!script list` for moderators.
+pub struct ScriptCommands {
+    sandbox: Sandbox,
+}
+
+impl Default for ScriptCommands {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScriptCommands {
+    pub fn new() -> Self {
+        Self {
+            sandbox: Sandbox::new(),
+        }
+    }
+
+    async fn process_script(
+        &self,
+        request: &CommandRequest<'_>,
+        arguments: &mut CommandArguments<'_>,
+    ) -> Result<String, Cow<'static, str>> {
+        let syntax = "!script (set|remove|show|list) [command] [source..]";
+        let scripts = ScriptsState::from_command_request(request)
+            .map_err(|_| "scripts are not registered for this channel")?;
+        let subcommand = arguments.next().ok_or(syntax)?;
+        if subcommand == "list" {
+            let scripts = scripts.read().await;
+            let commands: Vec<_> = scripts.iter().map(|(command, _)| command).collect();
+            if commands.is_empty() {
+                return Ok("there are no scripts".to_owned());
+            }
+            return Ok(format!("scripts: {}", commands.join(", ")));
+        }
+        let command = arguments
+            .next()
+            .filter(|command| command.starts_with('!') && command.len() > 1)
+            .ok_or(syntax)?;
+        match subcommand {
+            "set" => {
+                let source = arguments.next_rest().ok_or(syntax)?;
+                self.sandbox
+                    .check(source)
+                    .map_err(|e| format!("the script is invalid: {}", e))?;
+                let mut replaced = false;
+                scripts
+                    .update(|scripts| {
+                        let mut scripts = scripts.clone();
+                        replaced = scripts.set(command, source).is_some();
+                        scripts
+                    })
+                    .await;
+                Ok(if replaced {
+                    format!("updated the script of {}", command)
+                } else {
+                    format!("added the script of {}", command)
+                })
+            }
+            "remove" => {
+                let mut removed = false;
+                scripts
+                    .maybe_update(|scripts| {
+                        let mut scripts = scripts.clone();
+                        removed = scripts.remove(command).is_some();
+                        removed.then_some(scripts)
+                    })
+                    .await;
+                if removed {
+                    Ok(format!("removed the script of {}", command))
+                } else {
+                    Err(format!("{} has no script", command).into())
+                }
+            }
+            "show" => match scripts.read().await.get(command) {
+                Some(source) => Ok(format!("{}: {}", command, source)),
+                None => Err(format!("{} has no script", command).into()),
+            },
+            _ => Err(syntax.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for ScriptCommands {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        if arguments.next() != Some("!script") {
+            return None;
+        }
+        let sender = request.sender();
+        if !sender.is_moderator() && !sender.is_broadcaster() {
+            return None;
+        }
+        let text = match self.process_script(request, &mut arguments).await {
+            Ok(text) => text,
+            Err(error) => error.into_owned(),
+        };
+        Some(Response::new(format!(
+            "{} {}",
+            UserArgument::from(sender as &User),
+            text
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::Bot;
+
+    #[test]
+    fn test_script() {
+        let sandbox = Sandbox::new();
+        let bot = Bot::from(User::from_username("bot"));
+        let request = CommandRequest::from_parts(
+            "!hug liquidnya",
+            User::from_username("user"),
+            User::from_username("channel"),
+            &bot,
+        );
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        arguments.next();
+        let execution = Arc::new(Mutex::new(Execution {
+            counters: Some(Counters::default()),
+            ..Execution::default()
+        }));
+        let source = r#"respond("hi"); add_counter("Hugs", 2); `${sender.name} hugs ${args[0]} (${counter("hugs")})`"#;
+        assert_eq!(
+            sandbox.run(source, &request, &arguments, &execution),
+            Ok(Some("user hugs liquidnya (2)".to_owned()))
+        );
+        assert_eq!(lock(&execution).responses, ["hi"]);
+        assert!(sandbox
+            .run("loop {}", &request, &arguments, &execution)
+            .is_err());
+        assert!(sandbox.check("eval(\"1\")").is_err());
+    }
+}
@@ -0,0 +1,148 @@
+//! `!so <user>` for moderators, which recommends another channel with the category it streamed last.
+
+use crate::command::{CommandArguments, CommandProcessor};
+use crate::helix::{HelixChannel, HelixClient};
+use crate::request::{CommandRequest, FromCommandRequest};
+use crate::response::Response;
+use crate::user::{OwnedUser, UserArgument, UserResolver};
+use async_trait::async_trait;
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShoutoutMode {
+    /// Sends the shoutout as a chat message.
+    #[default]
+    Message,
+    /// Sends the shoutout with `/announce`, which highlights it in chat.
+    Announcement,
+}
+
+/// Processes `!so <user>` and `!shoutout <user>` for moderators.
+///
+/// The user is resolved from the chatters first and looked up with the [`HelixClient`] otherwise.
+#[derive(Debug, Clone)]
+pub struct ShoutoutCommands {
+    client: HelixClient,
+    mode: ShoutoutMode,
+}
+
+impl ShoutoutCommands {
+    pub fn new(client: HelixClient) -> Self {
+        Self {
+            client,
+            mode: ShoutoutMode::default(),
+        }
+    }
+
+    pub fn with_mode(self, mode: ShoutoutMode) -> Self {
+        Self { mode, ..self }
+    }
+
+    fn response(&self, text: String) -> Response<'static> {
+        match self.mode {
+            ShoutoutMode::Message => Response::new(text),
+            ShoutoutMode::Announcement => Response::new(format!("/announce {}", text)).as_command(),
+        }
+    }
+
+    async fn process_shoutout(
+        &self,
+        request: &CommandRequest<'_>,
+        arguments: &mut CommandArguments<'_>,
+    ) -> Result<String, Cow<'static, str>> {
+        let target = arguments
+            .next()
+            .filter(|_| arguments.as_str().is_empty())
+            .map(UserArgument::new)
+            .ok_or("!so <user>")?;
+        let resolver = match UserResolver::from_command_request(request) {
+            Ok(resolver) => resolver.with_fallback(self.client.clone()),
+            Err(infallible) => match infallible {},
+        };
+        let user = resolver
+            .resolve(&target)
+            .await
+            .map_err(|e| {
+                log::error!("Error resolving {}: {:?}", target, e);
+                "the user could not be looked up"
+            })?
+            .ok_or_else(|| format!("{} does not exist", target))?;
+        let channel = match user.user_id() {
+            Some(user_id) => self
+                .client
+                .get_channel_information(&user_id.to_string())
+                .await
+                .map_err(|e| {
+                    log::error!("Error getting the channel of {}: {:?}", target, e);
+                    "the channel could not be looked up"
+                })?,
+            None => None,
+        };
+        Ok(shoutout_text(&user, channel.as_ref()))
+    }
+}
+
+fn shoutout_text(user: &OwnedUser, channel: Option<&HelixChannel>) -> String {
+    let name = user.display_name().unwrap_or(user.username());
+    let text = format!(
+        "Go check out {} at https://twitch.tv/{}",
+        name,
+        user.username()
+    );
+    match channel.filter(|channel| !channel.game_name.is_empty()) {
+        Some(channel) => format!("{}, they were last playing {}!", text, channel.game_name),
+        None => format!("{}!", text),
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for ShoutoutCommands {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        if !matches!(arguments.next(), Some("!so" | "!shoutout")) {
+            return None;
+        }
+        let sender = request.sender();
+        if !sender.is_moderator() && !sender.is_broadcaster() {
+            return None;
+        }
+        match self.process_shoutout(request, &mut arguments).await {
+            Ok(text) => Some(self.response(text)),
+            Err(error) => Some(Response::new(format!(
+                "{} {}",
+                UserArgument::from(sender as &crate::user::User),
+                error
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::UserId;
+
+    #[test]
+    fn test_shoutout_text() {
+        let user = OwnedUser::new(
+            "liquidnya".to_owned(),
+            Some("LiquidNya".to_owned()),
+            Some(UserId::new(1)),
+        );
+        let channel = HelixChannel {
+            broadcaster_id: "1".to_owned(),
+            broadcaster_login: "liquidnya".to_owned(),
+            broadcaster_name: "LiquidNya".to_owned(),
+            game_name: "Super Mario Maker 2".to_owned(),
+            title: "nya".to_owned(),
+        };
+        assert_eq!(
+            shoutout_text(&user, Some(&channel)),
+            "Go check out LiquidNya at https://twitch.tv/liquidnya, they were last playing Super Mario Maker 2!"
+        );
+        assert_eq!(
+            shoutout_text(&user, None),
+            "Go check out LiquidNya at https://twitch.tv/liquidnya!"
+        );
+    }
+}
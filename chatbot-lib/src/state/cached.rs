@@ -0,0 +1,124 @@
+use arc_swap::ArcSwapOption;
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+struct Entry<T> {
+    value: Arc<T>,
+    refreshed: Instant,
+}
+
+/// A value which is refreshed once it is older than a time to live, e.g. the uptime of a stream
+/// which was requested from an external API.
+///
+/// Only one refresh runs at a time, requests which need the value while it is refreshed wait for
+/// the refresh instead of starting their own.
+/// The value is shared by registering it with [`ChatBot::with_state`](crate::ChatBot::with_state)
+/// or, for a value per channel, with [`ContainerBuilder::set`](super::ContainerBuilder::set).
+///
+/// ```ignore
+/// let uptime = request.state::<Cached<Uptime>>()?;
+/// let uptime = uptime
+///     .get_or_refresh(Duration::from_secs(60), || helix.uptime(channel))
+///     .await?;
+/// ```
+pub struct Cached<T> {
+    entry: ArcSwapOption<Entry<T>>,
+    lock: Semaphore,
+}
+
+impl<T> Default for Cached<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Cached<T> {
+    pub fn new() -> Self {
+        Self {
+            entry: ArcSwapOption::empty(),
+            lock: Semaphore::new(1),
+        }
+    }
+
+    /// The value if it was refreshed within `ttl`.
+    pub fn get(&self, ttl: Duration) -> Option<Arc<T>> {
+        match self.entry.load().deref() {
+            Some(entry) if entry.refreshed.elapsed() < ttl => Some(entry.value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it was refreshed within `ttl`, otherwise the value returned by
+    /// `refresh` is stored and returned.
+    ///
+    /// Errors are returned without replacing the previous value, such that the next request
+    /// tries again.
+    pub async fn get_or_refresh<F, Fut, E>(&self, ttl: Duration, refresh: F) -> Result<Arc<T>, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if let Some(value) = self.get(ttl) {
+            return Ok(value);
+        }
+        let _permit = self.lock.acquire().await.unwrap();
+        // another request might have refreshed the value while waiting
+        if let Some(value) = self.get(ttl) {
+            return Ok(value);
+        }
+        let value = Arc::new(refresh().await?);
+        self.entry.store(Some(Arc::new(Entry {
+            value: value.clone(),
+            refreshed: Instant::now(),
+        })));
+        Ok(value)
+    }
+
+    /// Drops the value, such that the next request refreshes it.
+    pub fn invalidate(&self) {
+        self.entry.store(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_get_or_refresh() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let cached = Cached::<u32>::new();
+            let refreshes = AtomicU32::new(0);
+            let refresh = || async { Ok::<_, ()>(refreshes.fetch_add(1, Ordering::Relaxed) + 1) };
+            let ttl = Duration::from_secs(60);
+            assert_eq!(cached.get(ttl), None);
+            assert_eq!(*cached.get_or_refresh(ttl, refresh).await.unwrap(), 1);
+            assert_eq!(*cached.get_or_refresh(ttl, refresh).await.unwrap(), 1);
+            assert_eq!(
+                *cached
+                    .get_or_refresh(Duration::ZERO, refresh)
+                    .await
+                    .unwrap(),
+                2
+            );
+
+            // failed refreshes keep the previous value
+            let failed = cached
+                .get_or_refresh(Duration::ZERO, || async { Err("offline") })
+                .await;
+            assert_eq!(failed, Err("offline"));
+            assert_eq!(cached.get(ttl).as_deref(), Some(&2));
+
+            cached.invalidate();
+            assert_eq!(cached.get(ttl), None);
+            assert_eq!(*cached.get_or_refresh(ttl, refresh).await.unwrap(), 3);
+        });
+    }
+}
@@ -0,0 +1,360 @@
+use super::persisted_state::Persisted;
+use super::{PersistedChannelState, PersistedType};
+use crate::command::{CommandArguments, CommandProcessor};
+use crate::request::CommandRequest;
+use crate::response::Response;
+use crate::user::{User, UserArgument};
+use async_trait::async_trait;
+use chrono::NaiveTime;
+use core::fmt::{Display, Formatter};
+use state::TypeMap;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Settings of a channel, which are changed by moderators with `!config set <key> <value>`.
+///
+/// The config has to be registered with
+/// [`ContainerBuilder::register_persisted_type`](super::ContainerBuilder::register_persisted_type)
+/// and can be used with [`ChannelConfigState`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ChannelConfig {
+    /// Replaces `!` as the prefix of commands.
+    pub prefix: Option<String>,
+    pub language: Option<String>,
+    pub disabled_features: BTreeSet<String>,
+    /// Commands of users are ignored during quiet hours, moderators can still use commands.
+    pub quiet_hours: Option<QuietHours>,
+    /// Greets users on their first message, `{user}` and `{channel}` are replaced.
+    pub greeting: Option<String>,
+    pub greeting_scope: GreetingScope,
+    /// Users who chat for the first time within the cooldown are greeted together afterwards.
+    pub greeting_cooldown: Option<Duration>,
+}
+
+/// Whether users are greeted on their first message since the bot started or only once ever.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum GreetingScope {
+    #[default]
+    Session,
+    /// Requires [`GreetedUsers`](crate::greeting::GreetedUsers) to be registered.
+    Ever,
+}
+
+impl Display for GreetingScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            GreetingScope::Session => f.write_str("session"),
+            GreetingScope::Ever => f.write_str("ever"),
+        }
+    }
+}
+
+/// A time range in UTC, which can wrap around midnight, e.g. `22:00-06:00`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+impl Display for QuietHours {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelConfigError {
+    UnknownKey(String),
+    InvalidValue(&'static str),
+}
+
+impl Display for ChannelConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ChannelConfigError::UnknownKey(key) => write!(
+                f,
+                "unknown key {}, use prefix, language, quiet_hours, greeting, greeting_scope, greeting_cooldown or feature.<name>",
+                key
+            ),
+            ChannelConfigError::InvalidValue(expected) => write!(f, "expected {}", expected),
+        }
+    }
+}
+
+impl std::error::Error for ChannelConfigError {}
+
+fn parse_switch(value: &str) -> Result<bool, ChannelConfigError> {
+    match value {
+        "on" | "true" | "enabled" => Ok(true),
+        "off" | "false" | "disabled" => Ok(false),
+        _ => Err(ChannelConfigError::InvalidValue("on or off")),
+    }
+}
+
+fn is_off(value: &str) -> bool {
+    matches!(value, "off" | "none" | "default")
+}
+
+impl ChannelConfig {
+    pub fn is_enabled(&self, feature: &str) -> bool {
+        !self.disabled_features.contains(feature)
+    }
+
+    pub fn is_quiet(&self, time: NaiveTime) -> bool {
+        self.quiet_hours.is_some_and(|hours| hours.contains(time))
+    }
+
+    pub fn get(&self, key: &str) -> Result<String, ChannelConfigError> {
+        let value = match key {
+            "prefix" => self.prefix.clone().unwrap_or_else(|| "!".to_owned()),
+            "language" => self
+                .language
+                .clone()
+                .unwrap_or_else(|| "default".to_owned()),
+            "quiet_hours" => self
+                .quiet_hours
+                .map_or_else(|| "off".to_owned(), |hours| hours.to_string()),
+            "greeting" => self.greeting.clone().unwrap_or_else(|| "off".to_owned()),
+            "greeting_scope" => self.greeting_scope.to_string(),
+            "greeting_cooldown" => self.greeting_cooldown.map_or_else(
+                || "off".to_owned(),
+                |cooldown| humantime::format_duration(cooldown).to_string(),
+            ),
+            key => match key.strip_prefix("feature.") {
+                Some(feature) if self.is_enabled(feature) => "on".to_owned(),
+                Some(_) => "off".to_owned(),
+                None => return Err(ChannelConfigError::UnknownKey(key.to_owned())),
+            },
+        };
+        Ok(value)
+    }
+
+    /// Sets a value by key, `off` resets `prefix`, `language`, `quiet_hours`, `greeting` and
+    /// `greeting_cooldown`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ChannelConfigError> {
+        match key {
+            "prefix" if is_off(value) || value == "!" => self.prefix = None,
+            "prefix" if value.contains(char::is_whitespace) => {
+                return Err(ChannelConfigError::InvalidValue(
+                    "a prefix without whitespace",
+                ))
+            }
+            "prefix" => self.prefix = Some(value.to_owned()),
+            "language" if is_off(value) => self.language = None,
+            "language" => self.language = Some(value.to_owned()),
+            "quiet_hours" if is_off(value) => self.quiet_hours = None,
+            "quiet_hours" => {
+                let invalid = ChannelConfigError::InvalidValue("a time range like 22:00-06:00");
+                let (start, end) = value.split_once('-').ok_or(invalid.clone())?;
+                let parse = |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M");
+                self.quiet_hours = Some(QuietHours {
+                    start: parse(start).map_err(|_| invalid.clone())?,
+                    end: parse(end).map_err(|_| invalid)?,
+                });
+            }
+            "greeting" if is_off(value) => self.greeting = None,
+            "greeting" => self.greeting = Some(value.to_owned()),
+            "greeting_scope" => {
+                self.greeting_scope = match value {
+                    "session" => GreetingScope::Session,
+                    "ever" => GreetingScope::Ever,
+                    _ => return Err(ChannelConfigError::InvalidValue("session or ever")),
+                }
+            }
+            "greeting_cooldown" if is_off(value) => self.greeting_cooldown = None,
+            "greeting_cooldown" => {
+                self.greeting_cooldown = Some(
+                    humantime::parse_duration(value)
+                        .map_err(|_| ChannelConfigError::InvalidValue("a duration like 30s"))?,
+                )
+            }
+            key => match key.strip_prefix("feature.") {
+                Some(feature) if parse_switch(value)? => {
+                    self.disabled_features.remove(feature);
+                }
+                Some(feature) => {
+                    self.disabled_features.insert(feature.to_owned());
+                }
+                None => return Err(ChannelConfigError::UnknownKey(key.to_owned())),
+            },
+        }
+        Ok(())
+    }
+
+    /// Turns a message into a command starting with `!` if it starts with the prefix of the channel.
+    pub fn command<'m>(&self, message: &'m str) -> Option<Cow<'m, str>> {
+        prefixed_command(self.prefix.as_deref(), message)
+    }
+}
+
+/// The part of `message` after `prefix`, if it starts with `prefix` followed by a command name.
+fn strip_command_prefix<'m>(prefix: &str, message: &'m str) -> Option<&'m str> {
+    message
+        .trim_start()
+        .strip_prefix(prefix)
+        .filter(|rest| !rest.is_empty() && !rest.starts_with(char::is_whitespace))
+}
+
+/// Whether [`prefixed_command`] returns a command, without allocating it.
+pub(crate) fn has_command_prefix(prefix: Option<&str>, message: &str) -> bool {
+    match prefix {
+        None | Some("!") => message.trim_start().starts_with('!'),
+        Some(prefix) => strip_command_prefix(prefix, message).is_some(),
+    }
+}
+
+/// The command of `message` with `!` instead of `prefix`, if it starts with `prefix`.
+pub(crate) fn prefixed_command<'m>(prefix: Option<&str>, message: &'m str) -> Option<Cow<'m, str>> {
+    match prefix {
+        None | Some("!") => {
+            let message = message.trim_start();
+            message.starts_with('!').then_some(Cow::Borrowed(message))
+        }
+        Some(prefix) => {
+            strip_command_prefix(prefix, message).map(|rest| Cow::Owned(format!("!{}", rest)))
+        }
+    }
+}
+
+impl PersistedType for ChannelConfig {
+    const FILENAME: &'static str = "config";
+
+    fn init(_channel: &str) -> Self {
+        Self::default()
+    }
+}
+
+pub type ChannelConfigState<'req> = PersistedChannelState<'req, ChannelConfig>;
+
+/// Reads the config if it was registered for the channel.
+pub(crate) async fn read_channel_config(
+    channel_container: &TypeMap![Send + Sync],
+    channel: &str,
+) -> Option<Arc<ChannelConfig>> {
+    let persisted = channel_container.try_get::<Persisted<ChannelConfig>>()?;
+    Some(persisted.for_channel(channel).read().await)
+}
+
+/// Processes `!config get <key>` and `!config set <key> <value>` for moderators.
+pub struct ChannelConfigCommands;
+
+impl ChannelConfigCommands {
+    async fn process_config(
+        request: &CommandRequest<'_>,
+        arguments: &mut CommandArguments<'_>,
+    ) -> Result<String, Cow<'static, str>> {
+        let config = request
+            .context
+            .ok_or("the config is not available")?
+            .channel_state::<Persisted<ChannelConfig>>()
+            .map_err(|_| "the config is not registered for this channel")?;
+        let config = config.for_channel(request.channel().username());
+        let syntax = "!config get <key> or !config set <key> <value>";
+        match (arguments.next(), arguments.next()) {
+            (Some("get"), Some(key)) if arguments.as_str().is_empty() => {
+                let value = config.read().await.get(key);
+                value
+                    .map(|value| format!("{} is {}", key, value))
+                    .map_err(|e| e.to_string().into())
+            }
+            (Some("set"), Some(key)) => {
+                let value = arguments.next_rest().ok_or(syntax)?;
+                let mut result = Ok(());
+                config
+                    .maybe_update(|config| {
+                        let mut config = config.clone();
+                        result = config.set(key, value);
+                        result.is_ok().then_some(config)
+                    })
+                    .await;
+                result
+                    .map(|_| format!("{} is now {}", key, value))
+                    .map_err(|e| e.to_string().into())
+            }
+            _ => Err(syntax.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandProcessor for ChannelConfigCommands {
+    async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+        let mut arguments = CommandArguments::from(request.command() as &str);
+        if arguments.next() != Some("!config") {
+            return None;
+        }
+        let sender = request.sender();
+        if !sender.is_moderator() && !sender.is_broadcaster() {
+            return None;
+        }
+        let text = match Self::process_config(request, &mut arguments).await {
+            Ok(text) => text,
+            Err(error) => error.into_owned(),
+        };
+        Some(Response::new(format!(
+            "{} {}",
+            UserArgument::from(sender as &User),
+            text
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_get() {
+        let mut config = ChannelConfig::default();
+        assert_eq!(config.get("prefix").unwrap(), "!");
+        config.set("prefix", "?").unwrap();
+        config.set("feature.songs", "off").unwrap();
+        config.set("quiet_hours", "22:00-06:00").unwrap();
+        assert_eq!(config.get("prefix").unwrap(), "?");
+        assert_eq!(config.get("feature.songs").unwrap(), "off");
+        assert_eq!(config.get("quiet_hours").unwrap(), "22:00-06:00");
+        assert!(!config.is_enabled("songs"));
+        assert!(config.is_quiet(NaiveTime::from_hms_opt(23, 0, 0).unwrap()));
+        assert!(!config.is_quiet(NaiveTime::from_hms_opt(12, 0, 0).unwrap()));
+        assert_eq!(
+            config.set("volume", "10"),
+            Err(ChannelConfigError::UnknownKey("volume".to_owned()))
+        );
+        assert!(config.set("quiet_hours", "later").is_err());
+        config.set("greeting", "welcome {user}!").unwrap();
+        config.set("greeting_cooldown", "30s").unwrap();
+        assert_eq!(config.get("greeting_cooldown").unwrap(), "30s");
+        assert!(config.set("greeting_scope", "always").is_err());
+    }
+
+    #[test]
+    fn test_command_prefix() {
+        let mut config = ChannelConfig::default();
+        assert_eq!(config.command(" !song").as_deref(), Some("!song"));
+        config.set("prefix", "?").unwrap();
+        assert_eq!(config.command("?song add").as_deref(), Some("!song add"));
+        assert_eq!(config.command("!song"), None);
+        assert_eq!(config.command("? song"), None);
+        assert!(has_command_prefix(Some("?"), " ?song"));
+        assert!(!has_command_prefix(Some("?"), "? song"));
+        assert!(!has_command_prefix(None, "hello !song"));
+    }
+}
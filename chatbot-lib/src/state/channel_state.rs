@@ -1,4 +1,4 @@
-use super::persisted_state::{Persisted, PersistedType};
+use super::persisted_state::{DataDir, PendingWrites, Persisted, PersistedType, Reload};
 use core::borrow::Borrow;
 use core::fmt;
 use core::fmt::Display;
@@ -6,19 +6,17 @@ use core::hash::Hash;
 use derive_more::{Deref, From};
 use state::TypeMap;
 use std::collections::hash_map::Entry;
-use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::{collections::HashMap, unreachable};
 use tokio::sync::{RwLock, RwLockReadGuard};
 
 pub(crate) struct CachedChannelContainer<'a> {
-    #[allow(clippy::redundant_allocation)] // TODO: maybe there is a way to make all of this better
-    cache: HashMap<String, Rc<Arc<TypeMap![Send + Sync]>>>, // the Rc is just used, such that the Arc (within the Rc) can be copied without having to synchronise the atomic reference in the arc
+    cache: HashMap<String, Arc<TypeMap![Send + Sync]>>,
     container: &'a ChannelContainer,
 }
 
 impl<'a> CachedChannelContainer<'a> {
-    pub async fn get<'b, T: ?Sized>(&'b mut self, channel: &T) -> Rc<Arc<TypeMap![Send + Sync]>>
+    pub async fn get<'b, T: ?Sized>(&'b mut self, channel: &T) -> Arc<TypeMap![Send + Sync]>
     where
         String: Borrow<T>,
         T: Eq + Hash + ToOwned<Owned = String>,
@@ -27,39 +25,76 @@ impl<'a> CachedChannelContainer<'a> {
             Some(channel) => channel.clone(),
             None => match self.cache.entry(channel.to_owned()) {
                 Entry::Occupied(_) => unreachable!(),
-                Entry::Vacant(vacant) => vacant
-                    .insert(Rc::new(self.container.get_arc(channel).await))
-                    .clone(),
+                Entry::Vacant(vacant) => {
+                    vacant.insert(self.container.get_arc(channel).await).clone()
+                }
             },
         }
     }
 }
 
+/// The persisted types of a channel by their file name.
+struct Reloaders(HashMap<String, Arc<dyn Reload>>);
+
 pub struct ContainerBuilder {
     inner: TypeMap![Send + Sync],
+    pending_writes: PendingWrites,
+    data_dir: DataDir,
+    reloaders: Mutex<HashMap<String, Arc<dyn Reload>>>,
 }
 
 impl ContainerBuilder {
-    fn new() -> Self {
+    fn new(pending_writes: PendingWrites, data_dir: DataDir) -> Self {
         ContainerBuilder {
             inner: <TypeMap![Send + Sync]>::new(),
+            pending_writes,
+            data_dir,
+            reloaders: Default::default(),
         }
     }
 
     fn into_inner(self) -> TypeMap![Send + Sync] {
+        let reloaders = self.reloaders.into_inner().unwrap();
+        self.inner.set(Reloaders(reloaders));
         self.inner
     }
 
+    fn set_persisted<T: PersistedType>(&self, persisted: Persisted<T>) {
+        let reloader = persisted.reloader();
+        self.reloaders
+            .lock()
+            .unwrap()
+            .insert(reloader.file_name(), reloader);
+        self.inner.set(persisted);
+    }
+
     pub fn set<T: Send + Sync + 'static>(&self, value: T) {
         self.inner.set(value);
     }
 
     pub fn register_persisted_type<T: PersistedType>(&self) {
-        self.inner.set(Persisted::<T>::new());
+        self.set_persisted(Persisted::<T>::new(
+            self.pending_writes.clone(),
+            self.data_dir.clone(),
+        ));
+    }
+
+    /// Registers a persisted type which is stored in Redis and shared with other bots, see
+    /// [`SharedState`](super::SharedState).
+    #[cfg(feature = "redis")]
+    pub fn register_shared_type<T: PersistedType>(&self, store: &super::RedisStore) {
+        self.inner.set(super::SharedState::<T>::new(
+            store.clone(),
+            self.data_dir.clone(),
+        ));
     }
 
     pub fn register_persisted_value<T: PersistedType>(&self, value: T) {
-        self.inner.set(Persisted::<T>::from(value));
+        self.set_persisted(Persisted::<T>::from_value(
+            value,
+            self.pending_writes.clone(),
+            self.data_dir.clone(),
+        ));
     }
 }
 
@@ -68,6 +103,8 @@ pub type ChannelContainerTemplate = Box<dyn Fn(&str, &ContainerBuilder) + Send +
 pub struct ChannelContainer {
     container: RwLock<HashMap<String, Arc<TypeMap![Send + Sync]>>>,
     template: ChannelContainerTemplate,
+    pending_writes: PendingWrites,
+    data_dir: DataDir,
 }
 
 #[derive(From)]
@@ -86,9 +123,116 @@ impl ChannelContainer {
         Self {
             container: RwLock::new(HashMap::new()),
             template: f,
+            pending_writes: Default::default(),
+            data_dir: Default::default(),
+        }
+    }
+
+    /// Stores the persisted types in `data_dir` instead of `data`.
+    pub fn with_data_dir(mut self, data_dir: DataDir) -> Self {
+        self.data_dir = data_dir;
+        self
+    }
+
+    pub fn data_dir(&self) -> &DataDir {
+        &self.data_dir
+    }
+
+    /// Writes all pending debounced updates of persisted types to disk.
+    pub async fn flush(&self) {
+        self.pending_writes.flush().await;
+    }
+
+    /// Creates the containers of the channels and loads their persisted types, such that the first
+    /// messages in these channels do not wait for them, e.g. for the channels joined at startup.
+    pub async fn prewarm<I, S>(&self, channels: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for channel in channels {
+            let channel = channel.as_ref().trim_start_matches('#');
+            let container = self.get_arc(channel).await;
+            if let Some(reloaders) = container.try_get::<Reloaders>() {
+                for reloader in reloaders.0.values() {
+                    reloader.preload(channel).await;
+                }
+            }
+            log::debug!("Prepared the state of channel {}", channel);
         }
     }
 
+    /// The channels whose containers were created, in alphabetical order.
+    pub async fn channels(&self) -> Vec<String> {
+        let mut channels: Vec<_> = self.container.read().await.keys().cloned().collect();
+        channels.sort_unstable();
+        channels
+    }
+
+    /// Reads the persisted type stored in `file_name`, e.g. `quotes.ron`, from disk again the next
+    /// time it is used in the channel.
+    ///
+    /// Returns `false` if the channel was not used yet, no persisted type is stored in the file or
+    /// an update was not written to disk yet.
+    pub async fn reload(&self, channel: &str, file_name: &str) -> bool {
+        let container = match self.try_get_arc(channel).await {
+            Some(container) => container,
+            None => return false,
+        };
+        let reloader = match container
+            .try_get::<Reloaders>()
+            .and_then(|reloaders| reloaders.0.get(file_name))
+        {
+            Some(reloader) => reloader,
+            None => return false,
+        };
+        reloader.reload(channel).await
+    }
+
+    /// Watches the [`DataDir`] and reloads the persisted types of a channel whenever their files
+    /// are changed, e.g. by editing `data/<channel>/quotes.ron` while the bot is running.
+    ///
+    /// Runs until the future is dropped, only failing to start watching returns an error.
+    #[cfg(feature = "watch")]
+    pub async fn watch(&self) -> notify::Result<()> {
+        use notify::{EventKind, RecursiveMode, Watcher};
+
+        let dir = std::env::current_dir()?.join(self.data_dir.path());
+        tokio::fs::create_dir_all(&dir).await?;
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // the receiver is only dropped together with the watcher
+            let _ = sender.send(event);
+        })?;
+        watcher.watch(&dir, RecursiveMode::Recursive)?;
+        log::info!("Watching {} for changes", dir.display());
+        while let Some(event) = receiver.recv().await {
+            let event: notify::Event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!("Error watching {}: {:?}", dir.display(), e);
+                    continue;
+                }
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                continue;
+            }
+            for path in event.paths {
+                let channel = path
+                    .parent()
+                    .and_then(|parent| parent.file_name())
+                    .and_then(|channel| channel.to_str());
+                let file_name = path.file_name().and_then(|file_name| file_name.to_str());
+                if let (Some(channel), Some(file_name)) = (channel, file_name) {
+                    if self.reload(channel, file_name).await {
+                        log::info!("Reloaded {} of channel {}", file_name, channel);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub(crate) fn create_local_cache(&self) -> CachedChannelContainer {
         CachedChannelContainer {
             cache: Default::default(),
@@ -96,6 +240,11 @@ impl ChannelContainer {
         }
     }
 
+    /// The container of the channel, without creating it if it does not exist yet.
+    pub(crate) async fn try_get_arc(&self, channel: &str) -> Option<Arc<TypeMap![Send + Sync]>> {
+        self.container.read().await.get(channel).cloned()
+    }
+
     pub async fn get_arc<T: ?Sized>(&self, channel: &T) -> Arc<TypeMap![Send + Sync]>
     where
         String: Borrow<T>,
@@ -128,7 +277,7 @@ impl ChannelContainer {
         // insert new channel container
         let mut map = self.container.write().await;
         let key = channel.to_owned();
-        let value = ContainerBuilder::new();
+        let value = ContainerBuilder::new(self.pending_writes.clone(), self.data_dir.clone());
         (self.template)(&key, &value);
         let mut value = value.into_inner();
         value.freeze();
@@ -173,7 +322,7 @@ impl ChannelContainer {
         // insert new channel container
         let mut map = self.container.write().await;
         let key = channel.to_owned();
-        let value = ContainerBuilder::new();
+        let value = ContainerBuilder::new(self.pending_writes.clone(), self.data_dir.clone());
         (self.template)(&key, &value);
         let mut value = value.into_inner();
         value.freeze();
@@ -192,6 +341,8 @@ pub enum ChannelStateError {
     NoContext,
     NoChannelContainer,
     NoValue(&'static str),
+    /// The container of another channel was not created yet.
+    NoChannel(String),
 }
 
 impl Display for ChannelStateError {
@@ -199,6 +350,9 @@ impl Display for ChannelStateError {
         match self {
             ChannelStateError::NoContext => write!(f, "CommandRequest is missing context"),
             ChannelStateError::NoChannelContainer => write!(f, "No ChannelContainer was setup"),
+            ChannelStateError::NoChannel(channel) => {
+                write!(f, "No state was created for channel {}", channel)
+            }
             ChannelStateError::NoValue(type_name) => write!(
                 f,
                 "No value set for type {} in {}",
@@ -221,3 +375,60 @@ impl From<StateError> for ChannelStateError {
     }
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Greeting(String);
+
+    impl PersistedType for Greeting {
+        const FILENAME: &'static str = "greeting";
+
+        fn init(_channel: &str) -> Self {
+            Greeting("hello".to_owned())
+        }
+    }
+
+    #[test]
+    fn test_reload() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let channel = format!("chatbot-test-reload-{}", std::process::id());
+            let container = ChannelContainer::new(Box::new(|_channel, builder| {
+                builder.register_persisted_value(Greeting("hi".to_owned()));
+            }));
+            // the channel was not used yet
+            assert!(!container.reload(&channel, "greeting.ron").await);
+
+            let guard = container.get(channel.as_str()).await;
+            let greeting = guard.get::<Persisted<Greeting>>().for_channel(&channel);
+            assert_eq!(greeting.read().await.0, "hi");
+            assert!(!container.reload(&channel, "quotes.ron").await);
+            assert!(container.reload(&channel, "greeting.ron").await);
+            // there is no file, so the value is initialized again
+            assert_eq!(greeting.read().await.0, "hello");
+        });
+    }
+
+    #[test]
+    fn test_prewarm() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let channel = format!("chatbot-test-prewarm-{}", std::process::id());
+            let container = ChannelContainer::new(Box::new(|_channel, builder| {
+                builder.register_persisted_type::<Greeting>();
+            }));
+            assert!(container.channels().await.is_empty());
+            container.prewarm([format!("#{}", channel)]).await;
+            assert_eq!(container.channels().await, [channel.clone()]);
+            let guard = container.get(channel.as_str()).await;
+            assert!(guard.get::<Persisted<Greeting>>().is_loaded());
+        });
+    }
+}
@@ -1,37 +1,230 @@
+use super::persisted_state::{read_from_root, store_in_root, DataDir};
+use super::PersistedType;
+use crate::request::Bot;
 use crate::request::Channel;
 use crate::request::Sender;
+use crate::request::{CommandRequest, FromCommandRequest};
 use crate::user::ChannelId;
+use crate::user::NameMatching;
 use crate::user::OwnedUser;
 use crate::user::User;
 use crate::user::UserArgument;
 use crate::user::UserId;
 use async_trait::async_trait;
-use chashmap::CHashMap;
 use rand::seq::SliceRandom;
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fmt::Display;
 use std::sync::Arc;
-use std::sync::Mutex;
 use std::time::Duration;
 use std::time::Instant;
-use tokio::sync::RwLock;
+use std::time::SystemTime;
+use tokio::sync::{RwLock, TryLockError};
 use uuid::Uuid;
 
 #[derive(Debug)]
 struct UserEntry {
     username: String,
     display_name: Option<String>,
+    first_seen: SystemTime,
+    message_count: u64,
     last_chatted: Instant,
     last_message: String,
     last_message_id: MessageId,
 }
 
-// TODO: FIXME: use async synchronization instead! locking on an async thread might be bad
+impl UserEntry {
+    fn stats(&self) -> ChatterStats {
+        ChatterStats {
+            first_seen: self.first_seen,
+            message_count: self.message_count,
+            last_chatted: SystemTime::now() - self.last_chatted.elapsed(),
+        }
+    }
+
+    fn last_message(&self, user_id: UserId) -> LastMessage {
+        LastMessage {
+            user: OwnedUser::new(
+                self.username.clone(),
+                self.display_name.clone(),
+                Some(user_id),
+            ),
+            text: self.last_message.clone(),
+            id: self.last_message_id.to_string(),
+            sent_at: SystemTime::now() - self.last_chatted.elapsed(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatterStats {
+    first_seen: SystemTime,
+    message_count: u64,
+    last_chatted: SystemTime,
+}
+
+impl ChatterStats {
+    /// First message of the user since the bot started or the user was cleared from chat.
+    pub fn first_seen(&self) -> SystemTime {
+        self.first_seen
+    }
+
+    pub fn message_count(&self) -> u64 {
+        self.message_count
+    }
+
+    pub fn last_chatted(&self) -> SystemTime {
+        self.last_chatted
+    }
+}
+
+/// The last message of a user in a channel, see [`ChannelChatters::last_message`].
+#[derive(Debug, Clone)]
+pub struct LastMessage {
+    user: OwnedUser,
+    text: String,
+    id: String,
+    sent_at: SystemTime,
+}
+
+impl LastMessage {
+    /// The user with the username and display name used for the message.
+    pub fn user(&self) -> &OwnedUser {
+        &self.user
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The id of the message, e.g. to reply to it.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn sent_at(&self) -> SystemTime {
+        self.sent_at
+    }
+}
+
+/// Usernames of common chat bots, which are excluded by [`ChatterListOptions::exclude_bots`].
+pub const KNOWN_BOTS: &[&str] = &[
+    "nightbot",
+    "streamelements",
+    "streamlabs",
+    "moobot",
+    "fossabot",
+    "wizebot",
+    "soundalerts",
+    "sery_bot",
+];
+
+/// The order of the users returned by [`ChannelChatters::get_list`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChatterOrder {
+    /// The user who chatted last comes first.
+    #[default]
+    Recency,
+    /// Ordered by display name, or username if there is no display name.
+    Alphabetical,
+}
+
+/// Which users are returned by [`ChannelChatters::get_list`] and in which order.
+#[derive(Debug, Clone, Default)]
+pub struct ChatterListOptions<'a> {
+    order: ChatterOrder,
+    offset: usize,
+    limit: Option<usize>,
+    exclude: Vec<UserArgument<'a>>,
+}
+
+impl<'a> ChatterListOptions<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn order(mut self, order: ChatterOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Skips the first `offset` users, e.g. to show the next page.
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn exclude<I>(mut self, users: I) -> Self
+    where
+        I: IntoIterator<Item = UserArgument<'a>>,
+    {
+        self.exclude.extend(users);
+        self
+    }
+
+    /// Excludes the [`KNOWN_BOTS`].
+    pub fn exclude_bots(self) -> Self {
+        self.exclude(KNOWN_BOTS.iter().copied().map(UserArgument::from_username))
+    }
+
+    pub fn exclude_self(self, bot: &Bot<'a>) -> Self {
+        let username = bot.username();
+        self.exclude([UserArgument::from_username(username)])
+    }
+}
+
+#[derive(Debug)]
+pub enum ChatterStatsError {
+    NoContext,
+    Busy,
+    Unknown,
+}
+
+impl Display for ChatterStatsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            ChatterStatsError::NoContext => write!(f, "CommandRequest is missing context"),
+            ChatterStatsError::Busy => write!(f, "Chatters are currently being updated"),
+            ChatterStatsError::Unknown => write!(f, "Chatter has not been seen yet"),
+        }
+    }
+}
+
+impl std::error::Error for ChatterStatsError {}
+
+impl<'a, 'req> FromCommandRequest<'a, 'req> for ChatterStats {
+    type Error = ChatterStatsError;
+
+    fn from_command_request(request: &'a CommandRequest<'req>) -> Result<Self, Self::Error> {
+        let chatters = request
+            .context
+            .ok_or(ChatterStatsError::NoContext)?
+            .chatters();
+        chatters
+            .try_stats(request.channel(), request.sender())
+            .map_err(|_| ChatterStatsError::Busy)?
+            .ok_or(ChatterStatsError::Unknown)
+    }
+}
+
+type ChannelEntries = Arc<RwLock<HashMap<UserId, UserEntry>>>;
+
 #[derive(Debug, Clone, Default)]
 pub struct ChannelChatters {
-    chatters: Arc<CHashMap<ChannelId, Arc<CHashMap<UserId, UserEntry>>>>,
-    channels: Arc<CHashMap<String, ChannelId>>,
+    chatters: Arc<RwLock<HashMap<ChannelId, ChannelEntries>>>,
+    channels: Arc<RwLock<HashMap<String, ChannelId>>>,
     all_chatters: Arc<RwLock<AllChatters>>,
     all_channels: Arc<RwLock<AllChannels>>,
+    // usernames of users in the channel, including lurkers, by channel name
+    presence: Arc<RwLock<HashMap<String, HashSet<String>>>>,
+    matching: NameMatching,
+    data_dir: DataDir,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -40,20 +233,29 @@ struct AllChatters {
     display_names: HashMap<String, usize>,
     user_ids: HashMap<UserId, usize>,
     users: Vec<OwnedUser>,
+    // true if users changed since the last snapshot
+    changed: bool,
+    // the usernames and display names are stored by their key
+    matching: NameMatching,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ChattersSnapshot {
+    users: Vec<OwnedUser>,
+}
 
-struct AllChannels {
-    chatters: AllChatters,
-    //entries: Vec<ChannelEntry>,
+impl PersistedType for ChattersSnapshot {
+    const FILENAME: &'static str = "chatters";
+
+    fn init(_channel: &str) -> Self {
+        ChattersSnapshot { users: vec![] }
+    }
 }
-/*
-#[derive(Debug, Clone, Default)]
 
-struct ChannelEntry {
+#[derive(Debug, Clone, Default)]
+struct AllChannels {
+    chatters: AllChatters,
 }
-*/
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum MessageId {
@@ -72,6 +274,15 @@ impl PartialEq<str> for MessageId {
     }
 }
 
+impl Display for MessageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageId::String(id) => write!(f, "{}", id),
+            MessageId::Uuid(id) => write!(f, "{}", id),
+        }
+    }
+}
+
 impl From<&str> for MessageId {
     fn from(value: &str) -> Self {
         match Uuid::try_parse(value) {
@@ -117,15 +328,18 @@ impl AllChatters {
         if let Some(user_id) = chatter.user_id() {
             self.user_ids.get(&user_id).cloned()
         } else {
-            self.usernames.get(chatter.username()).cloned()
+            self.usernames
+                .get(self.matching.key(chatter.username()).as_ref())
+                .cloned()
         }
     }
 
     fn index_from_userargument(&self, user: &UserArgument) -> Option<usize> {
+        let key = self.matching.key(user.as_argument());
         self.usernames
-            .get(user.as_argument())
+            .get(key.as_ref())
+            .or_else(|| self.display_names.get(key.as_ref()))
             .cloned()
-            .or_else(|| self.display_names.get(user.as_argument()).cloned())
     }
 
     fn needs_update_or_insert(&self, chatter: &User) -> Option<usize> {
@@ -156,11 +370,14 @@ impl AllChatters {
             }
         );
 
+        self.changed = true;
         let index = self.users.len();
         self.users.push(OwnedUser::from_user(chatter));
-        self.usernames.insert(chatter.username().to_owned(), index);
+        self.usernames
+            .insert(self.matching.key(chatter.username()).into_owned(), index);
         if let Some(display_name) = chatter.display_name() {
-            self.display_names.insert(display_name.to_owned(), index);
+            self.display_names
+                .insert(self.matching.key(display_name).into_owned(), index);
         }
         if let Some(user_id) = chatter.user_id() {
             self.user_ids.insert(user_id, index);
@@ -175,6 +392,12 @@ impl AllChatters {
             let previous_username = user.update_username(chatter.username());
             let previous_display_name = user.update_display_name(chatter.display_name());
             let insert_user_id = user.set_user_id(chatter.user_id());
+            if previous_username.is_some()
+                || previous_display_name.is_some()
+                || insert_user_id.is_some()
+            {
+                self.changed = true;
+            }
             if let Some(previous_username) = previous_username {
                 log::debug!(
                     "username changed from {} to {} [user id {:?}]",
@@ -182,8 +405,10 @@ impl AllChatters {
                     chatter.username(),
                     chatter.user_id()
                 );
-                self.usernames.remove(&previous_username);
-                self.usernames.insert(chatter.username().to_owned(), index);
+                self.usernames
+                    .remove(self.matching.key(&previous_username).as_ref());
+                self.usernames
+                    .insert(self.matching.key(chatter.username()).into_owned(), index);
             }
             if let Some(previous_display_name) = previous_display_name {
                 log::debug!(
@@ -194,10 +419,12 @@ impl AllChatters {
                     chatter.user_id()
                 );
                 if let Some(previous_display_name) = previous_display_name {
-                    self.display_names.remove(&previous_display_name);
+                    self.display_names
+                        .remove(self.matching.key(&previous_display_name).as_ref());
                 }
                 if let Some(display_name) = chatter.display_name() {
-                    self.display_names.insert(display_name.to_owned(), index);
+                    self.display_names
+                        .insert(self.matching.key(display_name).into_owned(), index);
                 }
             }
             if let Some(user_id) = insert_user_id {
@@ -221,6 +448,31 @@ impl ChannelChatters {
         ChannelChatters::default()
     }
 
+    /// Looks up users by comparing their names with `matching` instead of the default.
+    pub fn with_name_matching(matching: NameMatching) -> Self {
+        Self {
+            all_chatters: Arc::new(RwLock::new(AllChatters {
+                matching,
+                ..AllChatters::default()
+            })),
+            matching,
+            ..Self::default()
+        }
+    }
+
+    pub fn name_matching(&self) -> NameMatching {
+        self.matching
+    }
+
+    /// Writes the snapshots to `data_dir` instead of `data`.
+    pub fn with_data_dir(self, data_dir: DataDir) -> Self {
+        Self { data_dir, ..self }
+    }
+
+    pub fn data_dir(&self) -> &DataDir {
+        &self.data_dir
+    }
+
     pub async fn get<'a, 'b, T: 'a>(&self, user: T) -> Option<OwnedUser>
     where
         T: Into<UserArgument<'a>>,
@@ -233,38 +485,94 @@ impl ChannelChatters {
             .map(|index| chatters.users[index].clone())
     }
 
+    /// Restores known users from the last snapshot written by [`ChannelChatters::snapshot`].
+    pub async fn restore(&self) -> anyhow::Result<()> {
+        let snapshot = read_from_root::<ChattersSnapshot>(&self.data_dir).await?;
+        if let Some(snapshot) = snapshot {
+            let mut chatters = self.all_chatters.write().await;
+            let changed = chatters.changed;
+            for user in &snapshot.users {
+                chatters.update_or_insert(&User::from_owned(user));
+            }
+            // restored users do not have to be written again
+            chatters.changed = changed;
+            log::info!("Restored {} chatters", snapshot.users.len());
+        }
+        Ok(())
+    }
+
+    /// Writes all known users to disk, if they changed since the last snapshot.
+    pub async fn snapshot(&self) -> anyhow::Result<()> {
+        let snapshot = {
+            let mut chatters = self.all_chatters.write().await;
+            if !chatters.changed {
+                return Ok(());
+            }
+            chatters.changed = false;
+            ChattersSnapshot {
+                users: chatters.users.clone(),
+            }
+        };
+        let result = store_in_root(&self.data_dir, Arc::new(snapshot)).await;
+        if result.is_err() {
+            // try again with the next snapshot
+            self.all_chatters.write().await.changed = true;
+        }
+        result
+    }
+
+    /// Spawns a task which writes a snapshot of all known users every `interval`.
+    pub fn spawn_snapshots(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let chatters = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            // the first tick completes immediately
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(e) = chatters.snapshot().await {
+                    log::error!("Error saving chatters to disk: {:?}", e);
+                }
+            }
+        })
+    }
+
+    async fn channel_id(&self, channel: &'_ Channel<'_>) -> Option<ChannelId> {
+        match channel.channel_id() {
+            Some(channel_id) => Some(channel_id),
+            None => self.channels.read().await.get(channel.username()).copied(),
+        }
+    }
+
+    async fn channel_chatters(&self, channel_id: ChannelId) -> Option<ChannelEntries> {
+        // the lock of all channels is released before locking the chatters of the channel
+        self.chatters.read().await.get(&channel_id).cloned()
+    }
+
     pub async fn clear_chat(
         &self,
         channel: &'_ Channel<'_>,
         user_id: Option<UserId>,
         name: Option<&str>,
     ) {
-        fn clear(
-            chatters: &ChannelChatters,
-            channel_id: ChannelId,
-            user_id: Option<UserId>,
-            name: Option<&str>,
-        ) {
-            let chatters = chatters.chatters.get(&channel_id);
-            if let Some(chatters) = chatters {
-                if let Some(user_id) = user_id {
-                    chatters.remove(&user_id);
-                } else if let Some(username) = name {
-                    // slow :(
-                    chatters.retain(|_key, value| value.username != username);
-                } else {
-                    chatters.clear();
-                }
+        let channel_id = match self.channel_id(channel).await {
+            Some(channel_id) => channel_id,
+            None => {
+                // fallback clear all chatters from all channels D:
+                self.chatters.write().await.clear();
+                return;
+            }
+        };
+        if let Some(chatters) = self.channel_chatters(channel_id).await {
+            let mut chatters = chatters.write().await;
+            if let Some(user_id) = user_id {
+                chatters.remove(&user_id);
+            } else if let Some(username) = name {
+                // slow :(
+                chatters.retain(|_key, value| value.username != username);
+            } else {
+                chatters.clear();
             }
-        }
-
-        if let Some(channel_id) = channel.user_id() {
-            clear(self, channel_id, user_id, name);
-        } else if let Some(channel_id) = self.channels.get(channel.username()) {
-            clear(self, *channel_id, user_id, name);
-        } else {
-            // fallback clear all chatters from all channels D:
-            self.chatters.clear();
         }
     }
 
@@ -274,119 +582,300 @@ impl ChannelChatters {
         message_id: Option<&'_ str>,
         login: Option<&'_ str>,
     ) {
-        fn clear(
-            chatters: &ChannelChatters,
-            channel_id: ChannelId,
-            message_id: Option<&str>,
-            login: Option<&str>,
-        ) {
-            let chatters = chatters.chatters.get(&channel_id);
-            if let Some(chatters) = chatters {
-                if message_id.is_some() || login.is_some() {
-                    // slow :(
-                    let message_id: Option<MessageId> = message_id.map(MessageId::from);
-                    chatters.retain(|_key, value| {
-                        message_id
-                            .as_ref()
-                            .map_or(true, |message_id| &value.last_message_id != message_id)
-                            && login.map_or(true, |username| value.username != username)
-                    });
-                } else {
-                    // fallback clear all chatters D:
-                    chatters.clear();
-                }
+        let channel_id = match self.channel_id(channel).await {
+            Some(channel_id) => channel_id,
+            None => {
+                // fallback clear all chatters from all channels D:
+                self.chatters.write().await.clear();
+                return;
+            }
+        };
+        if let Some(chatters) = self.channel_chatters(channel_id).await {
+            let mut chatters = chatters.write().await;
+            if message_id.is_some() || login.is_some() {
+                // slow :(
+                let message_id: Option<MessageId> = message_id.map(MessageId::from);
+                chatters.retain(|_key, value| {
+                    message_id
+                        .as_ref()
+                        .map_or(true, |message_id| &value.last_message_id != message_id)
+                        && login.map_or(true, |username| value.username != username)
+                });
+            } else {
+                // fallback clear all chatters D:
+                chatters.clear();
             }
-        }
-
-        if let Some(channel_id) = channel.user_id() {
-            clear(self, channel_id, message_id, login);
-        } else if let Some(channel_id) = self.channels.get(channel.username()) {
-            clear(self, *channel_id, message_id, login);
-        } else {
-            // fallback clear all chatters from all channels D:
-            self.chatters.clear();
         }
     }
 
+    /// Records a chat message, returns `true` if it is the first message of the user in the channel
+    /// since the bot started or the user was cleared from chat.
     pub async fn notice_chatter(
         &self,
         channel: &'_ Channel<'_>,
         sender: &'_ Sender<'_>,
         data: &str,
         message_id: &str,
-    ) {
+    ) -> bool {
         self.all_chatters.notice_chatter(sender).await;
         self.all_channels.notice_chatter(channel).await;
+        self.join(channel, sender.username()).await;
 
-        let user_entry = || UserEntry {
-            username: sender.username().to_owned(),
-            display_name: sender.display_name().map(String::from),
-            last_chatted: Instant::now(),
-            last_message: data.to_owned(),
-            last_message_id: message_id.into(),
+        // messages sent by the bot only have the name of the channel
+        let (channel_id, user_id) = match (self.channel_id(channel).await, sender.user_id()) {
+            (Some(channel_id), Some(user_id)) => (channel_id, user_id),
+            _ => return false,
+        };
+        let chatters = match self.channel_chatters(channel_id).await {
+            Some(chatters) => chatters,
+            None => {
+                self.channels
+                    .write()
+                    .await
+                    .insert(channel.username().into(), channel_id);
+                self.chatters
+                    .write()
+                    .await
+                    .entry(channel_id)
+                    .or_default()
+                    .clone()
+            }
         };
-        if let Some(channel_id) = channel.user_id() {
-            if let Some(user_id) = sender.user_id() {
-                let chatters = self.chatters.get(&channel_id);
-                if let Some(chatters) = chatters {
-                    chatters.upsert(user_id, user_entry, |user| {
-                        user.last_chatted = Instant::now();
-                        if user.last_message != data {
-                            user.last_message = data.to_owned();
-                        }
-                        if &user.last_message_id != message_id {
-                            user.last_message_id = message_id.into();
-                        }
-                        if user.username != sender.username() {
-                            user.username = sender.username().to_owned();
-                        }
-                        if user.display_name.as_deref() != sender.display_name() {
-                            user.display_name = sender.display_name().map(String::from);
-                        }
-                    });
-                } else {
-                    drop(chatters);
-                    self.channels.insert(channel.username().into(), channel_id);
-                    let chatters = Arc::new(CHashMap::new());
-                    chatters.insert(user_id, (user_entry)());
-                    self.chatters.insert(channel_id, chatters);
+        let mut chatters = chatters.write().await;
+        let is_new = match chatters.entry(user_id) {
+            Entry::Occupied(mut entry) => {
+                let user = entry.get_mut();
+                user.message_count += 1;
+                user.last_chatted = Instant::now();
+                if user.last_message != data {
+                    user.last_message = data.to_owned();
                 }
+                if &user.last_message_id != message_id {
+                    user.last_message_id = message_id.into();
+                }
+                if user.username != sender.username() {
+                    user.username = sender.username().to_owned();
+                }
+                if user.display_name.as_deref() != sender.display_name() {
+                    user.display_name = sender.display_name().map(String::from);
+                }
+                false
             }
-        }
+            Entry::Vacant(entry) => {
+                entry.insert(UserEntry {
+                    username: sender.username().to_owned(),
+                    display_name: sender.display_name().map(String::from),
+                    first_seen: SystemTime::now(),
+                    message_count: 1,
+                    last_chatted: Instant::now(),
+                    last_message: data.to_owned(),
+                    last_message_id: message_id.into(),
+                });
+                true
+            }
+        };
         // TODO: add some cleanup to chatters maybe from time to time
-        // log::error!("{:?}", self.chatters);
+        is_new
+    }
+
+    /// Returns the stats of a user which chatted in the channel.
+    pub async fn stats<'a, T>(&self, channel_id: ChannelId, user: T) -> Option<ChatterStats>
+    where
+        T: Into<UserArgument<'a>> + 'a,
+    {
+        let argument = user.into();
+        let chatters = self.channel_chatters(channel_id).await?;
+        let chatters = chatters.read().await;
+        chatters
+            .iter()
+            .find(|(user_id, v)| self.is_entry_of(&argument, **user_id, v))
+            .map(|(_, v)| v.stats())
+    }
+
+    /// Returns the last message of a user in the channel, e.g. for `!lastseen <user>`.
+    pub async fn last_message<'a, T>(&self, channel_id: ChannelId, user: T) -> Option<LastMessage>
+    where
+        T: Into<UserArgument<'a>> + 'a,
+    {
+        let argument = user.into();
+        let chatters = self.channel_chatters(channel_id).await?;
+        let chatters = chatters.read().await;
+        chatters
+            .iter()
+            .find(|(user_id, v)| self.is_entry_of(&argument, **user_id, v))
+            .map(|(user_id, v)| v.last_message(*user_id))
+    }
+
+    fn is_entry_of(&self, argument: &UserArgument, user_id: UserId, entry: &UserEntry) -> bool {
+        argument.matches(
+            &User::new(
+                &entry.username,
+                entry.display_name.as_deref(),
+                Some(user_id),
+            ),
+            self.matching,
+        )
+    }
+
+    // used by extractors, which can not wait for the locks
+    fn try_stats(
+        &self,
+        channel: &'_ Channel<'_>,
+        user: &'_ User<'_>,
+    ) -> Result<Option<ChatterStats>, TryLockError> {
+        let channel_id = match channel.channel_id() {
+            Some(channel_id) => channel_id,
+            None => match self.channels.try_read()?.get(channel.username()) {
+                Some(channel_id) => *channel_id,
+                None => return Ok(None),
+            },
+        };
+        let chatters = match self.chatters.try_read()?.get(&channel_id) {
+            Some(chatters) => chatters.clone(),
+            None => return Ok(None),
+        };
+        let chatters = chatters.try_read()?;
+        Ok(match user.user_id() {
+            Some(user_id) => chatters.get(&user_id).map(UserEntry::stats),
+            None => chatters
+                .values()
+                .find(|v| v.username == user.username())
+                .map(UserEntry::stats),
+        })
+    }
+
+    /// Marks a user as present in the channel, e.g. when receiving a `JOIN`.
+    pub async fn join(&self, channel: &'_ Channel<'_>, username: &str) {
+        let presence = self.presence.read().await;
+        if presence
+            .get(channel.username())
+            .is_some_and(|users| users.contains(username))
+        {
+            return;
+        }
+        drop(presence);
+        self.presence
+            .write()
+            .await
+            .entry(channel.username().to_owned())
+            .or_default()
+            .insert(username.to_owned());
+    }
+
+    /// Marks a user as no longer present in the channel, e.g. when receiving a `PART`.
+    pub async fn part(&self, channel: &'_ Channel<'_>, username: &str) {
+        if let Some(users) = self.presence.write().await.get_mut(channel.username()) {
+            users.remove(username);
+        }
     }
 
+    /// Forgets all users present in the channel, e.g. when the bot left the channel.
+    pub async fn clear_presence(&self, channel: &'_ Channel<'_>) {
+        self.presence.write().await.remove(channel.username());
+    }
+
+    /// Returns true if the user joined the channel or chatted in it and did not leave since.
+    ///
+    /// Users which did not chat are only known if the membership capability is requested.
+    pub async fn is_present<'a, T>(&self, channel: &'_ Channel<'_>, user: T) -> bool
+    where
+        T: Into<UserArgument<'a>> + 'a,
+    {
+        let argument = user.into();
+        {
+            let presence = self.presence.read().await;
+            match presence.get(channel.username()) {
+                Some(users)
+                    if users.contains(self.matching.key(argument.as_argument()).as_ref()) =>
+                {
+                    return true
+                }
+                Some(_) => {}
+                None => return false,
+            }
+        }
+        // the argument might be a display name
+        match self.get(&argument).await {
+            Some(user) => self
+                .presence
+                .read()
+                .await
+                .get(channel.username())
+                .is_some_and(|users| users.contains(user.username())),
+            None => false,
+        }
+    }
+
+    /// Returns the usernames of all users present in the channel, including lurkers.
+    pub async fn get_present_list(&self, channel: &'_ Channel<'_>) -> Vec<String> {
+        self.presence
+            .read()
+            .await
+            .get(channel.username())
+            .map(|users| users.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the users which chatted within the last `within`.
     pub async fn get_list(
         &self,
         channel_id: ChannelId,
-        from: Duration,
-        display_name: bool,
-    ) -> Vec<String> {
-        if let Some(chatters) = self.chatters.get(&channel_id) {
-            let chatters = chatters.clone();
-            // read guard should be dropped here
-            // TODO: this is very slow and bad code :(
-            // but it is not called often, so maybe it's fine?
-            let result = Arc::new(Mutex::new(vec![]));
-            chatters.retain(|_, v| {
-                if v.last_chatted.elapsed() < from {
-                    let mut result = result.lock().unwrap();
-                    if display_name {
-                        if let Some(display_name) = &v.display_name {
-                            result.push(display_name.clone());
-                        } else {
-                            result.push(v.username.clone());
-                        }
-                    } else {
-                        result.push(v.username.clone());
-                    }
-                }
-                true
-            });
-            return Arc::try_unwrap(result).unwrap().into_inner().unwrap();
+        within: Duration,
+        options: &ChatterListOptions<'_>,
+    ) -> Vec<OwnedUser> {
+        let chatters = match self.channel_chatters(channel_id).await {
+            Some(chatters) => chatters,
+            None => return vec![],
+        };
+        let chatters = chatters.read().await;
+        let mut list: Vec<(&UserId, &UserEntry)> = chatters
+            .iter()
+            .filter(|(_, v)| v.last_chatted.elapsed() < within)
+            .filter(|(user_id, v)| {
+                !options
+                    .exclude
+                    .iter()
+                    .any(|excluded| self.is_entry_of(excluded, **user_id, v))
+            })
+            .collect();
+        match options.order {
+            ChatterOrder::Recency => list.sort_by_key(|(_, v)| std::cmp::Reverse(v.last_chatted)),
+            ChatterOrder::Alphabetical => list.sort_by_cached_key(|(_, v)| {
+                self.matching
+                    .key(v.display_name.as_ref().unwrap_or(&v.username))
+                    .into_owned()
+            }),
         }
-        vec![]
+        list.into_iter()
+            .skip(options.offset)
+            .take(options.limit.unwrap_or(usize::MAX))
+            .map(|(user_id, v)| {
+                OwnedUser::new(v.username.clone(), v.display_name.clone(), Some(*user_id))
+            })
+            .collect()
+    }
+
+    /// Picks a random user which chatted within the last `within`, excluding the given users.
+    pub async fn get_random_chatter(
+        &self,
+        channel_id: ChannelId,
+        within: Duration,
+        exclude: &[UserArgument<'_>],
+    ) -> Option<OwnedUser> {
+        let chatters = self.channel_chatters(channel_id).await?;
+        let chatters = chatters.read().await;
+        let list: Vec<User> = chatters
+            .iter()
+            .filter(|(_, v)| v.last_chatted.elapsed() < within)
+            .map(|(user_id, v)| User::new(&v.username, v.display_name.as_deref(), Some(*user_id)))
+            .filter(|user| {
+                !exclude
+                    .iter()
+                    .any(|excluded| excluded.matches(user, self.matching))
+            })
+            .collect();
+        let mut rng = rand::thread_rng();
+        list.choose(&mut rng).map(OwnedUser::from_user)
     }
 
     pub async fn get_random_message(
@@ -394,23 +883,218 @@ impl ChannelChatters {
         channel_id: ChannelId,
         from: Duration,
     ) -> Option<String> {
-        if let Some(chatters) = self.chatters.get(&channel_id) {
-            let chatters = chatters.clone();
-            // read guard should be dropped here
-            // TODO: this is very slow and bad code :(
-            // but it is not called often, so maybe it's fine?
-            let result = Arc::new(Mutex::new(vec![]));
-            chatters.retain(|_, v| {
-                if v.last_chatted.elapsed() < from {
-                    let mut result = result.lock().unwrap();
-                    result.push(v.last_message.clone());
-                }
-                true
-            });
-            let list = Arc::try_unwrap(result).unwrap().into_inner().unwrap();
-            let mut rng = rand::thread_rng();
-            return list.choose(&mut rng).map(|x| x.to_owned());
-        }
-        None
+        let chatters = self.channel_chatters(channel_id).await?;
+        let chatters = chatters.read().await;
+        let list: Vec<&String> = chatters
+            .values()
+            .filter(|v| v.last_chatted.elapsed() < from)
+            .map(|v| &v.last_message)
+            .collect();
+        let mut rng = rand::thread_rng();
+        list.choose(&mut rng).map(|x| (*x).to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChannelChatters, ChatterListOptions, ChatterOrder};
+    use crate::request::{Bot, Channel, Sender};
+    use crate::state::DataDir;
+    use crate::user::{ChannelId, OwnedUser, User, UserArgument, UserId};
+    use std::future::Future;
+    use std::time::Duration;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_get_random_chatter() {
+        block_on(async {
+            let chatters = ChannelChatters::new();
+            let channel: Channel = User::new("liquidnya", None, Some(UserId::new(1))).into();
+            let alice: Sender = User::new("alice", Some("Alice"), Some(UserId::new(2))).into();
+            let bob: Sender = User::new("bob", None, Some(UserId::new(3))).into();
+            chatters.notice_chatter(&channel, &alice, "hi", "a").await;
+            chatters.notice_chatter(&channel, &bob, "hello", "b").await;
+
+            let exclude = [UserArgument::new("@Alice")];
+            let chatter = chatters
+                .get_random_chatter(ChannelId::new(1), Duration::from_secs(60), &exclude)
+                .await
+                .unwrap();
+            assert_eq!(chatter.username(), "bob");
+
+            let exclude = [UserArgument::new("alice"), UserArgument::new("bob")];
+            let chatter = chatters
+                .get_random_chatter(ChannelId::new(1), Duration::from_secs(60), &exclude)
+                .await;
+            assert!(chatter.is_none());
+            let chatter = chatters
+                .get_random_chatter(ChannelId::new(2), Duration::from_secs(60), &[])
+                .await;
+            assert!(chatter.is_none());
+        });
+    }
+
+    #[test]
+    fn test_stats() {
+        block_on(async {
+            let chatters = ChannelChatters::new();
+            let channel: Channel = User::new("liquidnya", None, Some(UserId::new(1))).into();
+            let alice: Sender = User::new("alice", Some("Alice"), Some(UserId::new(2))).into();
+            assert!(chatters.notice_chatter(&channel, &alice, "hi", "a").await);
+            assert!(
+                !chatters
+                    .notice_chatter(&channel, &alice, "hi again", "b")
+                    .await
+            );
+
+            let stats = chatters
+                .stats(ChannelId::new(1), UserArgument::new("@Alice"))
+                .await
+                .unwrap();
+            assert_eq!(stats.message_count(), 2);
+            let stats = chatters.try_stats(&channel, &alice).unwrap().unwrap();
+            assert_eq!(stats.message_count(), 2);
+            assert!(chatters
+                .stats(ChannelId::new(1), UserArgument::new("bob"))
+                .await
+                .is_none());
+        });
+    }
+
+    #[test]
+    fn test_last_message() {
+        block_on(async {
+            let chatters = ChannelChatters::new();
+            let channel: Channel = User::new("liquidnya", None, Some(UserId::new(1))).into();
+            let alice: Sender = User::new("alice", Some("Alice"), Some(UserId::new(2))).into();
+            chatters.notice_chatter(&channel, &alice, "hi", "a").await;
+            chatters
+                .notice_chatter(&channel, &alice, "hello everyone", "b")
+                .await;
+
+            let message = chatters
+                .last_message(ChannelId::new(1), UserArgument::new("@alice"))
+                .await
+                .unwrap();
+            assert_eq!(message.text(), "hello everyone");
+            assert_eq!(message.id(), "b");
+            assert_eq!(message.user().display_name(), Some("Alice"));
+            assert!(message.sent_at() <= std::time::SystemTime::now());
+            assert!(chatters
+                .last_message(ChannelId::new(2), UserArgument::new("alice"))
+                .await
+                .is_none());
+        });
+    }
+
+    #[test]
+    fn test_get_list() {
+        block_on(async {
+            let chatters = ChannelChatters::new();
+            let channel: Channel = User::new("liquidnya", None, Some(UserId::new(1))).into();
+            let bot: Bot = User::new("helperblock", None, Some(UserId::new(2))).into();
+            for (index, name) in ["carol", "Alice", "nightbot", "helperblock", "bob"]
+                .into_iter()
+                .enumerate()
+            {
+                let username = name.to_lowercase();
+                let user: Sender =
+                    User::new(&username, Some(name), Some(UserId::new(index as i64 + 3))).into();
+                chatters.notice_chatter(&channel, &user, "hi", name).await;
+                std::thread::sleep(Duration::from_millis(2));
+            }
+            let names = |list: Vec<OwnedUser>| {
+                list.iter()
+                    .map(|user| user.username().to_owned())
+                    .collect::<Vec<_>>()
+            };
+
+            let options = ChatterListOptions::new().exclude_bots().exclude_self(&bot);
+            let list = chatters
+                .get_list(ChannelId::new(1), Duration::from_secs(60), &options)
+                .await;
+            assert_eq!(names(list), ["bob", "alice", "carol"]);
+
+            let options = options.order(ChatterOrder::Alphabetical).offset(1).limit(1);
+            let list = chatters
+                .get_list(ChannelId::new(1), Duration::from_secs(60), &options)
+                .await;
+            assert_eq!(names(list), ["bob"]);
+        });
+    }
+
+    #[test]
+    fn test_case_insensitive_lookup() {
+        block_on(async {
+            let chatters = ChannelChatters::new();
+            let channel: Channel = User::new("liquidnya", None, Some(UserId::new(1))).into();
+            let user: Sender = User::new("someuser", Some("SomeUser"), Some(UserId::new(2))).into();
+            chatters.notice_chatter(&channel, &user, "hi", "a").await;
+            for argument in ["@SOMEUSER", "someUser", "SomeUser"] {
+                let found = chatters.get(UserArgument::new(argument)).await.unwrap();
+                assert_eq!(found.display_name(), Some("SomeUser"));
+            }
+            assert!(
+                chatters
+                    .is_present(&channel, UserArgument::new("@SomeUser"))
+                    .await
+            );
+        });
+    }
+
+    #[test]
+    fn test_presence() {
+        block_on(async {
+            let chatters = ChannelChatters::new();
+            let channel: Channel = User::new("liquidnya", None, Some(UserId::new(1))).into();
+            let alice: Sender = User::new("alice", Some("Alice"), Some(UserId::new(2))).into();
+            chatters.join(&channel, "lurker").await;
+            chatters.notice_chatter(&channel, &alice, "hi", "a").await;
+            assert!(
+                chatters
+                    .is_present(&channel, UserArgument::new("lurker"))
+                    .await
+            );
+            assert!(
+                chatters
+                    .is_present(&channel, UserArgument::new("@Alice"))
+                    .await
+            );
+
+            chatters.part(&channel, "lurker").await;
+            assert!(
+                !chatters
+                    .is_present(&channel, UserArgument::new("lurker"))
+                    .await
+            );
+            assert_eq!(chatters.get_present_list(&channel).await, vec!["alice"]);
+        });
+    }
+
+    #[test]
+    fn test_snapshot() {
+        block_on(async {
+            let dir =
+                std::env::temp_dir().join(format!("chatbot-test-chatters-{}", std::process::id()));
+            let chatters = ChannelChatters::new().with_data_dir(DataDir::new(&dir));
+            let channel: Channel = User::new("liquidnya", None, Some(UserId::new(1))).into();
+            let alice: Sender = User::new("alice", Some("Alice"), Some(UserId::new(2))).into();
+            chatters.notice_chatter(&channel, &alice, "hi", "a").await;
+            chatters.snapshot().await.unwrap();
+            assert!(dir.join("chatters.ron").exists());
+
+            let restored = ChannelChatters::new().with_data_dir(DataDir::new(&dir));
+            restored.restore().await.unwrap();
+            let user = restored.get(UserArgument::new("@Alice")).await.unwrap();
+            assert_eq!(user.user_id(), Some(UserId::new(2)));
+            assert_eq!(user.display_name(), Some("Alice"));
+            std::fs::remove_dir_all(&dir).unwrap();
+        });
     }
 }
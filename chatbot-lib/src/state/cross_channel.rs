@@ -0,0 +1,111 @@
+use super::{ChannelContainer, ChannelStateError, Persisted, PersistedType};
+use std::sync::Arc;
+
+/// A read-only view of the state of all channels, e.g. for a leaderboard across the channels the
+/// bot joined.
+///
+/// Only channels whose state was created already can be read, which are the channels which
+/// received a message or were prepared with [`ChannelContainer::prewarm`].
+///
+/// ```ignore
+/// let channels = request.cross_channel()?;
+/// for channel in channels.channels().await {
+///     let counters = channels.persisted::<Counters>(&channel).await?;
+/// }
+/// ```
+#[derive(Clone, Copy)]
+pub struct CrossChannel<'req> {
+    channels: &'req ChannelContainer,
+}
+
+impl<'req> CrossChannel<'req> {
+    pub(crate) fn new(channels: &'req ChannelContainer) -> Self {
+        Self { channels }
+    }
+
+    /// The channels whose state can be read, in alphabetical order.
+    pub async fn channels(&self) -> Vec<String> {
+        self.channels.channels().await
+    }
+
+    /// Reads the persisted value of a channel.
+    pub async fn persisted<T: PersistedType>(
+        &self,
+        channel: &str,
+    ) -> Result<Arc<T>, ChannelStateError> {
+        let container = self.container(channel).await?;
+        let persisted = container
+            .try_get::<Persisted<T>>()
+            .ok_or_else(|| ChannelStateError::NoValue(std::any::type_name::<T>()))?;
+        Ok(persisted.for_channel(channel).read().await)
+    }
+
+    /// Calls `f` with the state of a channel which was set with
+    /// [`ContainerBuilder::set`](super::ContainerBuilder::set).
+    pub async fn with_state<T, R, F>(&self, channel: &str, f: F) -> Result<R, ChannelStateError>
+    where
+        T: Send + Sync + 'static,
+        F: FnOnce(&T) -> R,
+    {
+        let container = self.container(channel).await?;
+        let state = container
+            .try_get::<T>()
+            .ok_or_else(|| ChannelStateError::NoValue(std::any::type_name::<T>()))?;
+        Ok(f(state))
+    }
+
+    async fn container(
+        &self,
+        channel: &str,
+    ) -> Result<Arc<state::TypeMap![Send + Sync]>, ChannelStateError> {
+        let channel = channel.trim_start_matches('#');
+        self.channels
+            .try_get_arc(channel)
+            .await
+            .ok_or_else(|| ChannelStateError::NoChannel(channel.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Points(u32);
+
+    impl PersistedType for Points {
+        const FILENAME: &'static str = "points";
+
+        fn init(_channel: &str) -> Self {
+            Points(0)
+        }
+    }
+
+    #[test]
+    fn test_cross_channel() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let channel = format!("chatbot-test-cross-channel-{}", std::process::id());
+            let container = ChannelContainer::new(Box::new(|channel, builder| {
+                builder.register_persisted_value(Points(7));
+                builder.set(channel.len());
+            }));
+            let view = CrossChannel::new(&container);
+            assert!(matches!(
+                view.persisted::<Points>(&channel).await,
+                Err(ChannelStateError::NoChannel(_))
+            ));
+            container.prewarm([&channel]).await;
+            assert_eq!(view.channels().await, [channel.clone()]);
+            assert_eq!(view.persisted::<Points>(&channel).await.unwrap().0, 7);
+            let len = view.with_state(&channel, |len: &usize| *len).await;
+            assert_eq!(len.unwrap(), channel.len());
+            assert!(matches!(
+                view.with_state(&channel, |_: &u8| ()).await,
+                Err(ChannelStateError::NoValue(_))
+            ));
+        });
+    }
+}
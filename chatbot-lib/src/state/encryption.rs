@@ -0,0 +1,157 @@
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Marks encrypted files, such that files which were written before the encryption was turned on
+/// can still be read.
+const MAGIC: &[u8] = b"chatbot-encrypted-1\n";
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+/// The length of an encrypted key including its tag.
+const WRAPPED_KEY_LEN: usize = KEY_LEN + 16;
+
+static ENCRYPTION_KEY: OnceLock<EncryptionKey> = OnceLock::new();
+
+/// The key which encrypts the keys of the files of persisted types with
+/// [`PersistedType::ENCRYPTED`](super::PersistedType::ENCRYPTED) set.
+///
+/// Every file is encrypted with its own random key, which is stored in the file encrypted with
+/// this key.
+#[derive(Clone)]
+pub struct EncryptionKey([u8; KEY_LEN]);
+
+impl EncryptionKey {
+    /// Reads a key of 64 hex digits, e.g. generated with `openssl rand -hex 32`.
+    pub fn from_hex(hex: &str) -> Result<Self, InvalidKeyError> {
+        let hex = hex.trim();
+        if hex.len() != KEY_LEN * 2 || !hex.is_ascii() {
+            return Err(InvalidKeyError);
+        }
+        let mut key = [0; KEY_LEN];
+        for (byte, digits) in key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            let digits = std::str::from_utf8(digits).map_err(|_| InvalidKeyError)?;
+            *byte = u8::from_str_radix(digits, 16).map_err(|_| InvalidKeyError)?;
+        }
+        Ok(Self(key))
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(&self.0))
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidKeyError;
+
+impl fmt::Display for InvalidKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected an encryption key of {} hex digits",
+            KEY_LEN * 2
+        )
+    }
+}
+
+impl std::error::Error for InvalidKeyError {}
+
+/// Sets the key of encrypted persisted types, which can only be set once.
+///
+/// Returns `false` if a key was set already.
+pub fn set_encryption_key(key: EncryptionKey) -> bool {
+    ENCRYPTION_KEY.set(key).is_ok()
+}
+
+fn encryption_key() -> anyhow::Result<&'static EncryptionKey> {
+    ENCRYPTION_KEY
+        .get()
+        .ok_or_else(|| anyhow::anyhow!("No encryption key was set"))
+}
+
+pub(crate) fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub(crate) fn encrypt(plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    encrypt_with(encryption_key()?, plaintext)
+}
+
+pub(crate) fn decrypt(bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    decrypt_with(encryption_key()?, bytes)
+}
+
+// the file is the magic, the nonce and the encrypted key followed by the nonce and the data
+fn encrypt_with(key: &EncryptionKey, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let file_key = ChaCha20Poly1305::generate_key(&mut OsRng);
+    let key_nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let wrapped_key = key
+        .cipher()
+        .encrypt(&key_nonce, file_key.as_slice())
+        .map_err(|_| anyhow::anyhow!("Could not encrypt the key of the file"))?;
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(&file_key)
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow::anyhow!("Could not encrypt the file"))?;
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&key_nonce);
+    bytes.extend_from_slice(&wrapped_key);
+    bytes.extend_from_slice(&nonce);
+    bytes.extend_from_slice(&ciphertext);
+    Ok(bytes)
+}
+
+fn decrypt_with(key: &EncryptionKey, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let bytes = bytes
+        .strip_prefix(MAGIC)
+        .filter(|bytes| bytes.len() >= 2 * NONCE_LEN + WRAPPED_KEY_LEN)
+        .ok_or_else(|| anyhow::anyhow!("The file is not encrypted"))?;
+    let (key_nonce, bytes) = bytes.split_at(NONCE_LEN);
+    let (wrapped_key, bytes) = bytes.split_at(WRAPPED_KEY_LEN);
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let file_key = key
+        .cipher()
+        .decrypt(Nonce::from_slice(key_nonce), wrapped_key)
+        .map_err(|_| anyhow::anyhow!("The file was encrypted with another key"))?;
+    ChaCha20Poly1305::new(Key::from_slice(&file_key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("The encrypted file was changed"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEX: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn test_from_hex() {
+        let key = EncryptionKey::from_hex(HEX).unwrap();
+        assert_eq!(key.0[0x1f], 0x1f);
+        assert!(EncryptionKey::from_hex("0011").is_err());
+        assert!(EncryptionKey::from_hex(&HEX.replace('0', "g")).is_err());
+        assert_eq!(format!("{:?}", key), "EncryptionKey(..)");
+    }
+
+    #[test]
+    fn test_encrypt() {
+        let key = EncryptionKey::from_hex(HEX).unwrap();
+        let bytes = encrypt_with(&key, b"(notes: [])").unwrap();
+        assert!(is_encrypted(&bytes));
+        assert!(!bytes.windows(5).any(|window| window == b"notes"));
+        assert_eq!(decrypt_with(&key, &bytes).unwrap(), b"(notes: [])");
+
+        let other = EncryptionKey::from_hex(&HEX.replace('0', "f")).unwrap();
+        assert!(decrypt_with(&other, &bytes).is_err());
+        let mut changed = bytes.clone();
+        *changed.last_mut().unwrap() ^= 1;
+        assert!(decrypt_with(&key, &changed).is_err());
+        assert!(!is_encrypted(b"(notes: [])"));
+    }
+}
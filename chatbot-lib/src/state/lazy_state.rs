@@ -0,0 +1,73 @@
+use std::future::Future;
+use std::pin::Pin;
+use tokio::sync::OnceCell;
+
+type Init<T> = Box<dyn Fn() -> Pin<Box<dyn Future<Output = T> + Send>> + Send + Sync>;
+
+/// State which is built the first time it is used, registered with
+/// [`ChatBot::with_state_init`](crate::ChatBot::with_state_init).
+///
+/// Requests which use the state while it is built wait for it, such that it is built only once.
+/// If building it is cancelled it is built again by the next request.
+///
+/// ```ignore
+/// let bot = bot.with_state_init(|| async { ApiClient::connect().await });
+/// // in a command
+/// let client = request.state::<LazyState<ApiClient>>()?.get().await;
+/// ```
+pub struct LazyState<T> {
+    value: OnceCell<T>,
+    init: Init<T>,
+}
+
+impl<T: Send + Sync + 'static> LazyState<T> {
+    pub fn new<F, Fut>(init: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        Self {
+            value: OnceCell::new(),
+            init: Box::new(move || Box::pin(init())),
+        }
+    }
+
+    /// Builds the value unless it was built already.
+    pub async fn get(&self) -> &T {
+        self.value.get_or_init(|| (self.init)()).await
+    }
+
+    /// The value if it was built already.
+    pub fn try_get(&self) -> Option<&T> {
+        self.value.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_lazy_state() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let calls = Arc::new(AtomicU32::new(0));
+            let state = LazyState::new({
+                let calls = calls.clone();
+                move || {
+                    let calls = calls.clone();
+                    async move { calls.fetch_add(1, Ordering::Relaxed) + 42 }
+                }
+            });
+            assert_eq!(state.try_get(), None);
+            let (first, second) = futures_util::future::join(state.get(), state.get()).await;
+            assert_eq!((*first, *second), (42, 42));
+            assert_eq!(state.try_get(), Some(&42));
+            assert_eq!(calls.load(Ordering::Relaxed), 1);
+        });
+    }
+}
@@ -1,10 +1,40 @@
+mod cached;
+mod channel_config;
 mod channel_state;
 mod chatters;
+mod cross_channel;
+#[cfg(feature = "encryption")]
+mod encryption;
+mod lazy_state;
+mod persisted_format;
 mod persisted_state;
+#[cfg(feature = "redis")]
+mod shared_state;
 
+pub use self::cached::Cached;
+pub(crate) use self::channel_config::{has_command_prefix, prefixed_command, read_channel_config};
+pub use self::channel_config::{
+    ChannelConfig, ChannelConfigCommands, ChannelConfigError, ChannelConfigState, GreetingScope,
+    QuietHours,
+};
 pub(crate) use self::channel_state::CachedChannelContainer;
 pub use self::channel_state::{
     ChannelContainer, ChannelState, ChannelStateError, ContainerBuilder,
 };
-pub use self::chatters::ChannelChatters;
-pub use self::persisted_state::{PersistedChannelState, PersistedType};
+pub use self::chatters::{
+    ChannelChatters, ChatterListOptions, ChatterOrder, ChatterStats, ChatterStatsError,
+    LastMessage, KNOWN_BOTS,
+};
+pub use self::cross_channel::CrossChannel;
+#[cfg(feature = "encryption")]
+pub use self::encryption::{set_encryption_key, EncryptionKey, InvalidKeyError};
+pub use self::lazy_state::LazyState;
+pub use self::persisted_format::PersistedFormat;
+pub(crate) use self::persisted_state::Persisted;
+#[cfg(feature = "helix")]
+pub(crate) use self::persisted_state::{read_from_root, store_in_root};
+pub use self::persisted_state::{
+    DataDir, PersistedBackup, PersistedChannelState, PersistedType, WritePolicy,
+};
+#[cfg(feature = "redis")]
+pub use self::shared_state::{RedisStore, SharedChannelState, SharedState};
@@ -0,0 +1,80 @@
+use std::io::{Read, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PersistedFormat {
+    #[default]
+    Ron,
+    Json,
+    Toml,
+}
+
+impl PersistedFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PersistedFormat::Ron => "ron",
+            PersistedFormat::Json => "json",
+            PersistedFormat::Toml => "toml",
+        }
+    }
+
+    pub(crate) fn temp_extension(&self) -> String {
+        format!("{}.temp", self.extension())
+    }
+
+    pub(crate) fn serialize<W, T>(&self, mut writer: W, value: &T) -> anyhow::Result<()>
+    where
+        W: Write,
+        T: serde::Serialize + ?Sized,
+    {
+        match self {
+            PersistedFormat::Ron => ron::ser::to_writer_pretty(
+                writer,
+                value,
+                <ron::ser::PrettyConfig as Default>::default(),
+            )?,
+            PersistedFormat::Json => serde_json::to_writer_pretty(writer, value)?,
+            PersistedFormat::Toml => writer.write_all(toml::to_string_pretty(value)?.as_bytes())?,
+        }
+        Ok(())
+    }
+
+    pub(crate) fn deserialize<R, T>(&self, mut reader: R) -> anyhow::Result<T>
+    where
+        R: Read,
+        T: for<'de> serde::Deserialize<'de>,
+    {
+        Ok(match self {
+            PersistedFormat::Ron => ron::de::from_reader(reader)?,
+            PersistedFormat::Json => serde_json::from_reader(reader)?,
+            PersistedFormat::Toml => {
+                let mut string = String::new();
+                reader.read_to_string(&mut string)?;
+                toml::from_str(&string)?
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PersistedFormat;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut value = BTreeMap::new();
+        value.insert("queue".to_string(), vec![1u32, 2, 3]);
+        value.insert("points".to_string(), vec![]);
+        for format in [
+            PersistedFormat::Ron,
+            PersistedFormat::Json,
+            PersistedFormat::Toml,
+        ] {
+            let mut buffer = vec![];
+            format.serialize(&mut buffer, &value).unwrap();
+            let read_value: BTreeMap<String, Vec<u32>> =
+                format.deserialize(buffer.as_slice()).unwrap();
+            assert_eq!(read_value, value, "{:?}", format);
+        }
+    }
+}
@@ -1,21 +1,49 @@
-use super::{ChannelState, ChannelStateError};
-use crate::request::{CommandRequest, FromCommandRequest};
+use super::PersistedFormat;
 use arc_swap::ArcSwapOption;
-use ron::ser::PrettyConfig;
+use async_trait::async_trait;
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::convert::Infallible;
 use std::fs::OpenOptions;
+use std::io::{Read, Write};
 use std::ops::Deref;
-use std::path::PathBuf;
-use std::sync::Arc;
-use tokio::sync::Semaphore;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{watch, Semaphore};
 
 pub trait PersistedType:
     serde::Serialize + for<'de> serde::Deserialize<'de> + Sync + Send + 'static
 {
     const FILENAME: &'static str;
+    const FORMAT: PersistedFormat = PersistedFormat::Ron;
+    /// Version of the stored data, which has to be increased whenever the stored data changes.
+    ///
+    /// Files which were written before versioning was introduced have version `0`.
+    const VERSION: u32 = 0;
+    const WRITE_POLICY: WritePolicy = WritePolicy::Immediate;
+    /// Number of backups which are kept of previously written files, `0` disables backups.
+    const BACKUPS: usize = 3;
+    /// Encrypts the file with the key set by `state::set_encryption_key`, which requires the
+    /// `encryption` feature. Files which were written before are still read.
+    const ENCRYPTED: bool = false;
 
     // might be called multiple times!
     fn init(channel: &str) -> Self;
 
+    /// Converts data stored with an older version into the current version.
+    ///
+    /// `raw_value` is the stored data without the version, independent of the [`PersistedFormat`].
+    fn migrate(old_version: u32, _raw_value: serde_json::Value) -> anyhow::Result<Self> {
+        Err(anyhow::anyhow!(
+            "No migration from version {} to version {} for {}",
+            old_version,
+            Self::VERSION,
+            Self::FILENAME
+        ))
+    }
+
     fn handle_read_error(channel: &str, _error: anyhow::Error) -> Self {
         Self::init(channel)
     }
@@ -25,115 +53,399 @@ pub trait PersistedType:
     }
 }
 
-pub(crate) struct Persisted<T: PersistedType> {
+#[derive(serde::Serialize)]
+struct VersionedRef<'a, T> {
+    version: u32,
+    data: &'a T,
+}
+
+#[derive(serde::Deserialize)]
+struct Versioned<T> {
+    version: u32,
+    data: T,
+}
+
+fn split_version(raw_value: serde_json::Value) -> (Option<u32>, serde_json::Value) {
+    match raw_value {
+        serde_json::Value::Object(mut map) if map.len() == 2 && map.contains_key("data") => {
+            match map
+                .get("version")
+                .and_then(serde_json::Value::as_u64)
+                .and_then(|version| u32::try_from(version).ok())
+            {
+                Some(version) => (Some(version), map.remove("data").unwrap()),
+                None => (None, serde_json::Value::Object(map)),
+            }
+        }
+        raw_value => (None, raw_value),
+    }
+}
+
+pub(crate) fn serialize_versioned<T: PersistedType>(value: &T) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    T::FORMAT.serialize(
+        &mut bytes,
+        &VersionedRef {
+            version: T::VERSION,
+            data: value,
+        },
+    )?;
+    Ok(bytes)
+}
+
+// the content of the file, which is encrypted if the type is encrypted
+fn encode<T: PersistedType>(value: &T) -> anyhow::Result<Vec<u8>> {
+    let bytes = serialize_versioned(value)?;
+    if !T::ENCRYPTED {
+        return Ok(bytes);
+    }
+    #[cfg(feature = "encryption")]
+    return super::encryption::encrypt(&bytes);
+    #[cfg(not(feature = "encryption"))]
+    Err(anyhow::anyhow!(
+        "{} is encrypted, which requires the encryption feature",
+        T::FILENAME
+    ))
+}
+
+fn decode<T: PersistedType>(bytes: &[u8]) -> anyhow::Result<T> {
+    #[cfg(feature = "encryption")]
+    if super::encryption::is_encrypted(bytes) {
+        return deserialize_versioned(&super::encryption::decrypt(bytes)?);
+    }
+    deserialize_versioned(bytes)
+}
+
+pub(crate) fn deserialize_versioned<T: PersistedType>(bytes: &[u8]) -> anyhow::Result<T> {
+    if let Ok(value) = T::FORMAT.deserialize::<_, Versioned<T>>(bytes) {
+        if value.version == T::VERSION {
+            return Ok(value.data);
+        }
+    }
+    let raw_value = match T::FORMAT.deserialize::<_, serde_json::Value>(bytes) {
+        Ok(raw_value) => raw_value,
+        // files without a version might not be representable as raw value
+        Err(_) if T::VERSION == 0 => return T::FORMAT.deserialize(bytes),
+        Err(e) => return Err(e),
+    };
+    match split_version(raw_value) {
+        (Some(version), _) if version == T::VERSION => {
+            Ok(T::FORMAT.deserialize::<_, Versioned<T>>(bytes)?.data)
+        }
+        (None, _) if T::VERSION == 0 => T::FORMAT.deserialize(bytes),
+        (version, raw_value) => {
+            let version = version.unwrap_or(0);
+            log::info!(
+                "Migrating {} from version {} to version {}",
+                <T as PersistedType>::FILENAME,
+                version,
+                <T as PersistedType>::VERSION
+            );
+            <T as PersistedType>::migrate(version, raw_value)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WritePolicy {
+    /// Every update is written to disk before it is returned.
+    #[default]
+    Immediate,
+    /// Updates are coalesced and written to disk at most once per interval.
+    ///
+    /// Pending writes are flushed when the [`ChatBot`](crate::ChatBot) stops,
+    /// or by calling [`ChannelContainer::flush`](super::ChannelContainer::flush).
+    Debounced(Duration),
+}
+
+#[async_trait]
+trait ScheduledWrite: Send + Sync {
+    async fn flush(&self);
+}
+
+type ScheduledWrites = HashMap<(TypeId, String), Arc<dyn ScheduledWrite>>;
+
+#[derive(Clone, Default)]
+pub(crate) struct PendingWrites {
+    writes: Arc<Mutex<ScheduledWrites>>,
+}
+
+impl PendingWrites {
+    fn insert<T: PersistedType>(&self, channel: &str, write: Arc<dyn ScheduledWrite>) {
+        self.writes
+            .lock()
+            .unwrap()
+            .insert((TypeId::of::<T>(), channel.to_owned()), write);
+    }
+
+    pub async fn flush(&self) {
+        let writes: Vec<_> = self
+            .writes
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, write)| write)
+            .collect();
+        for write in writes {
+            write.flush().await;
+        }
+    }
+}
+
+struct PersistedShared<T: PersistedType> {
     inner: ArcSwapOption<T>,
     lock: Semaphore,
+    // true while a debounced write has not been written to disk yet
+    scheduled: AtomicBool,
+    data_dir: DataDir,
+    // created by the first call of `watch`, dropped again once nobody is watching
+    subscribers: Mutex<Option<watch::Sender<Arc<T>>>>,
 }
 
-impl<T: PersistedType> From<T> for Persisted<T> {
-    fn from(value: T) -> Self {
-        Self {
-            inner: ArcSwapOption::new(Some(Arc::new(value))),
-            lock: Semaphore::new(1),
+impl<T: PersistedType> PersistedShared<T> {
+    // has to be called while holding the lock, such that the values are sent in order
+    fn notify(&self, value: &Arc<T>) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if let Some(sender) = subscribers.as_ref() {
+            if sender.send(value.clone()).is_err() {
+                *subscribers = None;
+            }
+        }
+    }
+}
+
+struct ScheduledChannelWrite<T: PersistedType> {
+    shared: Arc<PersistedShared<T>>,
+    channel: String,
+}
+
+#[async_trait]
+impl<T: PersistedType> ScheduledWrite for ScheduledChannelWrite<T> {
+    async fn flush(&self) {
+        let permit = self.shared.lock.acquire().await.unwrap();
+        if !self.shared.scheduled.swap(false, Ordering::AcqRel) {
+            // already written
+            return;
+        }
+        if let Some(value) = self.shared.inner.load_full() {
+            let result = store_on_disk(&self.shared.data_dir, &self.channel, value).await;
+            drop(permit);
+            if let Err(e) = result {
+                log::error!(
+                    "Error saving {} for channel {} to disk: {:?}",
+                    <T as PersistedType>::FILENAME,
+                    self.channel,
+                    e
+                );
+                <T as PersistedType>::handle_write_error(&self.channel, e)
+            }
+        }
+    }
+}
+
+/// Drops the value of a persisted type which was loaded already, such that it is read from disk
+/// again the next time it is used.
+#[async_trait]
+pub(crate) trait Reload: Send + Sync {
+    /// The name of the file of the persisted type, e.g. `quotes.ron`.
+    fn file_name(&self) -> String;
+
+    /// Loads the value from disk unless it was loaded already.
+    async fn preload(&self, channel: &str);
+
+    /// Returns `false` if the value was not dropped, since an update was not written yet.
+    async fn reload(&self, channel: &str) -> bool;
+}
+
+struct PersistedReload<T: PersistedType> {
+    shared: Arc<PersistedShared<T>>,
+    pending_writes: PendingWrites,
+}
+
+#[async_trait]
+impl<T: PersistedType> Reload for PersistedReload<T> {
+    fn file_name(&self) -> String {
+        format!("{}.{}", T::FILENAME, T::FORMAT.extension())
+    }
+
+    async fn preload(&self, channel: &str) {
+        PersistedChannelState {
+            shared: &self.shared,
+            pending_writes: &self.pending_writes,
+            channel,
         }
+        .read()
+        .await;
     }
+
+    async fn reload(&self, channel: &str) -> bool {
+        // waits for running updates, which might have changed the file themselves
+        let _permit = self.shared.lock.acquire().await.unwrap();
+        if self.shared.scheduled.load(Ordering::Acquire) {
+            // reloading would lose the debounced update
+            log::warn!(
+                "Not reloading {} for channel {}, since an update was not written yet",
+                <T as PersistedType>::FILENAME,
+                channel
+            );
+            return false;
+        }
+        self.shared.inner.store(None);
+        true
+    }
+}
+
+pub(crate) struct Persisted<T: PersistedType> {
+    shared: Arc<PersistedShared<T>>,
+    pending_writes: PendingWrites,
 }
 
 impl<T: PersistedType> Persisted<T> {
-    pub fn new() -> Self {
+    pub fn new(pending_writes: PendingWrites, data_dir: DataDir) -> Self {
+        Self::from_option(None, pending_writes, data_dir)
+    }
+
+    pub fn from_value(value: T, pending_writes: PendingWrites, data_dir: DataDir) -> Self {
+        Self::from_option(Some(Arc::new(value)), pending_writes, data_dir)
+    }
+
+    fn from_option(
+        value: Option<Arc<T>>,
+        pending_writes: PendingWrites,
+        data_dir: DataDir,
+    ) -> Self {
         Self {
-            inner: ArcSwapOption::new(None),
-            lock: Semaphore::new(1),
+            shared: Arc::new(PersistedShared {
+                inner: ArcSwapOption::new(value),
+                lock: Semaphore::new(1),
+                scheduled: AtomicBool::new(false),
+                data_dir,
+                subscribers: Mutex::new(None),
+            }),
+            pending_writes,
         }
     }
 
-    fn for_channel<'a>(&'a self, channel: &'a str) -> PersistedChannelState<'a, T> {
+    #[cfg(test)]
+    pub(crate) fn is_loaded(&self) -> bool {
+        self.shared.inner.load().is_some()
+    }
+
+    pub(crate) fn reloader(&self) -> Arc<dyn Reload> {
+        Arc::new(PersistedReload {
+            shared: self.shared.clone(),
+            pending_writes: self.pending_writes.clone(),
+        })
+    }
+
+    pub(crate) fn for_channel<'a>(&'a self, channel: &'a str) -> PersistedChannelState<'a, T> {
         PersistedChannelState {
-            inner: &self.inner,
-            lock: &self.lock,
+            shared: &self.shared,
+            pending_writes: &self.pending_writes,
             channel,
         }
     }
 }
 
-pub struct PersistedChannelState<'a, T: PersistedType> {
-    inner: &'a ArcSwapOption<T>,
-    lock: &'a Semaphore,
-    channel: &'a str,
+#[derive(Debug, Clone)]
+pub struct PersistedBackup {
+    index: usize,
+    modified: Option<SystemTime>,
+    path: PathBuf,
 }
 
-impl<'a, 'req, T: PersistedType> FromCommandRequest<'a, 'req> for PersistedChannelState<'req, T> {
-    type Error = ChannelStateError;
+impl PersistedBackup {
+    /// Index of the backup, `1` is the newest backup.
+    pub fn index(&self) -> usize {
+        self.index
+    }
 
-    fn from_command_request(request: &'a CommandRequest<'req>) -> Result<Self, Self::Error> {
-        let channel_state =
-            <ChannelState<Persisted<T>> as FromCommandRequest>::from_command_request(request)?;
-        let channel = request.channel();
-        Ok(channel_state.for_channel(channel.username()))
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
     }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+pub struct PersistedChannelState<'a, T: PersistedType> {
+    shared: &'a Arc<PersistedShared<T>>,
+    pending_writes: &'a PendingWrites,
+    channel: &'a str,
 }
 
 impl<'a, T: PersistedType> PersistedChannelState<'a, T> {
     pub async fn read(&self) -> Arc<T> {
-        match self.inner.load().deref() {
+        match self.shared.inner.load().deref() {
             Some(value) => value.clone(),
             None => {
-                let permit = self.lock.acquire().await.unwrap();
-                if let Some(value) = self.inner.load().deref() {
-                    return value.clone();
-                }
-                let value = read_from_disk::<T>(self.channel).await;
-                let result = value.unwrap_or_else(|e| {
-                    log::error!(
-                        "Error loading {} for channel {} from disk: {:?}",
-                        <T as PersistedType>::FILENAME,
-                        self.channel,
-                        e
-                    );
-                    Some(<T as PersistedType>::handle_read_error(self.channel, e))
-                });
-                let result = result.unwrap_or_else(|| <T as PersistedType>::init(self.channel));
-                let result = Arc::new(result);
-                self.inner.store(Some(result.clone()));
+                let permit = self.shared.lock.acquire().await.unwrap();
+                let result = self.load_locked().await;
                 drop(permit);
                 result
             }
         }
     }
 
+    // has to be called while holding the lock
+    async fn load_locked(&self) -> Arc<T> {
+        if let Some(value) = self.shared.inner.load().deref() {
+            return value.clone();
+        }
+        let value = read_from_disk::<T>(&self.shared.data_dir, self.channel).await;
+        let result = value.unwrap_or_else(|e| {
+            log::error!(
+                "Error loading {} for channel {} from disk: {:?}",
+                <T as PersistedType>::FILENAME,
+                self.channel,
+                e
+            );
+            Some(<T as PersistedType>::handle_read_error(self.channel, e))
+        });
+        let result = result.unwrap_or_else(|| <T as PersistedType>::init(self.channel));
+        let result = Arc::new(result);
+        self.shared.inner.store(Some(result.clone()));
+        // the value changes if it was reloaded
+        self.shared.notify(&result);
+        result
+    }
+
     pub async fn maybe_update<R, F>(&self, mut f: F) -> (Arc<T>, Option<Arc<T>>)
     where
         F: FnMut(&T) -> Option<R>,
         R: Into<T>,
     {
-        let permit = self.lock.acquire().await.unwrap();
-        log::debug!("{} - MAYBE UPDATE", <T as PersistedType>::FILENAME);
-        let value = if let Some(value) = self.inner.load().deref() {
-            log::debug!("{} - EXSISTS", <T as PersistedType>::FILENAME);
-            value.clone()
-        } else {
-            log::debug!("{} - INIT", <T as PersistedType>::FILENAME);
+        match self.try_update(|value| Ok::<_, Infallible>(f(value))).await {
+            Ok(result) => result,
+            Err(e) => match e {},
+        }
+    }
 
-            let value = read_from_disk::<T>(self.channel).await;
-            let result = value.unwrap_or_else(|e| {
-                log::error!(
-                    "Error loading {} for channel {} from disk: {:?}",
-                    <T as PersistedType>::FILENAME,
-                    self.channel,
-                    e
-                );
-                Some(<T as PersistedType>::handle_read_error(self.channel, e))
-            });
-            let result = result.unwrap_or_else(|| <T as PersistedType>::init(self.channel));
-            let result = Arc::new(result);
-            self.inner.store(Some(result.clone()));
-            result
-        };
-        let optional_value = f(&value);
+    /// Like [`maybe_update`](Self::maybe_update), but `f` can fail, e.g. if the new value would
+    /// be invalid. The error is returned and nothing is written.
+    pub async fn try_update<R, E, F>(&self, mut f: F) -> Result<(Arc<T>, Option<Arc<T>>), E>
+    where
+        F: FnMut(&T) -> Result<Option<R>, E>,
+        R: Into<T>,
+    {
+        let permit = self.shared.lock.acquire().await.unwrap();
+        log::debug!("{} - MAYBE UPDATE", <T as PersistedType>::FILENAME);
+        let value = self.load_locked().await;
+        let optional_value = f(&value)?;
         if let Some(new_value) = optional_value {
             let new_value = Arc::new(new_value.into());
-            let result = store_on_disk(self.channel, new_value.clone()).await;
-            let old_value = self.inner.swap(Some(new_value.clone()));
+            let result = match <T as PersistedType>::WRITE_POLICY {
+                WritePolicy::Immediate => {
+                    store_on_disk(&self.shared.data_dir, self.channel, new_value.clone()).await
+                }
+                WritePolicy::Debounced(interval) => {
+                    self.schedule_write(interval);
+                    Ok(())
+                }
+            };
+            let old_value = self.shared.inner.swap(Some(new_value.clone()));
+            self.shared.notify(&new_value);
             drop(permit);
             if let Err(e) = result {
                 log::error!(
@@ -144,12 +456,12 @@ impl<'a, T: PersistedType> PersistedChannelState<'a, T> {
                 );
                 <T as PersistedType>::handle_write_error(self.channel, e)
             }
-            return (
+            return Ok((
                 old_value.expect("Expected value, since it was initialized and never set to None"),
                 Some(new_value),
-            );
+            ));
         }
-        (value, None)
+        Ok((value, None))
     }
 
     pub async fn update<R, F>(&self, mut f: F) -> (Arc<T>, Arc<T>)
@@ -160,47 +472,307 @@ impl<'a, T: PersistedType> PersistedChannelState<'a, T> {
         let (old, new) = self.maybe_update(move |value| Some((f)(value))).await;
         (old, new.unwrap())
     }
+
+    // has to be called while holding the lock
+    fn schedule_write(&self, interval: Duration) {
+        if self.shared.scheduled.swap(true, Ordering::AcqRel) {
+            // there is already a write scheduled, which is going to write the newest value
+            return;
+        }
+        let write = Arc::new(ScheduledChannelWrite {
+            shared: self.shared.clone(),
+            channel: self.channel.to_owned(),
+        });
+        self.pending_writes.insert::<T>(self.channel, write.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(interval).await;
+            write.flush().await;
+        });
+    }
+
+    /// Lists the backups of this state, the newest backup comes first.
+    pub async fn backups(&self) -> anyhow::Result<Vec<PersistedBackup>> {
+        let path = prepare_path::<T>(&self.shared.data_dir, self.channel)?;
+        let mut backups = vec![];
+        for index in 1..=<T as PersistedType>::BACKUPS {
+            let path = backup_path(&path, index);
+            match tokio::fs::metadata(&path).await {
+                Ok(metadata) => backups.push(PersistedBackup {
+                    index,
+                    modified: metadata.modified().ok(),
+                    path,
+                }),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(backups)
+    }
+
+    /// Replaces the current value with the value of a backup.
+    ///
+    /// The current value is kept as the newest backup.
+    pub async fn restore_backup(&self, backup: &PersistedBackup) -> anyhow::Result<Arc<T>> {
+        let permit = self.shared.lock.acquire().await.unwrap();
+        let path = backup.path.clone();
+        let value = tokio::task::spawn_blocking(move || -> anyhow::Result<T> {
+            let bytes = read_file(&path)?
+                .ok_or_else(|| anyhow::anyhow!("Backup {} does not exist", path.display()))?;
+            decode(&bytes)
+        })
+        .await??;
+        let value = Arc::new(value);
+        store_on_disk(&self.shared.data_dir, self.channel, value.clone()).await?;
+        self.shared.inner.store(Some(value.clone()));
+        self.shared.notify(&value);
+        // a pending debounced write would overwrite the restored value
+        self.shared.scheduled.store(false, Ordering::Release);
+        drop(permit);
+        Ok(value)
+    }
+
+    /// Subscribes to the value, such that background tasks can react to changes without polling.
+    ///
+    /// The receiver is updated after every update, but not if nothing was changed.
+    pub async fn watch(&self) -> watch::Receiver<Arc<T>> {
+        let permit = self.shared.lock.acquire().await.unwrap();
+        let value = self.load_locked().await;
+        let mut subscribers = self.shared.subscribers.lock().unwrap();
+        let receiver = match subscribers.as_ref() {
+            Some(sender) => sender.subscribe(),
+            None => {
+                let (sender, receiver) = watch::channel(value);
+                *subscribers = Some(sender);
+                receiver
+            }
+        };
+        drop(subscribers);
+        drop(permit);
+        receiver
+    }
+
+    /// Writes a pending debounced update to disk immediately.
+    pub async fn flush(&self) {
+        ScheduledChannelWrite {
+            shared: self.shared.clone(),
+            channel: self.channel.to_owned(),
+        }
+        .flush()
+        .await
+    }
+
+    /// Updates this state and the state of another persisted type of the same channel together.
+    ///
+    /// Both locks are held while `f` runs and both files are written before either of them is
+    /// replaced. The replacement is recorded in a journal, so if it fails or the bot crashes
+    /// after only one file was replaced, the other file is replaced when it is loaded again.
+    /// Both values are written immediately regardless of their [`WritePolicy`].
+    pub async fn maybe_update_with<T2, R, R2, F>(
+        &self,
+        other: &PersistedChannelState<'_, T2>,
+        mut f: F,
+    ) -> ((Arc<T>, Arc<T2>), Option<(Arc<T>, Arc<T2>)>)
+    where
+        T2: PersistedType,
+        F: FnMut(&T, &T2) -> Option<(R, R2)>,
+        R: Into<T>,
+        R2: Into<T2>,
+    {
+        assert_ne!(
+            TypeId::of::<T>(),
+            TypeId::of::<T2>(),
+            "Can not update {} with itself",
+            <T as PersistedType>::FILENAME
+        );
+        assert_eq!(
+            self.channel, other.channel,
+            "Can not update persisted state of different channels"
+        );
+        // always lock in the same order to avoid deadlocks
+        let (permit, other_permit) = if TypeId::of::<T>() < TypeId::of::<T2>() {
+            let permit = self.shared.lock.acquire().await.unwrap();
+            (permit, other.shared.lock.acquire().await.unwrap())
+        } else {
+            let other_permit = other.shared.lock.acquire().await.unwrap();
+            (self.shared.lock.acquire().await.unwrap(), other_permit)
+        };
+        log::debug!(
+            "{} + {} - MAYBE UPDATE",
+            <T as PersistedType>::FILENAME,
+            <T2 as PersistedType>::FILENAME
+        );
+        let value = self.load_locked().await;
+        let other_value = other.load_locked().await;
+        if let Some((new_value, new_other_value)) = f(&value, &other_value) {
+            let new_value = Arc::new(new_value.into());
+            let new_other_value = Arc::new(new_other_value.into());
+            let result = store_both_on_disk(
+                &self.shared.data_dir,
+                self.channel,
+                new_value.clone(),
+                new_other_value.clone(),
+            )
+            .await;
+            let old_value = self.shared.inner.swap(Some(new_value.clone()));
+            let old_other_value = other.shared.inner.swap(Some(new_other_value.clone()));
+            self.shared.notify(&new_value);
+            other.shared.notify(&new_other_value);
+            drop(other_permit);
+            drop(permit);
+            if let Err(e) = result {
+                log::error!(
+                    "Error saving {} and {} for channel {} to disk: {:?}",
+                    <T as PersistedType>::FILENAME,
+                    <T2 as PersistedType>::FILENAME,
+                    self.channel,
+                    e
+                );
+                let other_error = anyhow::anyhow!("{:#}", e);
+                <T as PersistedType>::handle_write_error(self.channel, e);
+                <T2 as PersistedType>::handle_write_error(self.channel, other_error);
+            }
+            return (
+                (
+                    old_value
+                        .expect("Expected value, since it was initialized and never set to None"),
+                    old_other_value
+                        .expect("Expected value, since it was initialized and never set to None"),
+                ),
+                Some((new_value, new_other_value)),
+            );
+        }
+        ((value, other_value), None)
+    }
+
+    pub async fn update_with<T2, R, R2, F>(
+        &self,
+        other: &PersistedChannelState<'_, T2>,
+        mut f: F,
+    ) -> ((Arc<T>, Arc<T2>), (Arc<T>, Arc<T2>))
+    where
+        T2: PersistedType,
+        F: FnMut(&T, &T2) -> (R, R2),
+        R: Into<T>,
+        R2: Into<T2>,
+    {
+        let (old, new) = self
+            .maybe_update_with(other, move |value, other_value| {
+                Some((f)(value, other_value))
+            })
+            .await;
+        (old, new.unwrap())
+    }
 }
 
-fn prepare_path<T: PersistedType>(channel: &str) -> anyhow::Result<PathBuf> {
-    let mut path = std::env::current_dir()?;
-    path.push("data");
-    path.push(channel);
+/// The directory of the persisted files, which contains a directory for every channel.
+///
+/// Several bots on the same host need their own directory, e.g. `data/bot1` and `data/bot2`.
+/// Relative paths are relative to the current directory, `data` by default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataDir(PathBuf);
+
+impl Default for DataDir {
+    fn default() -> Self {
+        Self::new("data")
+    }
+}
+
+impl DataDir {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self(path.into())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// The data directory itself, for the files which do not belong to a channel.
+    pub(crate) fn root_dir(&self) -> anyhow::Result<PathBuf> {
+        let mut path = std::env::current_dir()?;
+        path.push(&self.0);
+        Ok(path)
+    }
+
+    /// The directory with the persisted files of the channel.
+    pub(crate) fn channel_dir(&self, channel: &str) -> anyhow::Result<PathBuf> {
+        // the channel must not be able to leave the data directory
+        if matches!(channel, "" | "." | "..") || channel.contains(['/', '\\', ':']) {
+            anyhow::bail!("Invalid channel name {:?}", channel);
+        }
+        let mut path = self.root_dir()?;
+        path.push(channel);
+        Ok(path)
+    }
+}
+
+fn prepare_path<T: PersistedType>(data_dir: &DataDir, channel: &str) -> anyhow::Result<PathBuf> {
+    Ok(file_path::<T>(data_dir.channel_dir(channel)?))
+}
+
+fn file_path<T: PersistedType>(mut path: PathBuf) -> PathBuf {
     path.push(T::FILENAME);
-    path.set_extension("ron");
-    Ok(path)
+    path.set_extension(T::FORMAT.extension());
+    path
 }
 
-async fn prepare_paths<T: PersistedType>(channel: &str) -> anyhow::Result<(PathBuf, PathBuf)> {
-    let mut path = std::env::current_dir()?;
-    path.push("data");
-    path.push(channel);
+async fn prepare_paths<T: PersistedType>(
+    data_dir: &DataDir,
+    channel: &str,
+) -> anyhow::Result<(PathBuf, PathBuf)> {
+    file_paths::<T>(data_dir.channel_dir(channel)?).await
+}
+
+async fn file_paths<T: PersistedType>(mut path: PathBuf) -> anyhow::Result<(PathBuf, PathBuf)> {
     tokio::fs::create_dir_all(&path).await?;
     path.push(T::FILENAME);
     let mut temp_path = path.clone();
-    temp_path.set_extension("ron.temp");
-    path.set_extension("ron");
-    Ok(dbg!((temp_path, path)))
+    temp_path.set_extension(T::FORMAT.temp_extension());
+    path.set_extension(T::FORMAT.extension());
+    Ok((temp_path, path))
 }
 
-async fn store_on_disk<T: PersistedType>(channel: &str, store_value: Arc<T>) -> anyhow::Result<()> {
-    let (temp_path, path) = prepare_paths::<T>(channel).await?;
+fn write_temp_file<T: PersistedType>(temp_path: &Path, store_value: &T) -> anyhow::Result<()> {
+    let bytes = encode(store_value)?;
+    let mut file = OpenOptions::new()
+        .read(false)
+        .write(true)
+        .append(false)
+        // .create_new(true) // => could use create_new but then what happens if the file existed?
+        .truncate(true)
+        .create(true)
+        .open(temp_path)?;
+    file.write_all(&bytes)?;
+    file.sync_all()?;
+    drop(file);
+    Ok(())
+}
+
+pub(crate) async fn store_on_disk<T: PersistedType>(
+    data_dir: &DataDir,
+    channel: &str,
+    store_value: Arc<T>,
+) -> anyhow::Result<()> {
+    let (temp_path, path) = prepare_paths::<T>(data_dir, channel).await?;
+    store_at(temp_path, path, store_value).await
+}
+
+/// Like [`store_on_disk`], but for a file in the data directory itself.
+pub(crate) async fn store_in_root<T: PersistedType>(
+    data_dir: &DataDir,
+    store_value: Arc<T>,
+) -> anyhow::Result<()> {
+    let (temp_path, path) = file_paths::<T>(data_dir.root_dir()?).await?;
+    store_at(temp_path, path, store_value).await
+}
+
+async fn store_at<T: PersistedType>(
+    temp_path: PathBuf,
+    path: PathBuf,
+    store_value: Arc<T>,
+) -> anyhow::Result<()> {
     tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
-        let file = OpenOptions::new()
-            .read(false)
-            .write(true)
-            .append(false)
-            // .create_new(true) // => could use create_new but then what happens if the file existed?
-            .truncate(true)
-            .create(true)
-            .open(&temp_path)?;
-        ron::ser::to_writer_pretty(
-            &file,
-            &store_value.deref(),
-            <PrettyConfig as Default>::default(),
-        )?;
-        file.sync_all()?;
-        drop(file);
+        write_temp_file(&temp_path, store_value.deref())?;
+        rotate_backups(&path, T::BACKUPS)?;
         std::fs::rename(&temp_path, &path)?;
         Ok(())
     })
@@ -208,30 +780,499 @@ async fn store_on_disk<T: PersistedType>(channel: &str, store_value: Arc<T>) ->
     Ok(())
 }
 
-async fn read_from_disk<T: PersistedType>(channel: &str) -> anyhow::Result<Option<T>> {
-    let path = prepare_path::<T>(channel)?;
-    let value = tokio::task::spawn_blocking(move || -> anyhow::Result<Option<T>> {
-        let file = OpenOptions::new()
-            .read(true)
-            .write(false)
-            .append(false)
-            // .create_new(true) // => could use create_new but then what happens if the file existed?
-            .truncate(false)
-            .create(false)
-            .open(path);
-        let file = match file {
-            Ok(file) => file,
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                return Ok(None);
-            }
-            Err(e) => {
-                return Err(e.into());
+async fn store_both_on_disk<T: PersistedType, T2: PersistedType>(
+    data_dir: &DataDir,
+    channel: &str,
+    store_value: Arc<T>,
+    store_other_value: Arc<T2>,
+) -> anyhow::Result<()> {
+    store_both_with(
+        data_dir,
+        channel,
+        store_value,
+        store_other_value,
+        |from, to| std::fs::rename(from, to),
+    )
+    .await
+}
+
+// `rename` replaces the files, which fails on purpose in tests
+async fn store_both_with<T, T2, R>(
+    data_dir: &DataDir,
+    channel: &str,
+    store_value: Arc<T>,
+    store_other_value: Arc<T2>,
+    rename: R,
+) -> anyhow::Result<()>
+where
+    T: PersistedType,
+    T2: PersistedType,
+    R: FnMut(&Path, &Path) -> std::io::Result<()> + Send + 'static,
+{
+    let (temp_path, path) = prepare_paths::<T>(data_dir, channel).await?;
+    let (other_temp_path, other_path) = prepare_paths::<T2>(data_dir, channel).await?;
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        // write both temporary files first, such that nothing is replaced if one of them fails
+        write_temp_file(&temp_path, store_value.deref())?;
+        write_temp_file(&other_temp_path, store_other_value.deref())?;
+        rotate_backups(&path, T::BACKUPS)?;
+        rotate_backups(&other_path, T2::BACKUPS)?;
+        commit_files(&[(temp_path, path), (other_temp_path, other_path)], rename)
+    })
+    .await??;
+    Ok(())
+}
+
+fn file_name(path: &Path) -> std::borrow::Cow<'_, str> {
+    path.file_name().unwrap_or_default().to_string_lossy()
+}
+
+const JOURNAL_EXTENSION: &str = "commit";
+
+// e.g. `points.ron+queue.ron.commit`, lists `<temporary file>\t<file>` on every line
+fn journal_path(paths: &[(PathBuf, PathBuf)]) -> Option<PathBuf> {
+    let names: Vec<_> = paths.iter().map(|(_, path)| file_name(path)).collect();
+    let (_, path) = paths.first()?;
+    Some(path.with_file_name(format!("{}.{}", names.join("+"), JOURNAL_EXTENSION)))
+}
+
+/// Replaces every file with its temporary file. The replacements are written to a journal
+/// first, such that [`recover_commits`] can finish them if they were interrupted.
+fn commit_files<R>(paths: &[(PathBuf, PathBuf)], mut rename: R) -> anyhow::Result<()>
+where
+    R: FnMut(&Path, &Path) -> std::io::Result<()>,
+{
+    let journal = match journal_path(paths) {
+        Some(journal) => journal,
+        None => return Ok(()),
+    };
+    let mut contents = String::new();
+    for (temp_path, path) in paths {
+        contents.push_str(&format!("{}\t{}\n", file_name(temp_path), file_name(path)));
+    }
+    // the journal is only used once it was written completely
+    let mut temp_journal = journal.clone().into_os_string();
+    temp_journal.push(".temp");
+    let mut file = std::fs::File::create(&temp_journal)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&temp_journal, &journal)?;
+    for (temp_path, path) in paths {
+        rename(temp_path, path)?;
+    }
+    std::fs::remove_file(&journal)?;
+    Ok(())
+}
+
+/// Finishes the interrupted replacements of the journals which contain the file at `path`.
+fn recover_commits(path: &Path) -> anyhow::Result<()> {
+    let (dir, name) = match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => (dir, name.to_string_lossy()),
+        _ => return Ok(()),
+    };
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let journal = entry?.path();
+        if journal.extension().and_then(|extension| extension.to_str()) != Some(JOURNAL_EXTENSION) {
+            continue;
+        }
+        let contents = std::fs::read_to_string(&journal)?;
+        let renames: Vec<_> = contents
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .collect();
+        if !renames.iter().any(|(_, file)| *file == name) {
+            continue;
+        }
+        for (temp_file, file) in renames {
+            match std::fs::rename(dir.join(temp_file), dir.join(file)) {
+                // the file was replaced already
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
             }
+        }
+        log::warn!("Finished the interrupted write of {}", journal.display());
+        std::fs::remove_file(&journal)?;
+    }
+    Ok(())
+}
+
+fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{}.bak", index));
+    path.with_file_name(file_name)
+}
+
+// keeps a copy of the current file, the newest backup has index 1
+fn rotate_backups(path: &Path, backups: usize) -> anyhow::Result<()> {
+    if backups == 0 || !path.exists() {
+        return Ok(());
+    }
+    for index in (1..backups).rev() {
+        match std::fs::rename(backup_path(path, index), backup_path(path, index + 1)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    std::fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+fn read_file(path: &Path) -> anyhow::Result<Option<Vec<u8>>> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(false)
+        .append(false)
+        // .create_new(true) // => could use create_new but then what happens if the file existed?
+        .truncate(false)
+        .create(false)
+        .open(path);
+    let mut file = match file {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(None);
+        }
+        Err(e) => {
+            return Err(e.into());
+        }
+    };
+    let mut bytes = vec![];
+    file.read_to_end(&mut bytes)?;
+    drop(file);
+    Ok(Some(bytes))
+}
+
+pub(crate) async fn read_from_disk<T: PersistedType>(
+    data_dir: &DataDir,
+    channel: &str,
+) -> anyhow::Result<Option<T>> {
+    read_from(prepare_path::<T>(data_dir, channel)?).await
+}
+
+/// Like [`read_from_disk`], but for a file in the data directory itself.
+pub(crate) async fn read_from_root<T: PersistedType>(
+    data_dir: &DataDir,
+) -> anyhow::Result<Option<T>> {
+    read_from(file_path::<T>(data_dir.root_dir()?)).await
+}
+
+async fn read_from<T: PersistedType>(path: PathBuf) -> anyhow::Result<Option<T>> {
+    let value = tokio::task::spawn_blocking(move || -> anyhow::Result<Option<T>> {
+        recover_commits(&path)?;
+        let bytes = match read_file(&path)? {
+            Some(bytes) => bytes,
+            None => return Ok(None),
         };
-        let read_value = ron::de::from_reader(&file)?;
-        drop(file);
-        Ok(Some(read_value))
+        let error = match decode(&bytes) {
+            Ok(read_value) => return Ok(Some(read_value)),
+            Err(e) => e,
+        };
+        // try to recover from the newest backup which can be read
+        for index in 1..=T::BACKUPS {
+            let backup_path = backup_path(&path, index);
+            match read_file(&backup_path)
+                .and_then(|bytes| bytes.map(|bytes| decode(&bytes)).transpose())
+            {
+                Ok(Some(read_value)) => {
+                    log::warn!(
+                        "Recovered {} from {} after error: {:?}",
+                        path.display(),
+                        backup_path.display(),
+                        error
+                    );
+                    return Ok(Some(read_value));
+                }
+                Ok(None) => break,
+                Err(e) => log::warn!("Error loading backup {}: {:?}", backup_path.display(), e),
+            }
+        }
+        Err(error)
     })
     .await??;
     Ok(value)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        backup_path, deserialize_versioned, read_from_disk, rotate_backups, store_both_on_disk,
+        store_both_with, DataDir, PendingWrites, Persisted, PersistedFormat, PersistedType,
+        VersionedRef,
+    };
+    use std::sync::Arc;
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Points {
+        points: u32,
+    }
+
+    impl PersistedType for Points {
+        const FILENAME: &'static str = "points";
+
+        fn init(_channel: &str) -> Self {
+            Points { points: 0 }
+        }
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct PointsV2 {
+        points: u64,
+        bonus: u64,
+    }
+
+    impl PersistedType for PointsV2 {
+        const FILENAME: &'static str = "points";
+        const FORMAT: PersistedFormat = PersistedFormat::Json;
+        const VERSION: u32 = 2;
+
+        fn init(_channel: &str) -> Self {
+            PointsV2 {
+                points: 0,
+                bonus: 0,
+            }
+        }
+
+        fn migrate(old_version: u32, raw_value: serde_json::Value) -> anyhow::Result<Self> {
+            assert_eq!(old_version, 0);
+            Ok(PointsV2 {
+                points: raw_value["points"].as_u64().unwrap(),
+                bonus: 0,
+            })
+        }
+    }
+
+    #[test]
+    fn test_read_without_version() {
+        let value: Points = deserialize_versioned(b"(points: 5)").unwrap();
+        assert_eq!(value, Points { points: 5 });
+    }
+
+    #[test]
+    fn test_read_with_version() {
+        let mut bytes = vec![];
+        PersistedFormat::Ron
+            .serialize(
+                &mut bytes,
+                &VersionedRef {
+                    version: 0,
+                    data: &Points { points: 7 },
+                },
+            )
+            .unwrap();
+        let value: Points = deserialize_versioned(&bytes).unwrap();
+        assert_eq!(value, Points { points: 7 });
+    }
+
+    #[test]
+    fn test_migrate() {
+        let value: PointsV2 = deserialize_versioned(br#"{"points": 3}"#).unwrap();
+        assert_eq!(
+            value,
+            PointsV2 {
+                points: 3,
+                bonus: 0
+            }
+        );
+        let value: PointsV2 =
+            deserialize_versioned(br#"{"version": 2, "data": {"points": 3, "bonus": 1}}"#).unwrap();
+        assert_eq!(
+            value,
+            PointsV2 {
+                points: 3,
+                bonus: 1
+            }
+        );
+        assert!(deserialize_versioned::<Points>(br#"{"version": 1, "data": {}}"#).is_err());
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted() {
+        use super::super::encryption::is_encrypted;
+        use super::super::{set_encryption_key, EncryptionKey};
+        use super::{decode, encode};
+
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Notes(Vec<String>);
+
+        impl PersistedType for Notes {
+            const FILENAME: &'static str = "notes";
+            const ENCRYPTED: bool = true;
+
+            fn init(_channel: &str) -> Self {
+                Notes(vec![])
+            }
+        }
+
+        let key = "1f1e1d1c1b1a191817161514131211100f0e0d0c0b0a09080706050403020100";
+        set_encryption_key(EncryptionKey::from_hex(key).unwrap());
+        let notes = Notes(vec!["secret".to_owned()]);
+        let bytes = encode(&notes).unwrap();
+        assert!(is_encrypted(&bytes));
+        assert_eq!(decode::<Notes>(&bytes).unwrap(), notes);
+        // files which were written before the encryption was turned on
+        assert_eq!(decode::<Notes>(br#"(["secret"])"#).unwrap(), notes);
+    }
+
+    #[test]
+    fn test_channel_dir() {
+        let data_dir = DataDir::new("data");
+        assert!(data_dir
+            .channel_dir("liquidnya")
+            .unwrap()
+            .ends_with("data/liquidnya"));
+        for channel in ["", "..", "../etc", "/etc", "a\\b"] {
+            assert!(data_dir.channel_dir(channel).is_err(), "{}", channel);
+        }
+    }
+
+    #[test]
+    fn test_rotate_backups() {
+        let dir = std::env::temp_dir().join(format!("chatbot-test-backups-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("points.ron");
+        rotate_backups(&path, 2).unwrap();
+        assert!(!backup_path(&path, 1).exists());
+        for content in ["1", "2", "3"] {
+            rotate_backups(&path, 2).unwrap();
+            std::fs::write(&path, content).unwrap();
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "3");
+        assert_eq!(std::fs::read_to_string(backup_path(&path, 1)).unwrap(), "2");
+        assert_eq!(std::fs::read_to_string(backup_path(&path, 2)).unwrap(), "1");
+        assert!(!backup_path(&path, 3).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_try_update() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let dir =
+                std::env::temp_dir().join(format!("chatbot-test-update-{}", std::process::id()));
+            let persisted = Persisted::<Points>::new(PendingWrites::default(), DataDir::new(&dir));
+            let points = persisted.for_channel("liquidnya");
+            let result = points
+                .try_update(|_| Err::<Option<Points>, _>("too many points"))
+                .await;
+            assert_eq!(result.unwrap_err(), "too many points");
+            assert!(!dir.join("liquidnya").join("points.ron").exists());
+            let (old, new) = points
+                .try_update(|value| {
+                    Ok::<_, ()>(Some(Points {
+                        points: value.points + 1,
+                    }))
+                })
+                .await
+                .unwrap();
+            assert_eq!((old.points, new.unwrap().points), (0, 1));
+            assert!(dir.join("liquidnya").join("points.ron").exists());
+            std::fs::remove_dir_all(&dir).unwrap();
+        });
+    }
+
+    #[test]
+    fn test_watch() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let dir =
+                std::env::temp_dir().join(format!("chatbot-test-watch-{}", std::process::id()));
+            let persisted = Persisted::<Points>::new(PendingWrites::default(), DataDir::new(&dir));
+            let points = persisted.for_channel("liquidnya");
+            let mut receiver = points.watch().await;
+            assert_eq!(receiver.borrow().points, 0);
+            points.maybe_update(|_| None::<Points>).await;
+            points
+                .update(|value| Points {
+                    points: value.points + 2,
+                })
+                .await;
+            receiver.changed().await.unwrap();
+            assert_eq!(receiver.borrow().points, 2);
+
+            // the last receiver was dropped, so the next one starts with the current value
+            drop(receiver);
+            points
+                .update(|value| Points {
+                    points: value.points + 1,
+                })
+                .await;
+            let receiver = points.watch().await;
+            assert_eq!(receiver.borrow().points, 3);
+            std::fs::remove_dir_all(&dir).unwrap();
+        });
+    }
+
+    #[test]
+    fn test_interrupted_commit() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Queue(Vec<u32>);
+
+        impl PersistedType for Queue {
+            const FILENAME: &'static str = "queue";
+
+            fn init(_channel: &str) -> Self {
+                Queue(vec![])
+            }
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let dir =
+                std::env::temp_dir().join(format!("chatbot-test-commit-{}", std::process::id()));
+            let data_dir = DataDir::new(&dir);
+            let points = Arc::new(Points { points: 1 });
+            store_both_on_disk(&data_dir, "liquidnya", points, Arc::new(Queue(vec![1])))
+                .await
+                .unwrap();
+
+            // the second file is not replaced
+            let mut renames = 0;
+            let result = store_both_with(
+                &data_dir,
+                "liquidnya",
+                Arc::new(Points { points: 2 }),
+                Arc::new(Queue(vec![1, 2])),
+                move |from, to| {
+                    renames += 1;
+                    if renames == 2 {
+                        return Err(std::io::Error::other("injected failure"));
+                    }
+                    std::fs::rename(from, to)
+                },
+            )
+            .await;
+            assert!(result.is_err());
+            let queue_path = dir.join("liquidnya").join("queue.ron");
+            let queue: Queue = deserialize_versioned(&std::fs::read(&queue_path).unwrap()).unwrap();
+            assert_eq!(queue, Queue(vec![1]));
+
+            // loading either file finishes the write of both
+            let queue = read_from_disk::<Queue>(&data_dir, "liquidnya").await;
+            assert_eq!(queue.unwrap(), Some(Queue(vec![1, 2])));
+            let points = read_from_disk::<Points>(&data_dir, "liquidnya").await;
+            assert_eq!(points.unwrap(), Some(Points { points: 2 }));
+            let journals = std::fs::read_dir(dir.join("liquidnya"))
+                .unwrap()
+                .filter(|entry| {
+                    let path = entry.as_ref().unwrap().path();
+                    path.extension()
+                        .is_some_and(|extension| extension == "commit")
+                })
+                .count();
+            assert_eq!(journals, 0);
+            std::fs::remove_dir_all(&dir).unwrap();
+        });
+    }
+}
@@ -0,0 +1,202 @@
+use super::persisted_state::{deserialize_versioned, read_from_disk, serialize_versioned, DataDir};
+use super::PersistedType;
+use redis::aio::ConnectionManager;
+use redis::Script;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// Replaces the value only if it was not changed since it was read, `ARGV[1]` is `1` if there
+/// was a value, which is `ARGV[2]`.
+const COMPARE_AND_SET: &str = r"
+local current = redis.call('GET', KEYS[1])
+if (current == false and ARGV[1] == '0') or current == ARGV[2] then
+    redis.call('SET', KEYS[1], ARGV[3])
+    return 1
+end
+return 0
+";
+
+/// How often an update is tried again after another bot changed the value in the meantime.
+const MAX_UPDATE_ATTEMPTS: usize = 16;
+
+fn key(prefix: &str, channel: &str, filename: &str) -> String {
+    format!("{}:{}:{}", prefix, channel, filename)
+}
+
+/// A connection to Redis, which stores the [`SharedState`] of all channels.
+#[derive(Clone)]
+pub struct RedisStore {
+    connection: ConnectionManager,
+    prefix: String,
+}
+
+impl RedisStore {
+    /// Connects to a URL like `redis://127.0.0.1/`, the connection is restored automatically
+    /// after it was lost.
+    pub async fn connect(url: &str) -> redis::RedisResult<Self> {
+        let client = redis::Client::open(url)?;
+        Ok(Self {
+            connection: ConnectionManager::new(client).await?,
+            prefix: "chatbot".to_owned(),
+        })
+    }
+
+    /// Prefixes the keys, which are `<prefix>:<channel>:<filename>`, `chatbot` by default.
+    pub fn with_prefix<P: Into<String>>(mut self, prefix: P) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+}
+
+/// A persisted type which is stored in Redis instead of a file, such that several bots share it,
+/// registered with
+/// [`ContainerBuilder::register_shared_type`](super::ContainerBuilder::register_shared_type).
+///
+/// The value is not cached, since other bots might change it at any time. Updates are applied
+/// only if the value was not changed by another bot since it was read, otherwise they are tried
+/// again with the new value.
+///
+/// ```ignore
+/// let store = RedisStore::connect("redis://127.0.0.1/").await?;
+/// let channel_container = ChannelContainer::new(Box::new(move |_channel, builder| {
+///     builder.register_shared_type::<Points>(&store);
+/// }));
+/// // in a command
+/// let points = request.shared_state::<Points>()?;
+/// points.update(|points| points.add(user, 10)).await?;
+/// ```
+pub struct SharedState<T: PersistedType> {
+    store: RedisStore,
+    compare_and_set: Script,
+    // the directory of the files which are imported
+    data_dir: DataDir,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T: PersistedType> SharedState<T> {
+    pub(crate) fn new(store: RedisStore, data_dir: DataDir) -> Self {
+        Self {
+            store,
+            compare_and_set: Script::new(COMPARE_AND_SET),
+            data_dir,
+            _type: PhantomData,
+        }
+    }
+
+    pub(crate) fn for_channel<'a>(&'a self, channel: &'a str) -> SharedChannelState<'a, T> {
+        SharedChannelState {
+            shared: self,
+            channel,
+        }
+    }
+}
+
+pub struct SharedChannelState<'a, T: PersistedType> {
+    shared: &'a SharedState<T>,
+    channel: &'a str,
+}
+
+impl<'a, T: PersistedType> SharedChannelState<'a, T> {
+    fn key(&self) -> String {
+        key(&self.shared.store.prefix, self.channel, T::FILENAME)
+    }
+
+    // the stored bytes are needed to detect changes by other bots
+    async fn load(&self) -> anyhow::Result<(Option<Vec<u8>>, Arc<T>)> {
+        let mut connection = self.shared.store.connection.clone();
+        let bytes: Option<Vec<u8>> = redis::cmd("GET")
+            .arg(self.key())
+            .query_async(&mut connection)
+            .await?;
+        let value = match &bytes {
+            Some(bytes) => deserialize_versioned(bytes)?,
+            None => <T as PersistedType>::init(self.channel),
+        };
+        Ok((bytes, Arc::new(value)))
+    }
+
+    pub async fn read(&self) -> anyhow::Result<Arc<T>> {
+        Ok(self.load().await?.1)
+    }
+
+    /// `f` is called again if another bot changed the value while it was updated.
+    pub async fn maybe_update<R, F>(&self, mut f: F) -> anyhow::Result<(Arc<T>, Option<Arc<T>>)>
+    where
+        F: FnMut(&T) -> Option<R>,
+        R: Into<T>,
+    {
+        let mut connection = self.shared.store.connection.clone();
+        for _ in 0..MAX_UPDATE_ATTEMPTS {
+            let (bytes, value) = self.load().await?;
+            let new_value = match f(&value) {
+                Some(new_value) => Arc::new(new_value.into()),
+                None => return Ok((value, None)),
+            };
+            let new_bytes = serialize_versioned(new_value.as_ref())?;
+            let swapped: bool = self
+                .shared
+                .compare_and_set
+                .key(self.key())
+                .arg(u8::from(bytes.is_some()))
+                .arg(bytes.unwrap_or_default())
+                .arg(new_bytes)
+                .invoke_async(&mut connection)
+                .await?;
+            if swapped {
+                return Ok((value, Some(new_value)));
+            }
+            log::debug!(
+                "{} of channel {} was changed by another bot, updating it again",
+                <T as PersistedType>::FILENAME,
+                self.channel
+            );
+        }
+        Err(anyhow::anyhow!(
+            "{} of channel {} was changed by other bots {} times while updating it",
+            <T as PersistedType>::FILENAME,
+            self.channel,
+            MAX_UPDATE_ATTEMPTS
+        ))
+    }
+
+    pub async fn update<R, F>(&self, mut f: F) -> anyhow::Result<(Arc<T>, Arc<T>)>
+    where
+        F: FnMut(&T) -> R,
+        R: Into<T>,
+    {
+        let (old, new) = self.maybe_update(move |value| Some((f)(value))).await?;
+        Ok((old, new.unwrap()))
+    }
+
+    /// Copies the value which was persisted in a file to Redis, unless Redis has a value already.
+    ///
+    /// Returns `false` if nothing was copied.
+    pub async fn import_file(&self) -> anyhow::Result<bool> {
+        let value = match read_from_disk::<T>(&self.shared.data_dir, self.channel).await? {
+            Some(value) => value,
+            None => return Ok(false),
+        };
+        let mut connection = self.shared.store.connection.clone();
+        let imported: bool = redis::cmd("SET")
+            .arg(self.key())
+            .arg(serialize_versioned(&value)?)
+            .arg("NX")
+            .query_async::<Option<String>>(&mut connection)
+            .await?
+            .is_some();
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::key;
+
+    #[test]
+    fn test_key() {
+        assert_eq!(
+            key("chatbot", "liquidnya", "points"),
+            "chatbot:liquidnya:points"
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use super::BotStatus;
+use crate::http_server::{self, HttpResponse};
+use std::time::Duration;
+use tokio::io;
+use tokio::net::TcpListener;
+
+/// Answers `GET /health` with `200 OK` if the bot [is healthy](BotStatus::is_healthy)
+/// and `503 Service Unavailable` otherwise, and `GET /status` with the [snapshot](BotStatus::snapshot) as JSON.
+pub async fn serve_health(
+    listener: TcpListener,
+    status: BotStatus,
+    max_silence: Duration,
+) -> io::Result<()> {
+    http_server::serve(listener, move |request| {
+        let status = status.clone();
+        async move {
+            match (request.method.as_str(), request.path.as_str()) {
+                ("GET", "/health") if status.is_healthy(max_silence) => {
+                    HttpResponse::text("200 OK", "ok")
+                }
+                ("GET", "/health") => HttpResponse::text("503 Service Unavailable", "unhealthy"),
+                ("GET", "/status") => match serde_json::to_string(&status.snapshot()) {
+                    Ok(json) => HttpResponse::new("200 OK", "application/json", json),
+                    Err(_) => HttpResponse::text("500 Internal Server Error", "error"),
+                },
+                _ => HttpResponse::text("404 Not Found", "not found"),
+            }
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    async fn get(address: std::net::SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serve_health() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let status = BotStatus::new();
+            tokio::spawn(serve_health(
+                listener,
+                status.clone(),
+                Duration::from_secs(60),
+            ));
+
+            assert!(get(address, "/health")
+                .await
+                .starts_with("HTTP/1.1 503 Service Unavailable"));
+            status.set_connected(true);
+            status.join("liquidnya");
+            assert!(get(address, "/health").await.starts_with("HTTP/1.1 200 OK"));
+            let response = get(address, "/status").await;
+            assert!(response.contains(r#""connected":true"#));
+            assert!(response.contains(
+                r#""liquidnya":{"last_message":null,"queue_depth":0,"dropped_messages":0}"#
+            ));
+        });
+    }
+}
@@ -0,0 +1,236 @@
+//! The health of a running chat bot, which can be queried by commands with
+//! [`State<BotStatus>`](crate::State) or by orchestration systems over HTTP.
+
+#[cfg(feature = "health")]
+mod http;
+
+#[cfg(feature = "health")]
+pub use self::http::serve_health;
+
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+struct Status {
+    connected: bool,
+    connections: u64,
+    channels: BTreeSet<String>,
+    last_activity: Option<DateTime<Utc>>,
+    last_message: HashMap<String, DateTime<Utc>>,
+    queue_depths: HashMap<String, usize>,
+    dropped_messages: HashMap<String, u64>,
+}
+
+/// A handle to the status of a chat bot, which is updated while the bot runs.
+///
+/// The same handle can be passed to [`ChatBot::with_status`](crate::ChatBot::with_status)
+/// when the bot is started again after the connection was lost, which counts as a reconnect.
+#[derive(Debug, Clone, Default)]
+pub struct BotStatus {
+    status: Arc<Mutex<Status>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ChannelStatus {
+    pub last_message: Option<DateTime<Utc>>,
+    /// Messages waiting to be handled.
+    pub queue_depth: usize,
+    /// Messages which were dropped because too many messages were waiting, see
+    /// [`ChatBot::intake_capacity`](crate::ChatBot::intake_capacity).
+    pub dropped_messages: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct StatusSnapshot {
+    pub connected: bool,
+    pub reconnects: u64,
+    /// The last time anything was received from Twitch, including pings.
+    pub last_activity: Option<DateTime<Utc>>,
+    pub channels: BTreeMap<String, ChannelStatus>,
+}
+
+impl BotStatus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn status(&self) -> MutexGuard<'_, Status> {
+        self.status.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.status().connected
+    }
+
+    pub fn reconnects(&self) -> u64 {
+        self.status().connections.saturating_sub(1)
+    }
+
+    pub fn channels(&self) -> Vec<String> {
+        self.status().channels.iter().cloned().collect()
+    }
+
+    pub fn is_joined(&self, channel: &str) -> bool {
+        self.status().channels.contains(normalize(channel))
+    }
+
+    pub fn last_activity(&self) -> Option<DateTime<Utc>> {
+        self.status().last_activity
+    }
+
+    pub fn last_message(&self, channel: &str) -> Option<DateTime<Utc>> {
+        self.status().last_message.get(channel).copied()
+    }
+
+    pub fn queue_depth(&self, channel: &str) -> usize {
+        self.status()
+            .queue_depths
+            .get(channel)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// How many messages of the channel were dropped because too many messages were waiting.
+    pub fn dropped_messages(&self, channel: &str) -> u64 {
+        self.status()
+            .dropped_messages
+            .get(normalize(channel))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The bot is healthy if it is connected and received something within `max_silence`.
+    /// Twitch sends a ping about every five minutes, even if nobody chats.
+    pub fn is_healthy(&self, max_silence: Duration) -> bool {
+        self.is_healthy_at(max_silence, Utc::now())
+    }
+
+    fn is_healthy_at(&self, max_silence: Duration, now: DateTime<Utc>) -> bool {
+        let status = self.status();
+        let max_silence = chrono::Duration::from_std(max_silence).unwrap_or(chrono::Duration::MAX);
+        status.connected
+            && status
+                .last_activity
+                .is_some_and(|last_activity| now - last_activity <= max_silence)
+    }
+
+    pub fn snapshot(&self) -> StatusSnapshot {
+        let status = self.status();
+        let channels = status
+            .channels
+            .iter()
+            .chain(status.queue_depths.keys())
+            .map(|channel| {
+                let channel_status = ChannelStatus {
+                    last_message: status.last_message.get(channel).copied(),
+                    queue_depth: status
+                        .queue_depths
+                        .get(channel)
+                        .copied()
+                        .unwrap_or_default(),
+                    dropped_messages: status
+                        .dropped_messages
+                        .get(channel)
+                        .copied()
+                        .unwrap_or_default(),
+                };
+                (channel.clone(), channel_status)
+            })
+            .collect();
+        StatusSnapshot {
+            connected: status.connected,
+            reconnects: status.connections.saturating_sub(1),
+            last_activity: status.last_activity,
+            channels,
+        }
+    }
+
+    pub(crate) fn set_connected(&self, connected: bool) {
+        let mut status = self.status();
+        if connected {
+            status.connections += 1;
+            status.last_activity = Some(Utc::now());
+        } else {
+            status.channels.clear();
+            status.queue_depths.clear();
+        }
+        status.connected = connected;
+    }
+
+    pub(crate) fn join(&self, channel: &str) {
+        self.status().channels.insert(normalize(channel).to_owned());
+    }
+
+    pub(crate) fn part(&self, channel: &str) {
+        self.status().channels.remove(normalize(channel));
+    }
+
+    /// Records that something was received, `channel` is set for messages in a channel.
+    pub(crate) fn received(&self, channel: Option<&str>) {
+        let now = Utc::now();
+        let mut status = self.status();
+        status.last_activity = Some(now);
+        if let Some(channel) = channel {
+            status
+                .last_message
+                .insert(normalize(channel).to_owned(), now);
+        }
+    }
+
+    pub(crate) fn dropped(&self, channel: &str) {
+        *self
+            .status()
+            .dropped_messages
+            .entry(normalize(channel).to_owned())
+            .or_default() += 1;
+    }
+
+    pub(crate) fn set_queue_depth(&self, channel: &str, depth: usize) {
+        let mut status = self.status();
+        if depth == 0 {
+            status.queue_depths.remove(normalize(channel));
+        } else {
+            status
+                .queue_depths
+                .insert(normalize(channel).to_owned(), depth);
+        }
+    }
+}
+
+fn normalize(channel: &str) -> &str {
+    channel.trim_start_matches('#')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status() {
+        let status = BotStatus::new();
+        let later = Utc::now() + chrono::Duration::minutes(10);
+        assert!(!status.is_healthy_at(Duration::from_secs(60), later));
+
+        status.set_connected(true);
+        status.join("#liquidnya");
+        status.received(Some("#liquidnya"));
+        status.set_queue_depth("#liquidnya", 2);
+        assert!(status.is_connected());
+        assert_eq!(status.channels(), vec!["liquidnya".to_owned()]);
+        assert!(status.last_message("liquidnya").is_some());
+        assert_eq!(status.queue_depth("liquidnya"), 2);
+        status.dropped("#liquidnya");
+        assert_eq!(status.dropped_messages("liquidnya"), 1);
+        assert_eq!(status.snapshot().channels["liquidnya"].dropped_messages, 1);
+        assert!(status.is_healthy(Duration::from_secs(60)));
+        assert!(!status.is_healthy_at(Duration::from_secs(60), later));
+
+        status.set_connected(false);
+        status.set_connected(true);
+        let snapshot = status.snapshot();
+        assert_eq!(snapshot.reconnects, 1);
+        assert!(snapshot.channels.is_empty());
+    }
+}
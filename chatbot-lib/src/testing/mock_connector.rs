@@ -0,0 +1,266 @@
+use futures_io::{AsyncRead, AsyncWrite};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use twitchchat::connector::Connector;
+
+#[derive(Debug, Default)]
+struct Connection {
+    incoming: VecDeque<u8>,
+    outgoing: Vec<u8>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+impl Connection {
+    fn push_line(&mut self, line: &str) {
+        self.incoming.extend(line.trim_end().as_bytes());
+        self.incoming.extend(b"\r\n");
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// answers the messages the runner waits for
+    fn answer(&mut self, bot: &str, line: &str) {
+        if let Some(channel) = line.strip_prefix("JOIN ") {
+            self.push_line(&format!(":{bot}!{bot}@{bot}.tmi.twitch.tv JOIN {channel}"));
+        } else if let Some(channel) = line.strip_prefix("PART ") {
+            self.push_line(&format!(":{bot}!{bot}@{bot}.tmi.twitch.tv PART {channel}"));
+        } else if let Some(token) = line.strip_prefix("PING ") {
+            self.push_line(&format!(":tmi.twitch.tv PONG tmi.twitch.tv {token}"));
+        }
+    }
+}
+
+/// A [`Connector`] which does not connect to Twitch.
+/// Messages are injected and the messages sent by the bot are captured through the [`MockHandle`].
+#[derive(Debug, Clone)]
+pub struct MockConnector {
+    handle: MockHandle,
+}
+
+/// Injects messages into and captures messages from the connection of a [`MockConnector`].
+#[derive(Debug, Clone)]
+pub struct MockHandle {
+    bot: Arc<str>,
+    connection: Arc<Mutex<Connection>>,
+}
+
+/// The connection returned by a [`MockConnector`].
+#[derive(Debug)]
+pub struct MockStream {
+    handle: MockHandle,
+}
+
+impl MockConnector {
+    /// Creates a connector which answers the registration as the user `bot`.
+    pub fn new(bot: &str) -> (Self, MockHandle) {
+        let handle = MockHandle {
+            bot: bot.into(),
+            connection: Arc::default(),
+        };
+        (
+            Self {
+                handle: handle.clone(),
+            },
+            handle,
+        )
+    }
+}
+
+impl Connector for MockConnector {
+    type Output = MockStream;
+
+    fn connect(&mut self) -> Pin<Box<dyn Future<Output = io::Result<Self::Output>> + Send + Sync>> {
+        let handle = self.handle.clone();
+        Box::pin(async move {
+            let bot = handle.bot.clone();
+            handle.send_line(
+                ":tmi.twitch.tv CAP * ACK :twitch.tv/membership twitch.tv/tags twitch.tv/commands",
+            );
+            handle.send_line(&format!(":tmi.twitch.tv 001 {bot} :Welcome, GLHF!"));
+            handle.send_line(&format!(
+                "@badge-info=;badges=;color=;display-name={bot};emote-sets=0;user-id=1;user-type= :tmi.twitch.tv GLOBALUSERSTATE"
+            ));
+            Ok(MockStream { handle })
+        })
+    }
+}
+
+impl MockHandle {
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sends a raw IRC line to the bot, e.g. `@id=1 :user!user@user.tmi.twitch.tv PRIVMSG #channel :!song`.
+    pub fn send_line(&self, line: &str) {
+        self.connection().push_line(line);
+    }
+
+    /// Sends a chat message of `user` in `channel` to the bot.
+    pub fn privmsg(&self, id: &str, channel: &str, user: &str, message: &str) {
+        self.send_line(&format!(
+            "@id={id};display-name={user};mod=0 :{user}!{user}@{user}.tmi.twitch.tv PRIVMSG #{channel} :{message}"
+        ));
+    }
+
+    /// Ends the connection, which stops [`ChatBot::run`](crate::ChatBot::run).
+    pub fn close(&self) {
+        let mut connection = self.connection();
+        connection.closed = true;
+        if let Some(waker) = connection.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Every line the bot sent so far.
+    pub fn sent(&self) -> Vec<String> {
+        String::from_utf8_lossy(&self.connection().outgoing)
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// The messages the bot sent to chat as `(channel, message)`.
+    pub fn sent_messages(&self) -> Vec<(String, String)> {
+        self.sent()
+            .iter()
+            .filter_map(|line| {
+                let (_, privmsg) = line.split_once("PRIVMSG #")?;
+                let (channel, message) = privmsg.split_once(" :")?;
+                Some((channel.to_owned(), message.to_owned()))
+            })
+            .collect()
+    }
+}
+
+impl AsyncRead for &MockStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut connection = self.handle.connection();
+        if connection.incoming.is_empty() {
+            if connection.closed {
+                return Poll::Ready(Ok(0));
+            }
+            connection.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let len = buf.len().min(connection.incoming.len());
+        for (target, byte) in buf.iter_mut().zip(connection.incoming.drain(..len)) {
+            *target = byte;
+        }
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl AsyncWrite for &MockStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut connection = self.handle.connection();
+        let start = connection.outgoing.len();
+        connection.outgoing.extend_from_slice(buf);
+        // answer every line which was completed by this write
+        let line_start = connection.outgoing[..start]
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |index| index + 1);
+        let written = String::from_utf8_lossy(&connection.outgoing[line_start..]).into_owned();
+        let mut lines: Vec<_> = written.split("\r\n").collect();
+        lines.pop(); // the last line is not complete yet
+        for line in lines {
+            connection.answer(&self.handle.bot, line);
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.handle.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for MockStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &*self).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for MockStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &*self).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut &*self).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut &*self).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_stream() {
+        let (mut connector, handle) = MockConnector::new("bot");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let stream = runtime.block_on(connector.connect()).unwrap();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut buf = [0; 1024];
+        let Poll::Ready(Ok(len)) = Pin::new(&mut &stream).poll_read(&mut cx, &mut buf) else {
+            panic!("expected the registration");
+        };
+        assert!(String::from_utf8_lossy(&buf[..len]).contains("GLOBALUSERSTATE"));
+        assert!(Pin::new(&mut &stream)
+            .poll_read(&mut cx, &mut buf)
+            .is_pending());
+
+        let join = b"JOIN #channel\r\nPRIVMSG #channel :hello\r\n";
+        let _ = Pin::new(&mut &stream).poll_write(&mut cx, join);
+        let Poll::Ready(Ok(len)) = Pin::new(&mut &stream).poll_read(&mut cx, &mut buf) else {
+            panic!("expected the join");
+        };
+        assert_eq!(
+            String::from_utf8_lossy(&buf[..len]),
+            ":bot!bot@bot.tmi.twitch.tv JOIN #channel\r\n"
+        );
+        assert_eq!(
+            handle.sent_messages(),
+            vec![("channel".to_owned(), "hello".to_owned())]
+        );
+
+        handle.close();
+        assert!(matches!(
+            Pin::new(&mut &stream).poll_read(&mut cx, &mut buf),
+            Poll::Ready(Ok(0))
+        ));
+    }
+}
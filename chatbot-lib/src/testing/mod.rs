@@ -0,0 +1,224 @@
+//! Helpers for testing command processors without a connection to Twitch.
+
+mod mock_connector;
+
+pub use self::mock_connector::{MockConnector, MockHandle, MockStream};
+
+use crate::chat_bot::ChatBotContext;
+use crate::command::CommandProcessor;
+use crate::request::{Bot, Channel, CommandRequest, Sender};
+use crate::response::{Responder, Response};
+use crate::state::ChannelChatters;
+use crate::user::{OwnedUser, User};
+use async_trait::async_trait;
+use state::TypeMap;
+use tokio::io;
+use tokio::sync::Mutex;
+
+/// Collects all responses instead of sending them.
+#[derive(Debug, Default)]
+pub struct CollectingResponder {
+    responses: Vec<Response<'static>>,
+}
+
+impl CollectingResponder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn responses(&self) -> &[Response<'static>] {
+        &self.responses
+    }
+
+    pub fn into_responses(self) -> Vec<Response<'static>> {
+        self.responses
+    }
+}
+
+#[async_trait]
+impl Responder for CollectingResponder {
+    async fn respond(&mut self, response: &Response<'_>) -> io::Result<()> {
+        self.responses.push(response.clone().into_owned());
+        Ok(())
+    }
+}
+
+/// Builds a command request with state, channel state and chatters, e.g.
+/// `TestRequestBuilder::new("!song").sender("liquidblock").state(songs).build()`.
+pub struct TestRequestBuilder {
+    command: String,
+    sender: OwnedUser,
+    moderator: bool,
+    broadcaster: bool,
+    channel: OwnedUser,
+    bot: OwnedUser,
+    container: TypeMap![Send + Sync],
+    channel_container: Option<TypeMap![Send + Sync]>,
+    chatters: ChannelChatters,
+    language: Option<String>,
+}
+
+impl TestRequestBuilder {
+    pub fn new<T: Into<String>>(command: T) -> Self {
+        Self {
+            command: command.into(),
+            sender: OwnedUser::from_username("sender".to_owned()),
+            moderator: false,
+            broadcaster: false,
+            channel: OwnedUser::from_username("channel".to_owned()),
+            bot: OwnedUser::from_username("bot".to_owned()),
+            container: <TypeMap![Send + Sync]>::new(),
+            channel_container: None,
+            chatters: ChannelChatters::new(),
+            language: None,
+        }
+    }
+
+    pub fn sender<T: Into<String>>(self, username: T) -> Self {
+        self.sender_user(OwnedUser::from_username(username.into()))
+    }
+
+    pub fn sender_user(self, sender: OwnedUser) -> Self {
+        Self { sender, ..self }
+    }
+
+    pub fn moderator(self) -> Self {
+        Self {
+            moderator: true,
+            ..self
+        }
+    }
+
+    pub fn broadcaster(self) -> Self {
+        Self {
+            broadcaster: true,
+            ..self
+        }
+    }
+
+    pub fn channel<T: Into<String>>(self, username: T) -> Self {
+        Self {
+            channel: OwnedUser::from_username(username.into()),
+            ..self
+        }
+    }
+
+    pub fn bot<T: Into<String>>(self, username: T) -> Self {
+        Self {
+            bot: OwnedUser::from_username(username.into()),
+            ..self
+        }
+    }
+
+    pub fn state<T: Send + Sync + 'static>(self, state: T) -> Self {
+        self.container.set(state);
+        self
+    }
+
+    pub fn channel_state<T: Send + Sync + 'static>(mut self, state: T) -> Self {
+        self.channel_container
+            .get_or_insert_with(<TypeMap![Send + Sync]>::new)
+            .set(state);
+        self
+    }
+
+    pub fn chatters(self, chatters: ChannelChatters) -> Self {
+        Self { chatters, ..self }
+    }
+
+    /// The language of the channel, see [`locale`](crate::locale).
+    pub fn language<T: Into<String>>(self, language: T) -> Self {
+        Self {
+            language: Some(language.into()),
+            ..self
+        }
+    }
+
+    pub fn build(self) -> TestRequest {
+        TestRequest(self)
+    }
+}
+
+/// Owns everything a [`CommandRequest`] borrows.
+pub struct TestRequest(TestRequestBuilder);
+
+impl TestRequest {
+    /// Processes the request and returns the intermediate responses followed by the final response.
+    /// Responses sent through [`RespondLater`](crate::response::RespondLater) are not collected.
+    pub async fn process<P: CommandProcessor>(&self, processor: &P) -> Vec<Response<'static>> {
+        let builder = &self.0;
+        let context = ChatBotContext::new(
+            &builder.container,
+            builder.channel_container.as_ref(),
+            &builder.chatters,
+        )
+        .with_language(builder.language.as_deref());
+        let responder = Mutex::new(CollectingResponder::new());
+        let sender = Sender::new(
+            User::from_owned(&builder.sender),
+            builder.moderator,
+            builder.broadcaster,
+        );
+        let channel = Channel::from(User::from_owned(&builder.channel));
+        let bot = Bot::from(User::from_owned(&builder.bot));
+        let request = CommandRequest::new(
+            builder.command.as_str(),
+            sender,
+            channel,
+            &bot,
+            &context,
+            &responder,
+        );
+        let response = processor.process(&request).await.map(Response::into_owned);
+        let mut responder = responder.into_inner();
+        if let Some(response) = response {
+            responder.responses.push(response);
+        }
+        responder.into_responses()
+    }
+
+    /// The text of every response which would be sent to the chat.
+    pub async fn process_text<P: CommandProcessor>(&self, processor: &P) -> Vec<String> {
+        self.process(processor)
+            .await
+            .iter()
+            .filter_map(|response| response.response().map(str::to_owned))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::RequestResponder;
+
+    struct Greet;
+
+    #[async_trait]
+    impl CommandProcessor for Greet {
+        async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+            let greeting = request.context?.state::<&'static str>().ok()?;
+            RequestResponder::from(request)
+                .respond(&Response::new("one moment"))
+                .await
+                .ok()?;
+            Some(Response::new(format!("{} {}", *greeting, request.sender().username())).as_reply())
+        }
+    }
+
+    #[test]
+    fn test_process() {
+        let request = TestRequestBuilder::new("!greet")
+            .sender("liquidblock")
+            .state("hello")
+            .build();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let responses = runtime.block_on(request.process(&Greet));
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0].response(), Some("one moment"));
+        assert_eq!(responses[1].response(), Some("hello liquidblock"));
+        assert!(responses[1].reply());
+    }
+}
@@ -1,10 +1,166 @@
 mod user_argument;
+mod user_resolver;
 
-pub use self::user_argument::UserArgument;
+pub use self::user_argument::{MentionList, UserArgument};
+pub use self::user_resolver::{UserLookup, UserResolver};
 use std::mem;
 
-pub type UserId = i64;
-pub type ChannelId = UserId;
+use std::borrow::Cow;
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+macro_rules! id_type {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(
+            Debug,
+            Clone,
+            Copy,
+            PartialEq,
+            Eq,
+            Hash,
+            PartialOrd,
+            Ord,
+            serde::Serialize,
+            serde::Deserialize,
+        )]
+        #[serde(transparent)]
+        pub struct $name(i64);
+
+        impl $name {
+            pub const fn new(id: i64) -> Self {
+                Self(id)
+            }
+
+            pub const fn get(self) -> i64 {
+                self.0
+            }
+        }
+
+        impl From<i64> for $name {
+            fn from(id: i64) -> Self {
+                Self(id)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse().map(Self)
+            }
+        }
+    };
+}
+
+id_type!(
+    /// The id of a user, which stays the same when the user is renamed.
+    UserId
+);
+id_type!(
+    /// The id of a channel, which is the [`UserId`] of the broadcaster.
+    ChannelId
+);
+
+impl From<UserId> for ChannelId {
+    fn from(id: UserId) -> Self {
+        Self(id.0)
+    }
+}
+
+impl From<ChannelId> for UserId {
+    fn from(id: ChannelId) -> Self {
+        Self(id.0)
+    }
+}
+
+/// How usernames and display names are compared, when looking up chatters or comparing a
+/// [`UserArgument`] with a [`User`].
+///
+/// Usernames are lowercase ASCII, so the policies only differ for display names, which are
+/// localized for some users, e.g. `ΣΊΣΥΦΟΣ`. Looking up a name with
+/// [`NameMatching::UnicodeCaseFold`] is about as fast as with [`NameMatching::AsciiCaseInsensitive`]
+/// for ASCII names, which are most names, see `benches/name_matching.rs`. That is why it is the
+/// default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameMatching {
+    /// Names have to be equal.
+    Exact,
+    /// Names are equal independent of the case of ASCII letters.
+    AsciiCaseInsensitive,
+    /// Names are equal if their Unicode case folding is equal.
+    #[default]
+    UnicodeCaseFold,
+}
+
+impl NameMatching {
+    /// The name which is used to look up users by username or display name, e.g. the name in
+    /// lowercase. Only allocates if the name changes.
+    pub fn key(self, name: &str) -> Cow<'_, str> {
+        match self {
+            NameMatching::Exact => Cow::Borrowed(name),
+            NameMatching::UnicodeCaseFold if !name.is_ascii() => {
+                Cow::Owned(caseless::default_case_fold_str(name))
+            }
+            NameMatching::AsciiCaseInsensitive | NameMatching::UnicodeCaseFold => {
+                if name.bytes().any(|b| b.is_ascii_uppercase()) {
+                    Cow::Owned(name.to_ascii_lowercase())
+                } else {
+                    Cow::Borrowed(name)
+                }
+            }
+        }
+    }
+
+    pub fn matches(self, lhs: &str, rhs: &str) -> bool {
+        match self {
+            NameMatching::Exact => lhs == rhs,
+            NameMatching::AsciiCaseInsensitive => lhs.eq_ignore_ascii_case(rhs),
+            NameMatching::UnicodeCaseFold => {
+                if lhs.is_ascii() && rhs.is_ascii() {
+                    lhs.eq_ignore_ascii_case(rhs)
+                } else {
+                    self.key(lhs) == self.key(rhs)
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for NameMatching {
+    type Err = ParseNameMatchingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exact" => Ok(NameMatching::Exact),
+            "ascii_case_insensitive" => Ok(NameMatching::AsciiCaseInsensitive),
+            "unicode_case_fold" => Ok(NameMatching::UnicodeCaseFold),
+            _ => Err(ParseNameMatchingError),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseNameMatchingError;
+
+impl fmt::Display for ParseNameMatchingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected exact, ascii_case_insensitive or unicode_case_fold"
+        )
+    }
+}
+
+impl std::error::Error for ParseNameMatchingError {}
 
 #[derive(Debug, Clone)]
 pub struct User<'a> {
@@ -13,7 +169,7 @@ pub struct User<'a> {
     pub(crate) user_id: Option<UserId>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct OwnedUser {
     username: String,
     display_name: Option<String>,
@@ -141,3 +297,41 @@ impl PartialEq for OwnedUser {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_matching() {
+        assert!(NameMatching::Exact.matches("SomeUser", "SomeUser"));
+        assert!(!NameMatching::Exact.matches("SomeUser", "someuser"));
+        assert!(NameMatching::AsciiCaseInsensitive.matches("SomeUser", "someuser"));
+        assert!(!NameMatching::AsciiCaseInsensitive.matches("ΣΊΣΥΦΟΣ", "σίσυφος"));
+        assert!(NameMatching::UnicodeCaseFold.matches("SomeUser", "someuser"));
+        assert!(NameMatching::UnicodeCaseFold.matches("ΣΊΣΥΦΟΣ", "σίσυφος"));
+        assert!(NameMatching::UnicodeCaseFold.matches("Straße", "STRASSE"));
+        assert!(matches!(
+            NameMatching::UnicodeCaseFold.key("someuser"),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(
+            "ascii_case_insensitive".parse(),
+            Ok(NameMatching::AsciiCaseInsensitive)
+        );
+        assert!("lowercase".parse::<NameMatching>().is_err());
+    }
+
+    #[test]
+    fn test_ids_are_stored_as_numbers() {
+        let user = OwnedUser::new("liquidnya".to_owned(), None, Some(UserId::new(1337)));
+        let json = serde_json::to_string(&user).unwrap();
+        assert_eq!(
+            json,
+            r#"{"username":"liquidnya","display_name":null,"user_id":1337}"#
+        );
+        let user: OwnedUser = serde_json::from_str(&json).unwrap();
+        assert_eq!(user.user_id(), Some(UserId::new(1337)));
+        assert_eq!(ChannelId::from(UserId::new(1)), "1".parse().unwrap());
+    }
+}
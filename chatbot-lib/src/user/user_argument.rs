@@ -1,6 +1,7 @@
-use super::User;
+use super::{NameMatching, User};
 use crate::command::FromArgument;
 use core::fmt::{Display, Error, Formatter};
+use core::ops::Deref;
 
 #[derive(Debug, Clone)]
 pub struct UserArgument<'a>(&'a str);
@@ -27,6 +28,14 @@ impl<'a> UserArgument<'a> {
     pub fn as_argument(&self) -> &str {
         self.0
     }
+
+    /// Compares the username and the display name of the user with the argument.
+    pub fn matches(&self, user: &User<'_>, matching: NameMatching) -> bool {
+        matching.matches(self.0, user.username())
+            || user
+                .display_name()
+                .map_or(false, |display_name| matching.matches(self.0, display_name))
+    }
 }
 
 impl<'a> Display for UserArgument<'a> {
@@ -40,13 +49,9 @@ impl<'a> Display for UserArgument<'a> {
 }
 
 impl<'a> PartialEq<User<'_>> for UserArgument<'a> {
+    /// Compares the username and the display name with the default [`NameMatching`].
     fn eq(&self, other: &User<'_>) -> bool {
-        self.0 == other.username()
-            || other
-                .display_name()
-                .map_or(false, |display_name| self.0 == display_name)
-        // TODO: this is expensive and maybe not even wanted
-        // || self.0.to_ascii_lowercase() == other.username()
+        self.matches(other, NameMatching::default())
     }
 }
 
@@ -64,3 +69,82 @@ impl<'a> FromArgument<'a> for UserArgument<'a> {
         Ok(Self::new(argument))
     }
 }
+
+/// A list of users separated by whitespace and/or commas, e.g. `@a @b, @c`.
+#[derive(Debug, Clone, Default)]
+pub struct MentionList<'a>(Vec<UserArgument<'a>>);
+
+impl<'a> MentionList<'a> {
+    pub fn parse(argument: &'a str) -> Self {
+        Self(
+            argument
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|user| !user.is_empty() && *user != "@")
+                .map(UserArgument::new)
+                .collect(),
+        )
+    }
+
+    pub fn into_inner(self) -> Vec<UserArgument<'a>> {
+        self.0
+    }
+}
+
+impl<'a> Deref for MentionList<'a> {
+    type Target = [UserArgument<'a>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a> IntoIterator for MentionList<'a> {
+    type Item = UserArgument<'a>;
+    type IntoIter = std::vec::IntoIter<UserArgument<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, 'b> IntoIterator for &'b MentionList<'a> {
+    type Item = &'b UserArgument<'a>;
+    type IntoIter = std::slice::Iter<'b, UserArgument<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'a> From<MentionList<'a>> for Vec<UserArgument<'a>> {
+    fn from(list: MentionList<'a>) -> Self {
+        list.0
+    }
+}
+
+impl<'a> FromArgument<'a> for MentionList<'a> {
+    type Error = core::convert::Infallible;
+    fn from_argument(argument: &'a str) -> Result<Self, Self::Error> {
+        Ok(Self::parse(argument))
+    }
+}
+
+impl<'a> FromArgument<'a> for Vec<UserArgument<'a>> {
+    type Error = core::convert::Infallible;
+    fn from_argument(argument: &'a str) -> Result<Self, Self::Error> {
+        Ok(MentionList::parse(argument).into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mention_list() {
+        let list = MentionList::parse("@a @b,@c ,  d,, @");
+        let users: Vec<_> = list.iter().map(UserArgument::as_argument).collect();
+        assert_eq!(users, ["a", "b", "c", "d"]);
+        assert!(MentionList::parse(" , ").is_empty());
+    }
+}
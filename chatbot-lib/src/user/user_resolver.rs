@@ -0,0 +1,65 @@
+use super::{OwnedUser, UserArgument};
+use crate::request::{CommandRequest, FromCommandRequest};
+use crate::state::ChannelChatters;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Looks up users which are not known from chat, e.g. using the Twitch API.
+#[async_trait]
+pub trait UserLookup: Send + Sync {
+    async fn lookup(&self, user: &UserArgument<'_>) -> anyhow::Result<Option<OwnedUser>>;
+}
+
+#[derive(Clone)]
+pub struct UserResolver {
+    chatters: ChannelChatters,
+    fallback: Option<Arc<dyn UserLookup>>,
+}
+
+impl UserResolver {
+    pub fn new(chatters: ChannelChatters) -> Self {
+        Self {
+            chatters,
+            fallback: None,
+        }
+    }
+
+    pub fn with_fallback<L: UserLookup + 'static>(self, fallback: L) -> Self {
+        Self {
+            chatters: self.chatters,
+            fallback: Some(Arc::new(fallback)),
+        }
+    }
+
+    /// Resolves a user from chatters first and uses the fallback if the user id is unknown.
+    pub async fn resolve<'a, T>(&self, user: T) -> anyhow::Result<Option<OwnedUser>>
+    where
+        T: Into<UserArgument<'a>> + 'a,
+    {
+        let argument = user.into();
+        let chatter = self.chatters.get(&argument).await;
+        if chatter.as_ref().and_then(OwnedUser::user_id).is_some() {
+            return Ok(chatter);
+        }
+        match &self.fallback {
+            Some(fallback) => match fallback.lookup(&argument).await? {
+                Some(user) => Ok(Some(user)),
+                None => Ok(chatter),
+            },
+            None => Ok(chatter),
+        }
+    }
+}
+
+impl<'a, 'req> FromCommandRequest<'a, 'req> for UserResolver {
+    type Error = core::convert::Infallible;
+
+    fn from_command_request(request: &'a CommandRequest<'req>) -> Result<Self, Self::Error> {
+        let chatters = <ChannelChatters as FromCommandRequest>::from_command_request(request)?;
+        let fallback = request
+            .context
+            .and_then(|context| context.state::<Arc<dyn UserLookup>>().ok())
+            .map(|fallback| Arc::clone(&fallback));
+        Ok(Self { chatters, fallback })
+    }
+}
@@ -0,0 +1,214 @@
+//! Lets external systems like donation services trigger commands with authenticated HTTP requests.
+//!
+//! A webhook is a `POST /webhook` request with the header `Authorization: Bearer <token>` and a body
+//! like `{"channel": "liquidnya", "command": "!donation 5 nya"}`, which is processed by the command
+//! processors like a chat message. Events are forwarded as commands, e.g. `!donation`.
+
+use crate::http_server::{self, HttpRequest, HttpResponse};
+use crate::status::BotStatus;
+use crate::user::OwnedUser;
+use std::net::SocketAddr;
+use tokio::io;
+use tokio::net::TcpListener;
+
+/// Where to listen for webhooks and how they are authenticated.
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    address: SocketAddr,
+    token: String,
+    sender: String,
+    moderator: bool,
+}
+
+impl WebhookConfig {
+    /// Accepts webhooks on `address` which are authenticated with `token`.
+    pub fn new<A: Into<SocketAddr>, T: Into<String>>(address: A, token: T) -> Self {
+        Self {
+            address: address.into(),
+            token: token.into(),
+            sender: "webhook".to_owned(),
+            moderator: false,
+        }
+    }
+
+    /// The user which sends the commands, unless a webhook contains a `user`.
+    pub fn sender<T: Into<String>>(self, username: T) -> Self {
+        Self {
+            sender: username.into(),
+            ..self
+        }
+    }
+
+    /// Treats the sender as a moderator, which allows webhooks to trigger moderator commands.
+    pub fn moderator(self) -> Self {
+        Self {
+            moderator: true,
+            ..self
+        }
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    fn is_authorized(&self, request: &HttpRequest) -> bool {
+        request
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| constant_time_eq(token.trim().as_bytes(), self.token.as_bytes()))
+    }
+}
+
+/// Compares without returning early, so the token cannot be guessed by timing the responses.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Twitch logins are 1 to 25 lowercase letters, digits and underscores, which also keeps the
+/// channel from leaving the data directory.
+fn is_valid_login(name: &str) -> bool {
+    (1..=25).contains(&name.len())
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'_')
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct WebhookBody {
+    channel: String,
+    command: String,
+    user: Option<String>,
+}
+
+/// A command received through a webhook.
+#[derive(Debug, Clone)]
+pub(crate) struct WebhookCommand {
+    pub(crate) channel: String,
+    pub(crate) command: String,
+    pub(crate) sender: OwnedUser,
+    pub(crate) moderator: bool,
+}
+
+fn parse_webhook(
+    config: &WebhookConfig,
+    request: &HttpRequest,
+    status: &BotStatus,
+) -> Result<WebhookCommand, HttpResponse> {
+    if (request.method.as_str(), request.path.as_str()) != ("POST", "/webhook") {
+        return Err(HttpResponse::text("404 Not Found", "not found"));
+    }
+    if !config.is_authorized(request) {
+        return Err(HttpResponse::text("401 Unauthorized", "unauthorized"));
+    }
+    let body: WebhookBody = serde_json::from_slice(&request.body)
+        .map_err(|_| HttpResponse::text("400 Bad Request", "expected channel and command"))?;
+    let command = body.command.trim();
+    if !command.starts_with('!') || command.contains(['\r', '\n']) {
+        return Err(HttpResponse::text(
+            "400 Bad Request",
+            "the command has to start with ! and must be a single line",
+        ));
+    }
+    let channel = body.channel.trim_start_matches('#').to_lowercase();
+    if !is_valid_login(&channel) {
+        return Err(HttpResponse::text("400 Bad Request", "invalid channel"));
+    }
+    if !status.is_joined(&channel) {
+        return Err(HttpResponse::text(
+            "404 Not Found",
+            "the bot did not join the channel",
+        ));
+    }
+    let sender = body.user.unwrap_or_else(|| config.sender.clone());
+    Ok(WebhookCommand {
+        channel,
+        command: command.to_owned(),
+        sender: OwnedUser::from_username(sender.to_lowercase()),
+        moderator: config.moderator,
+    })
+}
+
+/// Passes every authenticated webhook for a joined channel to `dispatch`, which returns `false`
+/// if the bot stopped.
+pub(crate) async fn serve_webhooks<F>(
+    listener: TcpListener,
+    config: WebhookConfig,
+    status: BotStatus,
+    dispatch: F,
+) -> io::Result<()>
+where
+    F: Fn(WebhookCommand) -> bool + Clone + Send + Sync + 'static,
+{
+    http_server::serve(listener, move |request| {
+        let config = config.clone();
+        let status = status.clone();
+        let dispatch = dispatch.clone();
+        async move {
+            match parse_webhook(&config, &request, &status) {
+                Ok(command) => {
+                    log::debug!("Webhook: {:?}", command);
+                    if dispatch(command) {
+                        HttpResponse::text("202 Accepted", "accepted")
+                    } else {
+                        HttpResponse::text("503 Service Unavailable", "the bot stopped")
+                    }
+                }
+                Err(response) => response,
+            }
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(authorization: &str, body: &str) -> HttpRequest {
+        HttpRequest {
+            method: "POST".to_owned(),
+            path: "/webhook".to_owned(),
+            headers: vec![("Authorization".to_owned(), authorization.to_owned())],
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_parse_webhook() {
+        let config = WebhookConfig::new(([127, 0, 0, 1], 8080), "secret").sender("StreamElements");
+        let status = BotStatus::new();
+        status.join("liquidnya");
+        let body = r##"{"channel": "#LiquidNya", "command": "!donation 5 nya"}"##;
+
+        let command = parse_webhook(&config, &request("Bearer secret", body), &status).unwrap();
+        assert_eq!(command.channel, "liquidnya");
+        assert_eq!(command.command, "!donation 5 nya");
+        assert_eq!(command.sender.username(), "streamelements");
+
+        let response = parse_webhook(&config, &request("Bearer wrong", body), &status).unwrap_err();
+        assert_eq!(response.status, "401 Unauthorized");
+        let body = r#"{"channel": "liquidnya", "command": "hello"}"#;
+        let response =
+            parse_webhook(&config, &request("Bearer secret", body), &status).unwrap_err();
+        assert_eq!(response.status, "400 Bad Request");
+    }
+
+    #[test]
+    fn test_webhook_channel() {
+        let config = WebhookConfig::new(([127, 0, 0, 1], 8080), "secret");
+        let status = BotStatus::new();
+        status.join("liquidnya");
+        let parse = |channel: &str| {
+            let body = serde_json::json!({ "channel": channel, "command": "!hi" }).to_string();
+            parse_webhook(&config, &request("Bearer secret", &body), &status)
+                .map(|command| command.channel)
+                .map_err(|response| response.status)
+        };
+        assert_eq!(parse("liquidnya"), Ok("liquidnya".to_owned()));
+        assert_eq!(parse("../../etc"), Err("400 Bad Request"));
+        assert_eq!(parse("/etc"), Err("400 Bad Request"));
+        assert_eq!(parse(""), Err("400 Bad Request"));
+        assert_eq!(parse(&"a".repeat(26)), Err("400 Bad Request"));
+        assert_eq!(parse("other_channel"), Err("404 Not Found"));
+    }
+}
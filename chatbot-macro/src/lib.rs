@@ -15,12 +15,16 @@ use syn::Type;
 use syn::{punctuated::Punctuated, FnArg, Pat};
 
 mod meta;
+mod methods;
 mod pattern;
+mod pattern_tokens;
 mod rev_on;
+mod subcommands;
 mod token;
 
 use meta::{MetaCommandArguments, MetaCommandRequest};
 use pattern::CommandPattern;
+use pattern_tokens::PatternTokens;
 use rev_on::RevOnIterator;
 use token::{CommandPatternScanner, CommandPatternToken, Direction};
 
@@ -59,20 +63,85 @@ struct Commands {
     ident: Ident,
     _brace_token: syn::token::Bracket,
     commands: syn::punctuated::Punctuated<CommandPath, syn::Token![,]>,
+    options: CommandsOptions,
 }
 
 struct CommandPath {
+    /// `#[cfg(...)]` attributes, which are applied to the generated code of the command
+    attrs: Vec<syn::Attribute>,
     path: Path,
 }
 
 impl Parse for CommandPath {
     fn parse(input: ParseStream) -> syn::parse::Result<Self> {
+        let attrs = input.call(syn::Attribute::parse_outer)?;
+        for attr in &attrs {
+            if !attr.path().is_ident("cfg") {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "only `#[cfg(...)]` is supported for commands",
+                ));
+            }
+        }
         Ok(Self {
+            attrs,
             path: input.call(Path::parse_mod_style)?,
         })
     }
 }
 
+/// Options of the generated processor, written as `key = value` after the commands.
+#[derive(Default)]
+struct CommandsOptions {
+    /// only commands starting with the prefix are processed, the prefix is replaced with `!`
+    prefix: Option<syn::LitStr>,
+    /// the command name is matched ignoring the case
+    case_insensitive: bool,
+    /// called with the request if none of the commands matched
+    unknown_command: Option<syn::Expr>,
+}
+
+impl Parse for CommandsOptions {
+    fn parse(input: ParseStream) -> syn::parse::Result<Self> {
+        let mut options = Self::default();
+        for option in parse_trailing_arguments(input)? {
+            let lit = match &option.value {
+                syn::Expr::Lit(lit) => Some(&lit.lit),
+                _ => None,
+            };
+            if option.path.is_ident("prefix") {
+                match lit {
+                    Some(syn::Lit::Str(prefix)) => options.prefix = Some(prefix.clone()),
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            &option.value,
+                            "expected a string literal for `prefix`",
+                        ))
+                    }
+                }
+            } else if option.path.is_ident("case_insensitive") {
+                match lit {
+                    Some(syn::Lit::Bool(value)) => options.case_insensitive = value.value,
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            &option.value,
+                            "expected a bool literal for `case_insensitive`",
+                        ))
+                    }
+                }
+            } else if option.path.is_ident("unknown_command") {
+                options.unknown_command = Some(option.value);
+            } else {
+                return Err(syn::Error::new_spanned(
+                    &option.path,
+                    "unknown option, expected `prefix`, `case_insensitive` or `unknown_command`",
+                ));
+            }
+        }
+        Ok(options)
+    }
+}
+
 impl Parse for Commands {
     fn parse(input: ParseStream) -> syn::parse::Result<Self> {
         let content;
@@ -81,46 +150,108 @@ impl Parse for Commands {
         let brace_token = bracketed!(content in input);
         let commands = content
             .call(syn::punctuated::Punctuated::<CommandPath, syn::Token![,]>::parse_terminated)?;
+        let options = input.parse()?;
         Ok(Self {
             _struct_token: struct_token,
             ident,
             _brace_token: brace_token,
             commands,
+            options,
         })
     }
 }
 
-#[proc_macro]
-pub fn commands(item: TokenStream) -> TokenStream {
-    let commands = syn::parse_macro_input!(item as Commands);
-    let name = commands.ident;
-    let commands = commands.commands.into_iter().map(|command| {
-        let span = command.path.span();
-        let command = command.path;
-        let command_str = command
-            .segments
-            .iter()
-            .map(|segment| segment.ident.to_string())
-            .collect::<Vec<String>>()
-            .join("::");
-        let mut show_syntax = command.clone();
-        if let Some(id) = show_syntax.segments.last_mut() {
-            id.ident = format_ident!("show_syntax_{}", id.ident);
-        }
-        let mut command = command;
-        if let Some(id) = command.segments.last_mut() {
-            id.ident = format_ident!("async_command_{}", id.ident);
+/// Rejects commands that are listed twice or share the exact same pattern, since only the
+/// first one could ever be called. The checks are only applied if the `#[cfg(...)]` attributes
+/// of both commands are enabled.
+fn duplicate_checks(commands: &Commands) -> proc_macro2::TokenStream {
+    let mut checks = quote! {};
+    let paths: Vec<_> = commands
+        .commands
+        .iter()
+        .map(|command| {
+            let mut show_syntax = command.path.clone();
+            if let Some(id) = show_syntax.segments.last_mut() {
+                id.ident = format_ident!("show_syntax_{}", id.ident);
+            }
+            (&command.attrs, &command.path, show_syntax)
+        })
+        .collect();
+    for (index, (attrs, path, show_syntax)) in paths.iter().enumerate() {
+        let name = path.to_token_stream().to_string();
+        for (other_attrs, other, other_show_syntax) in &paths[..index] {
+            let other_name = other.to_token_stream().to_string();
+            if name == other_name {
+                let message = format!("`{}` is listed twice", name);
+                checks.extend(quote_spanned! {path.span()=>
+                    #(#attrs)*
+                    #(#other_attrs)*
+                    const _: () = ::core::panic!(#message);
+                });
+                continue;
+            }
+            let message = format!("`{}` has the same pattern as `{}`", name, other_name);
+            checks.extend(quote_spanned! {path.span()=>
+                #(#attrs)*
+                #(#other_attrs)*
+                const _: () = assert!(
+                    !::chatbot_lib::command::same_syntax(#other_show_syntax.1, #show_syntax.1),
+                    #message
+                );
+            });
         }
-        quote_spanned! {span=>
-            match #command (request).await {
+    }
+    checks
+}
+
+/// The response with an error `message` for the sender of the request.
+fn user_message(reply: bool, message: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    if reply {
+        quote!(::chatbot_lib::response::Response::new(#message).as_reply())
+    } else {
+        quote!(::chatbot_lib::response::Response::new(
+            format!("{} {}", ::chatbot_lib::user::UserArgument::from(request.sender() as &::chatbot_lib::user::User), #message)
+        ))
+    }
+}
+
+/// Generates the block of a processor which calls a command, e.g. `async_command_song_add`, and
+/// returns its response or collects its syntax. `into_owned` is needed if the response must not
+/// borrow from the request.
+fn command_attempt(
+    span: proc_macro2::Span,
+    command: proc_macro2::TokenStream,
+    show_syntax: proc_macro2::TokenStream,
+    syntax_message: proc_macro2::TokenStream,
+    command_str: &str,
+    reply: bool,
+    into_owned: bool,
+) -> proc_macro2::TokenStream {
+    let into_owned = if into_owned {
+        quote!(.map(::chatbot_lib::response::Response::into_owned))
+    } else {
+        quote!()
+    };
+    let message_response = user_message(reply, quote!(message.to_owned()));
+    let syntax_response = user_message(reply, quote!(syntax));
+    quote_spanned! {span=>
+        {
+            let start = ::std::time::Instant::now();
+            let result = #command (request).await;
+            ::chatbot_lib::command::record_metrics(request, #command_str, start, &result);
+            match result {
                 response @ Ok(_) => {
                     log::debug!("Calling {}", #command_str);
-                    return response.ok();
+                    return response.ok()#into_owned;
                 },
                 Err(e) => {
+                    if let Some(message) = e.user_message() {
+                        return Some(#message_response);
+                    }
                     if #show_syntax.0 {
                         if e.is_argument_error() {
-                            return Some(::chatbot_lib::response::Response::new(format!("{} {}", ::chatbot_lib::user::UserArgument::from(request.sender() as &User), #show_syntax.1)));
+                            let syntax = #syntax_message(request, &e);
+                            return Some(#syntax_response);
                         } else if e.is_subcommand_mismatch() {
                             if let Some(shared_syntax) = &mut shared_syntax {
                                 shared_syntax.append(#show_syntax.1);
@@ -133,32 +264,20 @@ pub fn commands(item: TokenStream) -> TokenStream {
                 },
             };
         }
-    });
-    let code = quote! {
-        struct #name;
-
-        #[async_trait]
-        impl CommandProcessor for #name {
-            async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
-                let mut shared_syntax : Option<::chatbot_lib::command::FindSharedSyntax> = None;
-                #(#commands)*
-                if let Some(shared_syntax) = shared_syntax {
-                    // TODO: use Display instead of ToString
-                    return Some(::chatbot_lib::response::Response::new(format!("{} {}", ::chatbot_lib::user::UserArgument::from(request.sender() as &User), shared_syntax.to_string())));
-                }
-                None
-            }
-        }
-    };
-    code.into()
+    }
 }
 
-#[proc_macro]
-pub fn commands_reply(item: TokenStream) -> TokenStream {
-    let commands = syn::parse_macro_input!(item as Commands);
+/// Generates the processor of `commands!` and `commands_reply!`, `reply` selects how errors are
+/// sent to the user.
+fn expand_commands(commands: Commands, reply: bool) -> proc_macro2::TokenStream {
+    let checks = duplicate_checks(&commands);
     let name = commands.ident;
+    let options = commands.options;
+    // responses can borrow from the normalized request, which is dropped at the end of `process`
+    let normalize = options.prefix.is_some() || options.case_insensitive;
     let commands = commands.commands.into_iter().map(|command| {
         let span = command.path.span();
+        let attrs = command.attrs;
         let command = command.path;
         let command_str = command
             .segments
@@ -170,78 +289,261 @@ pub fn commands_reply(item: TokenStream) -> TokenStream {
         if let Some(id) = show_syntax.segments.last_mut() {
             id.ident = format_ident!("show_syntax_{}", id.ident);
         }
+        let mut syntax_message = command.clone();
+        if let Some(id) = syntax_message.segments.last_mut() {
+            id.ident = format_ident!("syntax_message_{}", id.ident);
+        }
         let mut command = command;
         if let Some(id) = command.segments.last_mut() {
             id.ident = format_ident!("async_command_{}", id.ident);
         }
+        let attempt = command_attempt(
+            span,
+            quote!(#command),
+            quote!(#show_syntax),
+            quote!(#syntax_message),
+            &command_str,
+            reply,
+            normalize,
+        );
         quote_spanned! {span=>
-            match #command (request).await {
-                response @ Ok(_) => {
-                    log::debug!("Calling {}", #command_str);
-                    return response.ok();
-                },
-                Err(e) => {
-                    if #show_syntax.0 {
-                        if e.is_argument_error() {
-                            return Some(::chatbot_lib::response::Response::new(#show_syntax.1).as_reply());
-                        } else if e.is_subcommand_mismatch() {
-                            if let Some(shared_syntax) = &mut shared_syntax {
-                                shared_syntax.append(#show_syntax.1);
-                            } else {
-                                shared_syntax = Some(::chatbot_lib::command::FindSharedSyntax::new(#show_syntax.1));
-                            }
-                        }
-                    }
-                    log::debug!("Error calling {}: {:?}", #command_str, e)
-                },
-            };
+            #(#attrs)*
+            #attempt
         }
     });
-    let code = quote! {
+    let normalize_request = if normalize {
+        let prefix = match &options.prefix {
+            Some(prefix) => quote!(Some(#prefix)),
+            None => quote!(None),
+        };
+        let case_insensitive = options.case_insensitive;
+        quote! {
+            let command = ::chatbot_lib::command::normalize_command(request.command(), #prefix, #case_insensitive)?;
+            let request = &request.with_command(&command);
+        }
+    } else {
+        quote!()
+    };
+    let unknown_command = match &options.unknown_command {
+        Some(handler) => quote! {
+            if let Some(response) = (#handler)(request) {
+                return Some(response);
+            }
+        },
+        None => quote!(),
+    };
+    // TODO: use Display instead of ToString
+    let shared_syntax_response = user_message(
+        reply,
+        quote!(::chatbot_lib::locale::syntax(
+            request,
+            &shared_syntax.to_string()
+        )),
+    );
+    quote! {
+        #checks
+
         struct #name;
 
         #[async_trait]
         impl CommandProcessor for #name {
             async fn process<'a>(&self, request: &'a CommandRequest<'a>) -> Option<Response<'a>> {
+                #normalize_request
                 let mut shared_syntax : Option<::chatbot_lib::command::FindSharedSyntax> = None;
                 #(#commands)*
                 if let Some(shared_syntax) = shared_syntax {
-                    // TODO: use Display instead of ToString
-                    return Some(::chatbot_lib::response::Response::new(shared_syntax.to_string()).as_reply());
+                    return Some(#shared_syntax_response);
                 }
+                #unknown_command
                 None
             }
         }
-    };
-    code.into()
+    }
+}
+
+/// Generates a processor calling the listed commands in order, e.g.
+/// `commands!(struct Commands [hello, #[cfg(feature = "songs")] song_add], prefix = "?")`.
+///
+/// The options are `prefix = "?"`, `case_insensitive = true` and `unknown_command = handler`,
+/// where the handler is called like an `UnknownCommandHandler` if none of the commands matched.
+///
+/// Commands which could never be called, because an earlier command has the same pattern, are
+/// rejected:
+///
+/// ```compile_fail
+/// use async_trait::async_trait;
+/// use chatbot_lib::command::CommandProcessor;
+/// use chatbot_lib::request::CommandRequest;
+/// use chatbot_lib::response::Response;
+/// use chatbot_macro::{command, commands};
+///
+/// #[command("!song add <url>")]
+/// fn song_add(url: &str) -> String {
+///     url.to_owned()
+/// }
+///
+/// #[command("!song  add <url>")]
+/// fn song_add_again(url: &str) -> String {
+///     url.to_owned()
+/// }
+///
+/// commands!(struct Songs [song_add, song_add_again]);
+/// ```
+#[proc_macro]
+pub fn commands(item: TokenStream) -> TokenStream {
+    let commands = syn::parse_macro_input!(item as Commands);
+    expand_commands(commands, false).into()
+}
+
+/// Like [`commands!`], but errors are sent as replies instead of mentioning the user.
+#[proc_macro]
+pub fn commands_reply(item: TokenStream) -> TokenStream {
+    let commands = syn::parse_macro_input!(item as Commands);
+    expand_commands(commands, true).into()
 }
 
 enum MetaArguments {
     Arguments(Punctuated<syn::MetaNameValue, syn::Token![,]>),
-    Str(syn::LitStr),
+    /// pattern as a string literal, followed by optional `, key = value` arguments
+    Str(syn::LitStr, Punctuated<syn::MetaNameValue, syn::Token![,]>),
+    /// pattern written with Rust tokens, followed by optional `, key = value` arguments
+    Tokens(
+        proc_macro2::TokenStream,
+        Punctuated<syn::MetaNameValue, syn::Token![,]>,
+    ),
 }
 
 impl ToTokens for MetaArguments {
     fn to_tokens(&self, stream: &mut proc_macro2::TokenStream) {
         match self {
             Self::Arguments(args) => args.to_tokens(stream),
-            Self::Str(lit) => lit.to_tokens(stream),
+            Self::Str(lit, args) => {
+                lit.to_tokens(stream);
+                args.to_tokens(stream);
+            }
+            Self::Tokens(pattern, args) => {
+                pattern.to_tokens(stream);
+                args.to_tokens(stream);
+            }
         }
     }
 }
 
+fn parse_trailing_arguments(
+    input: ParseStream,
+) -> syn::parse::Result<Punctuated<syn::MetaNameValue, syn::Token![,]>> {
+    if input.is_empty() {
+        Ok(Punctuated::new())
+    } else {
+        input.parse::<syn::Token![,]>()?;
+        input.call(Punctuated::parse_terminated)
+    }
+}
+
 impl Parse for MetaArguments {
     fn parse(input: ParseStream) -> syn::parse::Result<Self> {
         if input.peek(syn::LitStr) {
-            input.parse().map(MetaArguments::Str)
-        } else {
+            let lit = input.parse()?;
+            Ok(MetaArguments::Str(lit, parse_trailing_arguments(input)?))
+        } else if input
+            .fork()
+            .call(Punctuated::<syn::MetaNameValue, syn::Token![,]>::parse_terminated)
+            .is_ok()
+        {
             Ok(MetaArguments::Arguments(input.call(
                 syn::punctuated::Punctuated::<syn::MetaNameValue, syn::Token![,]>::parse_terminated,
             )?))
+        } else {
+            let pattern = input.step(|cursor| {
+                let mut rest = *cursor;
+                let mut pattern = proc_macro2::TokenStream::new();
+                while let Some((token, next)) = rest.token_tree() {
+                    if matches!(&token, proc_macro2::TokenTree::Punct(punct) if punct.as_char() == ',')
+                    {
+                        break;
+                    }
+                    pattern.extend([token]);
+                    rest = next;
+                }
+                Ok((pattern, rest))
+            })?;
+            Ok(MetaArguments::Tokens(
+                pattern,
+                parse_trailing_arguments(input)?,
+            ))
         }
     }
 }
 
+impl MetaArguments {
+    fn arguments(&self) -> Option<&Punctuated<syn::MetaNameValue, syn::Token![,]>> {
+        match self {
+            Self::Arguments(args) | Self::Str(_, args) | Self::Tokens(_, args) => Some(args),
+        }
+    }
+}
+
+fn get_type_argument(args: &MetaArguments, name: &str) -> Option<syn::Result<syn::Type>> {
+    args.arguments()?
+        .iter()
+        .find(|arg| arg.path.is_ident(name))
+        .map(|arg| match &arg.value {
+            syn::Expr::Path(path) => Ok(syn::Type::Path(syn::TypePath {
+                qself: path.qself.clone(),
+                path: path.path.clone(),
+            })),
+            value => Err(syn::Error::new_spanned(
+                value,
+                format!("expected a type for `{}`", name),
+            )),
+        })
+}
+
+/// Returns the pattern and the span of every segment in it.
+fn get_pattern(args: &MetaArguments) -> syn::Result<(syn::LitStr, Vec<proc_macro2::Span>)> {
+    if let MetaArguments::Tokens(pattern, _) = args {
+        let pattern = PatternTokens::parse(pattern.clone())?;
+        return Ok((
+            syn::LitStr::new(&pattern.pattern, pattern.span()),
+            pattern.spans,
+        ));
+    }
+    let literal = get_str_argument(args, "pattern").unwrap_or_else(|| {
+        Err(syn::Error::new_spanned(
+            args,
+            "the key `pattern` is required",
+        ))
+    })?;
+    Ok((literal.clone(), literal_segment_spans(literal)))
+}
+
+/// The span of every whitespace separated segment in the pattern literal, which falls back to
+/// the span of the whole literal if the compiler can not create sub-spans (only nightly can) or
+/// the literal contains escapes.
+fn literal_segment_spans(literal: &syn::LitStr) -> Vec<proc_macro2::Span> {
+    let value = literal.value();
+    let token = literal.token();
+    let text = token.to_string();
+    // the offset of the value in the source, e.g. 1 for `"..."` and 3 for `r#"..."#`
+    let offset = text.find('"').map(|index| index + 1);
+    let unescaped =
+        offset.is_some_and(|offset| text.get(offset..offset + value.len()) == Some(&value));
+    let mut spans = Vec::new();
+    let mut rest = value.as_str();
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        let end = rest[start..]
+            .find(char::is_whitespace)
+            .map_or(rest.len(), |end| start + end);
+        let position = value.len() - rest.len();
+        let span = offset
+            .filter(|_| unescaped)
+            .and_then(|offset| token.subspan(offset + position + start..offset + position + end));
+        spans.push(span.unwrap_or_else(|| literal.span()));
+        rest = &rest[end..];
+    }
+    spans
+}
+
 fn get_str_argument<'a>(
     args: &'a MetaArguments,
     name: &str,
@@ -265,7 +567,7 @@ fn get_str_argument<'a>(
                     ))
                 }
             }),
-        MetaArguments::Str(str) if name == "pattern" => Some(Ok(str)),
+        MetaArguments::Str(str, _) if name == "pattern" => Some(Ok(str)),
         _ => None,
     }
 }
@@ -274,8 +576,8 @@ fn get_bool_argument<'a>(
     args: &'a MetaArguments,
     name: &str,
 ) -> Option<Result<&'a syn::LitBool, syn::Error>> {
-    match args {
-        MetaArguments::Arguments(args) => args
+    match args.arguments() {
+        Some(args) => args
             .iter()
             .find(|arg| arg.path.is_ident(name))
             .and_then(|arg| match &arg.value {
@@ -297,86 +599,282 @@ fn get_bool_argument<'a>(
     }
 }
 
-#[proc_macro_attribute]
-pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let input = syn::parse_macro_input!(item as syn::ItemFn);
-    let is_async = input.sig.asyncness.is_some();
-    let vis = &input.vis;
-    let name = &input.sig.ident;
+/// Matches `Vec<T>`, which collects the arguments of a take-all argument like `<users..>`.
+fn is_vec(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.segments.last().is_some_and(|segment| {
+        segment.ident == "Vec" && matches!(segment.arguments, syn::PathArguments::AngleBracketed(_))
+    }))
+}
 
-    // function arguments
-    let arguments = input.clone().sig.inputs;
-    let args = get_argument_names(&arguments);
-    let fn_args = match args {
-        Ok(args) => args,
-        Err(err) => {
-            return err.to_compile_error().into();
+/// Matches `&mut dyn Responder`, which is injected instead of being extracted from the request.
+fn is_responder(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(reference) if reference.mutability.is_some() => match &*reference.elem {
+            Type::TraitObject(object) => object.bounds.iter().any(|bound| {
+                matches!(bound, syn::TypeParamBound::Trait(bound)
+                    if bound.path.segments.last().is_some_and(|segment| segment.ident == "Responder"))
+            }),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Generates the code binding every function argument, either parsed from the command
+/// arguments according to the pattern or extracted from the request.
+fn argument_parser(
+    command_literal: &syn::LitStr,
+    segment_spans: Vec<proc_macro2::Span>,
+    fn_args: &[Argument],
+    error: &syn::Type,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let command_template = command_literal.value();
+    let segments: Vec<(CommandPattern, proc_macro2::Span)> = command_template
+        .split_whitespace()
+        .map(Into::into)
+        .zip(segment_spans)
+        .collect();
+    CommandPatternScanner::check(&segments, |name| {
+        fn_args.iter().find(|arg| arg.arg == name).map(|arg| arg.ty)
+    })?;
+    let mut command_args: IndexMap<CommandPattern, (proc_macro2::Span, Option<&Argument>)> =
+        segments
+            .into_iter()
+            .map(|(c, span)| (c, (span, None)))
+            .collect();
+
+    // match function arguments with command arguments
+    let mut argument_parsers = quote! {};
+    for arg in fn_args.iter() {
+        let name = &arg.arg;
+        if let Some((_, item)) = command_args.get_mut(name.as_str()) {
+            if item.replace(arg).is_some() {
+                return Err(syn::Error::new_spanned(
+                    &arg.ident,
+                    format!("Unexpected error: `{}` already defined.", name),
+                ));
+            }
+        } else if is_responder(arg.ty) {
+            let ident = &arg.ident;
+            argument_parsers.extend(quote_spanned! {arg.ty.span()=>
+                #[allow(non_snake_case)]
+                let mut #ident = ::chatbot_lib::response::RequestResponder::from(request);
+            });
+        } else {
+            let ident = &arg.ident;
+            argument_parsers.extend(quote_spanned! {arg.ty.span()=>
+                #[allow(non_snake_case)]
+                let #ident = ::chatbot_lib::command::from_command_request_with::<_, #error>(request)?;
+            });
         }
-    };
+    }
 
-    // command template and arguments
-    let meta_arguments = syn::parse_macro_input!(attr as MetaArguments); // TODO: add option to use Rust syntax instead of using a LitStr
+    let command_arguments = format_ident!("iter");
+    let command_request = format_ident!("request");
+    let command_arguments = MetaCommandArguments::new(&command_arguments, error);
 
-    let command_literal = get_str_argument(&meta_arguments, "pattern").unwrap_or_else(|| {
-        Err(syn::Error::new_spanned(
-            &meta_arguments,
-            "the key `pattern` is required",
-        ))
+    // flags are taken from the end of the arguments before parsing the positional arguments
+    let (flags, command_args): (Vec<_>, Vec<_>) = command_args
+        .into_iter()
+        .partition(|(pattern, _)| pattern.is_flag());
+    let flags_ident = format_ident!("command_flags");
+    let take_flags = if flags.is_empty() {
+        quote! {}
+    } else {
+        let prefixes: Vec<_> = flags
+            .iter()
+            .filter_map(|(pattern, _)| pattern.flag_prefix())
+            .collect();
+        command_arguments.to_take_flags(&flags_ident, &prefixes)
+    };
+    let flag_parsers = flags.iter().enumerate().map(|(index, (pattern, (span, arg)))| {
+        let name = pattern.key();
+        match arg {
+            Some(arg) => {
+                let ident = &arg.ident;
+                quote_spanned! {arg.ty.span()=>
+                    #[allow(non_snake_case)]
+                    let #ident = ::chatbot_lib::command::next_optional_argument_with::<_, #error>(#flags_ident[#index], #name)?;
+                }
+            }
+            None => syn::Error::new(
+                *span,
+                format!("`{}` can not be found in function arguments", name),
+            )
+            .to_compile_error(),
+        }
     });
 
-    let command_literal = match command_literal {
-        Err(e) => return e.to_compile_error().into(),
-        Ok(str) => str,
-    };
+    // command parsing
+    let command_parser = command_args
+        .into_iter()
+        .map(|(pattern, (span, ident_span))| {
+            (
+                pattern,
+                span,
+                ident_span.is_some_and(|args| is_vec(args.ty)),
+                ident_span.map(|args| (args.ident.clone(), args.ty.span())),
+            )
+        })
+        .rev_on(|(pattern, _, _, _)| pattern.is_taking_all())
+        .map(|((pattern, span, collect, ident_span), rev)| {
+            CommandPatternToken::new(
+                pattern,
+                if rev {
+                    Direction::Backwards
+                } else {
+                    Direction::Forwards
+                },
+                ident_span,
+                collect,
+                span,
+            )
+        })
+        .scan(
+            CommandPatternScanner::new(&command_arguments),
+            CommandPatternScanner::scan,
+        );
 
-    let show_syntax_default = syn::LitBool {
-        value: false,
-        span: proc_macro2::Span::call_site(),
-    };
-    let show_syntax =
-        get_bool_argument(&meta_arguments, "show_syntax").unwrap_or(Ok(&show_syntax_default));
-    let show_syntax = match show_syntax {
-        Err(e) => return e.to_compile_error().into(),
-        Ok(value) => value,
-    };
+    let command_arguments_binding =
+        command_arguments.to_binding(&MetaCommandRequest::new(&command_request));
+    let command_arguments_check = command_arguments.to_empty_check();
 
-    let reply_default = syn::LitBool {
-        value: false,
-        span: proc_macro2::Span::call_site(),
-    };
-    let reply = get_bool_argument(&meta_arguments, "reply").unwrap_or(Ok(&reply_default));
-    let reply = match reply {
-        Err(e) => return e.to_compile_error().into(),
-        Ok(value) => value,
-    };
+    Ok(quote! {
+        #argument_parsers
+        #command_arguments_binding
+        #take_flags
+        // parse command arguments
+        #(#command_parser)*
+        #(#flag_parsers)*
+        #command_arguments_check
+    })
+}
 
-    let result_default = syn::LitBool {
-        value: false,
-        span: proc_macro2::Span::call_site(),
-    };
-    let result = get_bool_argument(&meta_arguments, "result").unwrap_or(Ok(&result_default));
-    let result = match result {
-        Err(e) => return e.to_compile_error().into(),
-        Ok(value) => value,
-    };
+/// How the syntax is shown after the arguments of a command were rejected, set with
+/// `show_syntax = ...`.
+///
+/// A template or a function only replaces the message for argument errors. If none of the
+/// subcommands matched, the patterns of the commands are still combined into the shared syntax,
+/// e.g. `!playlist add|remove`, since the custom messages can not be combined.
+enum ShowSyntax {
+    /// `show_syntax = false`, the default
+    Hidden,
+    /// `show_syntax = true` shows the pattern of the command
+    Pattern,
+    /// `show_syntax = "..."` shows a usage message instead of the pattern, e.g. with an example
+    Template(syn::LitStr),
+    /// `show_syntax = usage` calls `fn usage(&CommandRequest, &CommandError<E>) -> String`, e.g.
+    /// to translate the usage message
+    Function(syn::Path),
+}
 
-    let command_template = command_literal.value();
-    let mut command_args: IndexMap<CommandPattern, Option<&Argument>> = command_template
-        .split_whitespace()
-        .map(Into::into)
-        .map(|c| (c, None))
-        .collect();
+impl ShowSyntax {
+    fn new(args: &MetaArguments) -> syn::Result<Self> {
+        let Some(arg) = args
+            .arguments()
+            .and_then(|args| args.iter().find(|arg| arg.path.is_ident("show_syntax")))
+        else {
+            return Ok(Self::Hidden);
+        };
+        match &arg.value {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Bool(value),
+                ..
+            }) if value.value => Ok(Self::Pattern),
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Bool(_),
+                ..
+            }) => Ok(Self::Hidden),
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(template),
+                ..
+            }) => Ok(Self::Template(template.clone())),
+            syn::Expr::Path(path) => Ok(Self::Function(path.path.clone())),
+            value => Err(syn::Error::new_spanned(
+                value,
+                "expected a bool, a string literal or a function for `show_syntax`",
+            )),
+        }
+    }
+
+    fn is_shown(&self) -> bool {
+        !matches!(self, Self::Hidden)
+    }
+
+    /// The expression creating the message for the `error` of a `request`, `syntax` is the
+    /// pattern shown to users.
+    fn to_message(&self, syntax: &syn::LitStr) -> proc_macro2::TokenStream {
+        match self {
+            Self::Hidden | Self::Pattern => {
+                quote!(::chatbot_lib::locale::syntax_hint(request, #syntax, error))
+            }
+            Self::Template(template) => {
+                quote!(::chatbot_lib::locale::syntax_hint(request, #template, error))
+            }
+            Self::Function(function) => quote!(#function(request, error)),
+        }
+    }
+}
+
+/// The options of `#[command(...)]` besides the pattern.
+struct CommandOptions {
+    pattern: syn::LitStr,
+    /// the pattern shown to users
+    syntax: syn::LitStr,
+    segment_spans: Vec<proc_macro2::Span>,
+    show_syntax: ShowSyntax,
+    reply: bool,
+    result: bool,
+    error: syn::Type,
+}
+
+impl CommandOptions {
+    fn new(meta_arguments: &MetaArguments) -> syn::Result<Self> {
+        let (pattern, segment_spans) = get_pattern(meta_arguments)?;
+        let bool_argument = |name| {
+            get_bool_argument(meta_arguments, name)
+                .transpose()
+                .map(|value| value.is_some_and(|value| value.value))
+        };
+        Ok(Self {
+            syntax: syn::LitStr::new(&pattern::syntax(&pattern.value()), pattern.span()),
+            pattern,
+            segment_spans,
+            show_syntax: ShowSyntax::new(meta_arguments)?,
+            reply: bool_argument("reply")?,
+            result: bool_argument("result")?,
+            error: get_type_argument(meta_arguments, "error")
+                .transpose()?
+                .unwrap_or_else(|| syn::parse_quote!(anyhow::Error)),
+        })
+    }
+}
+
+/// Generates the return type and the body of `command_<name>`, which converts the request to the
+/// arguments of the handler and calls it with `callee`, e.g. `song_add` or `self.song_add`.
+fn command_call(
+    callee: proc_macro2::TokenStream,
+    is_async: bool,
+    options: &CommandOptions,
+    fn_args: &[Argument],
+) -> syn::Result<(proc_macro2::TokenStream, proc_macro2::TokenStream)> {
+    let reply = options.reply;
+    let error = &options.error;
     let function_call = fn_args.iter().map(|arg| {
         let mut ident = arg.ident.clone();
         ident.set_span(arg.ty.span());
-        ident
+        if is_responder(arg.ty) {
+            quote!(&mut #ident)
+        } else {
+            quote!(#ident)
+        }
     });
 
-    let function_call = if result.value {
+    let function_call = if options.result {
         if is_async {
             quote! {
                 let result = async move {
-                    let result = #name(#(#function_call),*).await;
+                    let result = #callee(#(#function_call),*).await;
                     if #reply {
                         result.map(|result|::chatbot_lib::response::IntoResponse::into_response(result, request).as_reply())
                     } else {
@@ -387,7 +885,7 @@ pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
             }
         } else {
             quote! {
-                let result = #name(#(#function_call),*);
+                let result = #callee(#(#function_call),*);
                 if #reply {
                     Ok(result.map(|result|::chatbot_lib::response::IntoResponse::into_response(result, request).as_reply()))
                 } else {
@@ -398,7 +896,7 @@ pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
     } else if is_async {
         quote! {
             let result = async move {
-                let result = #name(#(#function_call),*).await;
+                let result = #callee(#(#function_call),*).await;
                 if #reply {
                     ::chatbot_lib::response::IntoResponse::into_response(result, request).as_reply()
                 } else {
@@ -409,7 +907,7 @@ pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
         }
     } else {
         quote! {
-            let result = #name(#(#function_call),*);
+            let result = #callee(#(#function_call),*);
             if #reply {
                 Ok(::chatbot_lib::response::IntoResponse::into_response(result, request).as_reply())
             } else {
@@ -417,13 +915,13 @@ pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
             }
         }
     };
-    let return_type = if result.value {
+    let return_type = if options.result {
         if is_async {
             quote!(
                 impl core::future::Future<
                         Output = Result<
                             ::chatbot_lib::response::Response<'s>,
-                            ::chatbot_lib::command::CommandError<anyhow::Error>,
+                            ::chatbot_lib::command::CommandError<#error>,
                         >,
                     > + 's
             )
@@ -431,7 +929,7 @@ pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
             quote!(
                 Result<
                     ::chatbot_lib::response::Response<'s>,
-                    ::chatbot_lib::command::CommandError<anyhow::Error>,
+                    ::chatbot_lib::command::CommandError<#error>,
                 >
             )
         }
@@ -441,73 +939,39 @@ pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
         quote!(::chatbot_lib::response::Response<'s>)
     };
 
-    // match function arguments with command arguments
-    let mut argument_parsers = quote! {};
-    for arg in fn_args.iter() {
-        let name = &arg.arg;
-        if let Some(item) = command_args.get_mut(name.as_str()) {
-            if item.replace(arg).is_some() {
-                return syn::Error::new_spanned(
-                    &arg.ident,
-                    format!("Unexpected error: `{}` already defined.", name),
-                )
-                .to_compile_error()
-                .into();
-            }
-        } else {
-            let ident = &arg.ident;
-            argument_parsers.extend(quote_spanned! {arg.ty.span()=>
-                #[allow(non_snake_case)]
-                let #ident = ::chatbot_lib::command::from_command_request_anyhow(request)?;
-            });
-        }
-    }
-
-    let command_arguments = format_ident!("iter");
-    let command_request = format_ident!("request");
-    let command_arguments = MetaCommandArguments::new(&command_arguments);
+    let parser = argument_parser(
+        &options.pattern,
+        options.segment_spans.clone(),
+        fn_args,
+        error,
+    )?;
+    let body = quote! {
+        // convert request and command arguments to function arguments
+        #parser
 
-    // command parsing
-    let command_parser = command_args
-        .into_iter()
-        .map(|(pattern, ident_span)| {
-            (
-                pattern,
-                ident_span.map(|args| (args.ident.clone(), args.ty.span())),
-            )
-        })
-        .rev_on(|(pattern, _)| pattern.is_taking_all())
-        .map(|((pattern, ident_span), rev)| {
-            CommandPatternToken::new(
-                pattern,
-                if rev {
-                    Direction::Backwards
-                } else {
-                    Direction::Forwards
-                },
-                ident_span,
-                command_literal.span(),
-            )
-        })
-        .scan(
-            CommandPatternScanner::new(&command_arguments),
-            CommandPatternScanner::scan,
-        );
+        #function_call
+    };
+    Ok((return_type, body))
+}
 
-    let call_name = format_ident!("command_{}", name);
-    let command_name = format_ident!("async_command_{}", name);
-    let show_syntax_name = format_ident!("show_syntax_{}", name);
-    let function_call2 = if result.value {
+/// Generates the body of `async_command_<name>`, which awaits the result of `call`, e.g.
+/// `command_song_add(request)`.
+fn command_await(
+    call: proc_macro2::TokenStream,
+    is_async: bool,
+    options: &CommandOptions,
+) -> proc_macro2::TokenStream {
+    if options.result {
         if is_async {
             quote! {
-                match #call_name (request) {
+                match #call {
                     Ok(future) => future.await,
                     Err(e) => Err(e),
                 }
             }
         } else {
             quote! {
-                match #call_name (request) {
+                match #call {
                     Ok(result) => result,
                     Err(e) => Err(e),
                 }
@@ -515,43 +979,117 @@ pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
         }
     } else if is_async {
         quote! {
-            match #call_name (request) {
+            match #call {
                 Ok(future) => Ok(future.await),
                 Err(e) => Err(e),
             }
         }
     } else {
         quote! {
-            #call_name (request)
+            #call
+        }
+    }
+}
+
+#[proc_macro_attribute]
+pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(item as syn::ItemFn);
+    let is_async = input.sig.asyncness.is_some();
+    let vis = &input.vis;
+    let name = &input.sig.ident;
+
+    // function arguments
+    let arguments = input.clone().sig.inputs;
+    let args = get_argument_names(&arguments);
+    let fn_args = match args {
+        Ok(args) => args,
+        Err(err) => {
+            return err.to_compile_error().into();
         }
     };
 
-    let command_arguments_binding =
-        command_arguments.to_binding(&MetaCommandRequest::new(&command_request));
-    let command_arguments_check = command_arguments.to_empty_check();
+    // command template and arguments
+    let meta_arguments = syn::parse_macro_input!(attr as MetaArguments);
+    let options = match CommandOptions::new(&meta_arguments) {
+        Ok(options) => options,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let error = &options.error;
+    let show_syntax = options.show_syntax.is_shown();
+    let command_literal = &options.syntax;
+    let syntax_message = options.show_syntax.to_message(command_literal);
+
+    let command_request = format_ident!("request");
+    let (return_type, body) = match command_call(quote!(#name), is_async, &options, &fn_args) {
+        Ok(call) => call,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let call_name = format_ident!("command_{}", name);
+    let command_name = format_ident!("async_command_{}", name);
+    let show_syntax_name = format_ident!("show_syntax_{}", name);
+    let syntax_message_name = format_ident!("syntax_message_{}", name);
+    let function_call2 = command_await(quote!(#call_name (request)), is_async, &options);
 
     // TODO: return type could be Either<Result<Response, CommandError>, impl Future<Oputput=Result<Response, CommandError>>>
     let result = quote! {
         #input
 
-        fn #call_name<'s, 'a: 's, 'req: 's>(#command_request: &'a ::chatbot_lib::request::CommandRequest<'req>) -> Result<#return_type, ::chatbot_lib::command::CommandError<anyhow::Error>> {
-            // convert request to function arguments
-            #argument_parsers
-            #command_arguments_binding
-            // parse command arguments
-            #(#command_parser)*
-            #command_arguments_check
-
-
-            #function_call
+        fn #call_name<'s, 'a: 's, 'req: 's>(#command_request: &'a ::chatbot_lib::request::CommandRequest<'req>) -> Result<#return_type, ::chatbot_lib::command::CommandError<#error>> {
+            #body
         }
 
-        #vis async fn #command_name<'s, 'a: 's, 'req: 's>(request: &'a ::chatbot_lib::request::CommandRequest<'req>) -> Result<::chatbot_lib::response::Response<'s>, ::chatbot_lib::command::CommandError<anyhow::Error>> {
+        #vis async fn #command_name<'s, 'a: 's, 'req: 's>(request: &'a ::chatbot_lib::request::CommandRequest<'req>) -> Result<::chatbot_lib::response::Response<'s>, ::chatbot_lib::command::CommandError<#error>> {
             #function_call2
         }
 
         #[allow(non_upper_case_globals)]
         #vis const #show_syntax_name: (bool, &'static str) = (#show_syntax, #command_literal);
+
+        #vis fn #syntax_message_name(request: &::chatbot_lib::request::CommandRequest<'_>, error: &::chatbot_lib::command::CommandError<#error>) -> String {
+            #syntax_message
+        }
     };
     result.into()
 }
+
+/// Generates a [`CommandProcessor`](chatbot_lib::command::CommandProcessor) for a struct from
+/// its methods with a `#[command(...)]` attribute, which take `&self` and are called in order.
+/// The arguments are handled like the arguments of a `#[command]` function, e.g.
+///
+/// ```ignore
+/// #[command_methods]
+/// impl Weather {
+///     #[command("!weather <city..>")]
+///     async fn weather(&self, city: &str) -> String {
+///         self.client.forecast(city).await
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn command_methods(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(item as syn::ItemImpl);
+    if !attr.is_empty() {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "`command_methods` has no arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+    methods::expand(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Generates a processor for an enum whose variants each carry a `#[command(...)]` pattern.
+/// The fields of a variant are parsed like the arguments of a `#[command]` function and are
+/// passed to an async handler function named after the variant, e.g. `Queue::remove_all`.
+#[proc_macro_attribute]
+pub fn subcommands(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let arguments = syn::parse_macro_input!(attr as subcommands::SubcommandsArguments);
+    let input = syn::parse_macro_input!(item as syn::ItemEnum);
+    subcommands::expand(arguments, input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
@@ -22,6 +22,8 @@ impl<'a> MetaCommandRequest<'a> {
 
 pub struct MetaCommandArguments<'a> {
     ident: &'a Ident,
+    /// error type of the generated `CommandError`
+    error: &'a syn::Type,
 }
 
 impl ToTokens for MetaCommandArguments<'_> {
@@ -50,8 +52,8 @@ impl ToTokens for MetaCommandArgumentsFunction {
 }
 
 impl<'a> MetaCommandArguments<'a> {
-    pub fn new(ident: &'a Ident) -> Self {
-        Self { ident }
+    pub fn new(ident: &'a Ident, error: &'a syn::Type) -> Self {
+        Self { ident, error }
     }
 
     pub fn to_binding(&self, request: &MetaCommandRequest) -> TokenStream {
@@ -69,6 +71,12 @@ impl<'a> MetaCommandArguments<'a> {
         }
     }
 
+    pub fn to_take_flags(&self, flags: &Ident, prefixes: &[&str]) -> TokenStream {
+        quote! {
+            let #flags = #self.take_flags([#(#prefixes),*]);
+        }
+    }
+
     pub fn next(&self) -> MetaCommandArgument<'_> {
         MetaCommandArgument {
             arguments: self,
@@ -97,28 +105,44 @@ pub struct MetaCommandArgument<'a> {
 
 impl MetaCommandArgument<'_> {
     pub fn to_argument(&self, name: &str) -> TokenStream {
+        let error = self.arguments.error;
         quote! {
-            ::chatbot_lib::command::next_argument_anyhow(#self, #name)?
+            ::chatbot_lib::command::next_argument_with::<_, #error>(#self, #name)?
         }
     }
 
     pub fn to_optional_argument(&self, name: &str) -> TokenStream {
+        let error = self.arguments.error;
         quote! {
-            ::chatbot_lib::command::next_optional_argument_anyhow(#self, #name)?
+            ::chatbot_lib::command::next_optional_argument_with::<_, #error>(#self, #name)?
+        }
+    }
+
+    /// Parses every argument of a take-all argument into a `Vec`.
+    pub fn to_arguments(&self, name: &str, optional: bool) -> TokenStream {
+        let error = self.arguments.error;
+        if optional {
+            quote! {
+                ::chatbot_lib::command::next_optional_arguments_with::<_, #error>(#self, #name)?
+            }
+        } else {
+            quote! {
+                ::chatbot_lib::command::next_arguments_with::<_, #error>(#self, #name)?
+            }
         }
     }
 
-    pub fn to_match_subcommand(&self, subcommand: &str) -> TokenStream {
+    pub fn to_match_subcommand(&self, subcommands: &[&str]) -> TokenStream {
         quote! {
-            if #self.ok_or(::chatbot_lib::command::CommandError::SubcommandMismatch)? != #subcommand {
+            if !matches!(#self.ok_or(::chatbot_lib::command::CommandError::SubcommandMismatch)?, #(#subcommands)|*) {
                 return Err(::chatbot_lib::command::CommandError::SubcommandMismatch);
             }
         }
     }
 
-    pub fn to_match_command(&self, command: &str) -> TokenStream {
+    pub fn to_match_command(&self, commands: &[&str]) -> TokenStream {
         quote! {
-            if #self.ok_or(::chatbot_lib::command::CommandError::CommandMismatch)? != #command {
+            if !matches!(#self.ok_or(::chatbot_lib::command::CommandError::CommandMismatch)?, #(#commands)|*) {
                 return Err(::chatbot_lib::command::CommandError::CommandMismatch);
             }
         }
@@ -0,0 +1,137 @@
+use crate::{
+    command_attempt, command_await, command_call, get_argument_names, user_message, CommandOptions,
+    MetaArguments,
+};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+
+fn is_command_attribute(attr: &syn::Attribute) -> bool {
+    attr.path().is_ident("command")
+}
+
+/// Generates `command_<name>`, `async_command_<name>` and `show_syntax_<name>` for a method with
+/// a `#[command(...)]` attribute, and returns the block calling it from the processor.
+fn expand_method(
+    method: &syn::ImplItemFn,
+    attr: &syn::Attribute,
+    items: &mut Vec<TokenStream>,
+) -> syn::Result<TokenStream> {
+    let name = &method.sig.ident;
+    let is_async = method.sig.asyncness.is_some();
+    match method.sig.inputs.first() {
+        Some(syn::FnArg::Receiver(receiver))
+            if receiver.reference.is_some() && receiver.mutability.is_none() => {}
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &method.sig,
+                "expected a method taking `&self`",
+            ))
+        }
+    }
+    let inputs: syn::punctuated::Punctuated<_, syn::Token![,]> =
+        method.sig.inputs.iter().skip(1).cloned().collect();
+    let fn_args = get_argument_names(&inputs)?;
+    let meta_arguments: MetaArguments = attr.parse_args()?;
+    let options = CommandOptions::new(&meta_arguments)?;
+    let error = &options.error;
+    let show_syntax = options.show_syntax.is_shown();
+    let pattern = &options.syntax;
+    let syntax_message = options.show_syntax.to_message(pattern);
+
+    let (return_type, body) = command_call(quote!(self.#name), is_async, &options, &fn_args)?;
+    let call_name = format_ident!("command_{}", name);
+    let command_name = format_ident!("async_command_{}", name);
+    let show_syntax_name = format_ident!("show_syntax_{}", name);
+    let syntax_message_name = format_ident!("syntax_message_{}", name);
+    let function_call = command_await(quote!(self.#call_name(request)), is_async, &options);
+    items.push(quote_spanned! {name.span()=>
+        fn #call_name<'s, 'req: 's>(&'s self, request: &'s ::chatbot_lib::request::CommandRequest<'req>) -> Result<#return_type, ::chatbot_lib::command::CommandError<#error>> {
+            #body
+        }
+
+        async fn #command_name<'s, 'req: 's>(&'s self, request: &'s ::chatbot_lib::request::CommandRequest<'req>) -> Result<::chatbot_lib::response::Response<'s>, ::chatbot_lib::command::CommandError<#error>> {
+            #function_call
+        }
+
+        #[allow(non_upper_case_globals)]
+        const #show_syntax_name: (bool, &'static str) = (#show_syntax, #pattern);
+
+        fn #syntax_message_name(request: &::chatbot_lib::request::CommandRequest<'_>, error: &::chatbot_lib::command::CommandError<#error>) -> String {
+            #syntax_message
+        }
+    });
+
+    // responses can borrow from `self`, which does not live as long as the request
+    Ok(command_attempt(
+        name.span(),
+        quote!(self.#command_name),
+        quote!(Self::#show_syntax_name),
+        quote!(Self::#syntax_message_name),
+        &name.to_string(),
+        false,
+        true,
+    ))
+}
+
+pub fn expand(input: syn::ItemImpl) -> syn::Result<TokenStream> {
+    if let Some((_, path, _)) = &input.trait_ {
+        return Err(syn::Error::new_spanned(
+            path,
+            "expected an inherent `impl` block",
+        ));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "generic `impl` blocks are not supported",
+        ));
+    }
+    let mut item = input.clone();
+    let mut items = Vec::new();
+    let mut attempts = Vec::new();
+    for impl_item in item.items.iter_mut() {
+        if let syn::ImplItem::Fn(method) = impl_item {
+            let Some(index) = method.attrs.iter().position(is_command_attribute) else {
+                continue;
+            };
+            let attr = method.attrs.remove(index);
+            attempts.push(expand_method(method, &attr, &mut items)?);
+        }
+    }
+    if attempts.is_empty() {
+        return Err(syn::Error::new(
+            input.span(),
+            "expected at least one method with a `#[command(...)]` attribute",
+        ));
+    }
+    let self_ty = &input.self_ty;
+    // TODO: use Display instead of ToString
+    let shared_syntax_response = user_message(
+        false,
+        quote!(::chatbot_lib::locale::syntax(
+            request,
+            &shared_syntax.to_string()
+        )),
+    );
+
+    Ok(quote! {
+        #item
+
+        impl #self_ty {
+            #(#items)*
+        }
+
+        #[async_trait]
+        impl ::chatbot_lib::command::CommandProcessor for #self_ty {
+            async fn process<'a>(&self, request: &'a ::chatbot_lib::request::CommandRequest<'a>) -> Option<::chatbot_lib::response::Response<'a>> {
+                let mut shared_syntax: Option<::chatbot_lib::command::FindSharedSyntax> = None;
+                #(#attempts)*
+                if let Some(shared_syntax) = shared_syntax {
+                    return Some(#shared_syntax_response);
+                }
+                None
+            }
+        }
+    })
+}
@@ -1,3 +1,4 @@
+use chatbot_lib::command::Segment;
 use std::fmt::Display;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -8,6 +9,14 @@ pub enum CommandPattern<'a> {
         name: &'a str,
         take_all: bool,
         optional: bool,
+        /// the type of `<name:Type>`, which has to match the function argument
+        ty: Option<&'a str>,
+    },
+    /// `--name=<placeholder>` or `name:<placeholder>`, may appear in any order at the end
+    Flag {
+        name: &'a str,
+        prefix: &'a str,
+        placeholder: &'a str,
     },
     TakeAll,
 }
@@ -22,24 +31,30 @@ impl Display for CommandPattern<'_> {
             CommandPattern::TakeAll => "..".fmt(formatter),
             CommandPattern::Argument {
                 name,
-                take_all: false,
-                optional: false,
-            } => write!(formatter, "<{}>", name),
-            CommandPattern::Argument {
-                name,
-                take_all: false,
-                optional: true,
-            } => write!(formatter, "[{}]", name),
-            CommandPattern::Argument {
-                name,
-                take_all: true,
-                optional: false,
-            } => write!(formatter, "<{}..>", name),
-            CommandPattern::Argument {
-                name,
-                take_all: true,
-                optional: true,
-            } => write!(formatter, "[{}..]", name),
+                take_all,
+                optional,
+                ty,
+            } => {
+                let (open, close) = if *optional { ('[', ']') } else { ('<', '>') };
+                let rest = if *take_all { ".." } else { "" };
+                match ty {
+                    Some(ty) => write!(
+                        formatter,
+                        "{}{}: {}{}{}",
+                        open,
+                        name,
+                        type_hint(ty),
+                        rest,
+                        close
+                    ),
+                    None => write!(formatter, "{}{}{}{}", open, name, rest, close),
+                }
+            }
+            CommandPattern::Flag {
+                prefix,
+                placeholder,
+                ..
+            } => write!(formatter, "{}<{}>", prefix, placeholder),
         }
     }
 }
@@ -49,7 +64,8 @@ impl<'a> CommandPattern<'a> {
         match self {
             CommandPattern::Command(value)
             | CommandPattern::Subcommand(value)
-            | CommandPattern::Argument { name: value, .. } => value,
+            | CommandPattern::Argument { name: value, .. }
+            | CommandPattern::Flag { name: value, .. } => value,
             CommandPattern::TakeAll => "",
         }
     }
@@ -61,6 +77,27 @@ impl<'a> CommandPattern<'a> {
         )
     }
 
+    /// The spellings a command or subcommand matches, e.g. `add` and `create` for `(add|create)`.
+    pub fn alternatives(&self) -> Vec<&'a str> {
+        match self {
+            CommandPattern::Command(value) | CommandPattern::Subcommand(value) => {
+                Segment::Subcommand(value).alternatives()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn is_flag(&self) -> bool {
+        matches!(self, CommandPattern::Flag { .. })
+    }
+
+    pub fn flag_prefix(&self) -> Option<&'a str> {
+        match self {
+            CommandPattern::Flag { prefix, .. } => Some(prefix),
+            _ => None,
+        }
+    }
+
     pub fn is_optional(&self) -> bool {
         matches!(
             self,
@@ -69,47 +106,75 @@ impl<'a> CommandPattern<'a> {
     }
 }
 
+/// The segments are parsed by [`Segment`], so patterns have the same syntax at runtime.
+impl<'a> From<Segment<'a>> for CommandPattern<'a> {
+    fn from(segment: Segment<'a>) -> Self {
+        match segment {
+            Segment::Command(value) => Self::Command(value),
+            Segment::Subcommand(value) => Self::Subcommand(value),
+            Segment::Argument {
+                name,
+                take_all,
+                optional,
+                ty,
+            } => Self::Argument {
+                name,
+                take_all,
+                optional,
+                ty,
+            },
+            Segment::Flag {
+                name,
+                prefix,
+                placeholder,
+            } => Self::Flag {
+                name,
+                prefix,
+                placeholder,
+            },
+            Segment::TakeAll => Self::TakeAll,
+        }
+    }
+}
+
 impl<'a> From<&'a str> for CommandPattern<'a> {
     fn from(value: &'a str) -> Self {
-        if value.starts_with('!') {
-            Self::Command(value)
-        } else if value == ".." {
-            Self::TakeAll
-        } else if let Some(value) = value
-            .strip_prefix('<')
-            .and_then(|value| value.strip_suffix('>'))
-        {
-            match value.strip_suffix("..") {
-                Some(value) => Self::Argument {
-                    name: value,
-                    take_all: true,
-                    optional: false,
-                },
-                None => Self::Argument {
-                    name: value,
-                    take_all: false,
-                    optional: false,
-                },
-            }
-        } else if let Some(value) = value
-            .strip_prefix('[')
-            .and_then(|value| value.strip_suffix(']'))
-        {
-            match value.strip_suffix("..") {
-                Some(value) => Self::Argument {
-                    name: value,
-                    take_all: true,
-                    optional: true,
-                },
-                None => Self::Argument {
-                    name: value,
-                    take_all: false,
-                    optional: true,
-                },
-            }
-        } else {
-            Self::Subcommand(value)
-        }
+        Segment::parse(value).into()
+    }
+}
+
+/// The pattern as it is shown to users, where typed arguments like `<count:u32>` are shown as
+/// `<count: number>`.
+pub fn syntax(pattern: &str) -> String {
+    let segments: Vec<_> = pattern
+        .split_whitespace()
+        .map(CommandPattern::from)
+        .collect();
+    if !segments
+        .iter()
+        .any(|segment| matches!(segment, CommandPattern::Argument { ty: Some(_), .. }))
+    {
+        return pattern.to_owned();
+    }
+    segments
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// How the type of a typed argument is shown in the syntax, e.g. `number` for `u32`.
+pub fn type_hint(ty: &str) -> &str {
+    let name = ty.rsplit("::").next().unwrap_or(ty);
+    let name = name.split('<').next().unwrap_or(name).trim();
+    match name {
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64" | "i128"
+        | "isize" | "f32" | "f64" | "Bounded" | "Percent" => "number",
+        "bool" => "true|false",
+        "str" | "String" => "text",
+        "Duration" => "duration",
+        "Url" => "url",
+        _ => ty,
     }
 }
 
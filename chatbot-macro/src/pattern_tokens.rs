@@ -0,0 +1,194 @@
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+
+/// A pattern written with Rust tokens, e.g. `!song add <command> [url]`.
+pub struct PatternTokens {
+    pub pattern: String,
+    /// span of every whitespace separated segment in `pattern`
+    pub spans: Vec<Span>,
+}
+
+fn token_text(token: &TokenTree) -> String {
+    match token {
+        TokenTree::Ident(ident) => {
+            let ident = ident.to_string();
+            match ident.strip_prefix("r#") {
+                Some(ident) => ident.to_owned(),
+                None => ident,
+            }
+        }
+        TokenTree::Group(group) => {
+            let (open, close) = match group.delimiter() {
+                Delimiter::Parenthesis => ("(", ")"),
+                Delimiter::Bracket => ("[", "]"),
+                Delimiter::Brace => ("{", "}"),
+                Delimiter::None => ("", ""),
+            };
+            let inner: String = group.stream().into_iter().map(|t| token_text(&t)).collect();
+            format!("{}{}{}", open, inner, close)
+        }
+        token => token.to_string(),
+    }
+}
+
+fn is_punct(token: Option<&TokenTree>, ch: char) -> bool {
+    matches!(token, Some(TokenTree::Punct(punct)) if punct.as_char() == ch)
+}
+
+fn is_name(token: Option<&TokenTree>) -> bool {
+    matches!(token, Some(TokenTree::Ident(_) | TokenTree::Literal(_)))
+}
+
+struct Segments {
+    tokens: Vec<TokenTree>,
+    index: usize,
+}
+
+impl Segments {
+    fn peek(&self, offset: usize) -> Option<&TokenTree> {
+        self.tokens.get(self.index + offset)
+    }
+
+    fn take(&mut self, segment: &mut String) {
+        if let Some(token) = self.tokens.get(self.index) {
+            self.index += 1;
+            segment.push_str(&token_text(token));
+        }
+    }
+
+    fn error(&self, span: Span, expected: &str) -> syn::Error {
+        let span = self.peek(0).map_or(span, TokenTree::span);
+        syn::Error::new(span, format!("expected {}", expected))
+    }
+
+    fn expect(&mut self, segment: &mut String, ch: char, span: Span) -> syn::Result<()> {
+        if is_punct(self.peek(0), ch) {
+            self.take(segment);
+            Ok(())
+        } else {
+            Err(self.error(span, &format!("`{}`", ch)))
+        }
+    }
+
+    /// a name like `song` or `set-title`
+    fn name(&mut self, segment: &mut String, span: Span) -> syn::Result<()> {
+        if !is_name(self.peek(0)) {
+            return Err(self.error(span, "a name"));
+        }
+        self.take(segment);
+        while is_punct(self.peek(0), '-') && is_name(self.peek(1)) {
+            self.take(segment);
+            self.take(segment);
+        }
+        Ok(())
+    }
+
+    /// a type like `u32` or `std::time::Duration`
+    fn type_path(&mut self, segment: &mut String, span: Span) -> syn::Result<()> {
+        if !matches!(self.peek(0), Some(TokenTree::Ident(_))) {
+            return Err(self.error(span, "a type"));
+        }
+        self.take(segment);
+        while is_punct(self.peek(0), ':')
+            && is_punct(self.peek(1), ':')
+            && matches!(self.peek(2), Some(TokenTree::Ident(_)))
+        {
+            self.take(segment);
+            self.take(segment);
+            self.take(segment);
+        }
+        Ok(())
+    }
+
+    /// an argument like `<name>`, `<name..>` or `<name:Type>`
+    fn argument(&mut self, segment: &mut String, span: Span) -> syn::Result<()> {
+        self.expect(segment, '<', span)?;
+        self.name(segment, span)?;
+        if is_punct(self.peek(0), ':') {
+            self.take(segment);
+            self.type_path(segment, span)?;
+        }
+        if is_punct(self.peek(0), '.') {
+            self.expect(segment, '.', span)?;
+            self.expect(segment, '.', span)?;
+        }
+        self.expect(segment, '>', span)
+    }
+
+    fn segment(&mut self) -> syn::Result<Option<(String, Span)>> {
+        let mut segment = String::new();
+        let token = match self.peek(0) {
+            None => return Ok(None),
+            Some(token) => token.clone(),
+        };
+        let span = token.span();
+        match token {
+            TokenTree::Group(group)
+                if matches!(
+                    group.delimiter(),
+                    Delimiter::Bracket | Delimiter::Parenthesis
+                ) =>
+            {
+                self.take(&mut segment);
+            }
+            TokenTree::Punct(punct) => match punct.as_char() {
+                '!' => {
+                    self.take(&mut segment);
+                    self.name(&mut segment, span)?;
+                }
+                '<' => self.argument(&mut segment, span)?,
+                '.' => {
+                    self.take(&mut segment);
+                    self.expect(&mut segment, '.', span)?;
+                }
+                '-' => {
+                    self.take(&mut segment);
+                    self.expect(&mut segment, '-', span)?;
+                    self.name(&mut segment, span)?;
+                    self.expect(&mut segment, '=', span)?;
+                    self.argument(&mut segment, span)?;
+                }
+                _ => return Err(syn::Error::new(span, "unexpected token in pattern")),
+            },
+            TokenTree::Ident(_) | TokenTree::Literal(_) => {
+                self.name(&mut segment, span)?;
+                if is_punct(self.peek(0), ':') {
+                    self.take(&mut segment);
+                    self.argument(&mut segment, span)?;
+                }
+            }
+            TokenTree::Group(_) => {
+                return Err(syn::Error::new(span, "unexpected token in pattern"))
+            }
+        }
+        if segment.contains(char::is_whitespace) {
+            return Err(syn::Error::new(
+                span,
+                "pattern segments can not contain whitespace",
+            ));
+        }
+        Ok(Some((segment, span)))
+    }
+}
+
+impl PatternTokens {
+    pub fn parse(tokens: TokenStream) -> syn::Result<Self> {
+        let mut segments = Segments {
+            tokens: tokens.into_iter().collect(),
+            index: 0,
+        };
+        let mut pattern = Vec::new();
+        let mut spans = Vec::new();
+        while let Some((segment, span)) = segments.segment()? {
+            pattern.push(segment);
+            spans.push(span);
+        }
+        Ok(Self {
+            pattern: pattern.join(" "),
+            spans,
+        })
+    }
+
+    pub fn span(&self) -> Span {
+        self.spans.first().copied().unwrap_or_else(Span::call_site)
+    }
+}
@@ -0,0 +1,312 @@
+use crate::pattern::CommandPattern;
+use crate::{
+    argument_parser, get_bool_argument, get_pattern, get_type_argument, parse_trailing_arguments,
+    Argument, MetaArguments, ShowSyntax,
+};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use syn::parse::{Parse, ParseStream};
+
+/// `#[subcommands(Processor, error = MyError)]`
+pub struct SubcommandsArguments {
+    processor: syn::Ident,
+    arguments: MetaArguments,
+}
+
+impl Parse for SubcommandsArguments {
+    fn parse(input: ParseStream) -> syn::parse::Result<Self> {
+        Ok(Self {
+            processor: input.parse()?,
+            arguments: MetaArguments::Arguments(parse_trailing_arguments(input)?),
+        })
+    }
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len() + 4);
+    for (index, ch) in name.char_indices() {
+        if ch.is_uppercase() {
+            if index > 0 {
+                result.push('_');
+            }
+            result.extend(ch.to_lowercase());
+        } else {
+            result.push(ch);
+        }
+    }
+    result
+}
+
+struct Variant<'a> {
+    ident: &'a syn::Ident,
+    handler: syn::Ident,
+    parse: syn::Ident,
+    fields: Vec<Argument<'a>>,
+    pattern: syn::LitStr,
+    spans: Vec<proc_macro2::Span>,
+    commands: Vec<String>,
+    show_syntax: ShowSyntax,
+    reply: bool,
+}
+
+fn is_command_attribute(attr: &syn::Attribute) -> bool {
+    attr.path().is_ident("command")
+}
+
+fn parse_variant(variant: &syn::Variant) -> syn::Result<Variant<'_>> {
+    let attr = variant
+        .attrs
+        .iter()
+        .find(|attr| is_command_attribute(attr))
+        .ok_or_else(|| {
+            syn::Error::new_spanned(variant, "expected a `#[command(...)]` attribute")
+        })?;
+    let meta_arguments: MetaArguments = attr.parse_args()?;
+    let (pattern, spans) = get_pattern(&meta_arguments)?;
+    let show_syntax = ShowSyntax::new(&meta_arguments)?;
+    let reply = get_bool_argument(&meta_arguments, "reply")
+        .transpose()?
+        .is_some_and(|value| value.value);
+    let commands = pattern
+        .value()
+        .split_whitespace()
+        .next()
+        .map(CommandPattern::from)
+        .filter(|pattern| matches!(pattern, CommandPattern::Command(_)))
+        .map(|pattern| {
+            pattern
+                .alternatives()
+                .into_iter()
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+    let fields = match &variant.fields {
+        syn::Fields::Named(fields) => fields
+            .named
+            .iter()
+            .filter_map(|field| field.ident.as_ref().map(|ident| (ident, &field.ty)))
+            .map(|(ident, ty)| Argument {
+                arg: ident.to_string(),
+                ident: format_ident!("argument_{}", ident, span = ident.span()),
+                ty,
+            })
+            .collect(),
+        syn::Fields::Unit => Vec::new(),
+        syn::Fields::Unnamed(fields) => {
+            return Err(syn::Error::new_spanned(
+                fields,
+                "expected named fields or a unit variant",
+            ))
+        }
+    };
+    let name = to_snake_case(&variant.ident.to_string());
+    Ok(Variant {
+        ident: &variant.ident,
+        handler: format_ident!("{}", name, span = variant.ident.span()),
+        parse: format_ident!("parse_{}", name),
+        fields,
+        pattern,
+        spans,
+        commands,
+        show_syntax,
+        reply,
+    })
+}
+
+pub fn expand(arguments: SubcommandsArguments, input: syn::ItemEnum) -> syn::Result<TokenStream> {
+    let processor = arguments.processor;
+    let error = get_type_argument(&arguments.arguments, "error")
+        .transpose()?
+        .unwrap_or_else(|| syn::parse_quote!(anyhow::Error));
+    let variants = input
+        .variants
+        .iter()
+        .map(parse_variant)
+        .collect::<syn::Result<Vec<_>>>()?;
+    let lifetime = match input.generics.params.len() {
+        0 => None,
+        1 => match input.generics.params.first() {
+            Some(syn::GenericParam::Lifetime(param)) => Some(param.lifetime.clone()),
+            _ => None,
+        },
+        _ => None,
+    };
+    if lifetime.is_none() && !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "only a single lifetime parameter is supported",
+        ));
+    }
+    let request_lifetime = lifetime
+        .clone()
+        .unwrap_or_else(|| syn::Lifetime::new("'_", proc_macro2::Span::call_site()));
+    let name = &input.ident;
+    let generics = &input.generics;
+
+    let mut parsers = Vec::with_capacity(variants.len());
+    for variant in &variants {
+        let parser = argument_parser(
+            &variant.pattern,
+            variant.spans.clone(),
+            &variant.fields,
+            &error,
+        )?;
+        let ident = variant.ident;
+        let parse = &variant.parse;
+        let fields = variant.fields.iter().map(|field| {
+            let name = format_ident!("{}", field.arg);
+            let ident = &field.ident;
+            quote!(#name: #ident)
+        });
+        parsers.push(quote_spanned! {variant.ident.span()=>
+            fn #parse(request: &#request_lifetime ::chatbot_lib::request::CommandRequest<'_>) -> Result<Self, ::chatbot_lib::command::CommandError<#error>> {
+                #parser
+                Ok(Self::#ident { #(#fields),* })
+            }
+        });
+    }
+
+    // all variants share the same commands, so they can be rejected at once
+    let commands: Vec<_> = variants
+        .iter()
+        .flat_map(|variant| variant.commands.iter().map(String::as_str))
+        .collect();
+    let command_check = if variants.iter().all(|variant| !variant.commands.is_empty()) {
+        quote! {
+            let command = ::chatbot_lib::command::CommandArguments::from(request.command() as &str).next();
+            if !matches!(command, Some(#(#commands)|*)) {
+                return None;
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let attempts = variants.iter().map(|variant| {
+        let parse = &variant.parse;
+        let show_syntax = variant.show_syntax.is_shown();
+        let pattern = syn::LitStr::new(
+            &crate::pattern::syntax(&variant.pattern.value()),
+            variant.pattern.span(),
+        );
+        let syntax_message = variant.show_syntax.to_message(&pattern);
+        let variant_str = variant.ident.to_string();
+        let syntax_response = if variant.reply {
+            quote!(::chatbot_lib::response::Response::new(syntax).as_reply())
+        } else {
+            quote!(::chatbot_lib::response::Response::new(format!("{} {}", ::chatbot_lib::user::UserArgument::from(request.sender() as &::chatbot_lib::user::User), syntax)))
+        };
+        quote! {
+            if parsed.is_none() {
+                let result = #name::#parse(request);
+                if result.is_err() {
+                    ::chatbot_lib::command::record_metrics(request, #variant_str, start, &result);
+                }
+                match result {
+                    Ok(value) => parsed = Some((value, #variant_str)),
+                    Err(e) => {
+                        if #show_syntax {
+                            if e.is_argument_error() {
+                                let syntax = {
+                                    let error = &e;
+                                    #syntax_message
+                                };
+                                return Some(#syntax_response);
+                            } else if e.is_subcommand_mismatch() {
+                                if let Some(shared_syntax) = &mut shared_syntax {
+                                    shared_syntax.append(#pattern);
+                                } else {
+                                    shared_syntax = Some(::chatbot_lib::command::FindSharedSyntax::new(#pattern));
+                                }
+                            }
+                        }
+                        log::debug!("Error parsing {}: {:?}", #variant_str, e)
+                    }
+                }
+            }
+        }
+    });
+
+    let handlers = variants.iter().map(|variant| {
+        let ident = variant.ident;
+        let handler = &variant.handler;
+        let fields: Vec<_> = variant
+            .fields
+            .iter()
+            .map(|field| format_ident!("{}", field.arg))
+            .collect();
+        let response = if variant.reply {
+            quote!(::chatbot_lib::response::IntoResponse::into_response(#name::#handler(#(#fields),*).await, request).as_reply())
+        } else {
+            quote!(::chatbot_lib::response::IntoResponse::into_response(#name::#handler(#(#fields),*).await, request))
+        };
+        quote! {
+            #name::#ident { #(#fields),* } => #response,
+        }
+    });
+
+    let any_show_syntax = variants
+        .iter()
+        .any(|variant| variant.show_syntax.is_shown());
+    let shared_syntax_response = if variants.iter().all(|variant| variant.reply) {
+        quote!(
+            ::chatbot_lib::response::Response::new(::chatbot_lib::locale::syntax(
+                request,
+                &shared_syntax.to_string()
+            ))
+            .as_reply()
+        )
+    } else {
+        quote!(::chatbot_lib::response::Response::new(format!(
+            "{} {}",
+            ::chatbot_lib::user::UserArgument::from(request.sender() as &::chatbot_lib::user::User),
+            ::chatbot_lib::locale::syntax(request, &shared_syntax.to_string())
+        )))
+    };
+    let shared_syntax = if any_show_syntax {
+        quote! {
+            if let Some(shared_syntax) = shared_syntax {
+                return Some(#shared_syntax_response);
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let mut item = input.clone();
+    for variant in item.variants.iter_mut() {
+        variant.attrs.retain(|attr| !is_command_attribute(attr));
+    }
+
+    Ok(quote! {
+        #item
+
+        impl #generics #name #generics {
+            #(#parsers)*
+        }
+
+        struct #processor;
+
+        #[async_trait]
+        impl ::chatbot_lib::command::CommandProcessor for #processor {
+            async fn process<'a>(&self, request: &'a ::chatbot_lib::request::CommandRequest<'a>) -> Option<::chatbot_lib::response::Response<'a>> {
+                #command_check
+                let start = ::std::time::Instant::now();
+                #[allow(unused_mut)]
+                let mut shared_syntax: Option<::chatbot_lib::command::FindSharedSyntax> = None;
+                let mut parsed = None;
+                #(#attempts)*
+                if let Some((parsed, command)) = parsed {
+                    let response = match parsed {
+                        #(#handlers)*
+                    };
+                    ::chatbot_lib::command::record_metrics(request, command, start, &Ok::<_, ::chatbot_lib::command::CommandError<()>>(()));
+                    return Some(response);
+                }
+                #shared_syntax
+                None
+            }
+        }
+    })
+}
@@ -4,6 +4,7 @@ use proc_macro2::TokenStream;
 use proc_macro2::{Ident, Span};
 use quote::quote_spanned;
 use quote::ToTokens;
+use std::collections::HashSet;
 
 #[derive(Debug)]
 pub enum Direction {
@@ -16,6 +17,8 @@ pub struct CommandPatternToken<'a> {
     pattern: CommandPattern<'a>,
     /// ident and it's type span
     ident_span: Option<(Ident, Span)>,
+    /// the function argument is a `Vec` which collects the arguments of `<name..>`
+    collect: bool,
     direction: Direction,
     /// span of the literal string
     span: Span,
@@ -26,11 +29,13 @@ impl<'a> CommandPatternToken<'a> {
         pattern: CommandPattern<'a>,
         direction: Direction,
         ident_span: Option<(Ident, Span)>,
+        collect: bool,
         span: Span,
     ) -> Self {
         Self {
             pattern,
             ident_span,
+            collect,
             direction,
             span,
         }
@@ -53,30 +58,34 @@ impl CommandPatternToken<'_> {
     pub fn into_token_stream(self, arguments: &MetaCommandArguments<'_>) -> TokenStream {
         match self {
             CommandPatternToken {
-                pattern: CommandPattern::Command(command),
+                pattern: pattern @ CommandPattern::Command(_),
                 ident_span: None,
                 direction,
                 ..
-            } => next(arguments, direction, false).to_match_command(command),
+            } => next(arguments, direction, false).to_match_command(&pattern.alternatives()),
             CommandPatternToken {
-                pattern: CommandPattern::Subcommand(subcommand),
+                pattern: pattern @ CommandPattern::Subcommand(_),
                 ident_span: None,
                 direction,
                 ..
-            } => next(arguments, direction, false).to_match_subcommand(subcommand),
+            } => next(arguments, direction, false).to_match_subcommand(&pattern.alternatives()),
             CommandPatternToken {
                 pattern:
                     CommandPattern::Argument {
                         name,
                         take_all,
                         optional,
+                        ..
                     },
                 ident_span: Some((ident, span)),
+                collect,
                 direction,
                 ..
             } => {
                 let next = next(arguments, direction, take_all);
-                let next = if optional {
+                let next = if collect && take_all {
+                    next.to_arguments(name, optional)
+                } else if optional {
                     next.to_optional_argument(name)
                 } else {
                     next.to_argument(name)
@@ -119,53 +128,267 @@ impl CommandPatternToken<'_> {
 
 pub struct CommandPatternScanner<'a> {
     arguments: &'a MetaCommandArguments<'a>,
-    optional: bool,
-    take_all: bool,
 }
 
 impl<'a> CommandPatternScanner<'a> {
     pub fn new(arguments: &'a MetaCommandArguments<'a>) -> Self {
-        Self {
-            arguments,
-            optional: false,
-            take_all: false,
+        Self { arguments }
+    }
+
+    /// Checks the whole pattern before any code is generated and reports all errors at once:
+    /// required segments after optional ones, optional segments after a greedy `<name..>` in the
+    /// middle, more than one `..`, segments which appear twice,
+    /// arguments which can not be found in the function arguments and typed arguments like
+    /// `<count:u32>` whose type differs from the function argument.
+    pub fn check<'t>(
+        segments: &[(CommandPattern<'_>, Span)],
+        argument_type: impl Fn(&str) -> Option<&'t syn::Type>,
+    ) -> syn::Result<()> {
+        let mut errors: Option<syn::Error> = None;
+        let mut push = |span: Span, message: String| {
+            let error = syn::Error::new(span, message);
+            match &mut errors {
+                Some(errors) => errors.combine(error),
+                None => errors = Some(error),
+            }
+        };
+        let mut optional: Option<&CommandPattern> = None;
+        let mut take_all: Option<&CommandPattern> = None;
+        let mut seen = HashSet::new();
+        for (pattern, span) in segments {
+            let span = *span;
+            if !matches!(pattern, CommandPattern::TakeAll) && !seen.insert(pattern.key()) {
+                push(span, format!("`{}` appears twice in the pattern", pattern));
+            }
+            if let CommandPattern::Argument { name, .. } | CommandPattern::Flag { name, .. } =
+                pattern
+            {
+                match argument_type(name) {
+                    None => push(
+                        span,
+                        format!("`{}` can not be found in function arguments", name),
+                    ),
+                    Some(argument) => {
+                        if let CommandPattern::Argument {
+                            ty: Some(ty),
+                            optional,
+                            take_all,
+                            ..
+                        } = pattern
+                        {
+                            if let Err(error) = check_type(ty, argument, *optional, *take_all) {
+                                push(span, format!("`{}` {}", name, error));
+                            }
+                        }
+                    }
+                }
+            }
+            // flags are taken from the end, so their position does not matter
+            if pattern.is_flag() {
+                continue;
+            }
+            if let (Some(take_all), true) = (take_all, pattern.is_optional()) {
+                // the segments after `<name..>` are taken from the back, so they must be required
+                push(
+                    span,
+                    format!(
+                        "`{}` can not be optional, since it follows `{}`",
+                        pattern, take_all
+                    ),
+                );
+            }
+            if pattern.is_optional() {
+                optional.get_or_insert(pattern);
+            } else if let Some(optional) = optional {
+                push(
+                    span,
+                    format!(
+                        "`{}` has to be optional, since it follows the optional `{}`",
+                        pattern, optional
+                    ),
+                );
+            }
+            if pattern.is_taking_all() {
+                match take_all {
+                    Some(take_all) => push(
+                        span,
+                        format!(
+                            "only one `..` is allowed, but `{}` follows `{}`",
+                            pattern, take_all
+                        ),
+                    ),
+                    None => take_all = Some(pattern),
+                }
+            }
         }
+        errors.map_or(Ok(()), Err)
     }
 }
 
-impl<'a, 'b> CommandPatternScanner<'a> {
-    pub fn scan(&mut self, token: CommandPatternToken<'b>) -> Option<TokenStream> {
-        let mut err = None;
-        if token.pattern.is_optional() {
-            self.optional = true;
-        } else if self.optional {
-            // last element is optional, but this one is not
-            err = Some(
-                syn::Error::new(
-                    token.span,
-                    format!("`{}` has to be optional", token.pattern),
-                )
-                .to_compile_error(),
-            );
+/// Compares the type of `<name:Type>` with the type of the function argument, where references,
+/// lifetimes and the path of a type are ignored, e.g. `Duration` matches `std::time::Duration`
+/// and `str` matches `&'a str`. Optional arguments may be wrapped in an `Option` and take-all
+/// arguments may be collected into a `Vec`.
+fn check_type(
+    ty: &str,
+    argument: &syn::Type,
+    optional: bool,
+    take_all: bool,
+) -> Result<(), String> {
+    let ty: syn::Type = syn::parse_str(ty).map_err(|_| format!("has the invalid type `{}`", ty))?;
+    let unwrapped = match (optional, take_all) {
+        (_, true) if crate::is_vec(argument) => generic_type(argument),
+        (true, _) => option_type(argument),
+        _ => None,
+    };
+    if same_type(&ty, argument) || unwrapped.is_some_and(|argument| same_type(&ty, argument)) {
+        Ok(())
+    } else {
+        Err(format!(
+            "is `{}` in the pattern, but `{}` in the function arguments",
+            ty.to_token_stream(),
+            argument.to_token_stream()
+        ))
+    }
+}
+
+fn option_type(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    if path.path.segments.last()?.ident != "Option" {
+        return None;
+    }
+    generic_type(ty)
+}
+
+/// The first type argument, e.g. `T` of `Vec<T>`.
+fn generic_type(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    match &path.path.segments.last()?.arguments {
+        syn::PathArguments::AngleBracketed(arguments) => match arguments.args.first()? {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn same_type(a: &syn::Type, b: &syn::Type) -> bool {
+    match (a, b) {
+        (syn::Type::Reference(a), b) => same_type(&a.elem, b),
+        (a, syn::Type::Reference(b)) => same_type(a, &b.elem),
+        (syn::Type::Paren(a), b) => same_type(&a.elem, b),
+        (a, syn::Type::Paren(b)) => same_type(a, &b.elem),
+        (syn::Type::Path(a), syn::Type::Path(b)) => {
+            match (a.path.segments.last(), b.path.segments.last()) {
+                (Some(a), Some(b)) => {
+                    a.ident == b.ident && same_arguments(&a.arguments, &b.arguments)
+                }
+                _ => false,
+            }
         }
-        if self.take_all {
-            // error, only one take all allowed and has to be the last item
-            err = Some(
-                syn::Error::new(
-                    token.span,
-                    format!("Only one `..` allowed in `{}`.", token.pattern),
-                )
-                .to_compile_error(),
-            );
+        (a, b) => a.to_token_stream().to_string() == b.to_token_stream().to_string(),
+    }
+}
+
+fn same_arguments(a: &syn::PathArguments, b: &syn::PathArguments) -> bool {
+    let types = |arguments: &syn::PathArguments| -> Vec<syn::Type> {
+        match arguments {
+            syn::PathArguments::AngleBracketed(arguments) => arguments
+                .args
+                .iter()
+                .filter_map(|argument| match argument {
+                    syn::GenericArgument::Type(ty) => Some(ty.clone()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
         }
-        if token.pattern.is_taking_all() {
-            self.take_all = true;
+    };
+    let (a, b) = (types(a), types(b));
+    a.len() == b.len() && a.iter().zip(&b).all(|(a, b)| same_type(a, b))
+}
+
+impl<'a, 'b> CommandPatternScanner<'a> {
+    pub fn scan(&mut self, token: CommandPatternToken<'b>) -> Option<TokenStream> {
+        // the pattern was checked before, so only the code for the argument is generated
+        Some(token.into_token_stream(self.arguments))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(pattern: &str, arguments: &[&str]) -> Vec<String> {
+        let arguments: Vec<(&str, syn::Type)> = arguments
+            .iter()
+            .map(|argument| match argument.split_once(": ") {
+                Some((name, ty)) => (name, syn::parse_str(ty).unwrap()),
+                None => (*argument, syn::parse_quote!(String)),
+            })
+            .collect();
+        let segments: Vec<_> = pattern
+            .split_whitespace()
+            .map(|segment| (CommandPattern::from(segment), Span::call_site()))
+            .collect();
+        let argument_type = |name: &str| {
+            arguments
+                .iter()
+                .find(|(argument, _)| *argument == name)
+                .map(|(_, ty)| ty)
+        };
+        match CommandPatternScanner::check(&segments, argument_type) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.into_iter().map(|error| error.to_string()).collect(),
         }
-        // generate code for the argument
-        let mut command = token.into_token_stream(self.arguments);
-        // extend with errors if there are any
-        command.extend(err);
-        // return result
-        Some(command)
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(check(
+            "!song add <name> [url] [reason..]",
+            &["name", "url", "reason"]
+        )
+        .is_empty());
+        assert!(check("!song <first> <rest..> <last>", &["first", "rest", "last"]).is_empty());
+        assert_eq!(
+            check("!timer add <name..> [duration]", &["name", "duration"]),
+            ["`[duration]` can not be optional, since it follows `<name..>`"]
+        );
+        assert_eq!(
+            check("!song [a] <b> <c..> <d..> <e>", &["a", "b", "c", "d"]),
+            [
+                "`<b>` has to be optional, since it follows the optional `[a]`",
+                "`<c..>` has to be optional, since it follows the optional `[a]`",
+                "`<d..>` has to be optional, since it follows the optional `[a]`",
+                "only one `..` is allowed, but `<d..>` follows `<c..>`",
+                "`e` can not be found in function arguments",
+                "`<e>` has to be optional, since it follows the optional `[a]`",
+            ]
+        );
+        assert_eq!(
+            check("!song <a> <a> --flag=<x>", &["a"]),
+            [
+                "`<a>` appears twice in the pattern",
+                "`flag` can not be found in function arguments",
+            ]
+        );
+        assert!(check(
+            "!song <count:u32> [wait:Duration] [name:str..]",
+            &[
+                "count: u32",
+                "wait: Option<std::time::Duration>",
+                "name: &'a str"
+            ]
+        )
+        .is_empty());
+        assert_eq!(
+            check("!song <count:u32>", &["count: i64"]),
+            ["`count` is `u32` in the pattern, but `i64` in the function arguments"]
+        );
     }
 }
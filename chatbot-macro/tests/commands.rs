@@ -0,0 +1,245 @@
+use async_trait::async_trait;
+use chatbot_lib::command::CommandProcessor;
+use chatbot_lib::request::{Bot, CommandRequest};
+use chatbot_lib::response::Response;
+use chatbot_lib::user::User;
+use chatbot_macro::{command, commands};
+
+#[command("!hello")]
+fn hello() -> &'static str {
+    "hello"
+}
+
+#[command("!echo <text..>")]
+fn echo(text: &str) -> String {
+    text.to_owned()
+}
+
+#[command("!never")]
+#[allow(unused)]
+fn never() -> &'static str {
+    "never"
+}
+
+fn unknown(request: &CommandRequest<'_>) -> Option<Response<'static>> {
+    Some(Response::new(format!(
+        "unknown: {}",
+        request.command() as &str
+    )))
+}
+
+commands!(
+    struct PrefixedCommands [
+        hello,
+        echo,
+        #[cfg(any())]
+        never,
+    ],
+    prefix = "?",
+    case_insensitive = true,
+    unknown_command = unknown,
+);
+
+// only one of the entries is enabled, so the command is not listed twice
+commands!(
+    struct ExclusiveCommands [
+        #[cfg(all())]
+        hello,
+        #[cfg(not(all()))]
+        hello,
+    ]
+);
+
+fn process(command: &str) -> Option<String> {
+    let user = User::from_username("user");
+    let bot = Bot::from(User::from_username("bot"));
+    let request = CommandRequest::from_parts(command, user.clone(), user, &bot);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime
+        .block_on(PrefixedCommands.process(&request))
+        .and_then(|response| response.response().map(String::from))
+}
+
+#[test]
+fn applies_options() {
+    assert_eq!(process("?hello").as_deref(), Some("hello"));
+    assert_eq!(process("?HeLLo").as_deref(), Some("hello"));
+    assert_eq!(process("?ECHO Some Text").as_deref(), Some("Some Text"));
+    assert_eq!(process("!hello"), None);
+    assert_eq!(process("?never").as_deref(), Some("unknown: !never"));
+}
+
+#[test]
+fn allows_exclusive_entries() {
+    let user = User::from_username("user");
+    let bot = Bot::from(User::from_username("bot"));
+    let request = CommandRequest::from_parts("!hello", user.clone(), user, &bot);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let response = runtime.block_on(ExclusiveCommands.process(&request));
+    assert_eq!(response.unwrap().response(), Some("hello"));
+}
+
+#[command("!quote <id:u32>")]
+fn quote_by_id(id: u32) -> String {
+    format!("quote {}", id)
+}
+
+#[command("!quote")]
+fn random_quote() -> &'static str {
+    "random quote"
+}
+
+// the second command is called if the arguments do not match the first one
+commands!(struct QuoteCommands [quote_by_id, random_quote]);
+
+#[test]
+fn allows_overloads() {
+    let user = User::from_username("user");
+    let bot = Bot::from(User::from_username("bot"));
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let process = |command: &str| {
+        let request = CommandRequest::from_parts(command, user.clone(), user.clone(), &bot);
+        runtime
+            .block_on(QuoteCommands.process(&request))
+            .and_then(|response| response.response().map(String::from))
+    };
+    assert_eq!(process("!quote 5").as_deref(), Some("quote 5"));
+    assert_eq!(process("!quote").as_deref(), Some("random quote"));
+}
+
+#[command("!ban-all <users..>")]
+fn ban_all(users: Vec<&str>) -> String {
+    users.join(",")
+}
+
+#[command("!sum [numbers:u32..]")]
+fn sum(numbers: Vec<u32>) -> String {
+    numbers.iter().sum::<u32>().to_string()
+}
+
+commands!(struct VarargsCommands [ban_all, sum]);
+
+#[test]
+fn collects_take_all_arguments() {
+    let user = User::from_username("user");
+    let bot = Bot::from(User::from_username("bot"));
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let process = |command: &str| {
+        let request = CommandRequest::from_parts(command, user.clone(), user.clone(), &bot);
+        runtime
+            .block_on(VarargsCommands.process(&request))
+            .and_then(|response| response.response().map(String::from))
+    };
+    assert_eq!(process("!ban-all a  b c").as_deref(), Some("a,b,c"));
+    assert_eq!(process("!ban-all"), None);
+    assert_eq!(process("!sum 1 2 3").as_deref(), Some("6"));
+    assert_eq!(process("!sum").as_deref(), Some("0"));
+    assert_eq!(process("!sum 1 x"), None);
+}
+
+#[command("!timer add <name..> <duration> every <count:u32>")]
+fn timer_add(name: &str, duration: &str, count: u32) -> String {
+    format!("{}|{}|{}", name, duration, count)
+}
+
+commands!(struct GreedyCommands [timer_add]);
+
+#[test]
+fn binds_trailing_arguments_after_greedy_middle() {
+    let user = User::from_username("user");
+    let bot = Bot::from(User::from_username("bot"));
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let process = |command: &str| {
+        let request = CommandRequest::from_parts(command, user.clone(), user.clone(), &bot);
+        runtime
+            .block_on(GreedyCommands.process(&request))
+            .and_then(|response| response.response().map(String::from))
+    };
+    assert_eq!(
+        process("!timer add drink some water 30m every 2").as_deref(),
+        Some("drink some water|30m|2")
+    );
+    assert_eq!(process("!timer add 30m every 2"), None);
+    assert_eq!(process("!timer add water 30m each 2"), None);
+}
+
+#[command(
+    "!volume <volume:u32>",
+    show_syntax = "!volume <0-100>, e.g. !volume 50"
+)]
+fn volume(volume: u32) -> String {
+    volume.to_string()
+}
+
+fn usage(
+    _request: &CommandRequest<'_>,
+    error: &chatbot_lib::command::CommandError<anyhow::Error>,
+) -> String {
+    match error.invalid_argument() {
+        Some(argument) => format!("{} is not a song, try !song <url>", argument.value),
+        None => "try !song <url>".to_owned(),
+    }
+}
+
+#[command("!song <url:u32>", show_syntax = usage)]
+fn song(url: u32) -> String {
+    url.to_string()
+}
+
+#[command(
+    "!playlist add <url:u32>",
+    show_syntax = "!playlist add <number>, e.g. !playlist add 3"
+)]
+fn playlist_add(url: u32) -> String {
+    url.to_string()
+}
+
+#[command("!playlist remove <index:u32>", show_syntax = usage)]
+fn playlist_remove(index: u32) -> String {
+    index.to_string()
+}
+
+commands!(struct SyntaxCommands [volume, song, playlist_add, playlist_remove]);
+
+#[test]
+fn shows_custom_syntax() {
+    let user = User::from_username("user");
+    let bot = Bot::from(User::from_username("bot"));
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let process = |command: &str| {
+        let request = CommandRequest::from_parts(command, user.clone(), user.clone(), &bot);
+        runtime
+            .block_on(SyntaxCommands.process(&request))
+            .and_then(|response| response.response().map(String::from))
+    };
+    assert_eq!(process("!volume 50").as_deref(), Some("50"));
+    assert_eq!(
+        process("!volume loud").as_deref(),
+        Some("@user !volume <0-100>, e.g. !volume 50 (\"loud\" is not a valid volume)")
+    );
+    assert_eq!(
+        process("!song abc").as_deref(),
+        Some("@user abc is not a song, try !song <url>")
+    );
+    // custom messages can not be combined, so the patterns are shown if no subcommand matched
+    assert_eq!(
+        process("!playlist clear").as_deref(),
+        Some("@user !playlist add|remove")
+    );
+    assert_eq!(
+        process("!playlist add x").as_deref(),
+        Some("@user !playlist add <number>, e.g. !playlist add 3 (\"x\" is not a valid url)")
+    );
+}
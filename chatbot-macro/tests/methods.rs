@@ -0,0 +1,57 @@
+use async_trait::async_trait;
+use chatbot_lib::command::CommandProcessor;
+use chatbot_lib::request::{Bot, Channel, CommandRequest};
+use chatbot_lib::user::User;
+use chatbot_macro::command_methods;
+
+struct Greeter {
+    greeting: String,
+}
+
+#[command_methods]
+impl Greeter {
+    #[command("!greet <name>")]
+    fn greet(&self, name: &str) -> String {
+        format!("{} {}", self.greeting, name)
+    }
+
+    #[command(!channel, show_syntax = true)]
+    async fn channel(&self, channel: &Channel<'_>) -> String {
+        format!("{} {}", self.greeting, channel.username())
+    }
+
+    fn unrelated(&self) -> usize {
+        self.greeting.len()
+    }
+}
+
+fn process(command: &str) -> Option<String> {
+    let greeter = Greeter {
+        greeting: "hi".to_owned(),
+    };
+    let user = User::from_username("user");
+    let bot = Bot::from(User::from_username("bot"));
+    let request = CommandRequest::from_parts(command, user.clone(), user, &bot);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime
+        .block_on(greeter.process(&request))
+        .and_then(|response| response.response().map(String::from))
+}
+
+#[test]
+fn dispatches_to_methods() {
+    assert_eq!(process("!greet nya").as_deref(), Some("hi nya"));
+    assert_eq!(process("!channel").as_deref(), Some("hi user"));
+    assert_eq!(process("!channel x").as_deref(), Some("@user !channel"));
+    assert_eq!(process("!other"), None);
+    assert_eq!(Greeter::show_syntax_greet, (false, "!greet <name>"));
+    assert_eq!(
+        Greeter {
+            greeting: "hello".to_owned()
+        }
+        .unrelated(),
+        5
+    );
+}
@@ -0,0 +1,60 @@
+use async_trait::async_trait;
+use chatbot_lib::command::CommandProcessor;
+use chatbot_lib::request::{Bot, CommandRequest};
+use chatbot_lib::user::User;
+use chatbot_macro::subcommands;
+
+#[subcommands(QueueCommands)]
+enum Queue<'a> {
+    #[command(!queue add <song..>)]
+    Add { song: &'a str },
+    #[command("!queue remove <index>", show_syntax = true, reply = true)]
+    RemoveAt { index: usize },
+    #[command("!queue list")]
+    List,
+}
+
+impl<'a> Queue<'a> {
+    async fn add(song: &str) -> String {
+        format!("added {}", song)
+    }
+
+    async fn remove_at(index: usize) -> String {
+        format!("removed {}", index)
+    }
+
+    async fn list() -> &'static str {
+        "empty"
+    }
+}
+
+fn process(command: &str) -> Option<String> {
+    let user = User::from_username("user");
+    let bot = Bot::from(User::from_username("bot"));
+    let request = CommandRequest::from_parts(command, user.clone(), user, &bot);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime
+        .block_on(QueueCommands.process(&request))
+        .and_then(|response| response.response().map(String::from))
+}
+
+#[test]
+fn dispatches_to_variant_handlers() {
+    assert_eq!(
+        process("!queue add a song").as_deref(),
+        Some("added a song")
+    );
+    assert_eq!(process("!queue remove 2").as_deref(), Some("removed 2"));
+    assert_eq!(process("!queue list").as_deref(), Some("empty"));
+    assert_eq!(
+        process("!queue remove x").as_deref(),
+        Some("!queue remove <index> (\"x\" is not a valid index)")
+    );
+    assert_eq!(
+        process("!queue clear").as_deref(),
+        Some("@user !queue remove <index>")
+    );
+    assert_eq!(process("!other list"), None);
+}
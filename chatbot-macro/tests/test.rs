@@ -2,6 +2,7 @@ use std::time::Duration;
 use url::Url;
 
 use chatbot_lib::request::Channel;
+use chatbot_lib::response::{Responder, Response};
 use chatbot_macro::command;
 
 #[command("!song add <command> <url> <cooldown>")]
@@ -10,8 +11,67 @@ fn song_add(command: &str, url: Url, cooldown: Duration, channel: &Channel<'_>)
     todo!()
 }
 
+#[command("!timer set <name> --cooldown=<duration> reply:<bool>")]
+#[allow(unused)]
+fn timer_set(name: &str, cooldown: Option<Duration>, reply: Option<bool>) -> String {
+    todo!()
+}
+
+#[command("(!so|!shoutout) (user|channel) <name>")]
+#[allow(unused)]
+fn shoutout(name: &str) -> String {
+    todo!()
+}
+
+#[command(!song remove <command> [reason..], show_syntax = true)]
+#[allow(unused)]
+fn song_remove(command: &str, reason: Option<&str>) -> String {
+    todo!()
+}
+
+#[derive(Debug)]
+#[allow(unused)]
+struct MyError(String);
+
+impl chatbot_lib::command::CommandErrorType for MyError {
+    fn from_error<E: std::error::Error + Send + Sync + 'static>(error: E) -> Self {
+        MyError(error.to_string())
+    }
+}
+
+#[command("!song skip [count]", error = MyError)]
+#[allow(unused)]
+fn song_skip(count: Option<u32>) -> String {
+    todo!()
+}
+
+#[command("!song search <query..>")]
+#[allow(unused)]
+async fn song_search(query: &str, responder: &mut dyn Responder) -> String {
+    let _ = responder.respond(&Response::new("searching…")).await;
+    todo!()
+}
+
 #[test]
 fn works() {
+    assert_eq!(
+        show_syntax_song_remove,
+        (true, "!song remove <command> [reason..]")
+    );
     //song_add("", "", Duration::from_secs(0));
     //let x = commands![song_add, song_add];
 }
+
+#[command(!song repeat <count:u32> [wait:Duration], show_syntax = true)]
+#[allow(unused)]
+fn song_repeat(count: u32, wait: Option<Duration>) -> String {
+    todo!()
+}
+
+#[test]
+fn typed_placeholders() {
+    assert_eq!(
+        show_syntax_song_repeat,
+        (true, "!song repeat <count: number> [wait: duration]")
+    );
+}